@@ -4,5 +4,10 @@ SCRIPT_DIR=$( cd -- "$( dirname -- "${BASH_SOURCE[0]}" )" &> /dev/null && pwd )
 
 cd $SCRIPT_DIR
 
-wasm-pack build --target web --out-dir pkg
+# `./rebuild.sh release` builds the stripped-down embed, without the dev-only features.
+if [ "$1" = "release" ]; then
+  wasm-pack build --release --target web --out-dir pkg -- --no-default-features
+else
+  wasm-pack build --target web --out-dir pkg
+fi
 cp ./pkg/tmv_bg.wasm ../web/public/
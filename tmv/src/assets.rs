@@ -0,0 +1,44 @@
+use std::collections::{BTreeMap, HashMap};
+
+/// A stable hash of a resource's contents, as 16 hex digits. This is 64 bit FNV-1a, which is
+/// easy to reproduce in deploy scripts, and plenty for telling versions of a file apart.
+pub fn content_hash(bytes: &[u8]) -> String {
+  format!("{:016x}", fnv1a(bytes))
+}
+
+/// 64 bit FNV-1a, for when a hash is wanted as a number.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+  let mut hash: u64 = 0xcbf29ce484222325;
+  for byte in bytes {
+    hash ^= *byte as u64;
+    hash = hash.wrapping_mul(0x100000001b3);
+  }
+  hash
+}
+
+/// The content hash of every resource the game was loaded with, by path.
+#[derive(Debug, Clone, Default)]
+pub struct AssetManifest {
+  pub hashes: BTreeMap<String, String>,
+}
+
+impl AssetManifest {
+  pub fn from_resources(resources: &HashMap<String, Vec<u8>>) -> Self {
+    Self {
+      hashes: resources.iter().map(|(path, bytes)| (path.clone(), content_hash(bytes))).collect(),
+    }
+  }
+
+  /// The map and tileset files whose hashes don't match the expected ones, which usually
+  /// means the browser served a stale cached copy. Files missing from either side are
+  /// skipped, since the expected manifest may predate or postdate a resource.
+  pub fn stale_files(&self, expected: &HashMap<String, String>) -> Vec<String> {
+    self
+      .hashes
+      .iter()
+      .filter(|(path, _)| path.ends_with(".tmx") || path.ends_with(".tsx"))
+      .filter(|(path, hash)| matches!(expected.get(*path), Some(expected) if expected != *hash))
+      .map(|(path, _)| path.clone())
+      .collect()
+  }
+}
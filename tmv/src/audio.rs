@@ -0,0 +1,46 @@
+use serde::Serialize;
+
+#[cfg(feature = "audio")]
+// If nothing is draining the queue (like a headless run), later events are dropped rather
+// than piling up forever.
+const MAX_QUEUED_EVENTS: usize = 64;
+
+/// Something that happened that the page might want to play a sound for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SoundEvent {
+  Jump,
+  Dash,
+  Coin,
+  Damage,
+  Save,
+  Laser,
+  Splash,
+  Crumble,
+  DoorOpen,
+  Switch,
+  HeadBump,
+}
+
+/// The sound events since the page last drained them, oldest first.
+#[derive(Debug, Default)]
+pub struct SoundQueue {
+  events: Vec<SoundEvent>,
+}
+
+impl SoundQueue {
+  #[cfg(feature = "audio")]
+  pub fn emit(&mut self, event: SoundEvent) {
+    if self.events.len() < MAX_QUEUED_EVENTS {
+      self.events.push(event);
+    }
+  }
+
+  /// Builds without the audio feature drop every event, so the page never has sounds to play.
+  #[cfg(not(feature = "audio"))]
+  pub fn emit(&mut self, _event: SoundEvent) {}
+
+  pub fn drain(&mut self) -> Vec<SoundEvent> {
+    std::mem::take(&mut self.events)
+  }
+}
@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  math::{Rect, Vec2},
+  power_ups::PowerUp,
+  EntityId,
+};
+
+pub const QUEEN_RADIUS: f32 = 1.2;
+pub const QUEEN_DEFAULT_HP: i32 = 6;
+// Radians per second around the figure eight.
+const FLIGHT_SPEED: f32 = 0.8;
+const SUMMON_PERIOD: f32 = 6.0;
+const BEES_PER_WAVE: usize = 4;
+const DIVE_PERIOD: f32 = 4.5;
+// How long the queen shakes in place before diving, so the player can get out of the way.
+const TELEGRAPH_TIME: f32 = 0.9;
+const DIVE_SPEED: f32 = 22.0;
+const RETURN_SPEED: f32 = 8.0;
+const HIT_INVULNERABILITY: f32 = 1.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum QueenState {
+  Flying,
+  // Winding up to dive at `target`.
+  Telegraph { target: Vec2, time_left: f32 },
+  Diving { target: Vec2 },
+  // Heading back to where she left the figure eight.
+  Returning,
+}
+
+/// The giant bee queen miniboss, flying around the arena she was placed in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BeeQueen {
+  pub arena:        Rect,
+  pub position:     Vec2,
+  pub hp:           i32,
+  // What she spawned with, for her health bar.
+  pub max_hp:       i32,
+  pub state:        QueenState,
+  pub entity_id:    EntityId,
+  // The power-up left behind when she's defeated.
+  pub drops:        PowerUp,
+  phase:            f32,
+  summon_cooldown:  f32,
+  dive_cooldown:    f32,
+  pub invulnerable: f32,
+}
+
+/// What the game needs to do for the queen this frame.
+#[derive(Debug, Default)]
+pub struct QueenUpdate {
+  pub bees_to_summon: usize,
+}
+
+impl BeeQueen {
+  pub fn new(arena: Rect, hp: i32, drops: PowerUp, entity_id: EntityId) -> Self {
+    let mut queen = Self {
+      arena,
+      position: Vec2::default(),
+      hp,
+      max_hp: hp,
+      state: QueenState::Flying,
+      entity_id,
+      drops,
+      phase: 0.0,
+      summon_cooldown: SUMMON_PERIOD / 2.0,
+      dive_cooldown: DIVE_PERIOD,
+      invulnerable: 0.0,
+    };
+    queen.position = queen.flight_point();
+    queen
+  }
+
+  /// Where she is on the figure eight, which fills most of the arena.
+  fn flight_point(&self) -> Vec2 {
+    let center = self.arena.pos + 0.5 * self.arena.size;
+    let reach = Vec2(
+      (0.5 * self.arena.size.0 - QUEEN_RADIUS).max(0.0),
+      (0.5 * self.arena.size.1 - QUEEN_RADIUS).max(0.0),
+    );
+    center
+      + Vec2(
+        reach.0 * self.phase.sin(),
+        reach.1 * (2.0 * self.phase).sin(),
+      )
+  }
+
+  fn clamp_to_arena(&self, p: Vec2) -> Vec2 {
+    let min = self.arena.pos + Vec2(QUEEN_RADIUS, QUEEN_RADIUS);
+    let max = self.arena.max_corner() - Vec2(QUEEN_RADIUS, QUEEN_RADIUS);
+    Vec2(
+      p.0.clamp(min.0, max.0.max(min.0)),
+      p.1.clamp(min.1, max.1.max(min.1)),
+    )
+  }
+
+  /// Moves toward `target` at `speed`, returning whether she got there.
+  fn move_toward(&mut self, target: Vec2, speed: f32, dt: f32) -> bool {
+    let delta = target - self.position;
+    if delta.length() <= speed * dt {
+      self.position = target;
+      return true;
+    }
+    self.position += (speed * dt / delta.length()) * delta;
+    false
+  }
+
+  pub fn update(&mut self, dt: f32, player_pos: Vec2) -> QueenUpdate {
+    let mut update = QueenUpdate::default();
+    self.invulnerable = (self.invulnerable - dt).max(0.0);
+    match &mut self.state {
+      QueenState::Flying => {
+        self.phase += FLIGHT_SPEED * dt;
+        self.position = self.flight_point();
+        self.summon_cooldown -= dt;
+        if self.summon_cooldown <= 0.0 {
+          self.summon_cooldown = SUMMON_PERIOD;
+          update.bees_to_summon = BEES_PER_WAVE;
+        }
+        // Only dive when the player is actually in the arena.
+        self.dive_cooldown -= dt;
+        if self.dive_cooldown <= 0.0 && self.arena.contains_point(player_pos) {
+          self.dive_cooldown = DIVE_PERIOD;
+          self.state = QueenState::Telegraph {
+            target:    self.clamp_to_arena(player_pos),
+            time_left: TELEGRAPH_TIME,
+          };
+        }
+      }
+      QueenState::Telegraph { target, time_left } => {
+        *time_left -= dt;
+        if *time_left <= 0.0 {
+          self.state = QueenState::Diving { target: *target };
+        }
+      }
+      QueenState::Diving { target } => {
+        let target = *target;
+        if self.move_toward(target, DIVE_SPEED, dt) {
+          self.state = QueenState::Returning;
+        }
+      }
+      QueenState::Returning => {
+        if self.move_toward(self.flight_point(), RETURN_SPEED, dt) {
+          self.state = QueenState::Flying;
+        }
+      }
+    }
+    update
+  }
+
+  /// Takes a hit, unless she was just hit. Returns whether that defeated her.
+  pub fn hit(&mut self) -> bool {
+    if self.invulnerable > 0.0 {
+      return false;
+    }
+    self.hp -= 1;
+    self.invulnerable = HIT_INVULNERABILITY;
+    self.hp <= 0
+  }
+
+  pub fn telegraphing(&self) -> Option<Vec2> {
+    match self.state {
+      QueenState::Telegraph { target, .. } => Some(target),
+      _ => None,
+    }
+  }
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{math::Vec2, CharState, EntityId, GameObjectData};
+
+// Hits from the player's shots it takes to break a block with no hp property. Dashes
+// always break blocks outright.
+pub const DEFAULT_HP: i32 = 1;
+// How long a broken block takes to crumble away, in seconds.
+pub const CRUMBLE_TIME: f32 = 0.4;
+// The player stops just short of a block they dash into, so dashes reach this much further.
+pub const DASH_REACH: f32 = 0.15;
+// The same size as coins placed in the map.
+pub const DROP_RADIUS: f32 = 0.45;
+
+/// What a breakable block leaves behind when it breaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlockDrop {
+  Coin,
+  RareCoin,
+}
+
+impl BlockDrop {
+  pub fn from_property(name: &str) -> Option<Self> {
+    match name {
+      "coin" => Some(BlockDrop::Coin),
+      "rare_coin" => Some(BlockDrop::RareCoin),
+      _ => None,
+    }
+  }
+
+  /// The pickup a block drops, sharing the block's entity id, so picking it up is saved
+  /// like any other coin.
+  pub fn pickup(self, entity_id: EntityId) -> GameObjectData {
+    match self {
+      BlockDrop::Coin => GameObjectData::Coin { entity_id },
+      BlockDrop::RareCoin => GameObjectData::RareCoin { entity_id },
+    }
+  }
+
+  pub fn is_collected(self, char_state: &CharState, entity_id: EntityId) -> bool {
+    match self {
+      BlockDrop::Coin => char_state.coins.contains(&entity_id),
+      BlockDrop::RareCoin => char_state.rare_coins.contains(&entity_id),
+    }
+  }
+}
+
+/// The four quarters a block falls apart into, as (center offset, size) pairs, `progress`
+/// of the way through crumbling. They spread out and shrink as they fall.
+pub fn crumble_pieces(progress: f32) -> [(Vec2, f32); 4] {
+  let progress = progress.clamp(0.0, 1.0);
+  let size = 0.45 * (1.0 - 0.5 * progress);
+  [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)].map(|(x, y)| {
+    let spread = 0.25 + 0.4 * progress;
+    // The top quarters fall a little slower, so the pieces tumble apart rather than in a grid.
+    let fall = (1.5 - 0.3 * y) * progress * progress;
+    (Vec2(x * spread, y * 0.25 + fall), size)
+  })
+}
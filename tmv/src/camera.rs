@@ -1,43 +1,431 @@
-use crate::{game_maps::GameMap, math::Vec2, tile_rendering::TILE_SIZE};
+use serde::Serialize;
+use wasm_bindgen::JsValue;
+
+use crate::{
+  game_maps::GameMap,
+  math::{batch, geometry::segment_intersection, Rect, Vec2},
+  tile_rendering::TILE_SIZE,
+  tuning::CameraTuning,
+  SCREEN_HEIGHT, SCREEN_WIDTH,
+};
+
+// How quickly, per second, the camera eases toward its focus offset.
+pub const FOCUS_BLEND_RATE: f32 = 3.0;
+// Holding up or down while standing still for this long starts peeking.
+pub const PEEK_DELAY: f32 = 0.6;
+// How far, in tiles, peeking moves the camera, and how quickly per second it gets there.
+pub const PEEK_DISTANCE: f32 = 6.0;
+pub const PEEK_BLEND_RATE: f32 = 4.0;
+// Horizontal speeds below this count as standing still for peeking.
+pub const PEEK_MAX_SPEED: f32 = 0.5;
+// How quickly, per second, the camera eases into where the camera bounds push it, so
+// crossing into a differently shaped region pans rather than cuts.
+pub const BOUNDS_BLEND_RATE: f32 = 6.0;
+// How quickly, per second, the camera eases toward following the player, so dashes and
+// falls don't yank the view along with them.
+pub const FOLLOW_BLEND_RATE: f32 = 10.0;
+// How far ahead of the player, in tiles, the camera looks once they're running at
+// LOOKAHEAD_FULL_SPEED (in tiles per second), and how quickly per second it shifts over.
+pub const LOOKAHEAD_DISTANCE: f32 = 3.0;
+pub const LOOKAHEAD_FULL_SPEED: f32 = 15.0;
+pub const LOOKAHEAD_BLEND_RATE: f32 = 2.0;
+// The player can move this far up or down from the height the camera is tracking, in tiles,
+// before it follows, so small jumps don't bounce the view.
+pub const VERTICAL_DEADZONE: f32 = 1.5;
+
+/// The mapping between world space and screen pixels for one view: the game, or the map
+/// screen. Everything drawn in world space should go through this, rather than redoing the
+/// math at each draw site.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+  // The world position at the top left of the screen, before shake.
+  pub pos:   Vec2,
+  // Screen pixels per world unit, on each axis.
+  pub scale: Vec2,
+  // An offset in screen pixels, for screen shake.
+  pub shake: Vec2,
+}
+
+impl Camera {
+  /// The normal game view, with `pos` at the top left corner, `zoom` times closer than usual.
+  pub fn game_view(pos: Vec2, zoom: f32) -> Self {
+    Self {
+      pos,
+      scale: Vec2(zoom * TILE_SIZE, zoom * TILE_SIZE),
+      shake: Vec2::default(),
+    }
+  }
+
+  /// The map screen view of `bounds`, which at a zoom of 1 fills the screen (stretching as
+  /// needed), centered on `center_uv` (0 to 1 across the bounds on each axis).
+  pub fn map_view(bounds: Rect, center_uv: Vec2, zoom: f32) -> Self {
+    let top_left_uv = center_uv - Vec2(0.5 / zoom, 0.5 / zoom);
+    Self {
+      pos:   bounds.pos + Vec2(top_left_uv.0 * bounds.size.0, top_left_uv.1 * bounds.size.1),
+      scale: Vec2(
+        zoom * SCREEN_WIDTH / bounds.size.0,
+        zoom * SCREEN_HEIGHT / bounds.size.1,
+      ),
+      shake: Vec2::default(),
+    }
+  }
+
+  pub fn with_shake(self, shake: Vec2) -> Self {
+    Self { shake, ..self }
+  }
+
+  pub fn world_to_screen(&self, world: Vec2) -> Vec2 {
+    let delta = world - self.pos;
+    Vec2(self.scale.0 * delta.0, self.scale.1 * delta.1) + self.shake
+  }
+
+  /// `world_to_screen` for a whole slice of points at once, into `out`. Only for views that
+  /// scale both axes the same, like the game view.
+  pub fn points_to_screen(&self, points: &[Vec2], out: &mut [Vec2]) {
+    debug_assert_eq!(self.scale.0, self.scale.1);
+    let scale = self.scale.0;
+    batch::transform_points(points, self.pos - self.shake / scale, scale, out);
+  }
+
+  pub fn screen_to_world(&self, screen: Vec2) -> Vec2 {
+    let delta = screen - self.shake;
+    self.pos + Vec2(delta.0 / self.scale.0, delta.1 / self.scale.1)
+  }
+
+  /// A world space size in screen pixels.
+  pub fn size_to_screen(&self, size: Vec2) -> Vec2 {
+    Vec2(self.scale.0 * size.0, self.scale.1 * size.1)
+  }
+
+  /// A world space length in screen pixels, for things like radii. Views that stretch
+  /// (like the map screen) use the horizontal scale.
+  pub fn length_to_screen(&self, length: f32) -> f32 {
+    self.scale.0 * length
+  }
+
+  pub fn rect_to_screen(&self, rect: Rect) -> Rect {
+    Rect {
+      pos:  self.world_to_screen(rect.pos),
+      size: self.size_to_screen(rect.size),
+    }
+  }
+
+  /// The part of the world that's on screen.
+  pub fn view_rect(&self) -> Rect {
+    let top_left = self.screen_to_world(Vec2(0.0, 0.0));
+    Rect {
+      pos:  top_left,
+      size: self.screen_to_world(Vec2(SCREEN_WIDTH, SCREEN_HEIGHT)) - top_left,
+    }
+  }
+}
+
+/// Where the camera centers while following the player, before any focus, peek, or bounds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CameraFollow {
+  pub center: Vec2,
+  // How far the camera is looking ahead horizontally, eased.
+  lookahead:  f32,
+  // The height the camera is tracking, which only moves once the player leaves the deadzone
+  // around it, or lands somewhere.
+  tracked_y:  f32,
+}
+
+impl CameraFollow {
+  /// Centers right on the player, for when they appear somewhere new.
+  pub fn snap(&mut self, player_pos: Vec2) {
+    *self = Self {
+      center:    player_pos,
+      lookahead: 0.0,
+      tracked_y: player_pos.1,
+    };
+  }
+
+  /// Eases toward the player, looking ahead the way they're going (`facing` is -1 for left
+  /// and 1 for right).
+  pub fn update(
+    &mut self,
+    tuning: &CameraTuning,
+    dt: f32,
+    player_pos: Vec2,
+    velocity: Vec2,
+    facing: f32,
+    grounded: bool,
+  ) {
+    let speed_fraction = (velocity.0.abs() / tuning.lookahead_full_speed).min(1.0);
+    let lookahead_target = facing * tuning.lookahead_distance * speed_fraction;
+    self.lookahead +=
+      (1.0 - (-tuning.lookahead_blend_rate * dt).exp()) * (lookahead_target - self.lookahead);
+    self.tracked_y = match grounded {
+      true => player_pos.1,
+      false => self.tracked_y.clamp(
+        player_pos.1 - tuning.vertical_deadzone,
+        player_pos.1 + tuning.vertical_deadzone,
+      ),
+    };
+    let target = Vec2(player_pos.0 + self.lookahead, self.tracked_y);
+    self.center += (1.0 - (-tuning.follow_blend_rate * dt).exp()) * (target - self.center);
+  }
+}
+
+/// A point of interest from a camera_focus rect. While the player is within `radius`
+/// of it, the camera is pulled `weight` of the way from the player toward it.
+#[derive(Debug, Clone)]
+pub struct CameraFocus {
+  pub center: Vec2,
+  pub weight: f32,
+  pub radius: f32,
+}
+
+/// Where the camera should center relative to the player, given all the focuses. The pull
+/// fades out smoothly toward the edge of each focus's radius, so walking in and out of range
+/// doesn't jolt the camera, and overlapping focuses share the pull by weight.
+pub fn focus_offset(focuses: &[CameraFocus], player_pos: Vec2) -> Vec2 {
+  let mut total_influence = 0.0;
+  let mut offset = Vec2::default();
+  for focus in focuses {
+    let delta = focus.center - player_pos;
+    let closeness = 1.0 - delta.length() / focus.radius;
+    if closeness <= 0.0 {
+      continue;
+    }
+    let influence = focus.weight * closeness * closeness * (3.0 - 2.0 * closeness);
+    offset += influence * delta;
+    total_influence += influence;
+  }
+  match total_influence > 1.0 {
+    true => offset / total_influence,
+    false => offset,
+  }
+}
 
 pub struct Boundary {
   pub a: Vec2,
   pub b: Vec2,
 }
 
+impl Boundary {
+  /// Whether any part of the segment lies within the rect.
+  pub fn touches_rect(&self, rect: Rect) -> bool {
+    if rect.contains_point(self.a) || rect.contains_point(self.b) {
+      return true;
+    }
+    let corners = [
+      rect.pos,
+      rect.pos + Vec2(rect.size.0, 0.0),
+      rect.max_corner(),
+      rect.pos + Vec2(0.0, rect.size.1),
+    ];
+    (0..4).any(|i| segment_intersection(self.a, self.b, corners[i], corners[(i + 1) % 4]).is_some())
+  }
+}
+
+/// One polyline or polygon from the CameraBounds layer, in world coordinates. Polygons
+/// don't repeat their first point at the end.
+pub struct CameraPolyline {
+  pub points: Vec<Vec2>,
+  pub closed: bool,
+}
+
+/// A polyline as a Tiled object would have it: in pixels, with points relative to the object.
+#[derive(Serialize)]
+struct TiledPolyline {
+  x:      f32,
+  y:      f32,
+  closed: bool,
+  points: Vec<(f32, f32)>,
+}
+
 pub struct CameraBounds {
+  pub polylines:  Vec<CameraPolyline>,
+  // The segments of all the polylines, kept in sync by `rebuild_boundaries`.
   pub boundaries: Vec<Boundary>,
 }
 
 impl CameraBounds {
+  /// Maps without a CameraBounds layer get no bounds.
   pub fn from_game_map(game_map: &GameMap) -> Self {
-    let layer = game_map.map.layers().find(|l| l.name == "CameraBounds").unwrap();
-    let mut boundaries = Vec::new();
-
-    match layer.layer_type() {
-      tiled::LayerType::ObjectLayer(object_layer) => {
-        for object in object_layer.objects() {
-          match &object.shape {
-            tiled::ObjectShape::Polyline { points } | tiled::ObjectShape::Polygon { points } => {
-              let mut points =
-                points.iter().map(|p| (p.0 / TILE_SIZE, p.1 / TILE_SIZE)).collect::<Vec<_>>();
-              // If the shape is a polygon, we close it.
-              if let tiled::ObjectShape::Polygon { .. } = object.shape {
-                points.push(points[0]);
-              }
-              for i in 0..points.len() - 1 {
-                boundaries.push(Boundary {
-                  a: Vec2(points[i].0, points[i].1),
-                  b: Vec2(points[i + 1].0, points[i + 1].1),
-                });
+    let mut polylines = Vec::new();
+    if let Some(layer) = game_map.map.layers().find(|l| l.name == "CameraBounds") {
+      match layer.layer_type() {
+        tiled::LayerType::ObjectLayer(object_layer) => {
+          for object in object_layer.objects() {
+            match &object.shape {
+              tiled::ObjectShape::Polyline { points } | tiled::ObjectShape::Polygon { points } => {
+                // Points are relative to the object's position.
+                let points = points
+                  .iter()
+                  .map(|p| Vec2((object.x + p.0) / TILE_SIZE, (object.y + p.1) / TILE_SIZE))
+                  .collect();
+                let closed = matches!(object.shape, tiled::ObjectShape::Polygon { .. });
+                polylines.push(CameraPolyline { points, closed });
               }
+              _ => panic!("Unsupported object shape: {:?}", object.shape),
             }
-            _ => panic!("Unsupported object shape: {:?}", object.shape),
           }
         }
+        _ => panic!("Unsupported layer type"),
+      }
+    }
+    let mut bounds = Self {
+      polylines,
+      boundaries: Vec::new(),
+    };
+    bounds.rebuild_boundaries();
+    bounds
+  }
+
+  fn rebuild_boundaries(&mut self) {
+    self.boundaries.clear();
+    for polyline in &self.polylines {
+      let points = &polyline.points;
+      let segment_count = match polyline.closed {
+        true => points.len(),
+        false => points.len().saturating_sub(1),
+      };
+      for i in 0..segment_count {
+        self.boundaries.push(Boundary {
+          a: points[i],
+          b: points[(i + 1) % points.len()],
+        });
       }
-      _ => panic!("Unsupported layer type"),
     }
-    Self { boundaries }
+  }
+
+  /// The first boundary crossed moving from `from` to `to`, with the fraction of the way
+  /// along the movement where it's crossed.
+  pub fn first_crossing(&self, from: Vec2, to: Vec2) -> Option<(f32, &Boundary)> {
+    self
+      .boundaries
+      .iter()
+      .filter_map(|boundary| {
+        segment_intersection(from, to, boundary.a, boundary.b).map(|(t, _)| (t, boundary))
+      })
+      .min_by(|(t1, _), (t2, _)| t1.total_cmp(t2))
+  }
+
+  /// How far the camera can actually get moving from `from` toward `to`: stopping just
+  /// short of the first boundary in the way, or all the way there if nothing is.
+  pub fn clamp_movement(&self, from: Vec2, to: Vec2) -> Vec2 {
+    match self.first_crossing(from, to) {
+      Some((t, _)) => from + (t - 0.01).max(0.0) * (to - from),
+      None => to,
+    }
+  }
+
+  /// Moves `view` so that no boundary cuts through it, treating each boundary as vertical or
+  /// horizontal by whichever way it mostly runs, and as a wall on the far side of `anchor`
+  /// (the player) that the view stops at. If walls on both sides are closer together than
+  /// the view is wide, the view is centered between them.
+  pub fn constrain_view(&self, view: Rect, anchor: Vec2) -> Rect {
+    // The furthest each edge of the view can go: (min x, max x), then (min y, max y).
+    let mut limits = [
+      (f32::NEG_INFINITY, f32::INFINITY),
+      (f32::NEG_INFINITY, f32::INFINITY),
+    ];
+    for boundary in self.boundaries_in_view(view) {
+      let delta = boundary.b - boundary.a;
+      let midpoint = Vec2(
+        (boundary.a.0 + boundary.b.0) / 2.0,
+        (boundary.a.1 + boundary.b.1) / 2.0,
+      );
+      let (axis, wall, anchor) = match delta.1.abs() >= delta.0.abs() {
+        true => (0, midpoint.0, anchor.0),
+        false => (1, midpoint.1, anchor.1),
+      };
+      let (min, max) = &mut limits[axis];
+      if wall < anchor {
+        *min = min.max(wall);
+      } else if wall > anchor {
+        *max = max.min(wall);
+      }
+    }
+    let constrain = |pos: f32, size: f32, (min, max): (f32, f32)| match max - min < size {
+      true => (min + max - size) / 2.0,
+      false => pos.max(min).min(max - size),
+    };
+    Rect {
+      pos:  Vec2(
+        constrain(view.pos.0, view.size.0, limits[0]),
+        constrain(view.pos.1, view.size.1, limits[1]),
+      ),
+      size: view.size,
+    }
+  }
+
+  /// The boundaries that can currently constrain a camera showing `view`.
+  pub fn boundaries_in_view(&self, view: Rect) -> impl Iterator<Item = &Boundary> {
+    self.boundaries.iter().filter(move |boundary| boundary.touches_rect(view))
+  }
+
+  /// The (polyline, point) index of the vertex closest to `pos`, if any is within `max_distance`.
+  pub fn nearest_vertex(&self, pos: Vec2, max_distance: f32) -> Option<(usize, usize)> {
+    self
+      .polylines
+      .iter()
+      .enumerate()
+      .flat_map(|(i, polyline)| {
+        polyline
+          .points
+          .iter()
+          .enumerate()
+          .map(move |(j, point)| ((i, j), (*point - pos).length()))
+      })
+      .filter(|(_, distance)| *distance <= max_distance)
+      .min_by(|(_, d1), (_, d2)| d1.total_cmp(d2))
+      .map(|(vertex, _)| vertex)
+  }
+
+  pub fn move_vertex(&mut self, (polyline, point): (usize, usize), pos: Vec2) {
+    self.polylines[polyline].points[point] = pos;
+    self.rebuild_boundaries();
+  }
+
+  /// The polylines as JSON, in the form Tiled stores them, for copying tuned bounds back into the map.
+  pub fn to_tiled_json(&self) -> String {
+    let polylines: Vec<TiledPolyline> = self
+      .polylines
+      .iter()
+      .filter(|polyline| !polyline.points.is_empty())
+      .map(|polyline| {
+        let origin = TILE_SIZE * polyline.points[0];
+        TiledPolyline {
+          x:      origin.0,
+          y:      origin.1,
+          closed: polyline.closed,
+          points: polyline
+            .points
+            .iter()
+            .map(|p| {
+              let relative = TILE_SIZE * *p - origin;
+              (relative.0, relative.1)
+            })
+            .collect(),
+        }
+      })
+      .collect();
+    serde_json::to_string_pretty(&polylines).unwrap()
+  }
+
+  /// Draws every boundary, with the ones touching the view highlighted, and the vertices.
+  pub fn draw(&self, ctx: &web_sys::CanvasRenderingContext2d, camera: &Camera, view: Rect) {
+    for boundary in &self.boundaries {
+      let active = boundary.touches_rect(view);
+      let (a, b) = (
+        camera.world_to_screen(boundary.a),
+        camera.world_to_screen(boundary.b),
+      );
+      ctx.set_stroke_style(&JsValue::from_str(if active { "#f0f" } else { "#808" }));
+      ctx.set_line_width(if active { 3.0 } else { 1.0 });
+      ctx.begin_path();
+      ctx.move_to(a.0 as f64, a.1 as f64);
+      ctx.line_to(b.0 as f64, b.1 as f64);
+      ctx.stroke();
+    }
+    ctx.set_fill_style(&JsValue::from_str("#f0f"));
+    for point in self.polylines.iter().flat_map(|polyline| &polyline.points) {
+      let p = camera.world_to_screen(*point);
+      ctx.fill_rect(p.0 as f64 - 3.0, p.1 as f64 - 3.0, 6.0, 6.0);
+    }
   }
 }
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+/// What the player gets for crossing a challenge room without taking damage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChallengeReward {
+  RareCoin,
+  Achievement,
+}
+
+impl ChallengeReward {
+  pub fn from_property(name: &str) -> Option<Self> {
+    match name {
+      "rare_coin" => Some(ChallengeReward::RareCoin),
+      "achievement" => Some(ChallengeReward::Achievement),
+      _ => None,
+    }
+  }
+}
+
+/// Damage accounting for one challenge room the player is currently inside.
+#[derive(Debug, Clone, Default)]
+pub struct ChallengeRun {
+  pub damage_taken: i32,
+}
+
+impl ChallengeRun {
+  pub fn is_flawless(&self) -> bool {
+    self.damage_taken == 0
+  }
+}
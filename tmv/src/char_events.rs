@@ -0,0 +1,240 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{math::Vec2, power_ups::PowerUp, CharState, EntityId};
+
+// Saves keep this many of the most recent events, which is plenty to see how they got there.
+const MAX_SAVED_HISTORY: usize = 256;
+
+/// One change to the player's progress. Every change to a CharState goes through one of
+/// these, so the log of them explains how a save came to be the way it is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CharEvent {
+  Coin {
+    entity_id: EntityId,
+  },
+  RareCoin {
+    entity_id: EntityId,
+  },
+  HpUp {
+    entity_id: EntityId,
+  },
+  PowerUp {
+    power_up: PowerUp,
+  },
+  Damage {
+    amount: i32,
+  },
+  Save {
+    // Missing from events logged before there was more than one map.
+    #[serde(default = "crate::default_save_map")]
+    save_map:   String,
+    save_point: Vec2,
+  },
+  Flag {
+    name: String,
+  },
+  Challenge {
+    name: String,
+  },
+  Secret {
+    name: String,
+  },
+  BlockBroken {
+    entity_id: EntityId,
+  },
+  HiveDestroyed {
+    entity_id: EntityId,
+  },
+  Key {
+    entity_id: EntityId,
+    color:     String,
+  },
+  // Uses up one of the player's keys of the door's color.
+  DoorOpened {
+    entity_id: EntityId,
+    color:     String,
+  },
+}
+
+impl CharEvent {
+  pub fn is_pickup(&self) -> bool {
+    matches!(
+      self,
+      CharEvent::Coin { .. }
+        | CharEvent::RareCoin { .. }
+        | CharEvent::HpUp { .. }
+        | CharEvent::PowerUp { .. }
+        | CharEvent::Key { .. }
+    )
+  }
+
+  // Returns whether the event added something that wasn't there before.
+  fn apply(&self, state: &mut CharState) -> bool {
+    match self {
+      CharEvent::Coin { entity_id } => state.coins.insert(*entity_id),
+      CharEvent::RareCoin { entity_id } => state.rare_coins.insert(*entity_id),
+      CharEvent::HpUp { entity_id } => {
+        let inserted = state.hp_ups.insert(*entity_id);
+        state.reset_hp();
+        inserted
+      }
+      CharEvent::PowerUp { power_up } => state.power_ups.insert(*power_up),
+      CharEvent::Damage { amount } => {
+        state.hp.set(state.hp.get() - amount);
+        false
+      }
+      CharEvent::Save {
+        save_map,
+        save_point,
+      } => {
+        state.save_map = save_map.clone();
+        state.save_point = *save_point;
+        state.reset_hp();
+        false
+      }
+      CharEvent::Flag { name } => state.flags.insert(name.clone()),
+      CharEvent::Challenge { name } => state.completed_challenges.insert(name.clone()),
+      CharEvent::Secret { name } => state.secrets.insert(name.clone()),
+      CharEvent::BlockBroken { entity_id } => state.broken_blocks.insert(*entity_id),
+      CharEvent::HiveDestroyed { entity_id } => state.destroyed_hives.insert(*entity_id),
+      CharEvent::Key { entity_id, color } => state.keys.insert(*entity_id, color.clone()).is_none(),
+      CharEvent::DoorOpened { entity_id, color } => {
+        state.opened_doors.insert(*entity_id, color.clone()).is_none()
+      }
+    }
+  }
+
+  /// Takes back whatever the event added, leaving hp and the save point alone.
+  pub fn revert_insert(&self, state: &mut CharState) {
+    match self {
+      CharEvent::Coin { entity_id } => {
+        state.coins.remove(entity_id);
+      }
+      CharEvent::RareCoin { entity_id } => {
+        state.rare_coins.remove(entity_id);
+      }
+      CharEvent::HpUp { entity_id } => {
+        state.hp_ups.remove(entity_id);
+      }
+      CharEvent::PowerUp { power_up } => {
+        state.power_ups.remove(power_up);
+      }
+      CharEvent::Flag { name } => {
+        state.flags.remove(name);
+      }
+      CharEvent::Challenge { name } => {
+        state.completed_challenges.remove(name);
+      }
+      CharEvent::Secret { name } => {
+        state.secrets.remove(name);
+      }
+      CharEvent::BlockBroken { entity_id } => {
+        state.broken_blocks.remove(entity_id);
+      }
+      CharEvent::HiveDestroyed { entity_id } => {
+        state.destroyed_hives.remove(entity_id);
+      }
+      CharEvent::Key { entity_id, .. } => {
+        state.keys.remove(entity_id);
+      }
+      CharEvent::DoorOpened { entity_id, .. } => {
+        state.opened_doors.remove(entity_id);
+      }
+      CharEvent::Damage { .. } | CharEvent::Save { .. } => {}
+    }
+  }
+}
+
+// What an event overwrote, so that it can be undone.
+#[derive(Debug, Clone)]
+struct Undo {
+  hp:         i32,
+  save_map:   String,
+  save_point: Vec2,
+  inserted:   bool,
+}
+
+/// The events applied this session, on top of the history loaded with the save.
+#[derive(Debug, Default)]
+pub struct CharEventLog {
+  history:   Vec<CharEvent>,
+  entries:   Vec<(CharEvent, Undo)>,
+  // How many of the entries made it into the saved state. Respawning drops the rest.
+  saved_len: usize,
+}
+
+impl CharEventLog {
+  pub fn from_history(history: Vec<CharEvent>) -> Self {
+    Self {
+      history,
+      ..Default::default()
+    }
+  }
+
+  /// Applies the event to `state`, logging it if it changed anything.
+  pub fn apply(&mut self, state: &mut CharState, event: CharEvent) {
+    let undo = Undo {
+      hp:         state.hp.get(),
+      save_map:   state.save_map.clone(),
+      save_point: state.save_point,
+      inserted:   event.apply(state),
+    };
+    if undo.inserted
+      || undo.hp != state.hp.get()
+      || undo.save_map != state.save_map
+      || undo.save_point != state.save_point
+    {
+      self.entries.push((event, undo));
+    }
+  }
+
+  /// Undoes the most recent event this session, putting `state` back how it was before it.
+  pub fn undo_last(&mut self, state: &mut CharState) -> Option<CharEvent> {
+    let (event, undo) = self.entries.pop()?;
+    if undo.inserted {
+      event.revert_insert(state);
+    }
+    state.hp.set(undo.hp);
+    state.save_map = undo.save_map;
+    state.save_point = undo.save_point;
+    self.saved_len = self.saved_len.min(self.entries.len());
+    Some(event)
+  }
+
+  /// Takes back the most recent pickup this session, even if other events came after it.
+  pub fn undo_last_pickup(&mut self, state: &mut CharState) -> Option<CharEvent> {
+    let index = self.entries.iter().rposition(|(event, _)| event.is_pickup())?;
+    let (event, undo) = self.entries.remove(index);
+    if undo.inserted {
+      event.revert_insert(state);
+    }
+    // Losing an hp up can leave the player with more hp than they can have.
+    state.hp.set(state.hp.get().min(state.hp_ups.len() as i32 + 1));
+    if index < self.saved_len {
+      self.saved_len -= 1;
+    }
+    Some(event)
+  }
+
+  pub fn mark_saved(&mut self) {
+    self.saved_len = self.entries.len();
+  }
+
+  pub fn revert_to_saved(&mut self) {
+    self.entries.truncate(self.saved_len);
+  }
+
+  /// Everything that happened, oldest first, including events since the last save.
+  pub fn events(&self) -> impl Iterator<Item = &CharEvent> {
+    self.history.iter().chain(self.entries.iter().map(|(event, _)| event))
+  }
+
+  /// The most recent events that are part of the saved state, to be stored with the save.
+  pub fn saved_history(&self) -> Vec<CharEvent> {
+    let saved =
+      self.history.iter().chain(self.entries[..self.saved_len].iter().map(|(event, _)| event));
+    let count = self.history.len() + self.saved_len;
+    saved.skip(count.saturating_sub(MAX_SAVED_HISTORY)).cloned().collect()
+  }
+}
@@ -0,0 +1,151 @@
+use web_sys::CanvasRenderingContext2d;
+
+use crate::{
+  camera::Camera,
+  math::{Rect, Vec2},
+  theme::Theme,
+};
+
+/// How fast a chase moves when its path doesn't say, in tiles per second.
+pub const DEFAULT_SPEED: f32 = 6.0;
+// How far ahead of the player the camera looks, in tiles, along the way the chase is going.
+const CAMERA_LEAD: f32 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChaseKind {
+  CrushingWall,
+  LavaWave,
+}
+
+impl ChaseKind {
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "wall" => Some(ChaseKind::CrushingWall),
+      "lava" => Some(ChaseKind::LavaWave),
+      _ => None,
+    }
+  }
+
+  fn style_name(self) -> &'static str {
+    match self {
+      ChaseKind::CrushingWall => "chase_wall",
+      ChaseKind::LavaWave => "chase_lava",
+    }
+  }
+}
+
+/// A hazard that follows a path through the map once its chase_start rect is touched, as
+/// defined by a chase polyline.
+#[derive(Debug, Clone)]
+pub struct ChasePath {
+  pub kind:   ChaseKind,
+  // The route the middle of the hazard takes, in tiles.
+  pub points: Vec<Vec2>,
+  // In tiles per second.
+  pub speed:  f32,
+  // The width and height of the hazard, in tiles.
+  pub size:   Vec2,
+}
+
+impl ChasePath {
+  pub fn length(&self) -> f32 {
+    self.points.windows(2).map(|w| (w[1] - w[0]).length()).sum()
+  }
+
+  /// Where the middle of the hazard is `distance` tiles along the path, and which way it's
+  /// heading there. Past the end it stays at the last point.
+  pub fn point_at(&self, distance: f32) -> (Vec2, Vec2) {
+    let mut remaining = distance.max(0.0);
+    let mut heading = Vec2(1.0, 0.0);
+    for w in self.points.windows(2) {
+      let segment = w[1] - w[0];
+      let length = segment.length();
+      if length == 0.0 {
+        continue;
+      }
+      heading = segment / length;
+      if remaining <= length {
+        return (w[0] + remaining * heading, heading);
+      }
+      remaining -= length;
+    }
+    (*self.points.last().unwrap_or(&Vec2::default()), heading)
+  }
+
+  pub fn hazard_rect(&self, distance: f32) -> Rect {
+    let (center, _) = self.point_at(distance);
+    Rect::new(center - self.size / 2.0, self.size)
+  }
+}
+
+/// The chase currently under way.
+#[derive(Debug, Clone)]
+pub struct Chase {
+  pub name:     String,
+  pub path:     ChasePath,
+  // How far along the path the hazard has got, in tiles.
+  pub distance: f32,
+}
+
+impl Chase {
+  pub fn new(name: String, path: ChasePath) -> Self {
+    Self {
+      name,
+      path,
+      distance: 0.0,
+    }
+  }
+
+  pub fn update(&mut self, dt: f32) {
+    self.distance = (self.distance + self.path.speed * dt).min(self.path.length());
+  }
+
+  pub fn hazard_rect(&self) -> Rect {
+    self.path.hazard_rect(self.distance)
+  }
+
+  /// How far to shift the camera, so the player can see where they're running to.
+  pub fn camera_lead(&self) -> Vec2 {
+    let (_, heading) = self.path.point_at(self.distance);
+    CAMERA_LEAD * heading
+  }
+
+  pub fn draw(&self, ctx: &CanvasRenderingContext2d, camera: &Camera, theme: &Theme, time: f32) {
+    let rect = self.hazard_rect();
+    if !rect.overlaps(camera.view_rect()) {
+      return;
+    }
+    let (_, heading) = self.path.point_at(self.distance);
+    let top_left = camera.world_to_screen(rect.pos);
+    let size = (
+      camera.length_to_screen(rect.size.0) as f64,
+      camera.length_to_screen(rect.size.1) as f64,
+    );
+    theme.style(self.path.kind.style_name()).apply(ctx);
+    ctx.fill_rect(top_left.0 as f64, top_left.1 as f64, size.0, size.1);
+    // The leading edge: spikes for a wall, and a churning crest for lava.
+    let center = rect.pos + rect.size / 2.0;
+    let half = rect.size / 2.0;
+    let front = center + Vec2(heading.0 * half.0, heading.1 * half.1);
+    let across = Vec2(-heading.1, heading.0);
+    let reach = (half.0 * heading.1).abs() + (half.1 * heading.0).abs();
+    let teeth = (2.0 * reach).ceil().max(1.0) as usize;
+    ctx.set_line_width(camera.length_to_screen(0.08) as f64);
+    ctx.begin_path();
+    for i in 0..=2 * teeth {
+      let along = -reach + reach * i as f32 / teeth as f32;
+      let out = match self.path.kind {
+        ChaseKind::CrushingWall => 0.4 * (i % 2) as f32,
+        ChaseKind::LavaWave => 0.2 + 0.15 * (3.0 * along + 6.0 * time).sin(),
+      };
+      let pos = camera.world_to_screen(front + along * across + out * heading);
+      match i {
+        0 => ctx.move_to(pos.0 as f64, pos.1 as f64),
+        _ => ctx.line_to(pos.0 as f64, pos.1 as f64),
+      }
+    }
+    ctx.fill();
+    ctx.stroke();
+    ctx.set_global_alpha(1.0);
+  }
+}
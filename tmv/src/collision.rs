@@ -1,5 +1,4 @@
 use std::{
-  cell::Cell,
   collections::{HashMap, HashSet},
   rc::Rc,
 };
@@ -12,7 +11,28 @@ use rapier2d::{
 use tiled::Chunk;
 
 use crate::{
-  game_maps::GameMap, math::Vec2, tile_rendering::TILE_SIZE, CharState, GameObject, GameObjectData,
+  bee_queen::BeeQueen,
+  breakable::{self, BlockDrop},
+  camera::CameraFocus,
+  challenges::ChallengeReward,
+  chase::{self, ChaseKind, ChasePath},
+  count_gate::Currency,
+  critters::{CritterDensities, CritterZone},
+  enemies::{self, Enemy},
+  entity_defs::{EntityDef, EntityKind},
+  game_maps::{GameMap, Placement},
+  keys,
+  math::{Rect, Vec2},
+  power_ups::PowerUp,
+  progression::{self, Gate},
+  projectiles::ProjectileKind,
+  surfaces::Surface,
+  switches::SwitchHit,
+  tile_rendering::TILE_SIZE,
+  triggers::{self, TriggerAction},
+  water::{self, WaterSurface},
+  wind::{self, WindZone},
+  CharState, GameObject, GameObjectData,
 };
 
 pub enum PhysicsKind {
@@ -22,6 +42,15 @@ pub enum PhysicsKind {
   Sensor,
 }
 
+/// How a pickup spawned during play behaves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickupPhysics {
+  // Hangs in the air where it was spawned, like pickups placed in the map.
+  Floating,
+  // Falls and settles onto walls and platforms, without getting in the player's way.
+  Falling,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct PhysicsObjectHandle {
   pub rigid_body: Option<RigidBodyHandle>,
@@ -34,9 +63,45 @@ pub const PLAYER_GROUP: Group = Group::GROUP_3;
 pub const WATER_GROUP: Group = Group::GROUP_4;
 pub const LAVA_GROUP: Group = Group::GROUP_5;
 pub const PLATFORMS_GROUP: Group = Group::GROUP_6;
+pub const PICKUPS_GROUP: Group = Group::GROUP_7;
 
 pub const BASIC_INT_GROUPS: InteractionGroups = InteractionGroups::new(BASIC_GROUP, Group::ALL);
 pub const WALLS_INT_GROUPS: InteractionGroups = InteractionGroups::new(WALLS_GROUP, Group::ALL);
+// Falling pickups only collide with the things they can rest on. The player's pickup query
+// doesn't filter by group, so they can still be collected.
+pub const PICKUPS_INT_GROUPS: InteractionGroups = InteractionGroups::new(
+  PICKUPS_GROUP,
+  Group::from_bits_truncate(WALLS_GROUP.bits() | PLATFORMS_GROUP.bits()),
+);
+
+// How wide the probe that feels for the tops of ledges is, in tiles.
+const LEDGE_PROBE_WIDTH: f32 = 0.3;
+// Safe standing spots are looked for on a grid this fine, within this far above ground, and
+// this far from any hazard. Each is tried from a little above, in case it's right on the ground.
+const SAFE_STANDING_STEP: f32 = 0.5;
+const SAFE_STANDING_DROP: f32 = 1.5;
+const SAFE_STANDING_LIFT: f32 = 0.25;
+const SAFE_STANDING_MARGIN: f32 = 0.5;
+
+/// The top of a wall the player can hang from, as found by `CollisionWorld::find_ledge`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ledge {
+  // -1 if the wall is to the left, or 1 for the right.
+  pub side:     f32,
+  // The height of the top edge.
+  pub top:      f32,
+  // Where the player ends up after climbing onto it.
+  pub climb_to: Vec2,
+}
+
+/// What a fragment added to the world, as returned by `CollisionWorld::load_fragment`.
+#[derive(Debug, Clone)]
+pub struct PlacedFragment {
+  pub placement:    Placement,
+  pub colliders:    Vec<ColliderHandle>,
+  // The numbers of the fragment's scripted interactions.
+  pub interactions: Vec<i32>,
+}
 
 // We make a struct to hold all the physics objects.
 pub struct CollisionWorld {
@@ -56,6 +121,28 @@ pub struct CollisionWorld {
   pub event_handler:          (), // ChannelEventCollector,
   pub char_controller:        KinematicCharacterController,
   pub spawn_point:            Vec2,
+  pub water_surfaces:         Vec<WaterSurface>,
+  // Where doors from other maps can put the player, by name.
+  pub named_spawns:           HashMap<String, Vec2>,
+  // The middle of every save point, for fast travel from the map screen.
+  pub save_points:            Vec<Vec2>,
+  // Rects (top-left corner and size, in tiles) making up each named map region.
+  pub map_regions:            HashMap<String, Vec<(Vec2, Vec2)>>,
+  pub camera_focuses:         Vec<CameraFocus>,
+  // Where passing between rooms needs a power-up, for working out the way through the map.
+  pub gates:                  Vec<Gate>,
+  pub critter_zones:          Vec<CritterZone>,
+  pub wind_zones:             Vec<WindZone>,
+  // The path each chase's hazard follows, by chase name.
+  pub chase_paths:            HashMap<String, ChasePath>,
+  // Walls made of these tiles can't be wall jumped off.
+  pub slick_cells:            HashSet<(i32, i32)>,
+  // The colliders of the walls made of special surface tiles, like ice.
+  pub surface_colliders:      HashMap<ColliderHandle, Surface>,
+  // The pattern each synchronized vanish block group follows (see vanish.rs).
+  pub vanish_groups:          HashMap<String, Vec<bool>>,
+  // What each interaction does when it goes off, by interaction number (see triggers.rs).
+  pub interaction_scripts:    HashMap<i32, Vec<TriggerAction>>,
   // pub collision_recv:         crossbeam::channel::Receiver<CollisionEvent>,
   // pub contact_force_recv:     crossbeam::channel::Receiver<ContactForceEvent>,
 }
@@ -81,6 +168,19 @@ impl CollisionWorld {
       event_handler:          (), //ChannelEventCollector::new(collision_send, contact_force_send),
       char_controller:        KinematicCharacterController::default(),
       spawn_point:            Vec2::default(),
+      water_surfaces:         Vec::new(),
+      named_spawns:           HashMap::new(),
+      save_points:            Vec::new(),
+      map_regions:            HashMap::new(),
+      camera_focuses:         Vec::new(),
+      gates:                  Vec::new(),
+      critter_zones:          Vec::new(),
+      wind_zones:             Vec::new(),
+      chase_paths:            HashMap::new(),
+      slick_cells:            HashSet::new(),
+      surface_colliders:      HashMap::new(),
+      vanish_groups:          HashMap::new(),
+      interaction_scripts:    HashMap::new(),
       // collision_recv,
       // contact_force_recv,
     }
@@ -92,7 +192,24 @@ impl CollisionWorld {
     game_map: &GameMap,
     objects: &mut HashMap<ColliderHandle, GameObject>,
   ) {
+    self.load_game_map_at(char_state, game_map, Placement::default(), objects);
+  }
+
+  /// Adds everything in the map to the world, wherever `placement` puts it. Walls are only
+  /// generated from this map's own tiles, so maps can be added alongside ones already loaded.
+  pub fn load_game_map_at(
+    &mut self,
+    char_state: &CharState,
+    game_map: &GameMap,
+    placement: Placement,
+    objects: &mut HashMap<ColliderHandle, GameObject>,
+  ) {
+    let offset = placement.offset;
     let mut all_solid_cells = HashSet::new();
+    let mut surface_cells: HashMap<Surface, HashSet<(i32, i32)>> = HashMap::new();
+    let mut water_cells = HashSet::new();
+    let mut lava_cells = HashSet::new();
+    let mut critter_rects = Vec::new();
 
     // The main layer includes some objects, like spikes.
     let main_layer = game_map.map.layers().find(|l| l.name == "Main").unwrap();
@@ -103,8 +220,8 @@ impl CollisionWorld {
             for y in 0..Chunk::HEIGHT as i32 {
               if let Some(tile) = chunk.get_tile(x, y) {
                 let tile_pos = (
-                  chunk_pos.0 * Chunk::WIDTH as i32 + x,
-                  chunk_pos.1 * Chunk::HEIGHT as i32 + y,
+                  chunk_pos.0 * Chunk::WIDTH as i32 + x + offset.0,
+                  chunk_pos.1 * Chunk::HEIGHT as i32 + y + offset.1,
                 );
                 let base_tile = tile.get_tile().unwrap();
                 let user_type: &str = match &base_tile.user_type {
@@ -113,11 +230,22 @@ impl CollisionWorld {
                 };
                 match user_type {
                   "nonsolid" | "marker" => {}
-                  "" => {
+                  _ if is_solid_tile(user_type) => {
                     all_solid_cells.insert(tile_pos);
+                    if let Some(surface) = Surface::from_user_type(user_type) {
+                      surface_cells.entry(surface).or_default().insert(tile_pos);
+                    }
                   }
                   _ => panic!("Unknown user_type: {}", user_type),
                 }
+                match base_tile.properties.get("wall") {
+                  Some(tiled::PropertyValue::StringValue(s)) if s == "slick" => {
+                    self.slick_cells.insert(tile_pos);
+                  }
+                  Some(tiled::PropertyValue::StringValue(s)) if s == "climbable" => {}
+                  Some(_) => panic!("wall must be climbable or slick"),
+                  None => {}
+                }
 
                 let name: &str = match base_tile.properties.get("name") {
                   Some(tiled::PropertyValue::StringValue(s)) => s,
@@ -133,22 +261,32 @@ impl CollisionWorld {
                   )
                 };
                 let mut orientation = Vec2(1.0, 0.0);
+                // The direction the top of the tile faces, for things like springs.
+                let mut up_orientation = Vec2(0.0, -1.0);
                 let mut is_mirrored = false;
                 if tile.flip_d {
                   (orientation.0, orientation.1) = (orientation.1, orientation.0);
+                  (up_orientation.0, up_orientation.1) = (up_orientation.1, up_orientation.0);
                   is_mirrored ^= true;
                 }
                 if tile.flip_v {
                   orientation.1 *= -1.0;
+                  up_orientation.1 *= -1.0;
                   is_mirrored ^= true;
                 }
                 if tile.flip_h {
                   orientation.0 *= -1.0;
+                  up_orientation.0 *= -1.0;
                   is_mirrored ^= true;
                 }
-                let entity_id = 1_000_000 * tile_pos.1 + tile_pos.0;
-                match name {
-                  "coin" | "rare_coin" | "hp_up" => {
+                // Descent rooms can go deep enough for this to wrap around.
+                let entity_id = game_map
+                  .entity_id_offset
+                  .wrapping_add(1_000_000i32.wrapping_mul(tile_pos.1))
+                  .wrapping_add(tile_pos.0);
+                let def = EntityDef::from_tile(name, &base_tile.properties);
+                match def.kind {
+                  EntityKind::Coin | EntityKind::RareCoin | EntityKind::HpUp => {
                     // If the player has already picked up this coin, skip it.
                     if char_state.coins.contains(&entity_id)
                       | char_state.rare_coins.contains(&entity_id)
@@ -157,21 +295,22 @@ impl CollisionWorld {
                       continue;
                     }
                   }
-                  "powerup" => {
-                    let power_up: &str = match base_tile.properties.get("powerup") {
-                      Some(tiled::PropertyValue::StringValue(s)) => s,
-                      _ => panic!("Powerup without powerup property"),
-                    };
+                  EntityKind::PowerUp => {
+                    let power_up =
+                      def.properties.string("powerup").expect("Powerup without powerup property");
+                    let power_up = PowerUp::from_name(power_up)
+                      .unwrap_or_else(|| panic!("Unknown powerup: {}", power_up));
                     // If the player has already picked up this powerup, skip it.
-                    if char_state.power_ups.contains(power_up) {
+                    if char_state.power_ups.contains(&power_up) {
                       continue;
                     }
                   }
                   _ => {}
                 }
-                match name {
-                  "water" => {
-                    let handle = make_circle(0.45);
+                match def.kind {
+                  EntityKind::Water => {
+                    water_cells.insert(tile_pos);
+                    let handle = make_circle(def.radius);
                     objects.insert(
                       handle.collider,
                       GameObject {
@@ -180,8 +319,9 @@ impl CollisionWorld {
                       },
                     );
                   }
-                  "lava" => {
-                    let handle = make_circle(0.45);
+                  EntityKind::Lava => {
+                    lava_cells.insert(tile_pos);
+                    let handle = make_circle(def.radius);
                     objects.insert(
                       handle.collider,
                       GameObject {
@@ -190,9 +330,8 @@ impl CollisionWorld {
                       },
                     );
                   }
-                  // Coin
-                  "coin" => {
-                    let handle = make_circle(0.45);
+                  EntityKind::Coin => {
+                    let handle = make_circle(def.radius);
                     objects.insert(
                       handle.collider,
                       GameObject {
@@ -201,9 +340,8 @@ impl CollisionWorld {
                       },
                     );
                   }
-                  // Rare coin
-                  "rare_coin" => {
-                    let handle = make_circle(0.45);
+                  EntityKind::RareCoin => {
+                    let handle = make_circle(def.radius);
                     objects.insert(
                       handle.collider,
                       GameObject {
@@ -212,8 +350,8 @@ impl CollisionWorld {
                       },
                     );
                   }
-                  "hp_up" => {
-                    let handle = make_circle(0.45);
+                  EntityKind::HpUp => {
+                    let handle = make_circle(def.radius);
                     objects.insert(
                       handle.collider,
                       GameObject {
@@ -222,82 +360,187 @@ impl CollisionWorld {
                       },
                     );
                   }
-                  "powerup" => {
-                    let power_up: &str = match base_tile.properties.get("powerup") {
-                      Some(tiled::PropertyValue::StringValue(s)) => s,
-                      _ => panic!("Powerup without powerup property"),
-                    };
-                    let handle = make_circle(0.45);
+                  EntityKind::PowerUp => {
+                    let power_up =
+                      PowerUp::from_name(def.properties.string("powerup").unwrap()).unwrap();
+                    let handle = make_circle(def.radius);
                     objects.insert(
                       handle.collider,
                       GameObject {
                         physics_handle: handle,
-                        data:           GameObjectData::PowerUp {
-                          power_up: power_up.to_string(),
-                        },
+                        data:           GameObjectData::PowerUp { power_up },
                       },
                     );
                   }
-                  "spike" => {
-                    let handle = make_circle(0.2);
+                  EntityKind::Spike => {
+                    let damage = def.properties.int("damage").unwrap_or(2);
+                    let handle = make_circle(def.radius);
                     objects.insert(
                       handle.collider,
                       GameObject {
                         physics_handle: handle,
-                        data:           GameObjectData::Spike,
+                        data:           GameObjectData::Spike { damage },
                       },
                     );
                   }
-                  "shooter1" => {
-                    let handle = make_circle(0.45);
+                  EntityKind::Shooter => {
+                    let projectile = match def.properties.string("projectile") {
+                      Some(s) => ProjectileKind::from_property(s)
+                        .unwrap_or_else(|| panic!("Unknown projectile: {}", s)),
+                      None => ProjectileKind::Bullet,
+                    };
+                    let handle = make_circle(def.radius);
                     objects.insert(
                       handle.collider,
                       GameObject {
                         physics_handle: handle,
                         data:           GameObjectData::Shooter1 {
                           orientation,
-                          cooldown: Cell::new(1.25),
-                          shoot_period: 1.4,
+                          // The delay before the first shot.
+                          cooldown: def.properties.number("cooldown").unwrap_or(1.25),
+                          shoot_period: def.properties.number("period").unwrap_or(1.4),
+                          projectile,
+                          hp: def.properties.int("hp").unwrap_or(crate::SHOOTER_DEFAULT_HP),
                         },
                       },
                     );
                   }
-                  "shooter2" => {
-                    let handle = make_circle(0.45);
+                  EntityKind::Beehive => {
+                    if char_state.destroyed_hives.contains(&entity_id) {
+                      continue;
+                    }
+                    let handle = make_circle(def.radius);
                     objects.insert(
                       handle.collider,
                       GameObject {
                         physics_handle: handle,
-                        data:           GameObjectData::Shooter1 {
-                          orientation,
-                          cooldown: Cell::new(1.25),
-                          shoot_period: 2.0,
+                        data:           GameObjectData::Beehive {
+                          cooldown: 0.0,
+                          entity_id,
+                          hp: def.properties.int("hp").unwrap_or(crate::BEEHIVE_DEFAULT_HP),
                         },
                       },
                     );
                   }
-                  "beehive" => {
-                    let handle = make_circle(0.45);
+                  EntityKind::CountGate => {
+                    let Some(count) = def.properties.int("count") else {
+                      continue;
+                    };
+                    // Plain coin walls predate the other currencies, so coins are the default.
+                    let currency = match def.properties.string("currency") {
+                      Some(s) => Currency::from_property(s)
+                        .unwrap_or_else(|| panic!("Unknown gate currency: {}", s)),
+                      None => Currency::Coins,
+                    };
+                    let handle = self.new_cuboid(
+                      PhysicsKind::Static,
+                      Vec2(tile_pos.0 as f32 + 0.5, tile_pos.1 as f32 + 0.5),
+                      def.size,
+                      0.05,
+                      false,
+                      WALLS_INT_GROUPS,
+                    );
                     objects.insert(
                       handle.collider,
                       GameObject {
                         physics_handle: handle,
-                        data:           GameObjectData::Beehive {
-                          cooldown: Cell::new(0.0),
+                        data:           GameObjectData::CountGate { currency, count },
+                      },
+                    );
+                  }
+                  EntityKind::Stone => {
+                    let handle = self.new_cuboid(
+                      PhysicsKind::Static,
+                      Vec2(tile_pos.0 as f32 + 0.5, tile_pos.1 as f32 + 0.5),
+                      def.size,
+                      0.05,
+                      false,
+                      WALLS_INT_GROUPS,
+                    );
+                    objects.insert(
+                      handle.collider,
+                      GameObject {
+                        physics_handle: handle,
+                        // Only stones given hp can be broken.
+                        data:           GameObjectData::Stone {
+                          hp: def.properties.int("hp"),
                         },
                       },
                     );
                   }
-                  "coin_wall" => {
-                    let count: i32 = match base_tile.properties.get("count") {
-                      Some(tiled::PropertyValue::IntValue(count)) => *count,
-                      Some(_) => panic!("count must be an int"),
-                      _ => continue,
-                    };
+                  EntityKind::BreakableBlock => {
+                    let drops = def.properties.string("drops").map(|s| {
+                      BlockDrop::from_property(s)
+                        .unwrap_or_else(|| panic!("Unknown block drop: {}", s))
+                    });
+                    // A broken block stays broken, but what it dropped waits where the block
+                    // was until it's picked up.
+                    if char_state.broken_blocks.contains(&entity_id) {
+                      let Some(drop) = drops else {
+                        continue;
+                      };
+                      if drop.is_collected(char_state, entity_id) {
+                        continue;
+                      }
+                      let handle = make_circle(breakable::DROP_RADIUS);
+                      objects.insert(
+                        handle.collider,
+                        GameObject {
+                          physics_handle: handle,
+                          data:           drop.pickup(entity_id),
+                        },
+                      );
+                      continue;
+                    }
+                    let hp = def.properties.int("hp").unwrap_or(breakable::DEFAULT_HP);
                     let handle = self.new_cuboid(
                       PhysicsKind::Static,
                       Vec2(tile_pos.0 as f32 + 0.5, tile_pos.1 as f32 + 0.5),
-                      Vec2(0.6, 0.6),
+                      def.size,
+                      0.05,
+                      false,
+                      WALLS_INT_GROUPS,
+                    );
+                    objects.insert(
+                      handle.collider,
+                      GameObject {
+                        physics_handle: handle,
+                        data:           GameObjectData::BreakableBlock {
+                          entity_id,
+                          hp,
+                          max_hp: hp,
+                          drops,
+                          crumbling: None,
+                        },
+                      },
+                    );
+                  }
+                  EntityKind::Key => {
+                    if char_state.keys.contains_key(&entity_id) {
+                      continue;
+                    }
+                    let color = def.properties.string("color").unwrap_or(keys::DEFAULT_COLOR);
+                    let handle = make_circle(def.radius);
+                    objects.insert(
+                      handle.collider,
+                      GameObject {
+                        physics_handle: handle,
+                        data:           GameObjectData::Key {
+                          entity_id,
+                          color: color.to_string(),
+                        },
+                      },
+                    );
+                  }
+                  EntityKind::LockedDoor => {
+                    if char_state.opened_doors.contains_key(&entity_id) {
+                      continue;
+                    }
+                    let color = def.properties.string("color").unwrap_or(keys::DEFAULT_COLOR);
+                    let handle = self.new_cuboid(
+                      PhysicsKind::Static,
+                      keys::door_center(tile_pos, def.size),
+                      def.size,
                       0.05,
                       false,
                       WALLS_INT_GROUPS,
@@ -306,35 +549,68 @@ impl CollisionWorld {
                       handle.collider,
                       GameObject {
                         physics_handle: handle,
-                        data:           GameObjectData::CoinWall { count },
+                        data:           GameObjectData::LockedDoor {
+                          entity_id,
+                          color: color.to_string(),
+                          opening: None,
+                        },
+                      },
+                    );
+                  }
+                  EntityKind::Switch => {
+                    let channel = def.properties.string("channel").expect("Switch needs a channel");
+                    let hit_by = match def.properties.string("hit_by") {
+                      Some(s) => SwitchHit::from_property(s)
+                        .unwrap_or_else(|| panic!("Unknown switch hit: {}", s)),
+                      None => SwitchHit::Touch,
+                    };
+                    let handle = make_circle(def.radius);
+                    objects.insert(
+                      handle.collider,
+                      GameObject {
+                        physics_handle: handle,
+                        data:           GameObjectData::Switch {
+                          channel: channel.to_string(),
+                          hit_by,
+                          pressed: false,
+                          flash: 0.0,
+                        },
                       },
                     );
                   }
-                  "stone" => {
+                  EntityKind::ToggleBlock => {
+                    let channel =
+                      def.properties.string("channel").expect("Toggle block needs a channel");
+                    // Channels start off, so a block that starts solid is solid while its
+                    // channel is off, and the other way around.
+                    let starts_solid = def.properties.bool("starts_solid").unwrap_or(true);
                     let handle = self.new_cuboid(
                       PhysicsKind::Static,
                       Vec2(tile_pos.0 as f32 + 0.5, tile_pos.1 as f32 + 0.5),
-                      Vec2(1.0, 1.0),
+                      def.size,
                       0.05,
                       false,
                       WALLS_INT_GROUPS,
                     );
+                    self.collider_set[handle.collider].set_enabled(starts_solid);
                     objects.insert(
                       handle.collider,
                       GameObject {
                         physics_handle: handle,
-                        data:           GameObjectData::Stone,
+                        data:           GameObjectData::ToggleBlock {
+                          channel:       channel.to_string(),
+                          solid_when_on: !starts_solid,
+                          is_solid:      starts_solid,
+                        },
                       },
                     );
                   }
-                  "save_left" => {
-                    let handle = make_circle(0.45);
+                  EntityKind::SavePoint => {
+                    let handle = make_circle(def.radius);
                     // Because only the left tile in the save point gets an entity, we shift it over half a tile.
-                    self.set_position(
-                      &handle,
-                      Vec2(tile_pos.0 as f32 + 1.0, tile_pos.1 as f32 + 0.5),
-                      true,
-                    );
+                    let save_point = Vec2(tile_pos.0 as f32 + 1.0, tile_pos.1 as f32 + 0.5);
+                    self.set_position(&handle, save_point, true);
+                    self.save_points.push(save_point);
                     objects.insert(
                       handle.collider,
                       GameObject {
@@ -343,7 +619,7 @@ impl CollisionWorld {
                       },
                     );
                   }
-                  "platform" => {
+                  EntityKind::Platform => {
                     let handle = self.new_static_walls(
                       (tile_pos.0 as f32, tile_pos.1 as f32),
                       &[(0.0, 0.3), (1.0, 0.3)],
@@ -363,11 +639,18 @@ impl CollisionWorld {
                       },
                     );
                   }
-                  "thwump" | "moving_platform" => {
+                  EntityKind::Thwump | EntityKind::MovingPlatform => {
+                    let home = Vec2(tile_pos.0 as f32 + 0.5, tile_pos.1 as f32 + 0.5);
+                    // Thwumps fall toward the bottom of their tile, turning to match.
+                    let fall_direction = -up_orientation;
+                    let size = match def.kind == EntityKind::Thwump && fall_direction.0 != 0.0 {
+                      true => Vec2(def.size.1, def.size.0),
+                      false => def.size,
+                    };
                     let handle = self.new_cuboid(
                       PhysicsKind::Kinematic,
-                      Vec2(tile_pos.0 as f32 + 0.5, tile_pos.1 as f32 + 0.5),
-                      Vec2(3.0, 1.0),
+                      home,
+                      size,
                       0.05,
                       false,
                       WALLS_INT_GROUPS,
@@ -376,23 +659,24 @@ impl CollisionWorld {
                       handle.collider,
                       GameObject {
                         physics_handle: handle,
-                        data:           match name {
-                          "thwump" => GameObjectData::Thwump {
-                            orientation,
+                        data:           match def.kind {
+                          EntityKind::Thwump => GameObjectData::Thwump {
+                            orientation: fall_direction,
+                            half_size: def.size / 2.0,
                             state: crate::ThwumpState::Idle,
+                            home,
                           },
-                          "moving_platform" => GameObjectData::MovingPlatform { orientation },
-                          _ => unreachable!(),
+                          _ => GameObjectData::MovingPlatform { orientation },
                         },
                       },
                     );
                   }
-                  "turn_laser" => {
+                  EntityKind::TurnLaser => {
                     let laser_origin = Vec2(tile_pos.0 as f32 + 0.5, tile_pos.1 as f32 + 0.5);
                     let handle = self.new_circle(
                       PhysicsKind::Static,
                       laser_origin,
-                      0.45,
+                      def.radius,
                       false,
                       Some(WALLS_INT_GROUPS),
                     );
@@ -408,11 +692,30 @@ impl CollisionWorld {
                       },
                     );
                   }
-                  "vanish_block" => {
+                  EntityKind::VanishBlock => {
+                    let pattern = def.properties.string("pattern");
+                    // A pattern without a group gets a group of its own, shared with every
+                    // other block with the same pattern.
+                    let group = match (def.properties.string("group"), pattern) {
+                      (Some(group), _) => Some(group.to_string()),
+                      (None, Some(pattern)) => Some(format!("pattern:{}", pattern)),
+                      (None, None) => None,
+                    };
+                    if let (Some(group), Some(pattern)) = (&group, pattern) {
+                      let pattern = crate::vanish::parse_pattern(pattern).unwrap();
+                      match self.vanish_groups.get(group) {
+                        Some(existing) if *existing != pattern => {
+                          panic!("Vanish block group {} has conflicting patterns", group)
+                        }
+                        _ => {
+                          self.vanish_groups.insert(group.clone(), pattern);
+                        }
+                      }
+                    }
                     let handle = self.new_cuboid(
                       PhysicsKind::Static,
                       Vec2(tile_pos.0 as f32 + 0.5, tile_pos.1 as f32 + 0.5),
-                      Vec2(1.0, 1.0),
+                      def.size,
                       0.05,
                       false,
                       WALLS_INT_GROUPS,
@@ -423,13 +726,111 @@ impl CollisionWorld {
                         physics_handle: handle,
                         data:           GameObjectData::VanishBlock {
                           vanish_timer: 1.0,
-                          is_solid:     true,
+                          is_solid: true,
+                          group,
                         },
                       },
                     );
                   }
-                  "spawn" => self.spawn_point = Vec2(tile_pos.0 as f32, tile_pos.1 as f32),
-                  _ => panic!("Unsupported tile name: {}", name),
+                  EntityKind::Spring => {
+                    let strength =
+                      def.properties.number("strength").unwrap_or(crate::SPRING_DEFAULT_STRENGTH);
+                    let handle = make_circle(def.radius);
+                    objects.insert(
+                      handle.collider,
+                      GameObject {
+                        physics_handle: handle,
+                        data:           GameObjectData::Spring {
+                          direction: up_orientation,
+                          strength,
+                          squash: 0.0,
+                        },
+                      },
+                    );
+                  }
+                  EntityKind::HazardSurface(effect) => {
+                    let handle = self.new_cuboid(
+                      PhysicsKind::Sensor,
+                      Vec2(tile_pos.0 as f32 + 0.5, tile_pos.1 as f32 + 0.5),
+                      def.size,
+                      0.05,
+                      false,
+                      BASIC_INT_GROUPS,
+                    );
+                    objects.insert(
+                      handle.collider,
+                      GameObject {
+                        physics_handle: handle,
+                        data:           GameObjectData::HazardSurface { effect },
+                      },
+                    );
+                  }
+                  EntityKind::AirBubble => {
+                    let respawn_time =
+                      def.properties.number("respawn").unwrap_or(crate::AIR_BUBBLE_DEFAULT_RESPAWN);
+                    let handle = make_circle(def.radius);
+                    objects.insert(
+                      handle.collider,
+                      GameObject {
+                        physics_handle: handle,
+                        data:           GameObjectData::AirBubble {
+                          respawn_time,
+                          cooldown: 0.0,
+                        },
+                      },
+                    );
+                  }
+                  EntityKind::BoostRail => {
+                    let speed =
+                      def.properties.number("speed").unwrap_or(crate::BOOST_DEFAULT_SPEED);
+                    let duration =
+                      def.properties.number("duration").unwrap_or(crate::BOOST_DEFAULT_DURATION);
+                    let handle = self.new_cuboid(
+                      PhysicsKind::Sensor,
+                      Vec2(tile_pos.0 as f32 + 0.5, tile_pos.1 as f32 + 0.5),
+                      def.size,
+                      0.05,
+                      false,
+                      BASIC_INT_GROUPS,
+                    );
+                    objects.insert(
+                      handle.collider,
+                      GameObject {
+                        physics_handle: handle,
+                        data:           GameObjectData::BoostRail { speed, duration },
+                      },
+                    );
+                  }
+                  EntityKind::Walker => {
+                    // Stands on the bottom of its tile, whatever its height.
+                    let enemy = Enemy::walker(
+                      Vec2(
+                        tile_pos.0 as f32 + 0.5,
+                        tile_pos.1 as f32 + 1.0 - def.size.1 / 2.0,
+                      ),
+                      def.size,
+                      def.properties.number("speed").unwrap_or(enemies::WALKER_DEFAULT_SPEED),
+                      def.properties.int("damage").unwrap_or(enemies::WALKER_DEFAULT_DAMAGE),
+                    );
+                    let handle = self.new_cuboid(
+                      PhysicsKind::Sensor,
+                      enemy.position,
+                      def.size,
+                      0.05,
+                      false,
+                      BASIC_INT_GROUPS,
+                    );
+                    objects.insert(
+                      handle.collider,
+                      GameObject {
+                        physics_handle: handle,
+                        data:           GameObjectData::Enemy { enemy },
+                      },
+                    );
+                  }
+                  EntityKind::Spawn => {
+                    self.spawn_point = Vec2(tile_pos.0 as f32, tile_pos.1 as f32)
+                  }
                 }
               }
             }
@@ -444,6 +845,11 @@ impl CollisionWorld {
     match collision_layer.layer_type() {
       tiled::LayerType::ObjectLayer(object_layer) => {
         for object in object_layer.objects() {
+          // Where the object goes, in world pixels.
+          let (object_x, object_y) = (
+            object.x + offset.0 as f32 * TILE_SIZE,
+            object.y + offset.1 as f32 * TILE_SIZE,
+          );
           match &object.shape {
             tiled::ObjectShape::Rect { width, height } => {
               let name: &str = match object.properties.get("name") {
@@ -452,20 +858,341 @@ impl CollisionWorld {
               };
               match name {
                 "interact" => {
+                  let dialogue = match object.properties.get("dialogue") {
+                    Some(tiled::PropertyValue::StringValue(s)) => Some(s.clone()),
+                    Some(_) => panic!("dialogue must be a string"),
+                    None => None,
+                  };
+                  // Interactions that only show dialogue don't need a number.
                   let interaction_number = match object.properties.get("interaction") {
-                    Some(tiled::PropertyValue::IntValue(i)) => *i,
+                    Some(tiled::PropertyValue::IntValue(i)) => *i + placement.id_offset,
+                    None if dialogue.is_some() => 0,
                     _ => panic!("interact rects must have an interaction property."),
                   };
+                  match object.properties.get("actions") {
+                    Some(tiled::PropertyValue::StringValue(s)) => {
+                      if interaction_number == 0 {
+                        panic!("interact rects with actions need an interaction number");
+                      }
+                      let actions = triggers::parse_actions(s).unwrap_or_else(|e| {
+                        panic!("Bad actions on interaction {}: {}", interaction_number, e)
+                      });
+                      let actions =
+                        actions.into_iter().map(|action| action.shifted(placement.shift()));
+                      self.interaction_scripts.insert(interaction_number, actions.collect());
+                    }
+                    Some(_) => panic!("actions must be a string"),
+                    None => {}
+                  }
+                  let requires_powerup = match object.properties.get("requires_powerup") {
+                    Some(tiled::PropertyValue::StringValue(s)) => Some(
+                      PowerUp::from_name(s)
+                        .unwrap_or_else(|| panic!("Unknown requires_powerup: {}", s)),
+                    ),
+                    Some(_) => panic!("requires_powerup must be a string"),
+                    None => None,
+                  };
                   crate::log(&format!(
                     "Rect: {}x{} @ ({}, {})",
-                    width, height, object.x, object.y
+                    width, height, object_x, object_y
                   ));
                   // Create a new cuboid collider for this interaction.
                   let handle = self.new_cuboid(
                     PhysicsKind::Sensor,
                     Vec2(
-                      (object.x + width / 2.0) / TILE_SIZE,
-                      (object.y + height / 2.0) / TILE_SIZE,
+                      (object_x + width / 2.0) / TILE_SIZE,
+                      (object_y + height / 2.0) / TILE_SIZE,
+                    ),
+                    Vec2(width / TILE_SIZE, height / TILE_SIZE),
+                    0.05,
+                    false,
+                    BASIC_INT_GROUPS,
+                  );
+                  objects.insert(
+                    handle.collider,
+                    GameObject {
+                      physics_handle: handle,
+                      data:           GameObjectData::Interaction {
+                        interaction_number,
+                        requires_powerup,
+                        dialogue,
+                      },
+                    },
+                  );
+                }
+                "trial_start" | "trial_finish" => {
+                  let trial = match object.properties.get("trial") {
+                    Some(tiled::PropertyValue::StringValue(s)) => s.clone(),
+                    _ => panic!("{} rects must have a trial property.", name),
+                  };
+                  let get_medal_time = |key: &str| match object.properties.get(key) {
+                    Some(tiled::PropertyValue::FloatValue(t)) => *t,
+                    Some(tiled::PropertyValue::IntValue(t)) => *t as f32,
+                    _ => f32::INFINITY,
+                  };
+                  let handle = self.new_cuboid(
+                    PhysicsKind::Sensor,
+                    Vec2(
+                      (object_x + width / 2.0) / TILE_SIZE,
+                      (object_y + height / 2.0) / TILE_SIZE,
+                    ),
+                    Vec2(width / TILE_SIZE, height / TILE_SIZE),
+                    0.05,
+                    false,
+                    BASIC_INT_GROUPS,
+                  );
+                  let data = match name {
+                    "trial_start" => GameObjectData::TrialStart {
+                      trial,
+                      medal_times: [
+                        get_medal_time("gold_time"),
+                        get_medal_time("silver_time"),
+                        get_medal_time("bronze_time"),
+                      ],
+                    },
+                    _ => GameObjectData::TrialFinish { trial },
+                  };
+                  objects.insert(
+                    handle.collider,
+                    GameObject {
+                      physics_handle: handle,
+                      data,
+                    },
+                  );
+                }
+                "chase_start" | "chase_end" => {
+                  let chase = match object.properties.get("chase") {
+                    Some(tiled::PropertyValue::StringValue(s)) => s.clone(),
+                    _ => panic!("{} rects must have a chase property.", name),
+                  };
+                  let handle = self.new_cuboid(
+                    PhysicsKind::Sensor,
+                    Vec2(
+                      (object_x + width / 2.0) / TILE_SIZE,
+                      (object_y + height / 2.0) / TILE_SIZE,
+                    ),
+                    Vec2(width / TILE_SIZE, height / TILE_SIZE),
+                    0.05,
+                    false,
+                    BASIC_INT_GROUPS,
+                  );
+                  let data = match name {
+                    "chase_start" => GameObjectData::ChaseStart { chase },
+                    _ => GameObjectData::ChaseEnd { chase },
+                  };
+                  objects.insert(
+                    handle.collider,
+                    GameObject {
+                      physics_handle: handle,
+                      data,
+                    },
+                  );
+                }
+                "challenge" | "challenge_goal" => {
+                  let challenge = match object.properties.get("challenge") {
+                    Some(tiled::PropertyValue::StringValue(s)) => s.clone(),
+                    _ => panic!("{} rects must have a challenge property.", name),
+                  };
+                  let center = Vec2(
+                    (object_x + width / 2.0) / TILE_SIZE,
+                    (object_y + height / 2.0) / TILE_SIZE,
+                  );
+                  let handle = self.new_cuboid(
+                    PhysicsKind::Sensor,
+                    center,
+                    Vec2(width / TILE_SIZE, height / TILE_SIZE),
+                    0.05,
+                    false,
+                    BASIC_INT_GROUPS,
+                  );
+                  let data = match name {
+                    "challenge" => GameObjectData::ChallengeRegion { challenge },
+                    _ => {
+                      let reward = match object.properties.get("reward") {
+                        Some(tiled::PropertyValue::StringValue(s)) => {
+                          ChallengeReward::from_property(s)
+                            .unwrap_or_else(|| panic!("Unknown challenge reward: {}", s))
+                        }
+                        _ => ChallengeReward::Achievement,
+                      };
+                      // Reward coins get an entity id from the goal's tile, like coins placed as tiles.
+                      let entity_id = game_map
+                        .entity_id_offset
+                        .wrapping_add(1_000_000i32.wrapping_mul(center.1.floor() as i32))
+                        .wrapping_add(center.0.floor() as i32);
+                      GameObjectData::ChallengeGoal {
+                        challenge,
+                        reward,
+                        entity_id,
+                      }
+                    }
+                  };
+                  objects.insert(
+                    handle.collider,
+                    GameObject {
+                      physics_handle: handle,
+                      data,
+                    },
+                  );
+                }
+                "bee_queen" => {
+                  let drops = match object.properties.get("drops") {
+                    Some(tiled::PropertyValue::StringValue(s)) => {
+                      PowerUp::from_name(s).unwrap_or_else(|| panic!("Unknown drops: {}", s))
+                    }
+                    Some(_) => panic!("drops must be a string"),
+                    None => PowerUp::Small,
+                  };
+                  // Once her drop has been collected, she's gone for good.
+                  if char_state.power_ups.contains(&drops) {
+                    continue;
+                  }
+                  let hp = match object.properties.get("hp") {
+                    Some(tiled::PropertyValue::IntValue(hp)) => *hp,
+                    Some(_) => panic!("hp must be an int"),
+                    None => crate::bee_queen::QUEEN_DEFAULT_HP,
+                  };
+                  let arena = Rect::new(
+                    Vec2(object_x / TILE_SIZE, object_y / TILE_SIZE),
+                    Vec2(width / TILE_SIZE, height / TILE_SIZE),
+                  );
+                  let center = arena.pos + 0.5 * arena.size;
+                  let entity_id = game_map
+                    .entity_id_offset
+                    .wrapping_add(1_000_000i32.wrapping_mul(center.1.floor() as i32))
+                    .wrapping_add(center.0.floor() as i32);
+                  let queen = BeeQueen::new(arena, hp, drops, entity_id);
+                  let handle = self.new_circle(
+                    PhysicsKind::Sensor,
+                    queen.position,
+                    crate::bee_queen::QUEEN_RADIUS,
+                    true,
+                    None,
+                  );
+                  objects.insert(
+                    handle.collider,
+                    GameObject {
+                      physics_handle: handle,
+                      data:           GameObjectData::BeeQueen { queen },
+                    },
+                  );
+                }
+                "map_region" => {
+                  let region = match object.properties.get("region") {
+                    Some(tiled::PropertyValue::StringValue(s)) => s.clone(),
+                    _ => panic!("map_region rects must have a region property."),
+                  };
+                  self.map_regions.entry(region).or_default().push((
+                    Vec2(object_x / TILE_SIZE, object_y / TILE_SIZE),
+                    Vec2(width / TILE_SIZE, height / TILE_SIZE),
+                  ));
+                }
+                "gate" => {
+                  let requires = match object.properties.get("requires_powerup") {
+                    Some(tiled::PropertyValue::StringValue(s)) => PowerUp::from_name(s)
+                      .unwrap_or_else(|| panic!("Unknown requires_powerup: {}", s)),
+                    _ => panic!("gate rects must have a requires_powerup property."),
+                  };
+                  self.gates.push(Gate {
+                    bounds: Rect::new(
+                      Vec2(object_x / TILE_SIZE, object_y / TILE_SIZE),
+                      Vec2(width / TILE_SIZE, height / TILE_SIZE),
+                    ),
+                    requires,
+                  });
+                }
+                "camera_focus" => {
+                  let get_number = |key: &str, default: f32| match object.properties.get(key) {
+                    Some(tiled::PropertyValue::FloatValue(x)) => *x,
+                    Some(tiled::PropertyValue::IntValue(x)) => *x as f32,
+                    Some(_) => panic!("{} must be a number", key),
+                    None => default,
+                  };
+                  self.camera_focuses.push(CameraFocus {
+                    center: Vec2(
+                      (object_x + width / 2.0) / TILE_SIZE,
+                      (object_y + height / 2.0) / TILE_SIZE,
+                    ),
+                    weight: get_number("weight", 0.5),
+                    // In tiles.
+                    radius: get_number("radius", 10.0),
+                  });
+                }
+                "critters" => {
+                  let get_density = |key: &str| match object.properties.get(key) {
+                    Some(tiled::PropertyValue::FloatValue(x)) => *x,
+                    Some(tiled::PropertyValue::IntValue(x)) => *x as f32,
+                    Some(_) => panic!("{} must be a number", key),
+                    None => 0.0,
+                  };
+                  // The habitats depend on every tile, so the zones are built at the end.
+                  critter_rects.push((
+                    Rect::new(
+                      Vec2(object_x / TILE_SIZE, object_y / TILE_SIZE),
+                      Vec2(width / TILE_SIZE, height / TILE_SIZE),
+                    ),
+                    CritterDensities {
+                      fish:        get_density("fish"),
+                      butterflies: get_density("butterflies"),
+                      bats:        get_density("bats"),
+                    },
+                  ));
+                }
+                "wind" => {
+                  let direction = match object.properties.get("direction") {
+                    Some(tiled::PropertyValue::StringValue(s)) => wind::parse_direction(s)
+                      .unwrap_or_else(|| panic!("Unknown wind direction: {}", s)),
+                    _ => panic!("wind rects must have a direction property."),
+                  };
+                  let strength = match object.properties.get("strength") {
+                    Some(tiled::PropertyValue::FloatValue(x)) => *x,
+                    Some(tiled::PropertyValue::IntValue(x)) => *x as f32,
+                    Some(_) => panic!("strength must be a number"),
+                    None => wind::DEFAULT_STRENGTH,
+                  };
+                  self.wind_zones.push(WindZone {
+                    rect: Rect::new(
+                      Vec2(object_x / TILE_SIZE, object_y / TILE_SIZE),
+                      Vec2(width / TILE_SIZE, height / TILE_SIZE),
+                    ),
+                    direction,
+                    strength,
+                  });
+                }
+                "map_station" => {
+                  let region = match object.properties.get("region") {
+                    Some(tiled::PropertyValue::StringValue(s)) => s.clone(),
+                    _ => panic!("map_station rects must have a region property."),
+                  };
+                  let handle = self.new_cuboid(
+                    PhysicsKind::Sensor,
+                    Vec2(
+                      (object_x + width / 2.0) / TILE_SIZE,
+                      (object_y + height / 2.0) / TILE_SIZE,
+                    ),
+                    Vec2(width / TILE_SIZE, height / TILE_SIZE),
+                    0.05,
+                    false,
+                    BASIC_INT_GROUPS,
+                  );
+                  objects.insert(
+                    handle.collider,
+                    GameObject {
+                      physics_handle: handle,
+                      data:           GameObjectData::MapStation { region },
+                    },
+                  );
+                }
+                "door" => {
+                  let get_string = |key: &str| match object.properties.get(key) {
+                    Some(tiled::PropertyValue::StringValue(s)) => s.clone(),
+                    _ => panic!("door rects must have a {} property.", key),
+                  };
+                  let handle = self.new_cuboid(
+                    PhysicsKind::Sensor,
+                    Vec2(
+                      (object_x + width / 2.0) / TILE_SIZE,
+                      (object_y + height / 2.0) / TILE_SIZE,
                     ),
                     Vec2(width / TILE_SIZE, height / TILE_SIZE),
                     0.05,
@@ -476,10 +1203,26 @@ impl CollisionWorld {
                     handle.collider,
                     GameObject {
                       physics_handle: handle,
-                      data:           GameObjectData::Interaction { interaction_number },
+                      data:           GameObjectData::Door {
+                        target_map:   get_string("target_map"),
+                        target_spawn: get_string("target_spawn"),
+                      },
                     },
                   );
                 }
+                "spawn" => {
+                  let spawn = match object.properties.get("spawn") {
+                    Some(tiled::PropertyValue::StringValue(s)) => s.clone(),
+                    _ => panic!("spawn rects must have a spawn property."),
+                  };
+                  self.named_spawns.insert(
+                    spawn,
+                    Vec2(
+                      (object_x + width / 2.0) / TILE_SIZE,
+                      (object_y + height / 2.0) / TILE_SIZE,
+                    ),
+                  );
+                }
                 _ => panic!("Unsupported rect name: {}", name),
               }
             }
@@ -491,8 +1234,43 @@ impl CollisionWorld {
               if let tiled::ObjectShape::Polygon { .. } = object.shape {
                 points.push(points[0]);
               }
+              // Polylines named chase are the paths chase hazards follow, not walls.
+              if matches!(
+                object.properties.get("name"),
+                Some(tiled::PropertyValue::StringValue(s)) if s == "chase"
+              ) {
+                let chase = match object.properties.get("chase") {
+                  Some(tiled::PropertyValue::StringValue(s)) => s.clone(),
+                  _ => panic!("chase polylines must have a chase property."),
+                };
+                let get_number = |key: &str, default: f32| match object.properties.get(key) {
+                  Some(tiled::PropertyValue::FloatValue(x)) => *x,
+                  Some(tiled::PropertyValue::IntValue(x)) => *x as f32,
+                  Some(_) => panic!("{} must be a number", key),
+                  None => default,
+                };
+                let kind = match object.properties.get("kind") {
+                  Some(tiled::PropertyValue::StringValue(s)) => {
+                    ChaseKind::from_name(s).unwrap_or_else(|| panic!("Unknown chase kind: {}", s))
+                  }
+                  Some(_) => panic!("kind must be a string"),
+                  None => ChaseKind::CrushingWall,
+                };
+                let origin = Vec2(object_x / TILE_SIZE, object_y / TILE_SIZE);
+                self.chase_paths.insert(
+                  chase,
+                  ChasePath {
+                    kind,
+                    points: points.iter().map(|p| origin + Vec2(p.0, p.1)).collect(),
+                    speed: get_number("speed", chase::DEFAULT_SPEED),
+                    // In tiles.
+                    size: Vec2(get_number("width", 2.0), get_number("height", 10.0)),
+                  },
+                );
+                continue;
+              }
               self.new_static_walls(
-                (object.x / TILE_SIZE, object.y / TILE_SIZE),
+                (object_x / TILE_SIZE, object_y / TILE_SIZE),
                 &points[..],
                 WALLS_INT_GROUPS,
               );
@@ -504,46 +1282,78 @@ impl CollisionWorld {
       _ => panic!("Unsupported layer type"),
     }
 
-    // We now generate walls from our solid cells.
-    let min_x = all_solid_cells.iter().map(|c| c.0).min().unwrap();
-    let max_x = all_solid_cells.iter().map(|c| c.0).max().unwrap();
-    let min_y = all_solid_cells.iter().map(|c| c.1).min().unwrap();
-    let max_y = all_solid_cells.iter().map(|c| c.1).max().unwrap();
-    let mut walls: Vec<((i32, i32), (i32, i32))> = Vec::new();
-    // Horizontal scans.
-    for y in min_y..=max_y + 1 {
-      let mut row_start: Option<i32> = None;
-      for x in min_x..=max_x + 1 {
-        let is_boundary = all_solid_cells.contains(&(x, y)) ^ all_solid_cells.contains(&(x, y - 1));
-        match (is_boundary, row_start) {
-          (true, None) => row_start = Some(x),
-          (true, Some(_)) => {}
-          (false, Some(start)) => {
-            walls.push(((start, y), (x, y)));
-            row_start = None;
-          }
-          (false, None) => {}
-        }
-      }
-    }
-    // Vertical scans.
-    for x in min_x..=max_x + 1 {
-      let mut row_start: Option<i32> = None;
-      for y in min_y..=max_y + 1 {
-        let is_boundary = all_solid_cells.contains(&(x, y)) ^ all_solid_cells.contains(&(x - 1, y));
-        match (is_boundary, row_start) {
-          (true, None) => row_start = Some(y),
-          (true, Some(_)) => {}
-          (false, Some(start)) => {
-            walls.push(((x, start), (x, y)));
-            row_start = None;
-          }
-          (false, None) => {}
-        }
-      }
+    self.water_surfaces.extend(water::find_surfaces(&water_cells));
+    // Water and lava hold the player back until they have the power-ups for them, so they gate
+    // any doors they reach, alongside the gate rects drawn in the map.
+    self.gates.extend(progression::gates_from_cells(&water_cells, PowerUp::Water));
+    self.gates.extend(progression::gates_from_cells(&lava_cells, PowerUp::Lava));
+    self.critter_zones.extend(critter_rects.into_iter().map(|(bounds, densities)| {
+      CritterZone::new(bounds, densities, &all_solid_cells, &water_cells)
+    }));
+
+    // Each kind of surface gets walls of its own, and the plain walls are everything else.
+    let mut plain_cells = all_solid_cells;
+    for (surface, cells) in surface_cells {
+      plain_cells.retain(|cell| !cells.contains(cell));
+      let collider = self.insert_walls(generate_walls(&cells));
+      self.surface_colliders.insert(collider, surface);
     }
+    let walls = generate_walls(&plain_cells);
     crate::log(&format!("Found {} walls", walls.len()));
-    // We now insert the walls into the physics world.
+    self.insert_walls(walls);
+  }
+
+  /// Adds a map fragment to the world after it's already been loaded, keeping track of what
+  /// it added so that it can be taken out again with `remove_fragment`.
+  pub fn load_fragment(
+    &mut self,
+    char_state: &CharState,
+    game_map: &GameMap,
+    placement: Placement,
+    objects: &mut HashMap<ColliderHandle, GameObject>,
+  ) -> PlacedFragment {
+    let existing_colliders: HashSet<ColliderHandle> =
+      self.collider_set.iter().map(|(handle, _)| handle).collect();
+    let existing_interactions: HashSet<i32> = self.interaction_scripts.keys().copied().collect();
+    self.load_game_map_at(char_state, game_map, placement, objects);
+    PlacedFragment {
+      placement,
+      colliders: self
+        .collider_set
+        .iter()
+        .map(|(handle, _)| handle)
+        .filter(|handle| !existing_colliders.contains(handle))
+        .collect(),
+      interactions: self
+        .interaction_scripts
+        .keys()
+        .copied()
+        .filter(|number| !existing_interactions.contains(number))
+        .collect(),
+    }
+  }
+
+  /// Takes out everything a fragment added, along with its objects.
+  pub fn remove_fragment(
+    &mut self,
+    fragment: PlacedFragment,
+    objects: &mut HashMap<ColliderHandle, GameObject>,
+  ) {
+    for collider in fragment.colliders {
+      objects.remove(&collider);
+      let rigid_body = self.collider_set.get(collider).and_then(|collider| collider.parent());
+      self.remove_object(PhysicsObjectHandle {
+        rigid_body,
+        collider,
+      });
+    }
+    for number in fragment.interactions {
+      self.interaction_scripts.remove(&number);
+    }
+  }
+
+  /// Puts walls, as from `generate_walls`, into the physics world as one polyline.
+  fn insert_walls(&mut self, walls: Vec<((i32, i32), (i32, i32))>) -> ColliderHandle {
     let rigid_body = self.rigid_body_set.insert(
       RigidBodyBuilder::fixed()
         .position(Isometry::new(Vector2::new(0.0, 0.0), nalgebra::zero()))
@@ -564,7 +1374,204 @@ impl CollisionWorld {
       ColliderBuilder::polyline(vertices, Some(indices)).collision_groups(WALLS_INT_GROUPS),
       rigid_body,
       &mut self.rigid_body_set,
+    )
+  }
+
+  /// Whether a ray from `origin` hits anything solid within `max_distance`. Sensors, like
+  /// the player and pickups, don't count.
+  pub fn ray_hits_wall(&self, origin: Vec2, direction: Vec2, max_distance: f32) -> bool {
+    let ray = Ray::new(
+      Point::new(origin.0, origin.1),
+      Vector2::new(direction.0, direction.1),
+    );
+    let filter = QueryFilter::default().exclude_sensors().exclude_dynamic();
+    self
+      .query_pipeline
+      .cast_ray(
+        &self.rigid_body_set,
+        &self.collider_set,
+        &ray,
+        max_distance,
+        true,
+        filter,
+      )
+      .is_some()
+  }
+
+  /// Whether there's a slick tile right beside a box at `pos` of the given size, on the left
+  /// for a `side` of -1, or on the right for 1.
+  pub fn is_beside_slick_wall(&self, pos: Vec2, size: Vec2, side: f32) -> bool {
+    let x = (pos.0 + side * (size.0 / 2.0 + 0.05)).floor() as i32;
+    let (top, bottom) = (pos.1 - size.1 / 2.0 + 0.05, pos.1 + size.1 / 2.0 - 0.05);
+    (top.floor() as i32..=bottom.floor() as i32).any(|y| self.slick_cells.contains(&(x, y)))
+  }
+
+  /// The special surface right under the middle of a box at `pos` of the given size, if it's
+  /// standing on one.
+  pub fn surface_below(&self, pos: Vec2, size: Vec2) -> Option<Surface> {
+    let ray = Ray::new(
+      Point::new(pos.0, pos.1 + size.1 / 2.0 - 0.05),
+      Vector2::new(0.0, 1.0),
     );
+    let filter = QueryFilter::default().exclude_sensors().exclude_dynamic();
+    let (collider, _) = self.query_pipeline.cast_ray(
+      &self.rigid_body_set,
+      &self.collider_set,
+      &ray,
+      0.2,
+      true,
+      filter,
+    )?;
+    self.surface_colliders.get(&collider).copied()
+  }
+
+  /// The ledge a box at `pos` of the given size could grab, on the left for a `side` of -1,
+  /// or on the right for 1: the top edge of a wall right beside it, within `reach` of the
+  /// top of the box, with room for the box to stand up on it.
+  pub fn find_ledge(&self, pos: Vec2, size: Vec2, side: f32, reach: f32) -> Option<Ledge> {
+    let filter = QueryFilter::default()
+      .exclude_sensors()
+      .groups(InteractionGroups::new(PLAYER_GROUP, WALLS_GROUP));
+    // Sweep a thin probe down just past the side of the box, from above its head.
+    let probe = Cuboid::new(Vector2::new(
+      LEDGE_PROBE_WIDTH / 2.0,
+      LEDGE_PROBE_WIDTH / 2.0,
+    ));
+    let probe_x = pos.0 + side * (size.0 / 2.0 + LEDGE_PROBE_WIDTH / 2.0 + 0.05);
+    let head = pos.1 - size.1 / 2.0;
+    let (_, hit) = self.query_pipeline.cast_shape(
+      &self.rigid_body_set,
+      &self.collider_set,
+      &Isometry::new(Vector2::new(probe_x, head - reach), 0.0),
+      &Vector2::new(0.0, 1.0),
+      &probe,
+      2.0 * reach,
+      true,
+      filter,
+    )?;
+    // Starting out already touching the wall means it goes up too far to grab.
+    if hit.toi <= 0.0 {
+      return None;
+    }
+    let top = head - reach + hit.toi + LEDGE_PROBE_WIDTH / 2.0;
+    let climb_to = Vec2(pos.0 + side * (size.0 + 0.1), top - size.1 / 2.0 - 0.01);
+    let standing = Cuboid::new(Vector2::new(size.0 / 2.0, size.1 / 2.0));
+    let no_room = self
+      .query_pipeline
+      .intersection_with_shape(
+        &self.rigid_body_set,
+        &self.collider_set,
+        &Isometry::new(Vector2::new(climb_to.0, climb_to.1), 0.0),
+        &standing,
+        filter,
+      )
+      .is_some();
+    match no_room {
+      true => None,
+      false => Some(Ledge {
+        side,
+        top,
+        climb_to,
+      }),
+    }
+  }
+
+  /// The nearest spot to `pos`, on a half tile grid within `radius` tiles, where a box of the
+  /// given size can stand on the ground without being in a wall or next to anything
+  /// `is_hazard` picks out. Gives back `pos` itself if there's nowhere like that.
+  pub fn nearest_safe_standing(
+    &self,
+    pos: Vec2,
+    size: Vec2,
+    radius: f32,
+    is_hazard: impl Fn(ColliderHandle) -> bool,
+  ) -> Vec2 {
+    let filter = QueryFilter::default().exclude_sensors().groups(InteractionGroups::new(
+      PLAYER_GROUP,
+      WALLS_GROUP | PLATFORMS_GROUP,
+    ));
+    let standing = Cuboid::new(Vector2::new(size.0 / 2.0, size.1 / 2.0));
+    let margin = Cuboid::new(Vector2::new(
+      size.0 / 2.0 + SAFE_STANDING_MARGIN,
+      size.1 / 2.0 + SAFE_STANDING_MARGIN,
+    ));
+    let steps = (radius / SAFE_STANDING_STEP) as i32;
+    let mut candidates: Vec<Vec2> = (-steps..=steps)
+      .flat_map(|x| (-steps..=steps).map(move |y| SAFE_STANDING_STEP * Vec2(x as f32, y as f32)))
+      .filter(|offset| offset.length() <= radius)
+      .collect();
+    candidates.sort_by(|a, b| a.length().partial_cmp(&b.length()).unwrap());
+    for offset in candidates {
+      // Drop the box onto whatever's below, from a little higher so a spot already on the
+      // ground still counts, failing if it starts in a wall or there's no ground close enough.
+      let start = pos + offset - Vec2(0.0, SAFE_STANDING_LIFT);
+      let Some((_, hit)) = self.query_pipeline.cast_shape(
+        &self.rigid_body_set,
+        &self.collider_set,
+        &Isometry::new(Vector2::new(start.0, start.1), 0.0),
+        &Vector2::new(0.0, 1.0),
+        &standing,
+        SAFE_STANDING_LIFT + SAFE_STANDING_DROP,
+        true,
+        filter,
+      ) else {
+        continue;
+      };
+      if hit.toi <= 0.0 {
+        continue;
+      }
+      let landed = start + Vec2(0.0, hit.toi - 0.01);
+      let mut hazardous = false;
+      self.query_pipeline.intersections_with_shape(
+        &self.rigid_body_set,
+        &self.collider_set,
+        &Isometry::new(Vector2::new(landed.0, landed.1), 0.0),
+        &margin,
+        QueryFilter::default(),
+        |handle| {
+          hazardous = is_hazard(handle);
+          !hazardous
+        },
+      );
+      if !hazardous {
+        return landed;
+      }
+    }
+    pos
+  }
+
+  /// The name of the map region `pos` is in, if any.
+  pub fn region_at(&self, pos: Vec2) -> Option<&str> {
+    self
+      .map_regions
+      .iter()
+      .find(|(_, rects)| {
+        rects.iter().any(|(corner, size)| Rect::new(*corner, *size).contains_point(pos))
+      })
+      .map(|(region, _)| region.as_str())
+  }
+
+  /// Works out which map reveal chunks (keyed by their corner, in multiples of
+  /// `granularity`) each map region overlaps.
+  pub fn region_chunks(&self, granularity: i32) -> HashMap<String, HashSet<(i32, i32)>> {
+    let g = granularity as f32;
+    let mut region_chunks = HashMap::new();
+    for (region, rects) in &self.map_regions {
+      let chunks: &mut HashSet<(i32, i32)> = region_chunks.entry(region.clone()).or_default();
+      for (corner, size) in rects {
+        let (x0, y0) = ((corner.0 / g).floor() as i32, (corner.1 / g).floor() as i32);
+        let (x1, y1) = (
+          ((corner.0 + size.0) / g).ceil() as i32,
+          ((corner.1 + size.1) / g).ceil() as i32,
+        );
+        for cx in x0..x1 {
+          for cy in y0..y1 {
+            chunks.insert((cx * granularity, cy * granularity));
+          }
+        }
+      }
+    }
+    region_chunks
   }
 
   pub fn new_static_walls(
@@ -658,6 +1665,24 @@ impl CollisionWorld {
     }
   }
 
+  pub fn new_pickup(
+    &mut self,
+    physics: PickupPhysics,
+    position: Vec2,
+    radius: f32,
+  ) -> PhysicsObjectHandle {
+    match physics {
+      PickupPhysics::Floating => self.new_circle(PhysicsKind::Sensor, position, radius, true, None),
+      PickupPhysics::Falling => self.new_circle(
+        PhysicsKind::Dynamic,
+        position,
+        radius,
+        false,
+        Some(PICKUPS_INT_GROUPS),
+      ),
+    }
+  }
+
   pub fn remove_object(&mut self, handle: PhysicsObjectHandle) {
     if let Some(rigid_body) = handle.rigid_body {
       self.rigid_body_set.remove(
@@ -702,6 +1727,22 @@ impl CollisionWorld {
     rigid_body.set_linvel(Vector2::new(velocity.0, velocity.1), true);
   }
 
+  pub fn is_dynamic(&self, handle: &PhysicsObjectHandle) -> bool {
+    let rigid_body = handle.rigid_body.and_then(|rigid_body| self.rigid_body_set.get(rigid_body));
+    matches!(rigid_body, Some(rigid_body) if rigid_body.is_dynamic())
+  }
+
+  /// The angle in radians the object has been turned through, for dynamic bodies that spin.
+  pub fn get_rotation(&self, handle: &PhysicsObjectHandle) -> Option<f32> {
+    let rigid_body = self.rigid_body_set.get(handle.rigid_body?)?;
+    Some(rigid_body.position().rotation.angle())
+  }
+
+  pub fn set_angular_velocity(&mut self, handle: &PhysicsObjectHandle, angular_velocity: f32) {
+    let rigid_body = self.rigid_body_set.get_mut(handle.rigid_body.unwrap()).unwrap();
+    rigid_body.set_angvel(angular_velocity, true);
+  }
+
   pub fn get_shape_and_position(
     &self,
     handle: &PhysicsObjectHandle,
@@ -711,6 +1752,15 @@ impl CollisionWorld {
     Some((collider.shape(), rigid_body.position()))
   }
 
+  /// The axis-aligned bounds of a collider, in world coordinates.
+  pub fn collider_bounds(&self, collider: ColliderHandle) -> Option<Rect> {
+    let aabb = self.collider_set.get(collider)?.compute_aabb();
+    Some(Rect::new(
+      Vec2(aabb.mins.x, aabb.mins.y),
+      Vec2(aabb.maxs.x - aabb.mins.x, aabb.maxs.y - aabb.mins.y),
+    ))
+  }
+
   pub fn check_character_controller_movement(
     &self,
     dt: f32,
@@ -800,3 +1850,54 @@ impl CollisionWorld {
     self.query_pipeline.update(&self.rigid_body_set, &self.collider_set);
   }
 }
+
+/// Whether tiles with the given user_type are walls: plain tiles and surfaces are, while
+/// "nonsolid" and "marker" tiles aren't.
+pub fn is_solid_tile(user_type: &str) -> bool {
+  user_type.is_empty() || Surface::from_user_type(user_type).is_some()
+}
+
+/// Traces the outlines of a set of solid cells as axis-aligned segments, merging runs of
+/// boundary along each row and column into single segments.
+pub fn generate_walls(all_solid_cells: &HashSet<(i32, i32)>) -> Vec<((i32, i32), (i32, i32))> {
+  let Some(min_x) = all_solid_cells.iter().map(|c| c.0).min() else {
+    return Vec::new();
+  };
+  let max_x = all_solid_cells.iter().map(|c| c.0).max().unwrap();
+  let min_y = all_solid_cells.iter().map(|c| c.1).min().unwrap();
+  let max_y = all_solid_cells.iter().map(|c| c.1).max().unwrap();
+  let mut walls: Vec<((i32, i32), (i32, i32))> = Vec::new();
+  // Horizontal scans.
+  for y in min_y..=max_y + 1 {
+    let mut row_start: Option<i32> = None;
+    for x in min_x..=max_x + 1 {
+      let is_boundary = all_solid_cells.contains(&(x, y)) ^ all_solid_cells.contains(&(x, y - 1));
+      match (is_boundary, row_start) {
+        (true, None) => row_start = Some(x),
+        (true, Some(_)) => {}
+        (false, Some(start)) => {
+          walls.push(((start, y), (x, y)));
+          row_start = None;
+        }
+        (false, None) => {}
+      }
+    }
+  }
+  // Vertical scans.
+  for x in min_x..=max_x + 1 {
+    let mut row_start: Option<i32> = None;
+    for y in min_y..=max_y + 1 {
+      let is_boundary = all_solid_cells.contains(&(x, y)) ^ all_solid_cells.contains(&(x - 1, y));
+      match (is_boundary, row_start) {
+        (true, None) => row_start = Some(y),
+        (true, Some(_)) => {}
+        (false, Some(start)) => {
+          walls.push(((x, start), (x, y)));
+          row_start = None;
+        }
+        (false, None) => {}
+      }
+    }
+  }
+  walls
+}
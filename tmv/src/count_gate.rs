@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+use crate::CharState;
+
+/// What a count gate counts, to open once the player has enough of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Currency {
+  Coins,
+  RareCoins,
+  Secrets,
+  HpUps,
+}
+
+impl Currency {
+  pub fn from_property(name: &str) -> Option<Self> {
+    match name {
+      "coins" => Some(Currency::Coins),
+      "rare_coins" => Some(Currency::RareCoins),
+      "secrets" => Some(Currency::Secrets),
+      "hp_ups" => Some(Currency::HpUps),
+      _ => None,
+    }
+  }
+
+  /// How much of this the player has.
+  pub fn held(self, char_state: &CharState) -> usize {
+    match self {
+      Currency::Coins => char_state.coins.len(),
+      Currency::RareCoins => char_state.rare_coins.len(),
+      Currency::Secrets => char_state.secrets.len(),
+      Currency::HpUps => char_state.hp_ups.len(),
+    }
+  }
+
+  /// The theme style of the icon shown on gates, matching the pickups themselves.
+  pub fn style(self) -> &'static str {
+    match self {
+      Currency::Coins => "coin",
+      Currency::RareCoins => "rare_coin",
+      Currency::Secrets => "secret",
+      Currency::HpUps => "hp_up",
+    }
+  }
+}
@@ -0,0 +1,275 @@
+use std::collections::{HashMap, HashSet};
+
+use wasm_bindgen::JsValue;
+use web_sys::CanvasRenderingContext2d;
+
+use crate::{
+  camera::Camera,
+  math::{Rect, Vec2},
+};
+
+// Zones this far outside the view, in tiles, keep their critters, so they're already
+// there by the time they scroll into view, and don't pop in and out at the edge.
+const ACTIVE_MARGIN: f32 = 8.0;
+// Fish swim at most this far either way from where they started, in tiles.
+const FISH_MAX_RANGE: f32 = 3.0;
+const BUTTERFLY_RANGE: f32 = 1.5;
+// Bats spend about this fraction of the time hanging still.
+const BAT_HANG_FRACTION: f32 = 0.7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CritterKind {
+  Fish,
+  Butterfly,
+  Bat,
+}
+
+/// How many of each critter a zone has per 100 tiles of fitting habitat, from the
+/// properties on its rect.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CritterDensities {
+  pub fish:        f32,
+  pub butterflies: f32,
+  pub bats:        f32,
+}
+
+// A spot a critter can live, and how far it can wander from it.
+#[derive(Debug, Clone, Copy)]
+struct Habitat {
+  home:  Vec2,
+  range: f32,
+}
+
+/// A rect of the map with critters in it, and the spots in it each kind can live: water
+/// for fish, just above the ground for butterflies, and just under the ceiling for bats.
+#[derive(Debug, Clone)]
+pub struct CritterZone {
+  pub bounds: Rect,
+  habitats:   Vec<(CritterKind, Vec<Habitat>, f32)>,
+}
+
+impl CritterZone {
+  pub fn new(
+    bounds: Rect,
+    densities: CritterDensities,
+    solid_cells: &HashSet<(i32, i32)>,
+    water_cells: &HashSet<(i32, i32)>,
+  ) -> Self {
+    let mut fish = Vec::new();
+    let mut butterflies = Vec::new();
+    let mut bats = Vec::new();
+    let max_corner = bounds.max_corner();
+    let is_open = |cell: &(i32, i32)| !solid_cells.contains(cell) && !water_cells.contains(cell);
+    for y in bounds.pos.1.floor() as i32..max_corner.1.ceil() as i32 {
+      for x in bounds.pos.0.floor() as i32..max_corner.0.ceil() as i32 {
+        let center = Vec2(x as f32 + 0.5, y as f32 + 0.5);
+        if water_cells.contains(&(x, y)) {
+          // Fish stay in their row, so they can swim as far as the water goes either way.
+          let reach = |step: i32| {
+            (1..).take_while(|i| water_cells.contains(&(x + step * i, y))).count() as f32
+          };
+          fish.push(Habitat {
+            home:  center,
+            range: reach(-1).min(reach(1)).min(FISH_MAX_RANGE),
+          });
+        } else if is_open(&(x, y)) && solid_cells.contains(&(x, y + 1)) {
+          butterflies.push(Habitat {
+            home:  center,
+            range: BUTTERFLY_RANGE,
+          });
+        } else if is_open(&(x, y)) && solid_cells.contains(&(x, y - 1)) {
+          bats.push(Habitat {
+            home:  Vec2(center.0, y as f32 + 0.2),
+            range: 0.0,
+          });
+        }
+      }
+    }
+    Self {
+      bounds,
+      habitats: vec![
+        (CritterKind::Fish, fish, densities.fish),
+        (CritterKind::Butterfly, butterflies, densities.butterflies),
+        (CritterKind::Bat, bats, densities.bats),
+      ],
+    }
+  }
+}
+
+/// One harmless creature. Its movement is a function of the time, so nothing needs
+/// stepping each frame.
+#[derive(Debug, Clone, Copy)]
+pub struct Critter {
+  pub kind: CritterKind,
+  home:     Vec2,
+  range:    f32,
+  // Offsets each critter's movement, so they don't all move in lockstep.
+  phase:    f32,
+  speed:    f32,
+}
+
+impl Critter {
+  /// Where the critter is at `time`, and which way it's facing (-1 for left, 1 for right).
+  pub fn position(&self, time: f32) -> (Vec2, f32) {
+    let t = self.speed * time + self.phase;
+    match self.kind {
+      CritterKind::Fish => (
+        self.home + Vec2(self.range * t.sin(), 0.1 * (3.0 * t).sin()),
+        t.cos().signum(),
+      ),
+      CritterKind::Butterfly => (
+        self.home
+          + Vec2(
+            self.range * t.sin(),
+            -0.6 + 0.4 * (2.3 * t).sin() + 0.1 * (11.0 * t).sin(),
+          ),
+        t.cos().signum(),
+      ),
+      CritterKind::Bat => {
+        // Every so often a bat lets go and flies a loop, then hangs back up.
+        let cycle = (0.15 * t).rem_euclid(1.0);
+        match cycle < BAT_HANG_FRACTION {
+          true => (self.home, 1.0),
+          false => {
+            let angle =
+              std::f32::consts::TAU * (cycle - BAT_HANG_FRACTION) / (1.0 - BAT_HANG_FRACTION);
+            (
+              self.home + Vec2(1.5 * angle.sin(), 0.8 * (1.0 - angle.cos())),
+              angle.cos().signum(),
+            )
+          }
+        }
+      }
+    }
+  }
+
+  pub fn draw(&self, context: &CanvasRenderingContext2d, camera: &Camera, time: f32) {
+    let (world_pos, facing) = self.position(time);
+    let pos = camera.world_to_screen(world_pos);
+    let unit = camera.length_to_screen(1.0) as f64;
+    let (x, y, facing) = (pos.0 as f64, pos.1 as f64, facing as f64);
+    context.begin_path();
+    match self.kind {
+      CritterKind::Fish => {
+        context.set_fill_style(&JsValue::from_str("#f93"));
+        context
+          .ellipse(
+            x,
+            y,
+            0.2 * unit,
+            0.08 * unit,
+            0.0,
+            0.0,
+            std::f64::consts::TAU,
+          )
+          .unwrap();
+        // The tail.
+        context.move_to(x - facing * 0.15 * unit, y);
+        context.line_to(x - facing * 0.3 * unit, y - 0.08 * unit);
+        context.line_to(x - facing * 0.3 * unit, y + 0.08 * unit);
+      }
+      CritterKind::Butterfly => {
+        context.set_fill_style(&JsValue::from_str("#fce"));
+        let flap = 0.04 + 0.08 * (25.0 * time + self.phase).sin().abs() as f64;
+        for side in [-1.0, 1.0] {
+          context.move_to(x, y);
+          context.line_to(x + side * flap * unit, y - 0.1 * unit);
+          context.line_to(x + side * flap * unit, y + 0.05 * unit);
+        }
+      }
+      CritterKind::Bat => {
+        context.set_fill_style(&JsValue::from_str("#213"));
+        let flap = match world_pos == self.home {
+          true => 0.0,
+          false => 0.1 * (18.0 * time + self.phase).sin() as f64,
+        };
+        context.move_to(x, y);
+        context.line_to(x - 0.25 * unit, y - flap * unit);
+        context.line_to(x - 0.1 * unit, y + 0.1 * unit);
+        context.line_to(x, y + 0.05 * unit);
+        context.line_to(x + 0.1 * unit, y + 0.1 * unit);
+        context.line_to(x + 0.25 * unit, y - flap * unit);
+      }
+    }
+    context.close_path();
+    context.fill();
+  }
+}
+
+/// The critters in the zones near the camera. Zones far from the view have none, and
+/// their critters go back into a spare pool for the next zone to reuse.
+#[derive(Debug, Default)]
+pub struct CritterSystem {
+  active: HashMap<usize, Vec<Critter>>,
+  spare:  Vec<Vec<Critter>>,
+}
+
+impl CritterSystem {
+  pub fn len(&self) -> usize {
+    self.active.values().map(|critters| critters.len()).sum()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Fills zones coming near `view` with critters, scaled by the effects density, and
+  /// empties the ones that have gone out of range.
+  pub fn update(&mut self, zones: &[CritterZone], view: Rect, density: f32) {
+    let margin = Vec2(ACTIVE_MARGIN, ACTIVE_MARGIN);
+    let range = Rect::new(view.pos - margin, view.size + 2.0 * margin);
+    for (index, zone) in zones.iter().enumerate() {
+      let in_range = zone.bounds.overlaps(range);
+      match (in_range, self.active.contains_key(&index)) {
+        (true, false) => {
+          let mut critters = self.spare.pop().unwrap_or_default();
+          populate(&mut critters, zone, density);
+          self.active.insert(index, critters);
+        }
+        (false, true) => {
+          let mut critters = self.active.remove(&index).unwrap();
+          critters.clear();
+          self.spare.push(critters);
+        }
+        _ => {}
+      }
+    }
+  }
+
+  pub fn clear(&mut self) {
+    for (_, mut critters) in self.active.drain() {
+      critters.clear();
+      self.spare.push(critters);
+    }
+  }
+
+  pub fn draw(&self, context: &CanvasRenderingContext2d, camera: &Camera, time: f32) {
+    let view = camera.view_rect();
+    // Critters wander at most a couple of tiles from home.
+    let view = Rect::new(view.pos - Vec2(2.0, 2.0), view.size + Vec2(4.0, 4.0));
+    for critter in self.active.values().flatten() {
+      if view.contains_point(critter.home) {
+        critter.draw(context, camera, time);
+      }
+    }
+  }
+}
+
+fn populate(critters: &mut Vec<Critter>, zone: &CritterZone, density: f32) {
+  for (kind, habitats, per_hundred) in &zone.habitats {
+    if habitats.is_empty() {
+      continue;
+    }
+    let count = (per_hundred * density * habitats.len() as f32 / 100.0).round() as usize;
+    for _ in 0..count.min(habitats.len()) {
+      let habitat = habitats[rand::random::<usize>() % habitats.len()];
+      critters.push(Critter {
+        kind:  *kind,
+        home:  habitat.home,
+        range: habitat.range,
+        phase: rand::random::<f32>() * std::f32::consts::TAU,
+        speed: 0.6 + 0.6 * rand::random::<f32>(),
+      });
+    }
+  }
+}
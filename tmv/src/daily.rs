@@ -0,0 +1,61 @@
+use crate::{assets, power_ups::PowerUp, side_run::SideRun, time_trials};
+
+/// The day's challenge: a fresh game against the clock, with gameplay randomness seeded
+/// from the date so that everyone playing on the same day faces the same run. It's over
+/// once every power-up in the world has been found.
+#[derive(Debug, Clone)]
+pub struct DailyChallenge {
+  // Like "2026-10-16".
+  pub date:        String,
+  pub seed:        u64,
+  // The code to share, once finished.
+  pub result_code: Option<String>,
+  pub run:         SideRun,
+}
+
+impl DailyChallenge {
+  pub fn new(date: &str, live_save_data: String) -> Result<Self, String> {
+    Ok(Self {
+      date:        date.to_string(),
+      seed:        seed_for_date(date)?,
+      result_code: None,
+      run:         SideRun {
+        live_save_data,
+        ..SideRun::default()
+      },
+    })
+  }
+
+  /// Stops the clock and works out the result code from how the run ended.
+  pub fn finish(&mut self, power_ups: &[PowerUp], deaths: u32) -> &str {
+    let mut power_ups: Vec<&str> = power_ups.iter().map(|power_up| power_up.name()).collect();
+    power_ups.sort();
+    let hash = self.run.result_hash(&[&self.seed, &deaths, &power_ups.join(",")]);
+    self.result_code.insert(format!(
+      "{} {} {}",
+      self.date,
+      time_trials::format_time(self.run.elapsed),
+      hash,
+    ))
+  }
+}
+
+/// The seed for a date given as YYYY-MM-DD.
+pub fn seed_for_date(date: &str) -> Result<u64, String> {
+  let parts: Vec<&str> = date.split('-').collect();
+  let valid = match parts[..] {
+    [year, month, day] => {
+      year.len() == 4
+        && month.len() == 2
+        && day.len() == 2
+        && parts.iter().all(|part| part.bytes().all(|b| b.is_ascii_digit()))
+        && (1..=12).contains(&month.parse::<u32>().unwrap())
+        && (1..=31).contains(&day.parse::<u32>().unwrap())
+    }
+    _ => false,
+  };
+  match valid {
+    true => Ok(assets::fnv1a(format!("tmv daily {}", date).as_bytes())),
+    false => Err(format!("Dates must be YYYY-MM-DD, not {}", date)),
+  }
+}
@@ -0,0 +1,73 @@
+use crate::{collision::PhysicsObjectHandle, math::Vec2};
+
+// How long everything freezes at the moment of death.
+pub const HIT_STOP_TIME: f32 = 0.15;
+// How long the body tumbles before the screen starts fading.
+pub const TOSS_TIME: f32 = 1.1;
+pub const FADE_TIME: f32 = 0.7;
+// How hard the body gets thrown: up, and away from the way the player was going.
+pub const TOSS_SPEED: Vec2 = Vec2(6.0, -18.0);
+pub const TOSS_SPIN: f32 = 9.0;
+pub const BODY_GRAVITY: f32 = 60.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeathPhase {
+  HitStop,
+  Toss,
+  Fade,
+  GameOver,
+}
+
+/// Runs the steps of dying in order: a freeze, the body being tossed, a fade to black, and
+/// then waiting on the game over screen for the player to respawn.
+pub struct DeathSequence {
+  pub phase: DeathPhase,
+  // Seconds spent in the current phase.
+  pub time:  f32,
+  // The tossed body, which collides with walls and falls under gravity.
+  pub body:  PhysicsObjectHandle,
+}
+
+impl DeathSequence {
+  pub fn new(body: PhysicsObjectHandle) -> Self {
+    Self {
+      phase: DeathPhase::HitStop,
+      time: 0.0,
+      body,
+    }
+  }
+
+  pub fn update(&mut self, dt: f32) {
+    self.time += dt;
+    let (length, next) = match self.phase {
+      DeathPhase::HitStop => (HIT_STOP_TIME, DeathPhase::Toss),
+      DeathPhase::Toss => (TOSS_TIME, DeathPhase::Fade),
+      DeathPhase::Fade => (FADE_TIME, DeathPhase::GameOver),
+      DeathPhase::GameOver => return,
+    };
+    if self.time >= length {
+      self.time -= length;
+      self.phase = next;
+    }
+  }
+
+  /// Whether the rest of the world should stay frozen.
+  pub fn is_hit_stopped(&self) -> bool {
+    self.phase == DeathPhase::HitStop
+  }
+
+  /// How dark the screen is, from 0 to 1.
+  pub fn fade_amount(&self) -> f32 {
+    match self.phase {
+      DeathPhase::HitStop | DeathPhase::Toss => 0.0,
+      DeathPhase::Fade => (self.time / FADE_TIME).min(1.0),
+      DeathPhase::GameOver => 1.0,
+    }
+  }
+
+  /// Respawning can skip the toss and fade, but not the freeze, so a press meant for
+  /// something else right as the player died doesn't skip straight past it.
+  pub fn can_respawn(&self) -> bool {
+    self.phase != DeathPhase::HitStop
+  }
+}
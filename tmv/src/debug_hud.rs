@@ -0,0 +1,349 @@
+use std::collections::{HashMap, HashSet};
+
+use rapier2d::prelude::ColliderHandle;
+use wasm_bindgen::JsValue;
+
+use crate::{
+  camera::Camera,
+  collision::{CollisionWorld, PhysicsObjectHandle},
+  inspector::kind_name,
+  math::Vec2,
+  GameObject, GameObjectData, ThwumpState, SCREEN_HEIGHT, SCREEN_WIDTH,
+};
+
+// How far ahead the velocity arrow reaches, in seconds of travel.
+const VELOCITY_ARROW_TIME: f32 = 0.1;
+
+/// A coarse label for what the player's movement is doing, derived from the movement flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovementState {
+  Dead,
+  Dashing,
+  // Dashing up, down, or diagonally, with the omni dash.
+  AimedDashing,
+  SpringLaunched,
+  Shrunken,
+  LedgeHanging,
+  Swimming,
+  Grounded,
+  WallSliding,
+  Rising,
+  Falling,
+}
+
+impl MovementState {
+  pub fn name(self) -> &'static str {
+    match self {
+      MovementState::Dead => "dead",
+      MovementState::Dashing => "dashing",
+      MovementState::AimedDashing => "aimed dash",
+      MovementState::SpringLaunched => "spring",
+      MovementState::Shrunken => "shrunken",
+      MovementState::LedgeHanging => "ledge hang",
+      MovementState::Swimming => "swimming",
+      MovementState::Grounded => "grounded",
+      MovementState::WallSliding => "wall slide",
+      MovementState::Rising => "rising",
+      MovementState::Falling => "falling",
+    }
+  }
+}
+
+/// Everything the movement readout shows, snapshotted from the game state before drawing.
+#[derive(Debug, Clone)]
+pub struct MovementReadout {
+  pub frame:       u64,
+  pub velocity:    Vec2,
+  pub state:       MovementState,
+  pub grounded:    bool,
+  pub wall_left:   bool,
+  pub wall_right:  bool,
+  pub dashes_left: u32,
+  pub double_jump: bool,
+  pub coyote_time: f32,
+}
+
+fn flag(name: &str, set: bool) -> String {
+  match set {
+    true => name.to_uppercase(),
+    false => name.to_string(),
+  }
+}
+
+/// Draws the readout as a small translucent panel with its top left corner at `origin`.
+pub fn draw_movement_readout(
+  ctx: &web_sys::CanvasRenderingContext2d,
+  origin: Vec2,
+  readout: &MovementReadout,
+) {
+  let lines = [
+    format!("frame {:>8}", readout.frame),
+    format!(
+      "vx {:>+7.2}  vy {:>+7.2}",
+      readout.velocity.0, readout.velocity.1
+    ),
+    format!("speed {:>6.2}", readout.velocity.length()),
+    format!("state {}", readout.state.name()),
+    // Upper case means the flag is set.
+    format!(
+      "{} {} {} {} {}",
+      flag("gnd", readout.grounded),
+      flag("wl", readout.wall_left),
+      flag("wr", readout.wall_right),
+      flag(
+        &format!("dash{}", readout.dashes_left),
+        readout.dashes_left > 0
+      ),
+      flag("dj", readout.double_jump),
+    ),
+    format!("coyote {:.3}", readout.coyote_time),
+  ];
+  ctx.set_fill_style(&JsValue::from_str("rgba(0, 0, 0, 0.6)"));
+  ctx.fill_rect(
+    origin.0 as f64,
+    origin.1 as f64,
+    250.0,
+    8.0 + 18.0 * lines.len() as f64,
+  );
+  ctx.set_font("14px monospace");
+  ctx.set_fill_style(&JsValue::from_str("#8f8"));
+  ctx.set_text_align("left");
+  ctx.set_text_baseline("top");
+  for (i, line) in lines.iter().enumerate() {
+    ctx
+      .fill_text(
+        line,
+        origin.0 as f64 + 6.0,
+        origin.1 as f64 + 4.0 + 18.0 * i as f64,
+      )
+      .unwrap();
+  }
+}
+
+fn hitbox_color(data: &GameObjectData) -> Option<&'static str> {
+  match data {
+    GameObjectData::Spike { .. }
+    | GameObjectData::Lava
+    | GameObjectData::HazardSurface { .. }
+    | GameObjectData::Bullet { .. }
+    | GameObjectData::Bee { .. }
+    | GameObjectData::BeeQueen { .. } => Some("#f44"),
+    GameObjectData::DeleteMe => None,
+    _ => Some("#fd4"),
+  }
+}
+
+/// Outlines the collider of every object on screen (damaging ones in red) and the player,
+/// with an arrow for the player's velocity.
+pub fn draw_hitboxes(
+  ctx: &web_sys::CanvasRenderingContext2d,
+  objects: &HashMap<ColliderHandle, GameObject>,
+  collision: &CollisionWorld,
+  player_physics: &PhysicsObjectHandle,
+  player_vel: Vec2,
+  camera: &Camera,
+) {
+  let stroke_bounds = |collider: ColliderHandle, color: &str| {
+    let Some(bounds) = collision.collider_bounds(collider) else {
+      return;
+    };
+    let top_left = camera.world_to_screen(bounds.pos);
+    let size = camera.size_to_screen(bounds.size);
+    if top_left.0 + size.0 < 0.0
+      || top_left.1 + size.1 < 0.0
+      || top_left.0 > SCREEN_WIDTH
+      || top_left.1 > SCREEN_HEIGHT
+    {
+      return;
+    }
+    ctx.set_stroke_style(&JsValue::from_str(color));
+    ctx.stroke_rect(
+      top_left.0 as f64,
+      top_left.1 as f64,
+      size.0 as f64,
+      size.1 as f64,
+    );
+  };
+  ctx.set_line_width(1.0);
+  for (handle, object) in objects {
+    if let Some(color) = hitbox_color(&object.data) {
+      stroke_bounds(*handle, color);
+    }
+  }
+  stroke_bounds(player_physics.collider, "#4f4");
+
+  let Some(player_pos) = collision.get_position(player_physics) else {
+    return;
+  };
+  let start = camera.world_to_screen(player_pos);
+  let end = camera.world_to_screen(player_pos + VELOCITY_ARROW_TIME * player_vel);
+  ctx.set_stroke_style(&JsValue::from_str("#4df"));
+  ctx.set_line_width(2.0);
+  ctx.begin_path();
+  ctx.move_to(start.0 as f64, start.1 as f64);
+  ctx.line_to(end.0 as f64, end.1 as f64);
+  ctx.stroke();
+}
+
+/// The groups object labels can be switched on and off by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum_macros::EnumIter)]
+pub enum LabelCategory {
+  Pickups,
+  Hazards,
+  Enemies,
+  Terrain,
+  Triggers,
+}
+
+impl LabelCategory {
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "pickups" => Some(LabelCategory::Pickups),
+      "hazards" => Some(LabelCategory::Hazards),
+      "enemies" => Some(LabelCategory::Enemies),
+      "terrain" => Some(LabelCategory::Terrain),
+      "triggers" => Some(LabelCategory::Triggers),
+      _ => None,
+    }
+  }
+
+  fn of(data: &GameObjectData) -> Option<Self> {
+    match data {
+      GameObjectData::Coin { .. }
+      | GameObjectData::RareCoin { .. }
+      | GameObjectData::HpUp { .. }
+      | GameObjectData::PowerUp { .. }
+      | GameObjectData::Key { .. }
+      | GameObjectData::AirBubble { .. } => Some(LabelCategory::Pickups),
+      GameObjectData::Spike { .. }
+      | GameObjectData::Lava
+      | GameObjectData::HazardSurface { .. }
+      | GameObjectData::Thwump { .. }
+      | GameObjectData::TurnLaser { .. }
+      | GameObjectData::Bullet { .. } => Some(LabelCategory::Hazards),
+      GameObjectData::Shooter1 { .. }
+      | GameObjectData::Beehive { .. }
+      | GameObjectData::BeeQueen { .. }
+      | GameObjectData::Enemy { .. }
+      | GameObjectData::Bee { .. } => Some(LabelCategory::Enemies),
+      GameObjectData::CountGate { .. }
+      | GameObjectData::Water
+      | GameObjectData::Platform { .. }
+      | GameObjectData::MovingPlatform { .. }
+      | GameObjectData::Stone { .. }
+      | GameObjectData::BreakableBlock { .. }
+      | GameObjectData::LockedDoor { .. }
+      | GameObjectData::ToggleBlock { .. }
+      | GameObjectData::VanishBlock { .. }
+      | GameObjectData::DestroyedDoor
+      | GameObjectData::Spring { .. }
+      | GameObjectData::BoostRail { .. } => Some(LabelCategory::Terrain),
+      GameObjectData::SavePoint
+      | GameObjectData::Interaction { .. }
+      | GameObjectData::TrialStart { .. }
+      | GameObjectData::TrialFinish { .. }
+      | GameObjectData::ChaseStart { .. }
+      | GameObjectData::ChaseEnd { .. }
+      | GameObjectData::ChallengeRegion { .. }
+      | GameObjectData::ChallengeGoal { .. }
+      | GameObjectData::MapStation { .. }
+      | GameObjectData::Switch { .. }
+      | GameObjectData::Door { .. } => Some(LabelCategory::Triggers),
+      GameObjectData::DeleteMe => None,
+    }
+  }
+}
+
+/// A short label for an object: its kind, then its entity id and whatever it's counting
+/// down, if it has them.
+fn object_label(data: &GameObjectData) -> String {
+  let entity_id = match data {
+    GameObjectData::Coin { entity_id }
+    | GameObjectData::RareCoin { entity_id }
+    | GameObjectData::HpUp { entity_id }
+    | GameObjectData::Beehive { entity_id, .. }
+    | GameObjectData::BreakableBlock { entity_id, .. }
+    | GameObjectData::Key { entity_id, .. }
+    | GameObjectData::LockedDoor { entity_id, .. }
+    | GameObjectData::ChallengeGoal { entity_id, .. } => Some(*entity_id),
+    _ => None,
+  };
+  let cooldown = match data {
+    GameObjectData::Shooter1 { cooldown, .. }
+    | GameObjectData::Beehive { cooldown, .. }
+    | GameObjectData::AirBubble { cooldown, .. } => Some(*cooldown),
+    GameObjectData::Thwump {
+      state: ThwumpState::Resting { time_left },
+      ..
+    } => Some(*time_left),
+    GameObjectData::Bee { lifespan, .. } => Some(*lifespan),
+    GameObjectData::Spring { squash, .. } => Some(*squash),
+    _ => None,
+  };
+  let mut label = kind_name(data);
+  if let Some(entity_id) = entity_id {
+    label += &format!(" #{}", entity_id);
+  }
+  if let Some(cooldown) = cooldown {
+    label += &format!(" {:.2}s", cooldown);
+  }
+  label
+}
+
+/// Labels every object on screen in one of `categories` just above its collider, so it's
+/// clear which collider is which.
+pub fn draw_object_labels(
+  ctx: &web_sys::CanvasRenderingContext2d,
+  objects: &HashMap<ColliderHandle, GameObject>,
+  collision: &CollisionWorld,
+  categories: &HashSet<LabelCategory>,
+  camera: &Camera,
+) {
+  let view = camera.view_rect();
+  ctx.set_font("11px monospace");
+  ctx.set_text_align("center");
+  ctx.set_text_baseline("bottom");
+  for (handle, object) in objects {
+    if !LabelCategory::of(&object.data).is_some_and(|category| categories.contains(&category)) {
+      continue;
+    }
+    let Some(bounds) = collision.collider_bounds(*handle) else {
+      continue;
+    };
+    if !bounds.overlaps(view) {
+      continue;
+    }
+    let label = object_label(&object.data);
+    let anchor = camera.world_to_screen(bounds.pos + Vec2(bounds.size.0 / 2.0, 0.0));
+    let width = ctx.measure_text(&label).unwrap().width();
+    ctx.set_fill_style(&JsValue::from_str("rgba(0, 0, 0, 0.6)"));
+    ctx.fill_rect(
+      anchor.0 as f64 - width / 2.0 - 2.0,
+      anchor.1 as f64 - 15.0,
+      width + 4.0,
+      13.0,
+    );
+    ctx.set_fill_style(&JsValue::from_str("#fff"));
+    ctx.fill_text(&label, anchor.0 as f64, anchor.1 as f64 - 2.0).unwrap();
+  }
+}
+
+/// Draws the replay position with its bottom right corner at `origin`.
+pub fn draw_replay_position(
+  ctx: &web_sys::CanvasRenderingContext2d,
+  origin: Vec2,
+  frame: usize,
+  length: usize,
+) {
+  ctx.set_font("14px monospace");
+  ctx.set_fill_style(&JsValue::from_str("#8f8"));
+  ctx.set_text_align("right");
+  ctx.set_text_baseline("bottom");
+  ctx
+    .fill_text(
+      &format!("replay {} / {}", frame, length),
+      origin.0 as f64,
+      origin.1 as f64,
+    )
+    .unwrap();
+}
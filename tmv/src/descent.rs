@@ -0,0 +1,176 @@
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+
+use serde::Serialize;
+
+use crate::{
+  collision::PlacedFragment,
+  game_maps::{self, GameMap},
+  rng::GameRng,
+  side_run::SideRun,
+};
+
+/// Where a descent starts, with the spawn point, and the shaft the rooms hang below.
+pub const ENTRANCE_MAP: &str = "/assets/descent_entrance.tmx";
+/// Every map whose path starts with this is a room template.
+pub const ROOM_TEMPLATE_PREFIX: &str = "/assets/descent_room";
+// Rooms are streamed in until there are this many tiles of them below the player.
+pub const LOOKAHEAD: f32 = 48.0;
+// Rooms are dropped once their bottom is this many tiles above the player.
+pub const KEEP_BEHIND: f32 = 64.0;
+// Templates of one more difficulty come up every this many tiles down.
+pub const DEPTH_PER_DIFFICULTY: f32 = 96.0;
+pub const POINTS_PER_COIN: u32 = 25;
+
+const CHUNK_HEIGHT: i32 = tiled::Chunk::HEIGHT as i32;
+
+/// Whether the map at `map_name` is only used for descents. Nothing collected in a descent
+/// is kept, so these maps' items aren't part of the main game.
+pub fn is_descent_map(map_name: &str) -> bool {
+  map_name == ENTRANCE_MAP || map_name.starts_with(ROOM_TEMPLATE_PREFIX)
+}
+
+/// A small map to stack into the descent. Templates are placed in whole chunks, so each
+/// should fill its chunks from top to bottom, with the way through lined up with the
+/// entrance's shaft. The map's difficulty property says how deep it can start showing up.
+pub struct RoomTemplate {
+  pub game_map:   Rc<GameMap>,
+  // The top and bottom rows of chunks it covers.
+  pub chunk_rows: (i32, i32),
+  pub difficulty: i32,
+}
+
+/// A room that's currently in the world.
+pub struct PlacedRoom {
+  // Everything added to the world for the room, to take out again once it's passed.
+  pub fragment: PlacedFragment,
+  pub bottom:   i32,
+}
+
+/// An endless run down through rooms picked from the templates, harder the deeper it gets.
+/// It's over when the player dies, and scores by how far down they got.
+pub struct Descent {
+  pub seed:         u32,
+  pub templates:    Vec<RoomTemplate>,
+  pub rooms:        VecDeque<PlacedRoom>,
+  pub rooms_placed: u32,
+  // The row the next room's top goes at.
+  pub next_top:     i32,
+  // Where the player started, which depth is measured down from.
+  pub start_y:      f32,
+  // The deepest the player has been, in tiles.
+  pub depth:        f32,
+  // Set once the run is over.
+  pub result:       Option<DescentResult>,
+  pub run:          SideRun,
+  rng:              GameRng,
+}
+
+/// How a descent went, for leaderboards. The code is a hash of the rest, so that submitted
+/// results can be checked for edits.
+#[derive(Debug, Clone, Serialize)]
+pub struct DescentResult {
+  pub seed:  u32,
+  pub depth: u32,
+  pub coins: u32,
+  pub time:  f32,
+  pub score: u32,
+  pub code:  String,
+}
+
+impl Descent {
+  pub fn new(
+    resources: &HashMap<String, Vec<u8>>,
+    seed: u32,
+    live_save_data: String,
+  ) -> Result<Self, String> {
+    let entrance = GameMap::from_resources(resources, ENTRANCE_MAP).map_err(|e| e.to_string())?;
+    let entrance_rows = entrance.chunk_rows().ok_or("The descent entrance is empty")?;
+    let mut template_names: Vec<&str> = resources
+      .keys()
+      .map(|name| name.as_str())
+      .filter(|name| name.starts_with(ROOM_TEMPLATE_PREFIX))
+      .collect();
+    // Sorted, so that the same seed always picks the same rooms.
+    template_names.sort();
+    let mut templates = Vec::new();
+    for game_map in
+      game_maps::load_fragments(resources, template_names).map_err(|e| e.to_string())?
+    {
+      let chunk_rows =
+        game_map.chunk_rows().ok_or_else(|| format!("{} is empty", game_map.name))?;
+      let difficulty = match game_map.map.properties.get("difficulty") {
+        Some(tiled::PropertyValue::IntValue(difficulty)) => *difficulty,
+        _ => 0,
+      };
+      templates.push(RoomTemplate {
+        game_map: Rc::new(game_map),
+        chunk_rows,
+        difficulty,
+      });
+    }
+    if !templates.iter().any(|template| template.difficulty <= 0) {
+      return Err("No descent room templates of difficulty 0".to_string());
+    }
+    Ok(Self {
+      seed,
+      templates,
+      rooms: VecDeque::new(),
+      rooms_placed: 0,
+      next_top: (entrance_rows.1 + 1) * CHUNK_HEIGHT,
+      start_y: 0.0,
+      depth: 0.0,
+      result: None,
+      run: SideRun {
+        live_save_data,
+        ..SideRun::default()
+      },
+      rng: GameRng::new(seed as u64),
+    })
+  }
+
+  /// The difficulty of rooms placed at row `y`.
+  pub fn difficulty_at(&self, y: i32) -> i32 {
+    ((y as f32 - self.start_y).max(0.0) / DEPTH_PER_DIFFICULTY) as i32
+  }
+
+  /// Picks the room to go next, and where, moving `next_top` down past it. The caller adds
+  /// it to the world. Rooms at the current difficulty are favored, with easier ones mixed in.
+  pub fn next_room(&mut self) -> (Rc<GameMap>, (i32, i32)) {
+    let difficulty = self.difficulty_at(self.next_top);
+    let hardest = self
+      .templates
+      .iter()
+      .map(|template| template.difficulty)
+      .filter(|d| *d <= difficulty)
+      .max()
+      .unwrap_or(0);
+    let candidates: Vec<&RoomTemplate> = match self.rng.next_f32() < 0.75 {
+      true => self.templates.iter().filter(|t| t.difficulty == hardest).collect(),
+      false => self.templates.iter().filter(|t| t.difficulty <= hardest).collect(),
+    };
+    let index = (self.rng.next_u64() % candidates.len() as u64) as usize;
+    let template = candidates[index];
+    let offset = (0, self.next_top - template.chunk_rows.0 * CHUNK_HEIGHT);
+    let height = (template.chunk_rows.1 - template.chunk_rows.0 + 1) * CHUNK_HEIGHT;
+    let game_map = template.game_map.clone();
+    self.next_top += height;
+    self.rooms_placed += 1;
+    (game_map, offset)
+  }
+
+  /// Ends the run, working out the result from how it went.
+  pub fn finish(&mut self, coins: u32) -> &DescentResult {
+    let depth = self.depth as u32;
+    let score = depth + POINTS_PER_COIN * coins;
+    let code = self.run.result_hash(&[&"descent", &self.seed, &depth, &coins, &score]);
+    self.result.insert(DescentResult {
+      seed: self.seed,
+      depth,
+      coins,
+      time: self.run.elapsed,
+      score,
+      code,
+    })
+  }
+}
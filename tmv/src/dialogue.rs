@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use wasm_bindgen::JsValue;
+use web_sys::CanvasRenderingContext2d;
+
+use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+const BOX_HEIGHT: f64 = 180.0;
+const BOX_MARGIN: f64 = 24.0;
+const TEXT_PADDING: f64 = 20.0;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DialogueChoice {
+  pub text: String,
+  // The script to carry on with, or None to end the conversation.
+  #[serde(default)]
+  pub next: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DialogueLine {
+  #[serde(default)]
+  pub speaker: Option<String>,
+  pub text:    String,
+  // Offered once the line is shown. A line with choices has to be the last in its script.
+  #[serde(default)]
+  pub choices: Vec<DialogueChoice>,
+}
+
+fn default_prompt() -> String {
+  "Press E to read".to_string()
+}
+
+/// One conversation, as an interaction rect's `dialogue` property names it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DialogueScript {
+  // Shown while the player stands at the interaction.
+  #[serde(default = "default_prompt")]
+  pub prompt: String,
+  pub lines:  Vec<DialogueLine>,
+}
+
+/// Every dialogue script, loaded from JSON mapping script names to scripts.
+#[derive(Debug, Clone, Default)]
+pub struct DialogueLibrary {
+  scripts: HashMap<String, DialogueScript>,
+}
+
+impl DialogueLibrary {
+  pub fn from_json(json: &str) -> Result<Self, String> {
+    let scripts: HashMap<String, DialogueScript> =
+      serde_json::from_str(json).map_err(|e| e.to_string())?;
+    for (name, script) in &scripts {
+      if script.lines.is_empty() {
+        return Err(format!("Dialogue {} has no lines", name));
+      }
+      for (i, line) in script.lines.iter().enumerate() {
+        if !line.choices.is_empty() && i + 1 != script.lines.len() {
+          return Err(format!(
+            "Dialogue {} has choices before its last line",
+            name
+          ));
+        }
+        for next in line.choices.iter().filter_map(|choice| choice.next.as_ref()) {
+          if !scripts.contains_key(next) {
+            return Err(format!(
+              "Dialogue {} leads to unknown dialogue {}",
+              name, next
+            ));
+          }
+        }
+      }
+    }
+    Ok(Self { scripts })
+  }
+
+  pub fn get(&self, name: &str) -> Option<&DialogueScript> {
+    self.scripts.get(name)
+  }
+}
+
+/// A conversation under way. Gameplay is paused until it's over.
+#[derive(Debug, Clone)]
+pub struct Dialogue {
+  pub script:      String,
+  pub line:        usize,
+  pub selected:    usize,
+  // The interaction that started it, which goes off once the conversation is over.
+  pub interaction: i32,
+}
+
+impl Dialogue {
+  pub fn new(script: String, interaction: i32) -> Self {
+    Self {
+      script,
+      line: 0,
+      selected: 0,
+      interaction,
+    }
+  }
+
+  pub fn current_line<'a>(&self, library: &'a DialogueLibrary) -> Option<&'a DialogueLine> {
+    library.get(&self.script)?.lines.get(self.line)
+  }
+
+  /// Moves the highlighted choice up (-1) or down (1), if there are choices.
+  pub fn move_selection(&mut self, library: &DialogueLibrary, delta: i32) {
+    let count = self.current_line(library).map_or(0, |line| line.choices.len());
+    if count > 0 {
+      self.selected = (self.selected as i32 + delta).rem_euclid(count as i32) as usize;
+    }
+  }
+
+  /// Pages on from the current line, taking the highlighted choice if it has any. Returns
+  /// false once the conversation is over.
+  pub fn advance(&mut self, library: &DialogueLibrary) -> bool {
+    let Some(line) = self.current_line(library) else {
+      return false;
+    };
+    match line.choices.get(self.selected) {
+      Some(choice) => match &choice.next {
+        Some(next) => {
+          *self = Self::new(next.clone(), self.interaction);
+          true
+        }
+        None => false,
+      },
+      None => {
+        self.line += 1;
+        self.current_line(library).is_some()
+      }
+    }
+  }
+
+  /// Draws the current line in a box along the bottom of the screen, with any choices
+  /// under it.
+  pub fn draw(&self, ctx: &CanvasRenderingContext2d, library: &DialogueLibrary) {
+    let Some(line) = self.current_line(library) else {
+      return;
+    };
+    let (x, y) = (BOX_MARGIN, SCREEN_HEIGHT as f64 - BOX_HEIGHT - BOX_MARGIN);
+    let width = SCREEN_WIDTH as f64 - 2.0 * BOX_MARGIN;
+    ctx.set_fill_style(&JsValue::from_str("rgba(0, 0, 0, 0.8)"));
+    ctx.fill_rect(x, y, width, BOX_HEIGHT);
+    ctx.set_stroke_style(&JsValue::from_str("#fff"));
+    ctx.set_line_width(2.0);
+    ctx.stroke_rect(x, y, width, BOX_HEIGHT);
+    ctx.set_text_align("left");
+    ctx.set_text_baseline("top");
+    let mut text_y = y + TEXT_PADDING;
+    if let Some(speaker) = &line.speaker {
+      ctx.set_font("bold 22px Arial");
+      ctx.set_fill_style(&JsValue::from_str("#fd4"));
+      ctx.fill_text(speaker, x + TEXT_PADDING, text_y).unwrap();
+      text_y += 32.0;
+    }
+    ctx.set_font("24px Arial");
+    ctx.set_fill_style(&JsValue::from_str("#fff"));
+    ctx.fill_text(&line.text, x + TEXT_PADDING, text_y).unwrap();
+    text_y += 40.0;
+    ctx.set_font("22px Arial");
+    for (i, choice) in line.choices.iter().enumerate() {
+      let (marker, color) = match i == self.selected {
+        true => ("> ", "#4df"),
+        false => ("  ", "#aaa"),
+      };
+      ctx.set_fill_style(&JsValue::from_str(color));
+      ctx
+        .fill_text(
+          &format!("{}{}", marker, choice.text),
+          x + TEXT_PADDING,
+          text_y + 28.0 * i as f64,
+        )
+        .unwrap();
+    }
+    if line.choices.is_empty() {
+      ctx.set_font("18px Arial");
+      ctx.set_text_align("right");
+      ctx.set_text_baseline("bottom");
+      ctx.set_fill_style(&JsValue::from_str("#aaa"));
+      ctx
+        .fill_text(
+          "E to continue",
+          x + width - TEXT_PADDING,
+          y + BOX_HEIGHT - TEXT_PADDING / 2.0,
+        )
+        .unwrap();
+    }
+  }
+}
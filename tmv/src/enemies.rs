@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::Vec2;
+
+/// How fast walkers go when their tile doesn't say, in tiles per second.
+pub const WALKER_DEFAULT_SPEED: f32 = 2.0;
+pub const WALKER_DEFAULT_DAMAGE: i32 = 1;
+// How far past their edges walkers check for walls and floor, in tiles.
+const PROBE_MARGIN: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EnemyKind {
+  // Walks back and forth along the ground, turning around at walls and ledges.
+  Walker,
+}
+
+/// A hostile creature that hurts the player on contact, and dies when dashed into.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Enemy {
+  pub kind:     EnemyKind,
+  pub position: Vec2,
+  pub size:     Vec2,
+  // -1 for left, 1 for right.
+  pub facing:   f32,
+  pub speed:    f32,
+  pub damage:   i32,
+}
+
+impl Enemy {
+  pub fn walker(position: Vec2, size: Vec2, speed: f32, damage: i32) -> Self {
+    Self {
+      kind: EnemyKind::Walker,
+      position,
+      size,
+      facing: 1.0,
+      speed,
+      damage,
+    }
+  }
+
+  /// Moves the enemy along, with `hits_wall(origin, direction, distance)` saying whether
+  /// there's anything solid within `distance` of `origin` going in `direction`.
+  pub fn update(&mut self, dt: f32, hits_wall: impl Fn(Vec2, Vec2, f32) -> bool) {
+    match self.kind {
+      EnemyKind::Walker => {
+        // Walkers never leave the ground, so they don't need gravity.
+        let direction = Vec2(self.facing, 0.0);
+        let reach = self.size.0 / 2.0 + PROBE_MARGIN;
+        let wall_ahead = hits_wall(self.position, direction, reach);
+        let front = self.position + reach * direction;
+        let floor_ahead = hits_wall(front, Vec2(0.0, 1.0), self.size.1 / 2.0 + PROBE_MARGIN);
+        if wall_ahead || !floor_ahead {
+          self.facing = -self.facing;
+        } else {
+          self.position.0 += self.facing * self.speed * dt;
+        }
+      }
+    }
+  }
+}
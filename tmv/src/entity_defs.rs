@@ -0,0 +1,165 @@
+use crate::{math::Vec2, status_effects::StatusEffect};
+
+/// Typed access to a tile's custom properties. Like the rest of map loading, a property of
+/// the wrong type is a bug in the map, so it panics rather than being ignored.
+#[derive(Clone, Copy)]
+pub struct TileProperties<'a>(pub &'a tiled::Properties);
+
+impl<'a> TileProperties<'a> {
+  pub fn string(&self, key: &str) -> Option<&'a str> {
+    match self.0.get(key) {
+      Some(tiled::PropertyValue::StringValue(s)) => Some(s),
+      Some(_) => panic!("{} must be a string", key),
+      None => None,
+    }
+  }
+
+  pub fn int(&self, key: &str) -> Option<i32> {
+    match self.0.get(key) {
+      Some(tiled::PropertyValue::IntValue(i)) => Some(*i),
+      Some(_) => panic!("{} must be an int", key),
+      None => None,
+    }
+  }
+
+  pub fn bool(&self, key: &str) -> Option<bool> {
+    match self.0.get(key) {
+      Some(tiled::PropertyValue::BoolValue(b)) => Some(*b),
+      Some(_) => panic!("{} must be a bool", key),
+      None => None,
+    }
+  }
+
+  /// Ints are accepted too, since Tiled makes it easy to type `2` for a float property.
+  pub fn number(&self, key: &str) -> Option<f32> {
+    match self.0.get(key) {
+      Some(tiled::PropertyValue::FloatValue(f)) => Some(*f),
+      Some(tiled::PropertyValue::IntValue(i)) => Some(*i as f32),
+      Some(_) => panic!("{} must be a number", key),
+      None => None,
+    }
+  }
+}
+
+/// The built-in behaviors a tile can have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityKind {
+  Water,
+  Lava,
+  Coin,
+  RareCoin,
+  HpUp,
+  PowerUp,
+  Spike,
+  Shooter,
+  Beehive,
+  CountGate,
+  Stone,
+  BreakableBlock,
+  Key,
+  LockedDoor,
+  Switch,
+  ToggleBlock,
+  SavePoint,
+  Platform,
+  Thwump,
+  MovingPlatform,
+  TurnLaser,
+  VanishBlock,
+  Spring,
+  HazardSurface(StatusEffect),
+  AirBubble,
+  BoostRail,
+  Walker,
+  Spawn,
+}
+
+/// Every entity type name a tile can use, either as its `name` or its `entity` property.
+pub const ENTITY_REGISTRY: &[(&str, EntityKind)] = &[
+  ("water", EntityKind::Water),
+  ("lava", EntityKind::Lava),
+  ("coin", EntityKind::Coin),
+  ("rare_coin", EntityKind::RareCoin),
+  ("hp_up", EntityKind::HpUp),
+  ("powerup", EntityKind::PowerUp),
+  ("spike", EntityKind::Spike),
+  ("shooter", EntityKind::Shooter),
+  ("beehive", EntityKind::Beehive),
+  ("coin_wall", EntityKind::CountGate),
+  ("count_gate", EntityKind::CountGate),
+  ("stone", EntityKind::Stone),
+  ("breakable_block", EntityKind::BreakableBlock),
+  ("key", EntityKind::Key),
+  ("locked_door", EntityKind::LockedDoor),
+  ("switch", EntityKind::Switch),
+  ("toggle_block", EntityKind::ToggleBlock),
+  ("save_left", EntityKind::SavePoint),
+  ("platform", EntityKind::Platform),
+  ("thwump", EntityKind::Thwump),
+  ("moving_platform", EntityKind::MovingPlatform),
+  ("turn_laser", EntityKind::TurnLaser),
+  ("vanish_block", EntityKind::VanishBlock),
+  ("spring", EntityKind::Spring),
+  ("poison", EntityKind::HazardSurface(StatusEffect::Poisoned)),
+  ("electric", EntityKind::HazardSurface(StatusEffect::Shocked)),
+  ("air_bubble", EntityKind::AirBubble),
+  ("boost_rail", EntityKind::BoostRail),
+  ("walker", EntityKind::Walker),
+  ("spawn", EntityKind::Spawn),
+];
+
+impl EntityKind {
+  pub fn from_name(name: &str) -> Option<Self> {
+    ENTITY_REGISTRY.iter().find(|(n, _)| *n == name).map(|(_, kind)| *kind)
+  }
+
+  /// The radius of the sensor circle, for kinds that are touched rather than stood on.
+  fn default_radius(self) -> f32 {
+    match self {
+      EntityKind::Spike => 0.2,
+      EntityKind::AirBubble => 0.4,
+      EntityKind::Key => 0.35,
+      _ => 0.45,
+    }
+  }
+
+  /// The size of the box, for kinds that are solid or fill their tile.
+  fn default_size(self) -> Vec2 {
+    match self {
+      EntityKind::CountGate => Vec2(0.6, 0.6),
+      EntityKind::Thwump | EntityKind::MovingPlatform => Vec2(3.0, 1.0),
+      EntityKind::LockedDoor => Vec2(1.0, 3.0),
+      _ => Vec2(1.0, 1.0),
+    }
+  }
+}
+
+/// Everything about an entity that comes from its tile: what kind it is, how big it is, and
+/// the tile's properties for the kind-specific parameters (damage, cooldowns, and so on).
+/// A new variant of an existing kind, like a faster shooter, is a new tile with an `entity`
+/// property naming the kind, and properties overriding whatever it needs to.
+pub struct EntityDef<'a> {
+  pub kind:       EntityKind,
+  pub radius:     f32,
+  pub size:       Vec2,
+  pub properties: TileProperties<'a>,
+}
+
+impl<'a> EntityDef<'a> {
+  pub fn from_tile(name: &str, properties: &'a tiled::Properties) -> Self {
+    let properties = TileProperties(properties);
+    let entity = properties.string("entity").unwrap_or(name);
+    let kind = EntityKind::from_name(entity)
+      .unwrap_or_else(|| panic!("Unsupported entity type: {} (on tile {})", entity, name));
+    let default_size = kind.default_size();
+    Self {
+      kind,
+      radius: properties.number("radius").unwrap_or(kind.default_radius()),
+      size: Vec2(
+        properties.number("width").unwrap_or(default_size.0),
+        properties.number("height").unwrap_or(default_size.1),
+      ),
+      properties,
+    }
+  }
+}
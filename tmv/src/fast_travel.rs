@@ -0,0 +1,55 @@
+use crate::{camera::Camera, math::Vec2};
+
+// How close, in screen pixels, a save point has to be to the middle of the map screen for
+// the map cursor to pick it.
+pub const PICK_RADIUS: f32 = 24.0;
+
+/// The save point out of `save_points` nearest `cursor`, a point on the map screen, if one
+/// is close enough to pick.
+pub fn pick(
+  save_points: impl IntoIterator<Item = Vec2>,
+  camera: &Camera,
+  cursor: Vec2,
+) -> Option<Vec2> {
+  save_points
+    .into_iter()
+    .map(|point| (point, (camera.world_to_screen(point) - cursor).length()))
+    .filter(|(_, distance)| *distance < PICK_RADIUS)
+    .min_by(|a, b| a.1.total_cmp(&b.1))
+    .map(|(point, _)| point)
+}
+
+/// Fast travel picked from the map screen, which takes a second press of interact to go.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FastTravel {
+  // The save point the map cursor is over.
+  pub target:     Option<Vec2>,
+  // Set by the first press, while the cursor stays on the same save point.
+  pub confirming: bool,
+}
+
+impl FastTravel {
+  /// Points the cursor at `target`, which cancels confirming if it's moved off what was
+  /// being confirmed.
+  pub fn aim(&mut self, target: Option<Vec2>) {
+    if target != self.target {
+      self.confirming = false;
+    }
+    self.target = target;
+  }
+
+  /// Handles a press of interact, returning where to travel once it's been confirmed.
+  pub fn press(&mut self) -> Option<Vec2> {
+    let target = self.target?;
+    match self.confirming {
+      true => {
+        *self = Self::default();
+        Some(target)
+      }
+      false => {
+        self.confirming = true;
+        None
+      }
+    }
+  }
+}
@@ -0,0 +1,68 @@
+// Frames longer than this, like when the page was throttled in the background, only count
+// for this long, so timers don't jump ahead all at once.
+pub const MAX_FRAME_DT: f32 = 0.1;
+
+/// What a timer keeps time by, which decides what stops it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+  // The world: the player, hazards, enemies, and everything counting down on them.
+  Gameplay,
+  // Menus and overlays, like panning the map screen, which keep going while gameplay waits.
+  Ui,
+  // Never stops, for what has to keep going to end a pause, like the hit-stop on death.
+  Always,
+}
+
+/// Something that stops some channels while it lasts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pause {
+  MapScreen,
+  Dialogue,
+  // The freeze frame when the player dies.
+  HitStop,
+}
+
+impl Pause {
+  pub fn stops(self, channel: Channel) -> bool {
+    match (self, channel) {
+      (_, Channel::Always) => false,
+      (Pause::MapScreen | Pause::Dialogue, Channel::Ui) => false,
+      (Pause::HitStop, Channel::Ui) => true,
+      (_, Channel::Gameplay) => true,
+    }
+  }
+}
+
+/// How much time each channel gets this frame, and has had in total, so every timed system
+/// agrees on when time is passing.
+#[derive(Debug, Clone, Default)]
+pub struct GameClock {
+  // By channel, in the order they're declared.
+  dt:   [f32; 3],
+  time: [f32; 3],
+}
+
+impl GameClock {
+  /// Starts a frame `dt` seconds long, during which the channels `pauses` stop get none of it.
+  pub fn tick(&mut self, dt: f32, pauses: &[Pause]) {
+    let dt = dt.min(MAX_FRAME_DT);
+    for channel in [Channel::Gameplay, Channel::Ui, Channel::Always] {
+      let running = !pauses.iter().any(|pause| pause.stops(channel));
+      self.dt[channel as usize] = match running {
+        true => dt,
+        false => 0.0,
+      };
+      self.time[channel as usize] += self.dt[channel as usize];
+    }
+  }
+
+  /// How long the current frame is, for timers on `channel`.
+  pub fn dt(&self, channel: Channel) -> f32 {
+    self.dt[channel as usize]
+  }
+
+  /// The seconds `channel` has run for since the game started.
+  pub fn time(&self, channel: Channel) -> f32 {
+    self.time[channel as usize]
+  }
+}
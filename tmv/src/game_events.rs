@@ -0,0 +1,76 @@
+use serde::Serialize;
+
+use crate::{math::Vec2, power_ups::PowerUp, EntityId};
+
+// Like sounds, events are dropped rather than piling up forever if nothing drains them.
+const MAX_QUEUED_EVENTS: usize = 256;
+
+/// Something that happened in the game, for the page to react to with UI or analytics.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GameEvent {
+  CoinCollected {
+    entity_id: EntityId,
+    rare:      bool,
+  },
+  HpUpCollected {
+    entity_id: EntityId,
+  },
+  PowerUpObtained {
+    power_up: PowerUp,
+  },
+  // `hp` is what the player has left afterwards.
+  Damaged {
+    amount: i32,
+    hp:     i32,
+  },
+  Died,
+  Saved {
+    save_map:   String,
+    save_point: Vec2,
+  },
+  InteractionTriggered {
+    number: i32,
+  },
+  // E was let go, or the player walked off, before a hold-to-interact went off.
+  InteractionCancelled {
+    number: i32,
+  },
+  // `code` is what to share, to compare runs of the same day's challenge.
+  DailyChallengeFinished {
+    date: String,
+    time: f32,
+    code: String,
+  },
+  // `code` is a hash of the rest, for leaderboards to check submissions against.
+  DescentEnded {
+    seed:  u32,
+    depth: u32,
+    score: u32,
+    code:  String,
+  },
+  // A milestone for speedrun timers. `id` stays the same across versions (see splits.rs),
+  // and `game_time` is the save's play time, in seconds.
+  Split {
+    id:        String,
+    game_time: f32,
+  },
+}
+
+/// The game events since the page last drained them, oldest first.
+#[derive(Debug, Default)]
+pub struct GameEventQueue {
+  events: Vec<GameEvent>,
+}
+
+impl GameEventQueue {
+  pub fn emit(&mut self, event: GameEvent) {
+    if self.events.len() < MAX_QUEUED_EVENTS {
+      self.events.push(event);
+    }
+  }
+
+  pub fn drain(&mut self) -> Vec<GameEvent> {
+    std::mem::take(&mut self.events)
+  }
+}
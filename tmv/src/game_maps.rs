@@ -3,8 +3,101 @@ use std::collections::HashMap;
 use anyhow::Error;
 use tiled::{Chunk, Loader};
 
+use crate::math::Vec2;
+
+/// The map a new game starts on, and the one the map screen shows.
+pub const START_MAP: &str = "/assets/map1.tmx";
+/// How far apart the interaction numbers of each copy of a fragment are put.
+pub const FRAGMENT_ID_STRIDE: i32 = 1000;
+
+const CHUNK_WIDTH: i32 = Chunk::WIDTH as i32;
+const CHUNK_HEIGHT: i32 = Chunk::HEIGHT as i32;
+
+/// Which side of the main tiles a parallax layer is drawn on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParallaxDepth {
+  Background,
+  Foreground,
+}
+
+/// An extra tile or image layer that scrolls at its own rate, set by the layer's parallax
+/// factor in Tiled. Layers opt in with a depth property of "background" or "foreground".
+#[derive(Debug, Clone, Copy)]
+pub struct ParallaxLayer {
+  pub layer_index: usize,
+  pub depth:       ParallaxDepth,
+}
+
+/// Where one copy of a map goes in the world. The main map goes at the default placement,
+/// and fragments, like descent rooms, go wherever they're put, each with an id offset of
+/// their own so that their interaction numbers don't collide with any other copy's.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Placement {
+  // In tiles.
+  pub offset:    (i32, i32),
+  pub id_offset: i32,
+}
+
+impl Placement {
+  /// The placement for the `instance`th fragment put in the world, counting from 1.
+  pub fn fragment(offset: (i32, i32), instance: i32) -> Self {
+    Self {
+      offset,
+      id_offset: FRAGMENT_ID_STRIDE * instance,
+    }
+  }
+
+  pub fn shift(&self) -> Vec2 {
+    Vec2(self.offset.0 as f32, self.offset.1 as f32)
+  }
+
+  /// Fragments are drawn a whole chunk at a time, so they have to be placed on chunk edges.
+  pub fn is_chunk_aligned(&self) -> bool {
+    self.offset.0 % CHUNK_WIDTH == 0 && self.offset.1 % CHUNK_HEIGHT == 0
+  }
+
+  pub fn chunk_offset(&self) -> (i32, i32) {
+    (
+      self.offset.0.div_euclid(CHUNK_WIDTH),
+      self.offset.1.div_euclid(CHUNK_HEIGHT),
+    )
+  }
+}
+
+/// A loader with every tileset in the resources already parsed.
+fn tileset_loader(resources: &HashMap<String, Vec<u8>>) -> Result<Loader, Error> {
+  let mut loader = Loader::new();
+  for (name, data) in resources {
+    //crate::log(&format!("Inspecting resource: {}", name));
+    if name.ends_with(".tsx") {
+      //crate::log(&format!(">> Loading tileset: {}", name));
+      loader.populate_tsx_cache_from(&data[..], name)?;
+    }
+  }
+  Ok(loader)
+}
+
+/// Loads several small maps at once, like room templates, parsing the tilesets only once.
+pub fn load_fragments<'a>(
+  resources: &HashMap<String, Vec<u8>>,
+  map_names: impl IntoIterator<Item = &'a str>,
+) -> Result<Vec<GameMap>, Error> {
+  let mut loader = tileset_loader(resources)?;
+  map_names
+    .into_iter()
+    .map(|name| GameMap::from_loader(&mut loader, resources, name))
+    .collect()
+}
+
 pub struct GameMap {
+  // The resource path the map was loaded from, which doors refer to it by.
+  pub name:               String,
   pub map:                tiled::Map,
+  // Added to the entity ids of everything in the map, so that collectibles in different maps
+  // don't share ids. Each map sets its own with an entity_id_offset map property.
+  pub entity_id_offset:   i32,
+  // In the order Tiled draws them, bottom first.
+  pub parallax_layers:    Vec<ParallaxLayer>,
   main_layer_index:       usize,
   background_layer_index: usize,
 }
@@ -14,19 +107,14 @@ impl GameMap {
     resources: &HashMap<String, Vec<u8>>,
     map_name: &str,
   ) -> Result<Self, Error> {
-    let mut loader = Loader::new();
-
-    // Preload all tilesets.
-    for (name, data) in resources {
-      //crate::log(&format!("Inspecting resource: {}", name));
-      if name.ends_with(".tsx") {
-        //crate::log(&format!(">> Loading tileset: {}", name));
-        let ts = loader.populate_tsx_cache_from(&data[..], name)?;
-        //println!("Tileset: {}", name);
-      }
-    }
+    Self::from_loader(&mut tileset_loader(resources)?, resources, map_name)
+  }
 
-    // Load the map.
+  fn from_loader(
+    loader: &mut Loader,
+    resources: &HashMap<String, Vec<u8>>,
+    map_name: &str,
+  ) -> Result<Self, Error> {
     let map = loader.load_tmx_map_from(&resources[map_name][..], map_name)?;
 
     // Select the one layer whose name is "Main".
@@ -36,9 +124,36 @@ impl GameMap {
       .layers()
       .position(|layer| layer.name == "Background")
       .expect("No layer named 'Background'");
+    let parallax_layers = map
+      .layers()
+      .enumerate()
+      .filter_map(|(layer_index, layer)| {
+        let depth = match layer.properties.get("depth") {
+          Some(tiled::PropertyValue::StringValue(s)) if s == "background" => {
+            ParallaxDepth::Background
+          }
+          Some(tiled::PropertyValue::StringValue(s)) if s == "foreground" => {
+            ParallaxDepth::Foreground
+          }
+          Some(_) => panic!(
+            "Layer {}'s depth must be background or foreground",
+            layer.name
+          ),
+          None => return None,
+        };
+        Some(ParallaxLayer { layer_index, depth })
+      })
+      .collect();
+    let entity_id_offset = match map.properties.get("entity_id_offset") {
+      Some(tiled::PropertyValue::IntValue(offset)) => *offset,
+      _ => 0,
+    };
 
     Ok(Self {
+      name: map_name.to_string(),
       map,
+      entity_id_offset,
+      parallax_layers,
       main_layer_index,
       background_layer_index,
     })
@@ -51,4 +166,22 @@ impl GameMap {
   pub fn get_background_layer(&self) -> tiled::Layer {
     self.map.get_layer(self.background_layer_index).unwrap()
   }
+
+  pub fn get_parallax_layer(&self, parallax_layer: &ParallaxLayer) -> tiled::Layer {
+    self.map.get_layer(parallax_layer.layer_index).unwrap()
+  }
+
+  /// The top and bottom rows of chunks with anything in them in the main layer, if any.
+  pub fn chunk_rows(&self) -> Option<(i32, i32)> {
+    match self.get_main_layer().layer_type() {
+      tiled::LayerType::TileLayer(tiled::TileLayer::Infinite(data)) => {
+        let rows = data.chunks().map(|(chunk_pos, _)| chunk_pos.1);
+        rows.fold(None, |bounds, row| match bounds {
+          None => Some((row, row)),
+          Some((top, bottom)) => Some((top.min(row), bottom.max(row))),
+        })
+      }
+      _ => None,
+    }
+  }
 }
@@ -0,0 +1,116 @@
+use rapier2d::{
+  na::Vector2,
+  prelude::{Group, InteractionGroups, Point, QueryFilter, Ray},
+};
+use web_sys::CanvasRenderingContext2d;
+
+use crate::{
+  camera::Camera,
+  collision::{CollisionWorld, WALLS_GROUP},
+  math::Vec2,
+  projectiles::ProjectileKind,
+};
+
+/// Hazards within this many tiles of the player get previews, fading out toward the edge.
+pub const PREVIEW_RANGE: f32 = 12.0;
+// Projectiles without a fuse are followed for this many seconds.
+const MAX_FLIGHT_TIME: f32 = 1.5;
+const TIME_STEP: f32 = 0.05;
+
+/// How strongly to draw the preview for a hazard at `pos`, from 0 to 1.
+pub fn fade(pos: Vec2, player_pos: Vec2) -> f32 {
+  (1.0 - (pos - player_pos).length() / PREVIEW_RANGE).clamp(0.0, 1.0)
+}
+
+/// The points along the flight of a projectile of `kind` fired from a turret at `origin`
+/// facing `orientation`, following the same launch velocity and gravity the real shots
+/// get, and stopping at the first wall or when its fuse runs out.
+pub fn projectile_path(
+  collision: &CollisionWorld,
+  kind: ProjectileKind,
+  origin: Vec2,
+  orientation: Vec2,
+) -> Vec<Vec2> {
+  let descriptor = kind.descriptor();
+  let filter = QueryFilter::default()
+    .exclude_sensors()
+    .groups(InteractionGroups::new(Group::ALL, WALLS_GROUP));
+  let flight_time = descriptor.fuse.unwrap_or(MAX_FLIGHT_TIME);
+  let mut velocity = kind.launch_velocity(orientation);
+  let mut pos = origin;
+  let mut path = vec![origin];
+  let mut time = 0.0;
+  while time < flight_time {
+    let step = velocity * TIME_STEP;
+    let ray = Ray::new(Point::new(pos.0, pos.1), Vector2::new(step.0, step.1));
+    // With the ray's direction a whole step long, 1.0 is the end of the step.
+    if let Some((_, toi)) = collision.query_pipeline.cast_ray(
+      &collision.rigid_body_set,
+      &collision.collider_set,
+      &ray,
+      1.0,
+      true,
+      filter,
+    ) {
+      path.push(pos + toi * step);
+      break;
+    }
+    pos += step;
+    velocity.1 += descriptor.gravity * TIME_STEP;
+    path.push(pos);
+    time += TIME_STEP;
+  }
+  path
+}
+
+/// Dots along the path, closer together where the projectile moves slower.
+pub fn draw_path(ctx: &CanvasRenderingContext2d, camera: &Camera, path: &[Vec2]) {
+  let radius = camera.length_to_screen(0.08) as f64;
+  ctx.begin_path();
+  for point in path.iter().step_by(2) {
+    let pos = camera.world_to_screen(*point);
+    ctx.move_to(pos.0 as f64 + radius, pos.1 as f64);
+    ctx
+      .arc(
+        pos.0 as f64,
+        pos.1 as f64,
+        radius,
+        0.0,
+        std::f64::consts::TAU,
+      )
+      .unwrap();
+  }
+  ctx.fill();
+}
+
+/// Shades the stretch an idle thwump at `pos` would fall through, `clearance` tiles long.
+pub fn draw_fall_zone(
+  ctx: &CanvasRenderingContext2d,
+  camera: &Camera,
+  pos: Vec2,
+  direction: Vec2,
+  half_size: Vec2,
+  clearance: f32,
+) {
+  let across = Vec2(-direction.1, direction.0);
+  let near = pos + half_size.1 * direction;
+  let far = near + clearance * direction;
+  ctx.begin_path();
+  for (i, corner) in [
+    near - half_size.0 * across,
+    far - half_size.0 * across,
+    far + half_size.0 * across,
+    near + half_size.0 * across,
+  ]
+  .into_iter()
+  .enumerate()
+  {
+    let corner = camera.world_to_screen(corner);
+    match i {
+      0 => ctx.move_to(corner.0 as f64, corner.1 as f64),
+      _ => ctx.line_to(corner.0 as f64, corner.1 as f64),
+    }
+  }
+  ctx.close_path();
+  ctx.fill();
+}
@@ -0,0 +1,97 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use crate::math::Vec2;
+
+// The side of a heatmap cell, in tiles.
+pub const CELL_SIZE: i32 = 4;
+// How often where the player is gets counted, in seconds of gameplay.
+pub const SAMPLE_INTERVAL: f32 = 0.5;
+
+/// Something worth counting where it happens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeatKind {
+  // The player was here when sampled, so cells add up to how long they lingered.
+  Presence,
+  Death,
+  Damage,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HeatCell {
+  // The top left corner of the cell, in tiles.
+  pub x:        i32,
+  pub y:        i32,
+  pub presence: u32,
+  pub deaths:   u32,
+  pub damage:   u32,
+}
+
+/// Counts of where the player goes, dies, and gets hurt over this session, by map, for
+/// seeing where a map is too hard or too confusing. Nothing here is saved.
+#[derive(Debug, Default)]
+pub struct Heatmap {
+  cells:        HashMap<(String, i32, i32), HeatCell>,
+  // Where the player is, as of the last update.
+  current:      Option<(String, i32, i32)>,
+  sample_timer: f32,
+}
+
+#[derive(Serialize)]
+struct HeatmapExport<'a> {
+  cell_size:       i32,
+  sample_interval: f32,
+  // Each map's cells that have anything in them, in row order.
+  maps:            BTreeMap<&'a str, Vec<&'a HeatCell>>,
+}
+
+impl Heatmap {
+  /// Moves the player to `pos` on `map`, counting their presence every sample interval.
+  pub fn update(&mut self, dt: f32, map: &str, pos: Vec2) {
+    let cell = (
+      map.to_string(),
+      (pos.0 / CELL_SIZE as f32).floor() as i32,
+      (pos.1 / CELL_SIZE as f32).floor() as i32,
+    );
+    self.current = Some(cell);
+    self.sample_timer += dt;
+    if self.sample_timer >= SAMPLE_INTERVAL {
+      self.sample_timer -= SAMPLE_INTERVAL;
+      self.record(HeatKind::Presence);
+    }
+  }
+
+  /// Counts `kind` in the player's cell.
+  pub fn record(&mut self, kind: HeatKind) {
+    let Some(key) = &self.current else {
+      return;
+    };
+    let cell = self.cells.entry(key.clone()).or_insert_with(|| HeatCell {
+      x: key.1 * CELL_SIZE,
+      y: key.2 * CELL_SIZE,
+      ..HeatCell::default()
+    });
+    match kind {
+      HeatKind::Presence => cell.presence += 1,
+      HeatKind::Death => cell.deaths += 1,
+      HeatKind::Damage => cell.damage += 1,
+    }
+  }
+
+  pub fn to_json(&self) -> String {
+    let mut maps: BTreeMap<&str, Vec<&HeatCell>> = BTreeMap::new();
+    for ((map, _, _), cell) in &self.cells {
+      maps.entry(map).or_default().push(cell);
+    }
+    for cells in maps.values_mut() {
+      cells.sort_by_key(|cell| (cell.y, cell.x));
+    }
+    serde_json::to_string(&HeatmapExport {
+      cell_size: CELL_SIZE,
+      sample_interval: SAMPLE_INTERVAL,
+      maps,
+    })
+    .unwrap()
+  }
+}
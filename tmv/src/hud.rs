@@ -0,0 +1,315 @@
+use wasm_bindgen::JsValue;
+
+use crate::{math::Vec2, power_ups::PowerUp, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+const AIR_BAR_SIZE: Vec2 = Vec2(300.0, 14.0);
+// The size of the hearts and ability icons in the status HUD, and how far apart its rows are.
+const STATUS_ICON_SIZE: f32 = 28.0;
+const STATUS_ROW_HEIGHT: f32 = 38.0;
+/// How far down the status HUD reaches, for putting things under it.
+pub const STATUS_HEIGHT: f32 = 3.0 * STATUS_ROW_HEIGHT + 16.0;
+
+/// Which part of the screen a HUD element hangs off of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HudAnchor {
+  TopLeft,
+  TopCenter,
+  TopRight,
+  BottomLeft,
+  BottomRight,
+}
+
+/// How much of each edge of the screen, in game screen pixels, is covered by notches,
+/// rounded corners, and the like, so the HUD should stay clear of it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SafeArea {
+  pub top:    f32,
+  pub right:  f32,
+  pub bottom: f32,
+  pub left:   f32,
+}
+
+impl SafeArea {
+  pub fn new(top: f32, right: f32, bottom: f32, left: f32) -> Self {
+    // Keep at least half the screen usable, however odd the reported insets are.
+    let clean = |inset: f32, limit: f32| match inset.is_finite() {
+      true => inset.clamp(0.0, limit / 4.0),
+      false => 0.0,
+    };
+    Self {
+      top:    clean(top, SCREEN_HEIGHT),
+      right:  clean(right, SCREEN_WIDTH),
+      bottom: clean(bottom, SCREEN_HEIGHT),
+      left:   clean(left, SCREEN_WIDTH),
+    }
+  }
+
+  /// The insets with the same extra margin added on every side.
+  pub fn with_margin(self, margin: f32) -> Self {
+    Self {
+      top:    self.top + margin,
+      right:  self.right + margin,
+      bottom: self.bottom + margin,
+      left:   self.left + margin,
+    }
+  }
+
+  /// The screen position HUD elements at `anchor` should be drawn relative to.
+  pub fn anchor(&self, anchor: HudAnchor) -> Vec2 {
+    match anchor {
+      HudAnchor::TopLeft => Vec2(self.left, self.top),
+      HudAnchor::TopCenter => Vec2((SCREEN_WIDTH + self.left - self.right) / 2.0, self.top),
+      HudAnchor::TopRight => Vec2(SCREEN_WIDTH - self.right, self.top),
+      HudAnchor::BottomLeft => Vec2(self.left, SCREEN_HEIGHT - self.bottom),
+      HudAnchor::BottomRight => Vec2(SCREEN_WIDTH - self.right, SCREEN_HEIGHT - self.bottom),
+    }
+  }
+}
+
+/// How close the player is to running out of air, which sets the look of the air bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AirWarning {
+  Normal,
+  Low,
+  Critical,
+}
+
+impl AirWarning {
+  pub fn from_fraction(fraction: f32) -> Self {
+    match fraction {
+      f if f <= 0.25 => AirWarning::Critical,
+      f if f <= 0.5 => AirWarning::Low,
+      _ => AirWarning::Normal,
+    }
+  }
+
+  pub fn color(self) -> &'static str {
+    match self {
+      AirWarning::Normal => "#6af",
+      AirWarning::Low => "#fd4",
+      AirWarning::Critical => "#f44",
+    }
+  }
+}
+
+/// Draws the air bar hanging down from `top_center`. `fraction` is how much air is left, and
+/// `pulse` (from 0 to 1) brightens the bar's outline, for flashing it when air is critical.
+pub fn draw_air_bar(
+  ctx: &web_sys::CanvasRenderingContext2d,
+  top_center: Vec2,
+  fraction: f32,
+  pulse: f32,
+) {
+  let warning = AirWarning::from_fraction(fraction);
+  let (x, y) = (
+    (top_center.0 - AIR_BAR_SIZE.0 / 2.0) as f64,
+    top_center.1 as f64,
+  );
+  ctx.set_fill_style(&JsValue::from_str("rgba(0, 0, 0, 0.5)"));
+  ctx.fill_rect(x, y, AIR_BAR_SIZE.0 as f64, AIR_BAR_SIZE.1 as f64);
+  ctx.set_fill_style(&JsValue::from_str(warning.color()));
+  ctx.fill_rect(
+    x,
+    y,
+    (AIR_BAR_SIZE.0 * fraction.clamp(0.0, 1.0)) as f64,
+    AIR_BAR_SIZE.1 as f64,
+  );
+  ctx.set_stroke_style(&JsValue::from_str(match warning {
+    AirWarning::Critical if pulse > 0.5 => "#fff",
+    _ => "#000",
+  }));
+  ctx.set_line_width(2.0);
+  ctx.stroke_rect(x, y, AIR_BAR_SIZE.0 as f64, AIR_BAR_SIZE.1 as f64);
+}
+
+/// Draws a ring around `center` that fills clockwise from the top, with `fraction` (from 0
+/// to 1) of it filled, for showing how long something has been held.
+pub fn draw_progress_ring(
+  ctx: &web_sys::CanvasRenderingContext2d,
+  center: Vec2,
+  radius: f64,
+  fraction: f64,
+) {
+  let (x, y) = (center.0 as f64, center.1 as f64);
+  let top = -std::f64::consts::FRAC_PI_2;
+  ctx.set_line_width(5.0);
+  ctx.set_stroke_style(&JsValue::from_str("rgba(0, 0, 0, 0.5)"));
+  ctx.begin_path();
+  ctx.arc(x, y, radius, 0.0, std::f64::consts::TAU).unwrap();
+  ctx.stroke();
+  ctx.set_stroke_style(&JsValue::from_str("white"));
+  ctx.begin_path();
+  ctx
+    .arc(
+      x,
+      y,
+      radius,
+      top,
+      top + std::f64::consts::TAU * fraction.clamp(0.0, 1.0),
+    )
+    .unwrap();
+  ctx.stroke();
+}
+
+/// Darkens the edges of the screen, with `strength` from 0 (no effect) to 1 (nearly drowned).
+pub fn draw_vignette(ctx: &web_sys::CanvasRenderingContext2d, strength: f32) {
+  if strength <= 0.0 {
+    return;
+  }
+  // Overlapping ever wider outlines build up to a gradient toward the edges.
+  ctx.set_stroke_style(&JsValue::from_str("#000"));
+  ctx.set_global_alpha((0.15 * strength.min(1.0)) as f64);
+  for i in 1..=6 {
+    ctx.set_line_width(60.0 * i as f64);
+    ctx.stroke_rect(0.0, 0.0, SCREEN_WIDTH as f64, SCREEN_HEIGHT as f64);
+  }
+  ctx.set_global_alpha(1.0);
+}
+
+/// Draws a warning banner hanging down from `top_center`, for problems the player should
+/// know about, like game files being out of date.
+pub fn draw_notice(ctx: &web_sys::CanvasRenderingContext2d, top_center: Vec2, text: &str) {
+  ctx.set_font("20px Arial");
+  ctx.set_text_align("center");
+  ctx.set_text_baseline("middle");
+  let width = ctx.measure_text(text).unwrap().width() + 24.0;
+  ctx.set_fill_style(&JsValue::from_str("rgba(120, 0, 0, 0.8)"));
+  ctx.fill_rect(
+    top_center.0 as f64 - width / 2.0,
+    top_center.1 as f64,
+    width,
+    32.0,
+  );
+  ctx.set_fill_style(&JsValue::from_str("#fff"));
+  ctx.fill_text(text, top_center.0 as f64, top_center.1 as f64 + 16.0).unwrap();
+}
+
+/// What the status HUD in the top right shows.
+#[derive(Debug, Clone)]
+pub struct PlayerStatus {
+  pub hp:          i32,
+  pub max_hp:      i32,
+  pub coins:       usize,
+  pub rare_coins:  usize,
+  // The colors of the keys the player is holding, with how many of each.
+  pub keys:        Vec<(String, usize)>,
+  // In the order they should be shown.
+  pub power_ups:   Vec<PowerUp>,
+  pub dashes_left: u32,
+  pub max_dashes:  u32,
+}
+
+// Traces a heart centered on (x, y), `size` pixels across.
+fn heart_path(ctx: &web_sys::CanvasRenderingContext2d, x: f64, y: f64, size: f64) {
+  ctx.begin_path();
+  ctx.move_to(x, y + 0.4 * size);
+  ctx.bezier_curve_to(
+    x - 0.6 * size,
+    y,
+    x - 0.45 * size,
+    y - 0.5 * size,
+    x,
+    y - 0.2 * size,
+  );
+  ctx.bezier_curve_to(
+    x + 0.45 * size,
+    y - 0.5 * size,
+    x + 0.6 * size,
+    y,
+    x,
+    y + 0.4 * size,
+  );
+  ctx.close_path();
+}
+
+/// Draws hearts for hp, the coin count, the abilities picked up, and how many dashes are
+/// ready, right-aligned under `top_right`, in rows STATUS_ROW_HEIGHT apart.
+pub fn draw_status(
+  ctx: &web_sys::CanvasRenderingContext2d,
+  top_right: Vec2,
+  status: &PlayerStatus,
+) {
+  let (right, top) = (top_right.0 as f64, top_right.1 as f64);
+  let row = STATUS_ROW_HEIGHT as f64;
+  let size = STATUS_ICON_SIZE as f64;
+  let step = size + 6.0;
+  ctx.set_line_width(2.0);
+  ctx.set_stroke_style(&JsValue::from_str("#000"));
+
+  // Hearts, full ones first, with an empty outline for each missing point of hp.
+  for i in 0..status.max_hp.max(0) {
+    let x = right - step * (status.max_hp - i) as f64 + size / 2.0;
+    heart_path(ctx, x, top + size / 2.0, size);
+    ctx.set_fill_style(&JsValue::from_str(match i < status.hp {
+      true => "#e33",
+      false => "rgba(0, 0, 0, 0.5)",
+    }));
+    ctx.fill();
+    ctx.stroke();
+  }
+
+  // Coins, with rare coins and then keys beside them once there are any.
+  ctx.set_font("24px monospace");
+  ctx.set_text_align("right");
+  ctx.set_text_baseline("middle");
+  let y = top + row + size / 2.0;
+  let mut x = right;
+  let mut counts = vec![(status.coins, "#fd4", false)];
+  if status.rare_coins > 0 {
+    counts.push((status.rare_coins, "#c6f", false));
+  }
+  for (color, count) in &status.keys {
+    counts.push((*count, color, true));
+  }
+  for (count, color, is_key) in counts {
+    let text = format!("{}", count);
+    ctx.set_fill_style(&JsValue::from_str("#fff"));
+    ctx.fill_text(&text, x, y).unwrap();
+    x -= ctx.measure_text(&text).unwrap().width() + 6.0 + size / 2.0;
+    ctx.set_fill_style(&JsValue::from_str(color));
+    ctx.begin_path();
+    match is_key {
+      // Keys are diamonds, so they don't look like coins of another color.
+      true => {
+        ctx.move_to(x, y - size / 3.0);
+        ctx.line_to(x + size / 3.0, y);
+        ctx.line_to(x, y + size / 3.0);
+        ctx.line_to(x - size / 3.0, y);
+        ctx.close_path();
+      }
+      false => ctx.arc(x, y, size / 3.0, 0.0, std::f64::consts::TAU).unwrap(),
+    }
+    ctx.fill();
+    ctx.stroke();
+    x -= size;
+  }
+
+  // Abilities, each with the letters from its pickup.
+  ctx.set_font("14px Arial");
+  ctx.set_text_align("center");
+  let y = top + 2.0 * row;
+  let count = status.power_ups.len();
+  for (i, power_up) in status.power_ups.iter().enumerate() {
+    let x = right - step * (count - i) as f64 + 6.0;
+    ctx.set_fill_style(&JsValue::from_str("rgba(0, 0, 34, 0.7)"));
+    ctx.fill_rect(x, y, size, size);
+    ctx.set_stroke_style(&JsValue::from_str("#44f"));
+    ctx.stroke_rect(x, y, size, size);
+    ctx.set_fill_style(&JsValue::from_str("#fff"));
+    ctx.fill_text(power_up.icon(), x + size / 2.0, y + size / 2.0).unwrap();
+  }
+
+  // A pip for each dash, lit while it's ready to use.
+  let y = top + 3.0 * row + 6.0;
+  ctx.set_stroke_style(&JsValue::from_str("#4df"));
+  for i in 0..status.max_dashes {
+    let x = right - 16.0 * (status.max_dashes - i) as f64 + 8.0;
+    ctx.begin_path();
+    ctx.arc(x, y, 5.0, 0.0, std::f64::consts::TAU).unwrap();
+    if i < status.dashes_left {
+      ctx.set_fill_style(&JsValue::from_str("#4df"));
+      ctx.fill();
+    }
+    ctx.stroke();
+  }
+}
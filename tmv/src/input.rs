@@ -0,0 +1,150 @@
+use std::collections::HashSet;
+
+use crate::settings::{DashMode, Settings};
+
+pub const JUMP_KEYS: [&str; 3] = ["ArrowUp", "w", "z"];
+pub const LEFT_KEYS: [&str; 2] = ["ArrowLeft", "a"];
+pub const RIGHT_KEYS: [&str; 2] = ["ArrowRight", "d"];
+pub const DASH_KEY: &str = "Shift";
+pub const SHOOT_KEY: &str = "c";
+// How often holding jump in water re-presses it when swim assist is on.
+const SWIM_ASSIST_REPEAT: f32 = 0.25;
+// Two presses of the same direction at most this many seconds apart make a double tap.
+const DOUBLE_TAP_WINDOW: f32 = 0.25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+  Jump,
+  Dash,
+  Shoot,
+}
+
+/// A press that arrived between steps, along with when it happened (a DOM timestamp in milliseconds).
+#[derive(Debug, Clone, Copy)]
+pub struct BufferedAction {
+  pub action:    Action,
+  pub timestamp: f64,
+}
+
+/// Sits between raw key events and the keys the movement code sees, so the
+/// accessibility input modes can be implemented purely as input rewriting.
+#[derive(Debug)]
+pub struct InputLayer {
+  pub raw_keys_held:    HashSet<String>,
+  auto_run_right:       bool,
+  swim_assist_cooldown: f32,
+  buffered_actions:     Vec<BufferedAction>,
+  // Seconds of input stepped so far, for timing double taps.
+  clock:                f32,
+  // The last left or right press (true for right), and when it was.
+  last_direction_tap:   Option<(bool, f32)>,
+  double_tapped:        bool,
+}
+
+/// The presses synthesized for this step, on top of the ones from real key events.
+#[derive(Debug, Default)]
+pub struct SynthesizedActions {
+  pub jump_hit: bool,
+  pub dash_hit: bool,
+}
+
+impl Default for InputLayer {
+  fn default() -> Self {
+    Self {
+      raw_keys_held:        HashSet::new(),
+      auto_run_right:       true,
+      swim_assist_cooldown: 0.0,
+      buffered_actions:     Vec::new(),
+      clock:                0.0,
+      last_direction_tap:   None,
+      double_tapped:        false,
+    }
+  }
+}
+
+impl InputLayer {
+  fn any_held(&self, keys: &[&str]) -> bool {
+    keys.iter().any(|key| self.raw_keys_held.contains(*key))
+  }
+
+  pub fn key_down(&mut self, key: &str) {
+    // With auto-run on, the arrows just steer.
+    if LEFT_KEYS.contains(&key) {
+      self.auto_run_right = false;
+    }
+    if RIGHT_KEYS.contains(&key) {
+      self.auto_run_right = true;
+    }
+    // Key repeat sends more downs while a key is held, which aren't taps.
+    let is_direction = LEFT_KEYS.contains(&key) || RIGHT_KEYS.contains(&key);
+    if is_direction && !self.raw_keys_held.contains(key) {
+      let right = RIGHT_KEYS.contains(&key);
+      match self.last_direction_tap {
+        Some((last_right, at)) if last_right == right && self.clock - at <= DOUBLE_TAP_WINDOW => {
+          self.double_tapped = true;
+          // A third tap starts over, rather than dashing again.
+          self.last_direction_tap = None;
+        }
+        _ => self.last_direction_tap = Some((right, self.clock)),
+      }
+    }
+    self.raw_keys_held.insert(key.to_string());
+  }
+
+  pub fn key_up(&mut self, key: &str) {
+    self.raw_keys_held.remove(key);
+  }
+
+  pub fn buffer_action(&mut self, action: Action, timestamp: f64) {
+    self.buffered_actions.push(BufferedAction { action, timestamp });
+  }
+
+  pub fn take_buffered_actions(&mut self) -> Vec<BufferedAction> {
+    std::mem::take(&mut self.buffered_actions)
+  }
+
+  /// Works out which keys the rest of the game should consider held this step.
+  pub fn effective_keys_held(&self, settings: &Settings, showing_map: bool) -> HashSet<String> {
+    let mut keys = self.raw_keys_held.clone();
+    // The map screen pans with the arrows, so leave them alone there.
+    if settings.auto_run && !showing_map {
+      for key in LEFT_KEYS.iter().chain(RIGHT_KEYS.iter()) {
+        keys.remove(*key);
+      }
+      keys.insert(
+        match self.auto_run_right {
+          true => RIGHT_KEYS[0],
+          false => LEFT_KEYS[0],
+        }
+        .to_string(),
+      );
+    }
+    keys
+  }
+
+  pub fn synthesize_actions(
+    &mut self,
+    settings: &Settings,
+    dt: f32,
+    in_water: bool,
+  ) -> SynthesizedActions {
+    let mut actions = SynthesizedActions::default();
+    if settings.dash_mode == DashMode::Hold && self.raw_keys_held.contains(DASH_KEY) {
+      actions.dash_hit = true;
+    }
+    if std::mem::take(&mut self.double_tapped) && settings.double_tap_dash {
+      actions.dash_hit = true;
+    }
+    self.clock += dt;
+    self.swim_assist_cooldown -= dt;
+    if settings.swim_assist
+      && in_water
+      && self.any_held(&JUMP_KEYS)
+      && self.swim_assist_cooldown <= 0.0
+    {
+      actions.jump_hit = true;
+      self.swim_assist_cooldown = SWIM_ASSIST_REPEAT;
+    }
+    actions
+  }
+}
@@ -0,0 +1,123 @@
+use rapier2d::prelude::ColliderHandle;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  collision::{CollisionWorld, PhysicsObjectHandle},
+  math::{Rect, Vec2},
+  GameObject, GameObjectData,
+};
+
+// Handles go to JS as plain numbers, with the generation above the index. Numbers are exact up
+// to 2^53, which no generation gets near.
+const GENERATION_SCALE: f64 = 4294967296.0;
+
+pub fn handle_to_js(handle: ColliderHandle) -> f64 {
+  let (index, generation) = handle.into_raw_parts();
+  generation as f64 * GENERATION_SCALE + index as f64
+}
+
+pub fn handle_from_js(handle: f64) -> ColliderHandle {
+  let generation = (handle / GENERATION_SCALE).floor();
+  let index = handle - generation * GENERATION_SCALE;
+  ColliderHandle::from_raw_parts(index as u32, generation as u32)
+}
+
+/// The name of the object's variant, like "Coin" or "Shooter1".
+pub fn kind_name(data: &GameObjectData) -> String {
+  match serde_json::to_value(data) {
+    Ok(serde_json::Value::String(name)) => name,
+    Ok(serde_json::Value::Object(fields)) => fields.keys().next().cloned().unwrap_or_default(),
+    _ => String::new(),
+  }
+}
+
+/// One line of a listing of the objects in an area.
+#[derive(Debug, Serialize)]
+pub struct ObjectSummary {
+  pub handle:   f64,
+  pub kind:     String,
+  pub position: Option<Vec2>,
+}
+
+impl ObjectSummary {
+  pub fn new(handle: ColliderHandle, object: &GameObject, collision: &CollisionWorld) -> Self {
+    Self {
+      handle:   handle_to_js(handle),
+      kind:     kind_name(&object.data),
+      position: collision.get_position(&object.physics_handle),
+    }
+  }
+}
+
+/// Everything about one object, for a devtools panel. Timers and other per-kind state are
+/// in `data`, as it would be saved.
+#[derive(Debug, Serialize)]
+pub struct ObjectDetails {
+  pub handle:      f64,
+  pub kind:        String,
+  pub position:    Option<Vec2>,
+  pub velocity:    Option<Vec2>,
+  pub bounds:      Option<Rect>,
+  // "Dynamic", "Fixed", and so on, or None for objects with no rigid body.
+  pub body_type:   Option<String>,
+  pub is_sensor:   bool,
+  // The bits of the collider's interaction groups.
+  pub memberships: u32,
+  pub filter:      u32,
+  pub data:        GameObjectData,
+}
+
+impl ObjectDetails {
+  pub fn new(handle: ColliderHandle, object: &GameObject, collision: &CollisionWorld) -> Self {
+    let physics = &object.physics_handle;
+    let collider = collision.collider_set.get(physics.collider);
+    let groups = collider.map(|collider| collider.collision_groups());
+    Self {
+      handle:      handle_to_js(handle),
+      kind:        kind_name(&object.data),
+      position:    collision.get_position(physics),
+      velocity:    collision.get_velocity(physics),
+      bounds:      collision.collider_bounds(physics.collider),
+      body_type:   physics
+        .rigid_body
+        .and_then(|rigid_body| collision.rigid_body_set.get(rigid_body))
+        .map(|rigid_body| format!("{:?}", rigid_body.body_type())),
+      is_sensor:   collider.is_some_and(|collider| collider.is_sensor()),
+      memberships: groups.map_or(0, |groups| groups.memberships.bits()),
+      filter:      groups.map_or(0, |groups| groups.filter.bits()),
+      data:        object.data.clone(),
+    }
+  }
+}
+
+/// Changes to make to an object from a devtools panel. Anything left out stays as it is.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ObjectEdit {
+  pub position: Option<Vec2>,
+  pub velocity: Option<Vec2>,
+  pub data:     Option<GameObjectData>,
+}
+
+impl ObjectEdit {
+  pub fn apply(
+    self,
+    physics: &PhysicsObjectHandle,
+    data: &mut GameObjectData,
+    collision: &mut CollisionWorld,
+  ) -> Result<(), String> {
+    if (self.position.is_some() || self.velocity.is_some()) && physics.rigid_body.is_none() {
+      return Err("Object has no rigid body to move".to_string());
+    }
+    if let Some(position) = self.position {
+      collision.set_position(physics, position, false);
+    }
+    if let Some(velocity) = self.velocity {
+      collision.set_velocity(physics, velocity);
+    }
+    if let Some(new_data) = self.data {
+      *data = new_data;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,22 @@
+use crate::math::Vec2;
+
+/// The color of keys and locked doors with no color property. Colors are CSS colors, which
+/// doubles as how they're drawn.
+pub const DEFAULT_COLOR: &str = "gold";
+// How long an unlocked door takes to slide up out of the way, in seconds.
+pub const DOOR_OPEN_TIME: f32 = 0.6;
+// The player stops just short of a door they walk into, so doors open from this much further.
+pub const DOOR_REACH: f32 = 0.15;
+
+/// Where a door `size` tiles big, placed at the tile `tile_pos`, has its middle. Doors hang
+/// down from their tile, so a tall door goes on its top tile.
+pub fn door_center(tile_pos: (i32, i32), size: Vec2) -> Vec2 {
+  Vec2(tile_pos.0 as f32 + 0.5, tile_pos.1 as f32 + size.1 / 2.0)
+}
+
+/// How much of a door is still showing, `time_left` seconds before it's finished opening.
+pub fn door_remaining(time_left: f32) -> f32 {
+  let progress = 1.0 - (time_left / DOOR_OPEN_TIME).clamp(0.0, 1.0);
+  // Slow to start, like it's being hauled up.
+  1.0 - progress * progress
+}
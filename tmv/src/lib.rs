@@ -5,38 +5,148 @@ use std::{
   rc::Rc,
 };
 
+use assets::AssetManifest;
+use audio::{SoundEvent, SoundQueue};
+use bee_queen::BeeQueen;
+use breakable::BlockDrop;
+use camera::{Camera, CameraBounds, CameraFollow};
+use challenges::{ChallengeReward, ChallengeRun};
+use char_events::{CharEvent, CharEventLog};
+use chase::Chase;
 use collision::{
-  CollisionWorld, PhysicsKind, PhysicsObjectHandle, BASIC_GROUP, BASIC_INT_GROUPS, PLAYER_GROUP,
-  WALLS_GROUP,
+  CollisionWorld, Ledge, PhysicsKind, PhysicsObjectHandle, PickupPhysics, PlacedFragment,
+  BASIC_GROUP, BASIC_INT_GROUPS, PLAYER_GROUP, WALLS_GROUP, WALLS_INT_GROUPS,
 };
-use game_maps::GameMap;
+use count_gate::Currency;
+use critters::CritterSystem;
+use daily::DailyChallenge;
+use death::{DeathPhase, DeathSequence};
+#[cfg(feature = "debug-tools")]
+use debug_hud::LabelCategory;
+use debug_hud::{MovementReadout, MovementState};
+use descent::{Descent, PlacedRoom};
+use dialogue::{Dialogue, DialogueLibrary};
+use enemies::Enemy;
+use fast_travel::FastTravel;
+use game_clock::{Channel, GameClock, Pause};
+use game_events::{GameEvent, GameEventQueue};
+use game_maps::{GameMap, Placement, START_MAP};
+use heatmap::{HeatKind, Heatmap};
+use hud::{AirWarning, HudAnchor, PlayerStatus, SafeArea};
+use input::{Action, InputLayer};
+use inspector::{ObjectDetails, ObjectEdit, ObjectSummary};
 use js_sys::Array;
-use math::{Rect, Vec2};
+use lod::UpdateTier;
+use map_diff::MapDiff;
+#[cfg(feature = "map-screen")]
+use map_markers::MapMarker;
+use map_reveal::RevealedMap;
+use map_rooms::RoomMap;
+use math::{batch, geometry::sweep_rect, Rect, Vec2};
+use minimap::{Minimap, MinimapRenderer};
+use particles::{FloatyText, ParticleSystem};
+use power_ups::PowerUp;
+use progression::ProgressionGraph;
+use projectiles::{ProjectileKind, RoomExitPolicy};
+use quality::QualityController;
 use rapier2d::{
   na::Vector2,
   prelude::{
-    ColliderHandle, Cuboid, Group, InteractionGroups, Isometry, Point, QueryFilter, Ray, Shape,
+    Ball, ColliderHandle, Cuboid, Group, InteractionGroups, Isometry, Point, QueryFilter, Ray,
+    Shape,
   },
 };
+use recap::SaveRecap;
+use render::{LayerId, Layers};
+use replay::{Replay, ReplayCheckpoint, ReplayFrame, ReplayPlayback, ReplayRecorder};
+use rng::GameRng;
+use run_stats::{RunStats, SaveStatistics};
+use save_repair::SaveRepairContext;
 use serde::{Deserialize, Serialize};
+use settings::Settings;
+use side_run::SideRun;
+use splits::Split;
+use sprites::PlayerAnimation;
+use status_effects::{StatusEffect, StatusEffects};
 use strum::IntoEnumIterator;
-use tile_rendering::TileRenderer;
+use surfaces::Surface;
+use switches::{SwitchChannels, SwitchHit};
+use theme::Theme;
+use tile_rendering::{HdImages, TileRenderer};
 use tiled::ObjectLayerData;
+use time_trials::{TimeTrial, TrialResult};
+use triggers::{Laser, SpawnKind, TriggerAction};
+use tuning::{MovementTuning, Tunables};
 use wasm_bindgen::prelude::*;
+use water::SplashTracker;
 
 pub mod game_maps;
 pub mod math;
 pub mod tile_rendering;
 //pub mod physics;
+pub mod assets;
+pub mod audio;
+pub mod bee_queen;
+pub mod breakable;
 pub mod camera;
+pub mod challenges;
+pub mod char_events;
+pub mod chase;
 pub mod collision;
+pub mod count_gate;
+pub mod critters;
+pub mod daily;
+pub mod death;
+pub mod debug_hud;
+pub mod descent;
+pub mod dialogue;
+pub mod enemies;
+pub mod entity_defs;
+pub mod fast_travel;
+pub mod game_clock;
+pub mod game_events;
+pub mod hazard_preview;
+pub mod heatmap;
+pub mod hud;
+pub mod input;
+pub mod inspector;
+pub mod keys;
+pub mod lighting;
+pub mod lod;
+pub mod map_diff;
+pub mod map_markers;
+pub mod map_reveal;
+pub mod map_rooms;
+pub mod minimap;
+pub mod particles;
+pub mod power_ups;
+pub mod progression;
+pub mod projectiles;
+pub mod quality;
+pub mod recap;
+pub mod render;
+pub mod replay;
+pub mod rng;
+pub mod run_stats;
+pub mod save_repair;
+pub mod settings;
+pub mod side_run;
+pub mod splits;
+pub mod sprites;
+pub mod status_effects;
+pub mod surfaces;
+pub mod switches;
+pub mod theme;
+pub mod thwump;
+pub mod time_trials;
+pub mod triggers;
+pub mod tuning;
+pub mod vanish;
+pub mod water;
+pub mod wind;
 
 use tile_rendering::TILE_SIZE;
 
-const UI_LAYER: usize = 0;
-const MAIN_LAYER: usize = 1;
-const BACKGROUND_LAYER: usize = 2;
-const SCRATCH_LAYER: usize = 3;
 const PLAYER_SIZE: Vec2 = Vec2(1.25, 2.5);
 const SHRUNKEN_SIZE: Vec2 = Vec2(1.25, 0.9);
 const JUMP_GRACE_PERIOD: f32 = 0.1;
@@ -45,10 +155,86 @@ const UNDERWATER_TIME: f32 = 8.0;
 const HIGH_UNDERWATER_TIME: f32 = 24.0;
 const SCREEN_WIDTH: f32 = 1200.0;
 const SCREEN_HEIGHT: f32 = 800.0;
-const MAP_REVELATION_DISCRETIZATION: i32 = 8;
+// The default map reveal granularity, and the one all saves from before it was configurable use.
+pub const MAP_REVELATION_DISCRETIZATION: i32 = 8;
+const MAP_REVEAL_WALK_RADIUS: f32 = 8.0;
+// Objects are still drawn this far off screen, in tiles, so the biggest ones (like thwumps
+// and moving platforms) don't pop out while part of them is still in view.
+const DRAW_CULL_MARGIN: f32 = 4.0;
+// Clicks on an interactable only count if the player is within this many tiles of where they clicked.
+const CLICK_INTERACT_RANGE: f32 = 4.0;
+// Clicking within this many pixels of a map pin removes it instead of placing a new one.
+const MAP_PIN_CLICK_RADIUS: f32 = 12.0;
+// Clicking to drop more pins than this does nothing until some are picked back up.
+const MAX_MAP_PINS: usize = 24;
+// How close, in screen pixels, a debug mode click has to be to grab a camera bounds vertex.
+#[cfg(feature = "editor")]
+const CAMERA_VERTEX_GRAB_RADIUS: f32 = 8.0;
+// Sub-frame steps shorter than this aren't worth splitting a step for.
+const MIN_SUB_STEP: f32 = 0.002;
+// How far (in pixels) past the screen edge the compass target has to be for the arrow to be fully opaque.
+const COMPASS_FADE_DISTANCE: f32 = 150.0;
+// Space between the HUD and the edges of the safe area, on top of the player's own margin setting.
+const HUD_MARGIN: f32 = 10.0;
+// Taking damage shakes the screen by up to this many pixels, fading out over this long.
+const SCREEN_SHAKE_PIXELS: f32 = 6.0;
+const SCREEN_SHAKE_TIME: f32 = 0.3;
+// Trying to stand up under a ceiling nudges the screen by this many pixels, fading out over
+// this long. Holding up only bumps the player's head again once the cooldown is over.
+const HEAD_BUMP_PIXELS: f32 = 3.0;
+const HEAD_BUMP_TIME: f32 = 0.15;
+const HEAD_BUMP_COOLDOWN: f32 = 0.5;
+// Splashes stop getting bigger past this speed, in tiles per second.
+const MAX_SPLASH_SPEED: f32 = 16.0;
+// How long the streaks showing wind last, in seconds.
+const WIND_STREAK_LIFE: f32 = 0.6;
 const BEE_SIZE: f32 = 0.5;
-const BEE_ACCEL: f32 = 4.0;
+const BEE_ACCEL: f32 = 8.0;
 const BEE_TOP_SPEED: f32 = 5.0;
+// Bees home in on the player within this many tiles, and otherwise just drift around.
+const BEE_SIGHT_RANGE: f32 = 15.0;
+const BEE_JITTER: f32 = 6.0;
+// Bees closer together than this push apart, so swarms spread out instead of stacking.
+const BEE_SEPARATION_RADIUS: f32 = 1.0;
+const BEE_SEPARATION_STRENGTH: f32 = 3.0;
+// Bees give up the chase once the player leaves their room.
+const BEE_ROOM_EXIT: RoomExitPolicy = RoomExitPolicy::FadeOut;
+pub const SPRING_DEFAULT_STRENGTH: f32 = 35.0;
+const SPRING_SQUASH_TIME: f32 = 0.25;
+pub const AIR_BUBBLE_DEFAULT_RESPAWN: f32 = 6.0;
+// Touching a boost rail multiplies the player's top speed by this much, until this many
+// seconds after they leave it.
+pub const BOOST_DEFAULT_SPEED: f32 = 1.5;
+pub const BOOST_DEFAULT_DURATION: f32 = 1.5;
+// How many of the player's recent positions the boost trail follows.
+const BOOST_TRAIL_LENGTH: usize = 12;
+// How long after a spring launch we skip the short-hop damping, so the launch height doesn't depend on holding jump.
+const SPRING_LAUNCH_TIME: f32 = 0.4;
+// How long the player takes to materialize at a save point, with input locked.
+const SPAWN_ANIMATION_TIME: f32 = 0.6;
+// How long the player can't be hurt after respawning, counting the time spent materializing.
+const RESPAWN_IMMUNITY_TIME: f32 = 1.5;
+// How far from the save point to look for somewhere safe to respawn, when it isn't itself.
+const SAFE_RESPAWN_RADIUS: f32 = 6.0;
+// How long the ring rippling out of a save point lasts, after the player walks off it.
+const SAVE_EXIT_PULSE_TIME: f32 = 0.5;
+// Falling pickups hop up this fast when dropped, then fall with this much gravity.
+const DROP_HOP_SPEED: f32 = 8.0;
+const DROP_GRAVITY: f32 = 40.0;
+// With the shoot power-up, the player can fire this often, and each shot does this much.
+const SHOOT_COOLDOWN: f32 = 0.3;
+const PLAYER_SHOT_DAMAGE: i32 = 1;
+// How many of the player's shots it takes to destroy these, unless their tiles say otherwise.
+pub const SHOOTER_DEFAULT_HP: i32 = 3;
+pub const BEEHIVE_DEFAULT_HP: i32 = 3;
+// Interactions that need E held, rather than tapped, go off after this many seconds.
+const INTERACT_HOLD_TIME: f32 = 1.0;
+// How much time each frame stepped by hand covers, while the simulation is paused.
+const FRAME_STEP_DT: f32 = 1.0 / 60.0;
+// Falling against a wall grabs its top edge, if that's within this many tiles of the top of
+// the player's head. After letting go, the player can't grab on again for a moment.
+const LEDGE_REACH: f32 = 0.6;
+const LEDGE_REGRAB_DELAY: f32 = 0.3;
 //const PLAYER_SIZE: Vec2 = Vec2(3.0, 3.0);
 
 pub trait IntoJsError {
@@ -76,7 +262,7 @@ impl<T> IntoJsError for Option<T> {
 pub enum ImageResource {
   WorldProperties,
   MainTiles,
-  MapSmall,
+  PlayerSprites,
 }
 
 impl ImageResource {
@@ -84,7 +270,25 @@ impl ImageResource {
     match self {
       ImageResource::WorldProperties => "/assets/images/colors_tileset.png",
       ImageResource::MainTiles => "/assets/images/main_tiles.png",
-      ImageResource::MapSmall => "/assets/images/map_small.png",
+      ImageResource::PlayerSprites => "/assets/images/player.png",
+    }
+  }
+
+  /// Higher resolution versions of this image, as (scale, path) pairs, where the image's
+  /// tiles are `scale * TILE_SIZE` pixels across. Until the art for one exists, it fails to
+  /// load and the tile renderer sticks with the 1x image.
+  pub fn hd_variants(&self) -> &'static [(u32, &'static str)] {
+    match self {
+      ImageResource::WorldProperties => &[
+        (2, "/assets/images/colors_tileset@2x.png"),
+        (4, "/assets/images/colors_tileset@4x.png"),
+      ],
+      ImageResource::MainTiles => &[
+        (2, "/assets/images/main_tiles@2x.png"),
+        (4, "/assets/images/main_tiles@4x.png"),
+      ],
+      // Sprites aren't drawn through the tile renderer.
+      ImageResource::PlayerSprites => &[],
     }
   }
 
@@ -104,6 +308,9 @@ pub fn get_all_image_paths() -> Array {
   let mut array = Array::new();
   for image_resource in ImageResource::iter() {
     array.push(&JsValue::from_str(image_resource.get_path()));
+    for (_, path) in image_resource.hd_variants() {
+      array.push(&JsValue::from_str(path));
+    }
   }
   array
 }
@@ -113,6 +320,13 @@ pub enum BinaryResource {
   Map1,
   WorldProperties,
   MainTiles,
+  Theme,
+  Tuning,
+  Dialogue,
+  DescentEntrance,
+  DescentRoomLedges,
+  DescentRoomSpikes,
+  DescentRoomFunnel,
 }
 
 impl BinaryResource {
@@ -121,6 +335,32 @@ impl BinaryResource {
       BinaryResource::Map1 => "/assets/map1.tmx",
       BinaryResource::WorldProperties => "/assets/world_properties.tsx",
       BinaryResource::MainTiles => "/assets/main_tiles.tsx",
+      BinaryResource::Theme => "/assets/theme.json",
+      BinaryResource::Tuning => "/assets/tuning.json",
+      BinaryResource::Dialogue => "/assets/dialogue.json",
+      BinaryResource::DescentEntrance => descent::ENTRANCE_MAP,
+      BinaryResource::DescentRoomLedges => "/assets/descent_room_ledges.tmx",
+      BinaryResource::DescentRoomSpikes => "/assets/descent_room_spikes.tmx",
+      BinaryResource::DescentRoomFunnel => "/assets/descent_room_funnel.tmx",
+    }
+  }
+
+  /// Parses this resource with `parse`, falling back to the default if it's missing. A
+  /// malformed file is logged rather than stopping the game from starting.
+  pub fn parse_or_default<T: Default>(
+    &self,
+    resources: &HashMap<String, Vec<u8>>,
+    parse: impl Fn(&str) -> Result<T, String>,
+  ) -> T {
+    let Some(data) = resources.get(self.get_path()) else {
+      return T::default();
+    };
+    match std::str::from_utf8(data).map_err(|e| e.to_string()).and_then(parse) {
+      Ok(value) => value,
+      Err(e) => {
+        crate::log(&format!("Ignoring bad {}: {}", self.get_path(), e));
+        T::default()
+      }
     }
   }
 }
@@ -134,12 +374,19 @@ pub fn get_all_resource_names() -> Array {
   array
 }
 
+#[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 extern "C" {
   #[wasm_bindgen(js_namespace = console)]
   pub fn log(s: &str);
 }
 
+// Native builds (the replay tests) have no console to log to.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn log(s: &str) {
+  eprintln!("{}", s);
+}
+
 #[wasm_bindgen]
 pub fn get_wasm_version() -> String {
   #[cfg(debug_assertions)]
@@ -150,62 +397,162 @@ pub fn get_wasm_version() -> String {
 }
 
 struct DrawContext {
-  canvases:      [web_sys::HtmlCanvasElement; 4],
-  contexts:      [web_sys::CanvasRenderingContext2d; 4],
+  layers:        Layers,
   images:        HashMap<ImageResource, web_sys::HtmlImageElement>,
+  hd_images:     HdImages,
   tile_renderer: TileRenderer,
+  minimap:       MinimapRenderer,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum InputEvent {
-  KeyDown { key: String },
-  KeyUp { key: String },
+  KeyDown {
+    key:       String,
+    // When the key was pressed, as a DOM timestamp in milliseconds.
+    #[serde(default)]
+    timestamp: Option<f64>,
+  },
+  KeyUp {
+    key: String,
+  },
+}
+
+// Positions are in canvas pixels.
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+pub enum MouseEvent {
+  MouseDown {
+    x:      f32,
+    y:      f32,
+    button: i32,
+  },
+  MouseUp {
+    x:      f32,
+    y:      f32,
+    button: i32,
+  },
+  MouseMove {
+    x: f32,
+    y: f32,
+  },
+  MouseLeave,
+  Wheel {
+    x:       f32,
+    y:       f32,
+    delta_y: f32,
+  },
+}
+
+// A click-and-drag on the map screen, which pans the map if it moves far enough.
+struct MapDrag {
+  start_screen_pos: Vec2,
+  start_shift_pos:  Vec2,
+  moved:            bool,
 }
 
 pub type EntityId = i32;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct CharState {
-  pub save_point:     Vec2,
-  pub hp:             Cell<i32>,
-  pub power_ups:      HashSet<String>,
-  pub coins:          HashSet<EntityId>,
-  pub rare_coins:     HashSet<EntityId>,
-  pub hp_ups:         HashSet<EntityId>,
-  pub int1_completed: bool,
-  pub int2_completed: bool,
+  // The map the save point is on.
+  #[serde(default = "default_save_map")]
+  pub save_map:             String,
+  pub save_point:           Vec2,
+  pub hp:                   Cell<i32>,
+  pub power_ups:            HashSet<PowerUp>,
+  pub coins:                HashSet<EntityId>,
+  pub rare_coins:           HashSet<EntityId>,
+  pub hp_ups:               HashSet<EntityId>,
+  // Progress set by interactions' set_flag actions (see triggers.rs).
+  #[serde(default)]
+  pub flags:                HashSet<String>,
+  #[serde(default)]
+  pub completed_challenges: HashSet<String>,
+  // Secrets the player has found, by the names interactions' find_secret actions give them.
+  #[serde(default)]
+  pub secrets:              HashSet<String>,
+  // Breakable blocks the player has broken, which stay broken.
+  #[serde(default)]
+  pub broken_blocks:        HashSet<EntityId>,
+  // Beehives the player has destroyed, which stay destroyed.
+  #[serde(default)]
+  pub destroyed_hives:      HashSet<EntityId>,
+  // Every key the player has picked up, with its color, whether or not it's been used.
+  #[serde(default)]
+  pub keys:                 HashMap<EntityId, String>,
+  // The locked doors the player has opened, each of which used up a key of its color.
+  #[serde(default)]
+  pub opened_doors:         HashMap<EntityId, String>,
 }
 
 impl CharState {
   pub fn reset_hp(&mut self) {
     self.hp.set(self.hp_ups.len() as i32 + 1);
   }
+
+  /// How many keys of `color` the player has that haven't been used on a door yet.
+  pub fn held_keys(&self, color: &str) -> usize {
+    let picked_up = self.keys.values().filter(|c| *c == color).count();
+    let used = self.opened_doors.values().filter(|c| *c == color).count();
+    picked_up.saturating_sub(used)
+  }
+
+  /// The colors of keys the player is holding, in order, with how many of each.
+  pub fn held_key_counts(&self) -> Vec<(String, usize)> {
+    let mut colors: Vec<&String> = self.keys.values().collect();
+    colors.sort();
+    colors.dedup();
+    colors
+      .into_iter()
+      .map(|color| (color.clone(), self.held_keys(color)))
+      .filter(|(_, count)| *count > 0)
+      .collect()
+  }
 }
 
 impl Default for CharState {
   fn default() -> Self {
     Self {
-      save_point:     Vec2::default(),
-      hp:             Cell::new(1),
-      power_ups:      HashSet::new(),
-      coins:          HashSet::new(),
-      rare_coins:     HashSet::new(),
-      hp_ups:         HashSet::new(),
-      int1_completed: false,
-      int2_completed: false,
+      save_map:             default_save_map(),
+      save_point:           Vec2::default(),
+      hp:                   Cell::new(1),
+      power_ups:            HashSet::new(),
+      coins:                HashSet::new(),
+      rare_coins:           HashSet::new(),
+      hp_ups:               HashSet::new(),
+      flags:                HashSet::new(),
+      completed_challenges: HashSet::new(),
+      secrets:              HashSet::new(),
+      broken_blocks:        HashSet::new(),
+      destroyed_hives:      HashSet::new(),
+      keys:                 HashMap::new(),
+      opened_doors:         HashMap::new(),
     }
   }
 }
 
-#[derive(Debug)]
+/// Where the player is in holding E down for an interaction that needs it held.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InteractHold {
+  Released,
+  // E has been held for `held` seconds at the interaction.
+  Charging { interaction: i32, held: f32 },
+  // The interaction went off, and E hasn't been let go since, so it doesn't go off again.
+  Completed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ThwumpState {
   Idle,
   Falling,
+  // Sitting where it landed for a moment before rising.
+  Resting { time_left: f32 },
   Rising,
 }
 
-#[derive(Debug)]
+// Everything in here is plain data, so objects can be saved and restored exactly.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum GameObjectData {
   Coin {
     entity_id: EntityId,
@@ -217,20 +564,32 @@ pub enum GameObjectData {
     entity_id: EntityId,
   },
   PowerUp {
-    power_up: String,
+    power_up: PowerUp,
+  },
+  // Opens once the player has `count` of `currency`.
+  CountGate {
+    currency: Currency,
+    count:    i32,
   },
-  CoinWall {
-    count: i32,
+  Spike {
+    damage: i32,
   },
-  Spike,
   SavePoint,
   Shooter1 {
     orientation:  Vec2,
-    cooldown:     Cell<f32>,
+    cooldown:     f32,
     shoot_period: f32,
+    projectile:   ProjectileKind,
+    hp:           i32,
   },
+  // Any enemy projectile, behaving as its kind's descriptor says.
   Bullet {
+    kind:     ProjectileKind,
+    // What the velocity should be, barring a collision.
     velocity: Vec2,
+    age:      f32,
+    // Seconds left, once it's fading out after the player left its room.
+    fading:   Option<f32>,
   },
   Water,
   Lava,
@@ -242,34 +601,138 @@ pub enum GameObjectData {
   MovingPlatform {
     orientation: Vec2,
   },
+  // The orientation is the direction the thwump falls in.
   Thwump {
     orientation: Vec2,
+    // Half its width across the way it falls, then half its depth along it.
+    half_size:   Vec2,
     state:       ThwumpState,
+    // Where it waits, and rises back to after falling.
+    home:        Vec2,
   },
   TurnLaser {
     is_mirrored: bool,
     angle:       f32,
     hit_point:   Vec2,
   },
-  FloatyText {
-    text:      String,
+  // Breakable by the player's shots if it has hp.
+  Stone {
+    hp: Option<i32>,
+  },
+  // Broken by a dash, or by enough of the player's shots, and then crumbles away over the
+  // seconds left in `crumbling`.
+  BreakableBlock {
+    entity_id: EntityId,
+    hp:        i32,
+    max_hp:    i32,
+    drops:     Option<BlockDrop>,
+    crumbling: Option<f32>,
+  },
+  Key {
+    entity_id: EntityId,
     color:     String,
-    time_left: f32,
   },
-  Stone,
+  // Opens for a key of the same color, sliding up out of the way over the seconds left in
+  // `opening`.
+  LockedDoor {
+    entity_id: EntityId,
+    color:     String,
+    opening:   Option<f32>,
+  },
+  // Toggles its channel when hit. `pressed` is whether it's still being hit from last frame,
+  // so standing on a switch only toggles it once.
+  Switch {
+    channel: String,
+    hit_by:  SwitchHit,
+    pressed: bool,
+    flash:   f32,
+  },
+  // Solid while its channel is on, or while it's off, whichever `solid_when_on` says.
+  ToggleBlock {
+    channel:       String,
+    solid_when_on: bool,
+    is_solid:      bool,
+  },
   VanishBlock {
     vanish_timer: f32,
     is_solid:     bool,
+    // Blocks in a group ignore the player and follow the group's pattern instead.
+    group:        Option<String>,
   },
   DestroyedDoor,
   Interaction {
     interaction_number: i32,
+    // The power-up the player needs before this interaction does anything.
+    requires_powerup:   Option<PowerUp>,
+    // The dialogue script to show before the interaction goes off.
+    dialogue:           Option<String>,
   },
   Beehive {
-    cooldown: Cell<f32>,
+    cooldown:  f32,
+    entity_id: EntityId,
+    hp:        i32,
+  },
+  BeeQueen {
+    queen: BeeQueen,
+  },
+  // Hurts on contact, and dies when dashed into.
+  Enemy {
+    enemy: Enemy,
   },
   Bee {
     lifespan: f32,
+    // The beehive this bee came from, which takes its bees with it when destroyed.
+    hive:     EntityId,
+  },
+  Spring {
+    direction: Vec2,
+    strength:  f32,
+    squash:    f32,
+  },
+  // A tile that poisons or shocks the player while they stay in contact.
+  HazardSurface {
+    effect: StatusEffect,
+  },
+  // Raises the player's top speed, and keeps them from losing speed in the air, until
+  // `duration` seconds after they leave it.
+  BoostRail {
+    speed:    f32,
+    duration: f32,
+  },
+  // Refills air on touch, then comes back after `respawn_time` seconds.
+  AirBubble {
+    respawn_time: f32,
+    cooldown:     f32,
+  },
+  TrialStart {
+    trial:       String,
+    medal_times: [f32; 3],
+  },
+  TrialFinish {
+    trial: String,
+  },
+  // Touching a start sets the named chase's hazard going, and reaching its end escapes it.
+  ChaseStart {
+    chase: String,
+  },
+  ChaseEnd {
+    chase: String,
+  },
+  ChallengeRegion {
+    challenge: String,
+  },
+  ChallengeGoal {
+    challenge: String,
+    reward:    ChallengeReward,
+    entity_id: EntityId,
+  },
+  MapStation {
+    region: String,
+  },
+  // Leads to the spawn named target_spawn in the map at the resource path target_map.
+  Door {
+    target_map:   String,
+    target_spawn: String,
   },
   DeleteMe,
 }
@@ -279,65 +742,316 @@ pub struct GameObject {
   pub data:           GameObjectData,
 }
 
+impl GameObjectData {
+  /// Whether the player's shots can hurt this.
+  pub fn is_damageable(&self) -> bool {
+    matches!(
+      self,
+      GameObjectData::Shooter1 { .. }
+        | GameObjectData::Beehive { .. }
+        | GameObjectData::Enemy { .. }
+        | GameObjectData::Stone { hp: Some(_) }
+        | GameObjectData::BreakableBlock {
+          crumbling: None,
+          ..
+        }
+    )
+  }
+
+  /// Knocks `damage` off a damageable object's hp, returning whether that destroyed it.
+  /// Anything else shrugs it off.
+  pub fn take_hit(&mut self, damage: i32) -> bool {
+    match self {
+      GameObjectData::Shooter1 { hp, .. }
+      | GameObjectData::Beehive { hp, .. }
+      | GameObjectData::Stone { hp: Some(hp) }
+      | GameObjectData::BreakableBlock {
+        hp,
+        crumbling: None,
+        ..
+      } => {
+        *hp -= damage;
+        *hp <= 0
+      }
+      GameObjectData::Enemy { .. } => true,
+      _ => false,
+    }
+  }
+}
+
 macro_rules! take_damage {
   ($self: expr, $damage: expr) => {{
-    if $self.damage_blink.get() <= 0.0 && $self.char_state.hp.get() > 0 {
-      $self.char_state.hp.set($self.char_state.hp.get() - $damage);
+    if $self.damage_blink.get() <= 0.0
+      && $self.respawn_immunity <= 0.0
+      && $self.char_state.hp.get() > 0
+    {
+      $self.char_events.apply(&mut $self.char_state, CharEvent::Damage { amount: $damage });
       $self.damage_blink.set(1.0);
+      $self.heatmap.record(HeatKind::Damage);
       $self.queued_damage_text.set(Some($damage));
+      $self.unaccounted_damage.set($self.unaccounted_damage.get() + $damage);
+      $self.game_events.emit(GameEvent::Damaged {
+        amount: $damage,
+        hp:     $self.char_state.hp.get(),
+      });
     }
   }};
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct LocalStorageSaveData {
-  pub char_state:   CharState,
-  pub revealed_map: HashSet<(i32, i32)>,
+  // Missing from saves written before the format was versioned, which count as version 0.
+  #[serde(default)]
+  pub version:                  u32,
+  pub char_state:               CharState,
+  pub revealed_map:             HashSet<(i32, i32)>,
+  #[serde(default = "default_revealed_map_granularity")]
+  pub revealed_map_granularity: i32,
+  #[serde(default)]
+  pub trial_best_times:         HashMap<String, f32>,
+  #[serde(default)]
+  pub map_pins:                 Vec<Vec2>,
+  #[serde(default)]
+  pub visited_rooms:            HashSet<usize>,
+  // The most recent changes that led to char_state, for debugging.
+  #[serde(default)]
+  pub char_history:             Vec<CharEvent>,
+  #[serde(default)]
+  pub run_stats:                RunStats,
+  // Shown when the save is loaded. Missing from saves from before there were recaps.
+  #[serde(default)]
+  pub recap:                    Option<SaveRecap>,
+}
+
+fn default_revealed_map_granularity() -> i32 {
+  MAP_REVELATION_DISCRETIZATION
+}
+
+pub(crate) fn default_save_map() -> String {
+  game_maps::START_MAP.to_string()
+}
+
+// Exported save files are this header followed by the same JSON we keep in local storage.
+const SAVE_FILE_MAGIC: &[u8] = b"TMVSAVE1\n";
+
+/// A snapshot of what's taking up memory, for diagnosing growth over long sessions.
+#[derive(Serialize)]
+pub struct MemoryStats {
+  pub rigid_bodies:         usize,
+  pub colliders:            usize,
+  pub objects:              usize,
+  pub particles:            usize,
+  pub resource_bytes:       usize,
+  pub cached_chunks:        usize,
+  pub scratch_canvas_size:  (u32, u32),
+  pub scratch_canvas_bytes: usize,
+  pub wasm_memory_bytes:    u32,
+}
+
+fn draw_cursor(context: &web_sys::CanvasRenderingContext2d, pos: Vec2) {
+  let (x, y) = (pos.0 as f64, pos.1 as f64);
+  context.set_stroke_style(&JsValue::from_str("white"));
+  context.set_line_width(2.0);
+  context.begin_path();
+  context.arc(x, y, 6.0, 0.0, 2.0 * std::f64::consts::PI).unwrap();
+  context.move_to(x - 12.0, y);
+  context.line_to(x - 4.0, y);
+  context.move_to(x + 4.0, y);
+  context.line_to(x + 12.0, y);
+  context.move_to(x, y - 12.0);
+  context.line_to(x, y - 4.0);
+  context.move_to(x, y + 4.0);
+  context.line_to(x, y + 12.0);
+  context.stroke();
+}
+
+/// Whether a beam from `start` to `end` passes through `view`, even if neither end is in it.
+fn beam_crosses(view: Rect, start: Vec2, end: Vec2) -> bool {
+  sweep_rect(Rect::new(start, Vec2::default()), end - start, view).is_some()
 }
 
 #[wasm_bindgen]
 pub struct GameState {
   resources:                 HashMap<String, Vec<u8>>,
-  draw_context:              DrawContext,
+  // Hashed at load, so they're still around after the resources are unloaded.
+  asset_manifest:            AssetManifest,
+  // Map files that didn't match the deployed versions, which the player gets told about.
+  stale_assets:              Vec<String>,
+  // None when running headless, as in the native replay tests.
+  draw_context:              Option<DrawContext>,
+  input:                     InputLayer,
+  // The keys held as the movement code should see them, after the input layer.
   keys_held:                 HashSet<String>,
   jump_hit:                  bool,
   dash_hit:                  bool,
+  shoot_hit:                 bool,
+  shoot_cooldown:            f32,
   interact_hit:              bool,
+  retry_hit:                 bool,
   camera_pos:                Vec2,
+  camera_follow:             CameraFollow,
+  // How far the camera bounds push the camera from centering on the player, eased.
+  camera_bounds_offset:      Vec2,
+  // The screen shake for this frame, in screen pixels.
+  shake_offset:              Vec2,
+  // Where the camera centers relative to the player, eased toward any nearby camera focus.
+  camera_focus_offset:       Vec2,
+  // Peeking: which way up or down is being held while standing still (-1 for up), for how
+  // long, and how far the camera has panned because of it.
+  peek_direction:            f32,
+  peek_time:                 f32,
+  peek_offset:               f32,
   game_map:                  Rc<GameMap>,
   showing_map:               bool,
   map_shift_pos:             Vec2,
   map_zoom:                  f32,
-  revealed_map:              HashSet<(i32, i32)>,
+  map_pins:                  Vec<Vec2>,
+  map_drag:                  Option<MapDrag>,
+  fast_travel:               FastTravel,
+  mouse_pos:                 Option<Vec2>,
+  // The camera bounds vertex being dragged around in debug mode.
+  camera_vertex_drag:        Option<(usize, usize)>,
+  camera_bounds:             CameraBounds,
+  // The rooms drawn on the map screen, and which of them the player has been in.
+  rooms:                     RoomMap,
+  visited_rooms:             HashSet<usize>,
+  // What the map screen shows of the map, and how much of the world it covers.
+  minimap:                   Minimap,
+  // The room the player was in as of the last step.
+  player_room:               Option<usize>,
+  // How long it's been since the player last picked anything up, how many things they had
+  // then, and the region to suggest once they've been stuck long enough.
+  stuck_time:                f32,
+  pickup_count:              usize,
+  route_hint:                Option<String>,
+  // Where the player left off as of their last save, and the recap shown after loading a
+  // save, with how long it has left.
+  saved_recap:               SaveRecap,
+  showing_recap:             Option<(SaveRecap, f32)>,
+  debug_mode:                bool,
+  // The kinds of objects labeled in debug mode.
+  #[cfg(feature = "debug-tools")]
+  debug_label_categories:    HashSet<LabelCategory>,
+  // Frozen for debugging, so that step_once and run_n_frames are the only way forward.
+  simulation_paused:         bool,
+  // Simulation steps taken outside the map screen, shown in the movement readout.
+  frame_counter:             u64,
+  // What every timer keeps time by. Synchronized vanish blocks go by its gameplay time.
+  clock:                     GameClock,
+  switch_channels:           SwitchChannels,
+  replay_recorder:           Option<ReplayRecorder>,
+  replay_playback:           Option<ReplayPlayback>,
+  revealed_map:              RevealedMap,
+  settings:                  Settings,
+  theme:                     Theme,
+  tuning:                    Tunables,
+  dialogue_library:          DialogueLibrary,
+  // The conversation on screen, which gameplay waits on.
+  dialogue:                  Option<Dialogue>,
+  device_pixel_ratio:        f32,
+  // The parts of the screen hidden by notches and rounded corners, as reported by the page.
+  safe_area:                 SafeArea,
+  quality:                   QualityController,
+  particles:                 ParticleSystem,
+  floaty_texts:              Vec<FloatyText>,
+  critters:                  CritterSystem,
+  // Update time owed to far-off objects that skipped frames, by collider.
+  lod_pending_dt:            HashMap<ColliderHandle, f32>,
+  sounds:                    SoundQueue,
+  game_events:               GameEventQueue,
+  status_effects:            StatusEffects,
+  splash_tracker:            SplashTracker,
+  // Randomness that affects gameplay, reseeded whenever a replay starts.
+  rng:                       GameRng,
   collision:                 CollisionWorld,
   player_physics:            PhysicsObjectHandle,
   player_vel:                Vec2,
-  have_dash:                 bool,
+  // Dashes left before the player next touches the ground.
+  dashes_left:               u32,
   dash_time:                 f32,
   dash_origin:               Vec2,
+  // A unit vector, which is only ever horizontal without the omni dash.
+  dash_direction:            Vec2,
   recently_blocked_to_left:  f32,
   recently_blocked_to_right: f32,
   grounded_last_frame:       bool,
+  // The special surface the player stood on last frame, like ice, if any.
+  standing_on:               Option<Surface>,
+  blocked_left_last_frame:   bool,
+  blocked_right_last_frame:  bool,
   grounded_recently:         f32,
   have_double_jump:          bool,
+  // The ledge the player is hanging from, if they are.
+  hanging_from:              Option<Ledge>,
+  ledge_regrab_time:         f32,
   touching_water:            bool,
   submerged_in_water:        bool,
   air_remaining:             f32,
   offered_interaction:       Option<i32>,
+  // The dialogue script the offered interaction starts, if it's one to read.
+  offered_dialogue:          Option<String>,
+  interact_hold:             InteractHold,
+  // The power-up missing for an interaction the player is at but can't use yet.
+  locked_interaction:        Option<PowerUp>,
+  offered_map_station:       Option<String>,
+  // The map and spawn the door the player is at leads to.
+  offered_door:              Option<(String, String)>,
+  // Reveal chunks covered by each map region, for map stations to uncover at once.
+  map_region_chunks:         HashMap<String, HashSet<(i32, i32)>>,
+  save_repair_context:       SaveRepairContext,
   damage_blink:              Cell<f32>,
   queued_damage_text:        Cell<Option<i32>>,
+  // Damage taken since challenge runs were last charged for it.
+  unaccounted_damage:        Cell<i32>,
   suppress_air_meter:        bool,
   char_state:                CharState,
   saved_char_state:          CharState,
+  char_events:               CharEventLog,
+  run_stats:                 RunStats,
+  // Where the player has been, died, and been hurt this session, for export_heatmap.
+  heatmap:                   Heatmap,
   objects:                   HashMap<ColliderHandle, GameObject>,
-  death_animation:           f32,
+  // From the moment the player dies until they respawn.
+  death:                     Option<DeathSequence>,
   facing_right:              bool,
+  player_animation:          PlayerAnimation,
+  // Seconds since player_animation last changed.
+  animation_time:            f32,
   shrink_time:               f32,
   shrunken:                  bool,
-
-  // Data for specific interactions.
-  int1_laser_time: f32,
-  int2_laser_time: f32,
+  spring_launch_time:        f32,
+  // Seconds of boost left from the last boost rail touched, and its top speed multiplier.
+  boost_time:                f32,
+  boost_speed:               f32,
+  // Where the player has been lately while boosted, oldest first, for drawing the trail.
+  boost_trail:               Vec<Vec2>,
+  player_stretch:            f32,
+  // Counts down while the player materializes, after spawning or respawning.
+  spawn_time:                f32,
+  // Counts down after respawning, while nothing can hurt the player.
+  respawn_immunity:          f32,
+  // Counts down from the cooldown after the player bumps their head trying to stand up.
+  head_bump:                 f32,
+  // Where the player last stood on solid ground, out of harm's way, for soft deaths.
+  last_safe_position:        Vec2,
+  // Where the save point the player is standing on is, if they are.
+  touching_save_point:       Option<Vec2>,
+  // The save point the player just left, and how long ago.
+  save_exit_pulse:           Option<(Vec2, f32)>,
+  active_trial:              Option<TimeTrial>,
+  last_trial_result:         Option<TrialResult>,
+  trial_best_times:          HashMap<String, f32>,
+  daily_challenge:           Option<DailyChallenge>,
+  descent:                   Option<Descent>,
+  // How many fragments have been put in the world since it was last rebuilt, for their ids.
+  fragment_instances:        i32,
+  // Fragments put in with `place_fragment`.
+  placed_fragments:          Vec<PlacedFragment>,
+  challenge_runs:            HashMap<String, ChallengeRun>,
+  // The chase the player is running from, if any.
+  chase:                     Option<Chase>,
+  // Lasers interactions have fired, until they finish.
+  lasers:                    Vec<Laser>,
 }
 
 #[wasm_bindgen]
@@ -350,34 +1064,37 @@ impl GameState {
     crate::log("Setting up game state");
     let document = web_sys::window().unwrap().document().to_js_error()?;
     let mut images = HashMap::new();
+    let mut hd_images = HashMap::new();
     for image_resource in ImageResource::iter() {
       let image = document.get_element_by_id(image_resource.get_path()).to_js_error()?;
       let image = image.dyn_into::<web_sys::HtmlImageElement>()?;
+      for (scale, path) in image_resource.hd_variants() {
+        let image = document.get_element_by_id(path).to_js_error()?;
+        hd_images.insert((image_resource.clone(), *scale), image.dyn_into()?);
+      }
       images.insert(image_resource, image);
     }
 
-    let mut canvases = Vec::new();
-    let mut contexts = Vec::new();
-    for (i, path) in [
-      "uiCanvas",
-      "mainCanvas",
-      "backgroundCanvas",
-      "scratchCanvas",
-    ]
-    .iter()
-    .enumerate()
-    {
-      let canvas = document.get_element_by_id(path).to_js_error()?;
-      let canvas: web_sys::HtmlCanvasElement = canvas.dyn_into::<web_sys::HtmlCanvasElement>()?;
-      let context2d =
-        canvas.get_context("2d")?.to_js_error()?.dyn_into::<web_sys::CanvasRenderingContext2d>()?;
-      canvases.push(canvas);
-      contexts.push(context2d);
-    }
+    let layers = Layers::from_document(&document)?;
 
     let game_map =
-      Rc::new(GameMap::from_resources(&resources, "/assets/map1.tmx").expect("Failed to load map"));
+      Rc::new(GameMap::from_resources(&resources, START_MAP).expect("Failed to load map"));
+    let draw_context = DrawContext {
+      layers,
+      images,
+      hd_images,
+      // FIXME: Don't hard-code this.
+      tile_renderer: TileRenderer::new(game_map.clone(), Vec2(2048.0, 1536.0)),
+      minimap: MinimapRenderer::new(&document, &Minimap::from_game_map(&game_map))?,
+    };
+    Ok(Self::from_parts(resources, game_map, Some(draw_context)))
+  }
 
+  fn from_parts(
+    resources: HashMap<String, Vec<u8>>,
+    game_map: Rc<GameMap>,
+    draw_context: Option<DrawContext>,
+  ) -> Self {
     let mut objects = HashMap::new();
 
     //let collision = Collision::from_game_map(&game_map);
@@ -394,255 +1111,1801 @@ impl GameState {
       false,
       BASIC_INT_GROUPS,
     );
+    char_state.save_map = game_map.name.clone();
     char_state.save_point = collision.spawn_point;
+    let save_point = char_state.save_point;
+    let map_region_chunks = collision.region_chunks(MAP_REVELATION_DISCRETIZATION);
+    let camera_bounds = CameraBounds::from_game_map(&game_map);
+    let rooms = RoomMap::from_camera_bounds(&camera_bounds);
+    let minimap = Minimap::from_game_map(&game_map);
+    let theme = BinaryResource::Theme.parse_or_default(&resources, Theme::from_json);
+    let tuning = BinaryResource::Tuning.parse_or_default(&resources, Tunables::from_json);
+    let dialogue_library =
+      BinaryResource::Dialogue.parse_or_default(&resources, DialogueLibrary::from_json);
+    // Nothing has been collected yet, so every collectible is still in `objects`.
+    let mut save_repair_context =
+      SaveRepairContext::from_objects(&game_map.name, &objects, &collision);
+    // A save can be from partway through any map, so the other maps get checked against too.
+    // Descent maps are left out, since their items can't end up in a save.
+    for map_name in resources.keys().filter(|name| name.ends_with(".tmx")) {
+      if *map_name == game_map.name || descent::is_descent_map(map_name) {
+        continue;
+      }
+      let other_map = GameMap::from_resources(&resources, map_name).expect("Failed to load map");
+      let mut other_collision = collision::CollisionWorld::new();
+      let mut other_objects = HashMap::new();
+      other_collision.load_game_map(&CharState::default(), &other_map, &mut other_objects);
+      save_repair_context.add_map(map_name, &other_objects, &other_collision);
+    }
+    save_repair_context.room_count = rooms.rooms.len();
 
-    let draw_context = DrawContext {
-      canvases: canvases.try_into().unwrap(),
-      contexts: contexts.try_into().unwrap(),
-      images,
-      // FIXME: Don't hard-code this.
-      tile_renderer: TileRenderer::new(game_map.clone(), Vec2(2048.0, 1536.0)),
-    };
-
-    Ok(Self {
+    Self {
+      asset_manifest: AssetManifest::from_resources(&resources),
+      stale_assets: Vec::new(),
       resources,
       draw_context,
+      input: InputLayer::default(),
       keys_held: HashSet::new(),
       jump_hit: false,
       dash_hit: false,
+      shoot_hit: false,
+      shoot_cooldown: 0.0,
       interact_hit: false,
+      retry_hit: false,
       camera_pos: Vec2::default(),
+      camera_follow: CameraFollow::default(),
+      camera_bounds_offset: Vec2::default(),
+      shake_offset: Vec2::default(),
+      camera_focus_offset: Vec2::default(),
+      peek_direction: 0.0,
+      peek_time: 0.0,
+      peek_offset: 0.0,
       game_map,
       showing_map: false,
       map_shift_pos: Vec2(0.5, 0.5),
       map_zoom: 1.0,
-      revealed_map: HashSet::new(),
+      map_pins: Vec::new(),
+      map_drag: None,
+      fast_travel: FastTravel::default(),
+      mouse_pos: None,
+      camera_vertex_drag: None,
+      camera_bounds,
+      rooms,
+      visited_rooms: HashSet::new(),
+      minimap,
+      player_room: None,
+      stuck_time: 0.0,
+      pickup_count: 0,
+      route_hint: None,
+      saved_recap: SaveRecap::default(),
+      showing_recap: None,
+      debug_mode: false,
+      simulation_paused: false,
+      #[cfg(feature = "debug-tools")]
+      debug_label_categories: LabelCategory::iter().collect(),
+      frame_counter: 0,
+      clock: GameClock::default(),
+      switch_channels: SwitchChannels::default(),
+      replay_recorder: None,
+      replay_playback: None,
+      revealed_map: RevealedMap::new(MAP_REVELATION_DISCRETIZATION),
+      settings: Settings::default(),
+      theme,
+      tuning,
+      dialogue_library,
+      dialogue: None,
+      device_pixel_ratio: 1.0,
+      safe_area: SafeArea::default(),
+      quality: QualityController::default(),
+      particles: ParticleSystem::default(),
+      floaty_texts: Vec::new(),
+      lod_pending_dt: HashMap::new(),
+      sounds: SoundQueue::default(),
+      game_events: GameEventQueue::default(),
+      status_effects: StatusEffects::default(),
+      splash_tracker: SplashTracker::default(),
+      critters: CritterSystem::default(),
+      rng: GameRng::default(),
       collision,
       player_physics,
       player_vel: Vec2::default(),
-      have_dash: false,
+      dashes_left: 0,
       dash_time: 0.0,
       dash_origin: Vec2::default(),
+      dash_direction: Vec2(1.0, 0.0),
       recently_blocked_to_left: 0.0,
       recently_blocked_to_right: 0.0,
       touching_water: false,
       submerged_in_water: false,
       air_remaining: 0.0,
       offered_interaction: None,
+      offered_dialogue: None,
+      interact_hold: InteractHold::Released,
+      locked_interaction: None,
+      offered_map_station: None,
+      offered_door: None,
+      map_region_chunks,
+      save_repair_context,
       damage_blink: Cell::new(0.0),
       queued_damage_text: Cell::new(None),
+      unaccounted_damage: Cell::new(0),
       suppress_air_meter: false,
       grounded_last_frame: false,
+      standing_on: None,
+      blocked_left_last_frame: false,
+      blocked_right_last_frame: false,
       grounded_recently: 0.0,
       have_double_jump: false,
+      hanging_from: None,
+      ledge_regrab_time: 0.0,
       char_state: char_state.clone(),
       saved_char_state: char_state,
+      char_events: CharEventLog::default(),
+      run_stats: RunStats::default(),
+      heatmap: Heatmap::default(),
       objects,
-      death_animation: 0.0,
+      death: None,
       facing_right: true,
+      player_animation: PlayerAnimation::Idle,
+      animation_time: 0.0,
       shrink_time: 0.0,
       shrunken: false,
-      int1_laser_time: 0.0,
-      int2_laser_time: 0.0,
-    })
+      spring_launch_time: 0.0,
+      boost_time: 0.0,
+      boost_speed: 1.0,
+      boost_trail: Vec::new(),
+      spawn_time: SPAWN_ANIMATION_TIME,
+      respawn_immunity: 0.0,
+      head_bump: 0.0,
+      last_safe_position: save_point,
+      touching_save_point: None,
+      save_exit_pulse: None,
+      player_stretch: 0.0,
+      active_trial: None,
+      daily_challenge: None,
+      descent: None,
+      fragment_instances: 0,
+      placed_fragments: Vec::new(),
+      last_trial_result: None,
+      trial_best_times: HashMap::new(),
+      challenge_runs: HashMap::new(),
+      chase: None,
+      lasers: Vec::new(),
+    }
   }
 
   pub fn get_char_state(&self) -> JsValue {
     serde_wasm_bindgen::to_value(&self.char_state).unwrap()
   }
 
-  pub fn get_info_line(&self) -> String {
-    format!(
-      "Coins: {:3}", //   Rare Coins: {:3}",
-      self.char_state.coins.len(),
-      //self.char_state.rare_coins.len(),
-    )
+  /// Every change to the player's progress, oldest first, for seeing how a save got the way it is.
+  pub fn get_char_events(&self) -> JsValue {
+    serde_wasm_bindgen::to_value(&self.char_events.events().collect::<Vec<_>>()).unwrap()
+  }
+
+  /// A debug mode tool: takes back the most recent pickup, and puts it back in the world.
+  /// Returns false if there was nothing to undo.
+  #[cfg(feature = "editor")]
+  pub fn undo_last_pickup(&mut self) -> bool {
+    if !self.debug_mode {
+      return false;
+    }
+    let Some(event) = self.char_events.undo_last_pickup(&mut self.char_state) else {
+      return false;
+    };
+    crate::log(&format!("Undid pickup: {:?}", event));
+    // Take it out of the save too, or dying would bring it back.
+    event.revert_insert(&mut self.saved_char_state);
+    let player_pos = self.collision.get_position(&self.player_physics).unwrap();
+    self.reload_world(player_pos);
+    if self.shrunken {
+      self.recreate_player_physics();
+    }
+    true
+  }
+
+  /// The sound events since the last call, as an array of names like "jump", oldest first.
+  #[cfg(feature = "audio")]
+  pub fn drain_sound_events(&mut self) -> JsValue {
+    serde_wasm_bindgen::to_value(&self.sounds.drain()).unwrap()
+  }
+
+  /// The game events since the last call, as objects with a "kind" like "coin_collected",
+  /// oldest first.
+  pub fn drain_game_events(&mut self) -> JsValue {
+    serde_wasm_bindgen::to_value(&self.game_events.drain()).unwrap()
+  }
+
+  pub fn get_memory_stats(&self) -> JsValue {
+    let scratch_canvas_size = match &self.draw_context {
+      Some(draw_context) => {
+        let scratch_canvas = &draw_context.layers[LayerId::Scratch].canvas;
+        (scratch_canvas.width(), scratch_canvas.height())
+      }
+      None => (0, 0),
+    };
+    let wasm_memory_bytes = wasm_bindgen::memory()
+      .dyn_into::<js_sys::WebAssembly::Memory>()
+      .map(|memory| memory.buffer().dyn_into::<js_sys::ArrayBuffer>().unwrap().byte_length())
+      .unwrap_or(0);
+    let stats = MemoryStats {
+      rigid_bodies: self.collision.rigid_body_set.len(),
+      colliders: self.collision.collider_set.len(),
+      objects: self.objects.len(),
+      particles: self.particles.len(),
+      resource_bytes: self.resources.values().map(|data| data.len()).sum(),
+      cached_chunks: self.draw_context.as_ref().map_or(0, |dc| dc.tile_renderer.cached_chunks),
+      scratch_canvas_size,
+      // Canvases are RGBA, so four bytes a pixel.
+      scratch_canvas_bytes: 4 * scratch_canvas_size.0 as usize * scratch_canvas_size.1 as usize,
+      wasm_memory_bytes,
+    };
+    serde_wasm_bindgen::to_value(&stats).unwrap()
+  }
+
+  /// Summaries of the objects whose colliders overlap the rect (JSON like
+  /// `{"pos": [x, y], "size": [w, h]}`, in tiles), for a devtools panel to list.
+  pub fn query_objects(&self, rect_json: &str) -> Result<JsValue, JsValue> {
+    let rect: Rect = serde_json::from_str(rect_json).to_js_error()?;
+    let mut summaries: Vec<ObjectSummary> = self
+      .objects
+      .iter()
+      .filter(|(_, object)| {
+        let bounds = self.collision.collider_bounds(object.physics_handle.collider);
+        bounds.is_some_and(|bounds| bounds.overlaps(rect))
+      })
+      .map(|(handle, object)| ObjectSummary::new(*handle, object, &self.collision))
+      .collect();
+    summaries.sort_by(|a, b| a.handle.total_cmp(&b.handle));
+    Ok(serde_wasm_bindgen::to_value(&summaries).unwrap())
+  }
+
+  /// Everything about the object with a handle from `query_objects`.
+  pub fn get_object_details(&self, handle: f64) -> Result<JsValue, JsValue> {
+    let handle = inspector::handle_from_js(handle);
+    let object = self.objects.get(&handle).ok_or("No object with that handle").to_js_error()?;
+    let details = ObjectDetails::new(handle, object, &self.collision);
+    Ok(serde_wasm_bindgen::to_value(&details).unwrap())
+  }
+
+  /// Tweaks an object live, from JSON like `{"position": [x, y]}` with any of the
+  /// position, velocity, and data from `get_object_details`.
+  pub fn edit_object(&mut self, handle: f64, edit_json: &str) -> Result<(), JsValue> {
+    let edit: ObjectEdit = serde_json::from_str(edit_json).to_js_error()?;
+    let object = self
+      .objects
+      .get_mut(&inspector::handle_from_js(handle))
+      .ok_or("No object with that handle")
+      .to_js_error()?;
+    edit
+      .apply(
+        &object.physics_handle,
+        &mut object.data,
+        &mut self.collision,
+      )
+      .to_js_error()
+  }
+
+  /// The content hash of each resource, by path, as JSON to deploy as the asset manifest.
+  pub fn get_asset_manifest(&self) -> String {
+    serde_json::to_string_pretty(&self.asset_manifest.hashes).unwrap()
+  }
+
+  /// Checks the map files we loaded against the deploy's manifest (as from
+  /// get_asset_manifest), and returns the paths of any that are stale.
+  pub fn verify_assets(&mut self, expected: JsValue) -> Result<JsValue, JsValue> {
+    let expected: HashMap<String, String> = serde_wasm_bindgen::from_value(expected)?;
+    self.stale_assets = self.asset_manifest.stale_files(&expected);
+    if !self.stale_assets.is_empty() {
+      crate::log(&format!("Stale assets: {:?}", self.stale_assets));
+    }
+    Ok(serde_wasm_bindgen::to_value(&self.stale_assets).unwrap())
+  }
+
+  /// Drops the raw bytes of the resources we were constructed with, except for the maps and
+  /// tilesets. Those get parsed again whenever the player goes through a door to another map,
+  /// or starts a descent, so they have to stay.
+  pub fn unload_resources(&mut self) {
+    self.resources.retain(|name, _| name.ends_with(".tmx") || name.ends_with(".tsx"));
+  }
+
+  /// Frees the prerendered tile chunks; they're rerendered on demand.
+  pub fn clear_render_caches(&mut self) {
+    let Some(DrawContext {
+      layers,
+      tile_renderer,
+      ..
+    }) = &mut self.draw_context
+    else {
+      return;
+    };
+    tile_renderer.clear_cache(&layers[LayerId::Scratch].canvas);
+  }
+
+  /// Whether the player is about to start drowning, for the page to play a heartbeat.
+  pub fn is_low_on_air(&self) -> bool {
+    self.air_fraction().map(AirWarning::from_fraction) == Some(AirWarning::Critical)
   }
 
   pub fn get_save_data(&self) -> String {
+    // Don't let a replay's or daily challenge's progress leak into the player's own save.
+    if let Some(playback) = &self.replay_playback {
+      return playback.live_save_data.clone();
+    }
+    if let Some(run) = self.side_run() {
+      return run.live_save_data.clone();
+    }
     // JSON serialize self.saved_char_state and self.revealed_map.
     let save_data = LocalStorageSaveData {
-      char_state:   self.saved_char_state.clone(),
-      revealed_map: self.revealed_map.clone(),
+      version:                  save_repair::SAVE_VERSION,
+      char_state:               self.saved_char_state.clone(),
+      revealed_map:             self.revealed_map.chunks().clone(),
+      revealed_map_granularity: self.revealed_map.granularity(),
+      trial_best_times:         self.trial_best_times.clone(),
+      map_pins:                 self.map_pins.clone(),
+      visited_rooms:            self.visited_rooms.clone(),
+      char_history:             self.char_events.saved_history(),
+      run_stats:                self.run_stats.clone(),
+      recap:                    Some(SaveRecap {
+        play_time: self.run_stats.play_time,
+        ..self.saved_recap.clone()
+      }),
     };
     serde_json::to_string(&save_data).unwrap()
   }
 
-  pub fn apply_save_data(&mut self, save_data: &str) -> Result<(), JsValue> {
-    let save_data: LocalStorageSaveData = serde_json::from_str(save_data).to_js_error()?;
+  /// Loads save data, repairing what it can. Returns a report of what was fixed.
+  pub fn apply_save_data(&mut self, save_data: &str) -> Result<JsValue, JsValue> {
+    let report = self.load_save_data(save_data).to_js_error()?;
+    Ok(serde_wasm_bindgen::to_value(&report).unwrap())
+  }
+
+  fn load_save_data(&mut self, save_data: &str) -> Result<save_repair::RepairReport, String> {
+    let mut report = save_repair::RepairReport::default();
+    let mut save_data = save_repair::parse_leniently(save_data, &mut report)?;
+    save_repair::repair(&mut save_data, &self.save_repair_context, &mut report);
     self.saved_char_state = save_data.char_state;
-    self.revealed_map = save_data.revealed_map;
+    self.revealed_map =
+      RevealedMap::from_chunks(save_data.revealed_map_granularity, save_data.revealed_map);
+    self.revealed_map.set_granularity(self.settings.map_reveal_granularity);
+    self.trial_best_times = save_data.trial_best_times;
+    self.map_pins = save_data.map_pins;
+    self.visited_rooms = save_data.visited_rooms;
+    self.char_events = CharEventLog::from_history(save_data.char_history);
+    self.run_stats = save_data.run_stats;
+    self.saved_recap = save_data.recap.clone().unwrap_or_default();
+    self.showing_recap = save_data.recap.map(|recap| (recap, recap::RECAP_TIME));
     self.respawn();
+    Ok(report)
+  }
+
+  /// The save as the bytes of a file players can keep as a backup or carry to another browser.
+  pub fn export_save_file(&self) -> Vec<u8> {
+    let mut bytes = SAVE_FILE_MAGIC.to_vec();
+    bytes.extend_from_slice(self.get_save_data().as_bytes());
+    bytes
+  }
+
+  /// Anonymized statistics for each of `saves` (an array of save data, or save files' text),
+  /// as JSON, for comparing runs. Saves that can't be read at all are left out.
+  pub fn export_run_statistics(&self, saves: JsValue) -> Result<String, JsValue> {
+    let saves: Vec<String> = serde_wasm_bindgen::from_value(saves)?;
+    let magic = std::str::from_utf8(SAVE_FILE_MAGIC).unwrap();
+    let mut statistics = Vec::new();
+    for (slot, save) in saves.iter().enumerate() {
+      let json = save.strip_prefix(magic).unwrap_or(save);
+      let mut report = save_repair::RepairReport::default();
+      match save_repair::parse_leniently(json, &mut report) {
+        Ok(mut save_data) => {
+          save_repair::repair(&mut save_data, &self.save_repair_context, &mut report);
+          statistics.push(SaveStatistics::from_save(
+            slot,
+            &save_data,
+            &self.save_repair_context,
+          ));
+        }
+        Err(e) => crate::log(&format!("Skipping unreadable save {}: {}", slot, e)),
+      }
+    }
+    Ok(serde_json::to_string_pretty(&statistics).unwrap())
+  }
+
+  /// Loads a file from `export_save_file`, repairing it like `apply_save_data` does.
+  pub fn import_save_file(&mut self, bytes: &[u8]) -> Result<JsValue, JsValue> {
+    let json = bytes.strip_prefix(SAVE_FILE_MAGIC).ok_or("Not a tmv save file").to_js_error()?;
+    let json = std::str::from_utf8(json).to_js_error()?;
+    self.apply_save_data(json)
+  }
+
+  pub fn get_settings(&self) -> String {
+    serde_json::to_string(&self.settings).unwrap()
+  }
+
+  pub fn apply_settings(&mut self, settings: &str) -> Result<(), JsValue> {
+    let mut settings: Settings = serde_json::from_str(settings).to_js_error()?;
+    settings.sanitize();
+    if settings.map_reveal_granularity != self.revealed_map.granularity() {
+      self.revealed_map.set_granularity(settings.map_reveal_granularity);
+      self.map_region_chunks = self.collision.region_chunks(settings.map_reveal_granularity);
+    }
+    self.quality.enabled = settings.auto_quality;
+    self.settings = settings;
+    Ok(())
+  }
+
+  /// Swaps in a new theme, e.g. the colorblind palette, without restarting.
+  pub fn apply_theme(&mut self, theme: &str) -> Result<(), JsValue> {
+    self.theme = Theme::from_json(theme).to_js_error()?;
+    Ok(())
+  }
+
+  pub fn get_movement_tuning(&self) -> String {
+    serde_json::to_string(&self.tuning.movement).unwrap()
+  }
+
+  /// Swaps in new movement tuning, for trying out changes without reloading.
+  pub fn apply_movement_tuning(&mut self, tuning: &str) -> Result<(), JsValue> {
+    self.tuning.movement = MovementTuning::from_json(tuning).to_js_error()?;
     Ok(())
   }
 
+  /// Changes one tunable, like `camera.vertical_deadzone`, for trying out values live.
+  pub fn set_tunable(&mut self, name: &str, value: f32) -> Result<(), JsValue> {
+    self.tuning.set(name, value).to_js_error()
+  }
+
+  /// Every tunable's current value, by name.
+  pub fn get_tunables(&self) -> String {
+    serde_json::to_string(&self.tuning.values()).unwrap()
+  }
+
+  /// The current tunables in the form of tuning.json, to make the values found live stick.
+  pub fn export_tunables(&self) -> String {
+    serde_json::to_string_pretty(&self.tuning).unwrap()
+  }
+
   pub fn apply_input_event(&mut self, event: &str) -> Result<(), JsValue> {
     let event: InputEvent = serde_json::from_str(event).to_js_error()?;
+    if self.replay_playback.is_some() {
+      return Ok(());
+    }
+    if let Some(recorder) = &mut self.replay_recorder {
+      recorder.record_event(event.clone());
+    }
+    self.handle_input_event(event);
+    Ok(())
+  }
+
+  fn handle_input_event(&mut self, event: InputEvent) {
     match event {
-      InputEvent::KeyDown { key } => {
-        if key == "ArrowUp" || key == "w" || key == "z" {
-          self.jump_hit = true;
-        }
-        if key == "Shift" {
-          self.dash_hit = true;
+      InputEvent::KeyDown { key, timestamp } => {
+        let action = match key.as_str() {
+          k if input::JUMP_KEYS.contains(&k) => Some(Action::Jump),
+          input::DASH_KEY => Some(Action::Dash),
+          input::SHOOT_KEY => Some(Action::Shoot),
+          _ => None,
+        };
+        match (action, timestamp) {
+          (Some(action), Some(timestamp)) if self.settings.immediate_input => {
+            self.input.buffer_action(action, timestamp);
+          }
+          (Some(action), _) => self.apply_action(action),
+          (None, _) => {}
         }
         if key == "e" {
           self.interact_hit = true;
         }
-        if key == "m" {
+        if let Some(dialogue) = &mut self.dialogue {
+          match key.as_str() {
+            "ArrowUp" | "w" => dialogue.move_selection(&self.dialogue_library, -1),
+            "ArrowDown" | "s" => dialogue.move_selection(&self.dialogue_library, 1),
+            _ => {}
+          }
+        }
+        #[cfg(feature = "map-screen")]
+        if key == "m" && self.on_start_map() {
           self.showing_map ^= true;
+          self.fast_travel = FastTravel::default();
+        }
+        if key == "r" {
+          self.retry_hit = true;
         }
-        if key == " " && self.char_state.hp.get() <= 0 {
+        if key == " " && matches!(&self.death, Some(death) if death.can_respawn()) {
           self.respawn();
         }
-        self.keys_held.insert(key);
+        self.input.key_down(&key);
       }
       InputEvent::KeyUp { key } => {
-        self.keys_held.remove(&key);
+        self.input.key_up(&key);
+      }
+    }
+  }
+
+  pub fn apply_mouse_event(&mut self, event: &str) -> Result<(), JsValue> {
+    let event: MouseEvent = serde_json::from_str(event).to_js_error()?;
+    match event {
+      MouseEvent::MouseDown { x, y, button: 0 } => {
+        let screen_pos = Vec2(x, y);
+        self.mouse_pos = Some(screen_pos);
+        if self.showing_map {
+          self.map_drag = Some(MapDrag {
+            start_screen_pos: screen_pos,
+            start_shift_pos:  self.map_shift_pos,
+            moved:            false,
+          });
+        } else if !self.editor_click(screen_pos) {
+          self.click_interact(self.camera().screen_to_world(screen_pos));
+        }
+      }
+      MouseEvent::MouseDown { .. } => {}
+      MouseEvent::MouseUp { x, y, button: 0 } => {
+        self.camera_vertex_drag = None;
+        if let Some(drag) = self.map_drag.take() {
+          if self.showing_map && !drag.moved {
+            self.toggle_map_pin(Vec2(x, y));
+          }
+        }
+      }
+      MouseEvent::MouseUp { .. } => {}
+      MouseEvent::MouseMove { x, y } => {
+        let screen_pos = Vec2(x, y);
+        self.mouse_pos = Some(screen_pos);
+        if let Some(vertex) = self.camera_vertex_drag {
+          let world_pos = self.camera().screen_to_world(screen_pos);
+          self.camera_bounds.move_vertex(vertex, world_pos);
+          self.rooms = RoomMap::from_camera_bounds(&self.camera_bounds);
+        }
+        if let Some(drag) = &mut self.map_drag {
+          let delta = screen_pos - drag.start_screen_pos;
+          drag.moved |= delta.length() > 4.0;
+          if drag.moved {
+            self.map_shift_pos = Vec2(
+              drag.start_shift_pos.0 - delta.0 / (self.map_zoom * SCREEN_WIDTH),
+              drag.start_shift_pos.1 - delta.1 / (self.map_zoom * SCREEN_HEIGHT),
+            );
+          }
+        }
+      }
+      MouseEvent::MouseLeave => {
+        self.mouse_pos = None;
+        self.camera_vertex_drag = None;
+        self.map_drag = None;
+      }
+      MouseEvent::Wheel { x, y, delta_y } => {
+        if self.showing_map {
+          // Zoom about the cursor, so the spot under it stays put.
+          let screen_pos = Vec2(x, y);
+          let uv = self.minimap.world_to_uv(self.map_camera().screen_to_world(screen_pos));
+          self.map_zoom = (self.map_zoom * 1.1f32.powf(-delta_y / 100.0)).clamp(1.0, 10.0);
+          self.map_shift_pos = Vec2(
+            uv.0 - (screen_pos.0 - SCREEN_WIDTH / 2.0) / (self.map_zoom * SCREEN_WIDTH),
+            uv.1 - (screen_pos.1 - SCREEN_HEIGHT / 2.0) / (self.map_zoom * SCREEN_HEIGHT),
+          );
+        }
       }
     }
     Ok(())
   }
 
-  pub fn respawn(&mut self) {
-    self.char_state = self.saved_char_state.clone();
-    self.death_animation = 0.0;
-    self.damage_blink.set(0.0);
-    self.player_vel = Vec2::default();
-    self.shrunken = false;
+  /// In debug mode, a click grabs the nearest camera bounds vertex to drag around, or failing
+  /// that moves the player there. Returns whether the click was used up.
+  #[cfg(feature = "editor")]
+  fn editor_click(&mut self, screen_pos: Vec2) -> bool {
+    if !self.debug_mode {
+      return false;
+    }
+    let world_pos = self.camera().screen_to_world(screen_pos);
+    let grab_radius = CAMERA_VERTEX_GRAB_RADIUS / TILE_SIZE;
+    self.camera_vertex_drag = self.camera_bounds.nearest_vertex(world_pos, grab_radius);
+    if self.camera_vertex_drag.is_none() {
+      self.collision.set_position(&self.player_physics, world_pos, true);
+      self.player_vel = Vec2::default();
+    }
+    true
+  }
 
-    self.objects = HashMap::new();
-    //let collision = Collision::from_game_map(&game_map);
-    self.collision = collision::CollisionWorld::new();
-    self.collision.load_game_map(&self.char_state, &self.game_map, &mut self.objects);
-    self.player_physics = self.collision.new_cuboid(
-      PhysicsKind::Sensor,
-      self.char_state.save_point,
-      PLAYER_SIZE,
-      0.25,
-      false,
-      BASIC_INT_GROUPS,
-    );
-    // FIXME: This should maybe also run on the initial load.
-    if self.char_state.int1_completed {
-      self.interaction1_delete_stone();
+  #[cfg(not(feature = "editor"))]
+  fn editor_click(&mut self, _screen_pos: Vec2) -> bool {
+    false
+  }
+
+  fn apply_action(&mut self, action: Action) {
+    match action {
+      Action::Jump => self.jump_hit = true,
+      Action::Dash => self.dash_hit = true,
+      Action::Shoot => self.shoot_hit = true,
     }
-    if self.char_state.int2_completed {
-      self.interaction2_delete_stone();
+  }
+
+  pub fn set_device_pixel_ratio(&mut self, ratio: f32) {
+    self.device_pixel_ratio = ratio.max(1.0);
+    self.apply_render_scale();
+  }
+
+  /// Feeds the time between the last two frames to the auto-quality controller.
+  pub fn report_frame_time(&mut self, frame_time: f32) {
+    if self.quality.report_frame_time(frame_time) {
+      crate::log(&format!(
+        "Render scale is now {}x",
+        self.quality.render_scale_factor()
+      ));
+      self.apply_render_scale();
     }
   }
 
-  fn recreate_player_physics(&mut self) {
-    let old_player_position = self.collision.get_position(&self.player_physics);
-    self.collision.remove_object(self.player_physics.clone());
-    self.player_physics = self.collision.new_cuboid(
-      PhysicsKind::Sensor,
-      old_player_position.unwrap(),
-      match self.shrunken {
-        true => SHRUNKEN_SIZE,
-        false => PLAYER_SIZE,
-      },
-      0.25,
-      false,
-      BASIC_INT_GROUPS,
-    );
+  /// Sizes the visible canvases' backing stores for the display's pixel density
+  /// (less whatever the auto-quality controller is taking off), and has the tile
+  /// renderer pick tile sources to match.
+  fn apply_render_scale(&mut self) {
+    let ratio = self.device_pixel_ratio * self.quality.render_scale_factor();
+    let Some(draw_context) = &mut self.draw_context else {
+      return;
+    };
+    for layer in LayerId::VISIBLE {
+      draw_context.layers[layer].set_pixel_ratio(ratio);
+    }
+    draw_context.tile_renderer.set_render_scale(ratio);
   }
 
-  fn create_bullet(&mut self, location: Vec2, velocity: Vec2) {
-    let physics_handle = self.collision.new_circle(
-      collision::PhysicsKind::Dynamic,
-      location,
-      0.25,
-      false,
-      Some(InteractionGroups::new(
-        BASIC_GROUP,
-        WALLS_GROUP | PLAYER_GROUP,
-      )),
-    );
-    // Set the velocity.
-    self.collision.set_velocity(&physics_handle, velocity);
-    self.objects.insert(
-      physics_handle.collider,
-      GameObject {
-        physics_handle,
-        data: GameObjectData::Bullet { velocity },
-      },
-    );
+  /// Takes the safe area insets (as from CSS env(safe-area-inset-*)), already converted to game screen pixels.
+  pub fn set_safe_area_insets(&mut self, top: f32, right: f32, bottom: f32, left: f32) {
+    self.safe_area = SafeArea::new(top, right, bottom, left);
   }
 
-  fn create_bee(&mut self, location: Vec2, velocity: Vec2) {
-    let physics_handle = self.collision.new_circle(
-      collision::PhysicsKind::Dynamic,
-      location,
+  #[cfg(feature = "debug-tools")]
+  pub fn set_debug_mode(&mut self, debug_mode: bool) {
+    self.debug_mode = debug_mode;
+    self.camera_vertex_drag = None;
+  }
+
+  /// Shows or hides debug mode's labels for one category of objects, like "hazards".
+  #[cfg(feature = "debug-tools")]
+  pub fn set_debug_labels(&mut self, category: &str, shown: bool) -> Result<(), JsValue> {
+    let category = LabelCategory::from_name(category)
+      .ok_or_else(|| format!("Unknown label category: {}", category))
+      .to_js_error()?;
+    match shown {
+      true => self.debug_label_categories.insert(category),
+      false => self.debug_label_categories.remove(&category),
+    };
+    Ok(())
+  }
+
+  /// Counts of where the player has lingered, died, and been hurt this session, as JSON:
+  /// for each map, the cells of a grid over it (by their top left tile) with anything in them.
+  pub fn export_heatmap(&self) -> String {
+    self.heatmap.to_json()
+  }
+
+  /// The camera bounds, including any vertices dragged around in debug mode, as Tiled polylines.
+  #[cfg(feature = "editor")]
+  pub fn export_camera_bounds(&self) -> String {
+    self.camera_bounds.to_tiled_json()
+  }
+
+  /// Starts the daily challenge for a date given as YYYY-MM-DD: a fresh game on the clock,
+  /// seeded from the date. The player's own game is put aside until `quit_daily_challenge`,
+  /// and any descent is ended.
+  pub fn start_daily_challenge(&mut self, date: &str) -> Result<(), JsValue> {
+    let mut challenge = DailyChallenge::new(date, String::new()).to_js_error()?;
+    self.close_replay();
+    challenge.run.live_save_data = self.end_side_runs();
+    self.restart_from(&self.new_game_save_data(), self.settings.clone());
+    self.rng = GameRng::new(challenge.seed);
+    self.daily_challenge = Some(challenge);
+    Ok(())
+  }
+
+  /// Goes back to the player's own game.
+  pub fn quit_daily_challenge(&mut self) {
+    if let Some(challenge) = self.daily_challenge.take() {
+      self.restart_from(&challenge.run.live_save_data, self.settings.clone());
+    }
+  }
+
+  /// The code to share once the daily challenge is finished, with the date, time, and a
+  /// hash of how the run ended.
+  pub fn daily_result_code(&self) -> Option<String> {
+    self.daily_challenge.as_ref()?.result_code.clone()
+  }
+
+  /// Starts an endless descent: a fresh game, falling through rooms stitched together from
+  /// the room templates, picked by `seed`. The player's own game is put aside until they
+  /// die, or call `quit_descent`, and any daily challenge is ended.
+  pub fn start_descent(&mut self, seed: u32) -> Result<(), JsValue> {
+    let mut descent = Descent::new(&self.resources, seed, String::new()).to_js_error()?;
+    self.close_replay();
+    descent.run.live_save_data = self.end_side_runs();
+    self.restart_from(&self.new_game_save_data(), self.settings.clone());
+    self.switch_map(descent::ENTRANCE_MAP).to_js_error()?;
+    self.reload_world(Vec2::default());
+    let spawn_point = self.collision.spawn_point;
+    self.collision.set_position(&self.player_physics, spawn_point, true);
+    self.snap_camera(spawn_point);
+    self.last_safe_position = spawn_point;
+    self.rng = GameRng::new(seed as u64);
+    descent.start_y = spawn_point.1;
+    self.descent = Some(descent);
+    Ok(())
+  }
+
+  /// The daily challenge or descent being played, if either is.
+  fn side_run(&self) -> Option<&SideRun> {
+    match (&self.daily_challenge, &self.descent) {
+      (Some(challenge), _) => Some(&challenge.run),
+      (None, Some(descent)) => Some(&descent.run),
+      (None, None) => None,
+    }
+  }
+
+  /// Ends any daily challenge or descent without going back to the player's own game, for
+  /// starting something else in its place. Returns the player's own save data, to go back
+  /// to once that's over.
+  fn end_side_runs(&mut self) -> String {
+    // Even from a daily challenge or a descent, this is the player's own game.
+    let live_save_data = self.get_save_data();
+    self.daily_challenge = None;
+    self.descent = None;
+    live_save_data
+  }
+
+  /// Goes back to the player's own game.
+  pub fn quit_descent(&mut self) {
+    if let Some(descent) = self.descent.take() {
+      self.restart_from(&descent.run.live_save_data, self.settings.clone());
+    }
+  }
+
+  /// How the descent went, as JSON, once it's over.
+  pub fn descent_result(&self) -> Option<String> {
+    let result = self.descent.as_ref()?.result.as_ref()?;
+    Some(serde_json::to_string(result).unwrap())
+  }
+
+  /// Puts a copy of the map at the resource path `map_name` into the world with its top left
+  /// at tile (x, y), for trying out room templates in place. Fragments are drawn in whole
+  /// chunks, so x and y have to be on chunk edges. It stays until `clear_fragments`, or the
+  /// world is rebuilt.
+  pub fn place_fragment(&mut self, map_name: &str, x: i32, y: i32) -> Result<(), JsValue> {
+    let placement = Placement {
+      offset: (x, y),
+      ..Placement::default()
+    };
+    if !placement.is_chunk_aligned() {
+      return Err(format!("({}, {}) isn't on a chunk edge", x, y)).to_js_error();
+    }
+    let game_map = GameMap::from_resources(&self.resources, map_name).to_js_error()?;
+    let fragment = self.add_fragment(Rc::new(game_map), (x, y));
+    self.placed_fragments.push(fragment);
+    Ok(())
+  }
+
+  /// Takes out every fragment put in with `place_fragment`.
+  pub fn clear_fragments(&mut self) {
+    for fragment in take(&mut self.placed_fragments) {
+      self.remove_fragment(fragment);
+    }
+  }
+
+  /// Adds a copy of a map fragment to the live world, and to what's drawn, with the next
+  /// free ids.
+  fn add_fragment(&mut self, game_map: Rc<GameMap>, offset: (i32, i32)) -> PlacedFragment {
+    self.fragment_instances += 1;
+    let placement = Placement::fragment(offset, self.fragment_instances);
+    let fragment =
+      self.collision.load_fragment(&self.char_state, &game_map, placement, &mut self.objects);
+    if let Some(draw_context) = &mut self.draw_context {
+      draw_context.tile_renderer.add_fragment(game_map, placement.chunk_offset());
+    }
+    fragment
+  }
+
+  fn remove_fragment(&mut self, fragment: PlacedFragment) {
+    if let Some(draw_context) = &mut self.draw_context {
+      draw_context.tile_renderer.remove_fragment(fragment.placement.chunk_offset());
+    }
+    self.collision.remove_fragment(fragment, &mut self.objects);
+  }
+
+  /// A save with nothing done yet, for modes that start from scratch.
+  fn new_game_save_data(&self) -> String {
+    let new_game = LocalStorageSaveData {
+      version:                  save_repair::SAVE_VERSION,
+      char_state:               CharState::default(),
+      revealed_map:             HashSet::new(),
+      revealed_map_granularity: self.settings.map_reveal_granularity,
+      trial_best_times:         HashMap::new(),
+      map_pins:                 Vec::new(),
+      visited_rooms:            HashSet::new(),
+      char_history:             Vec::new(),
+      run_stats:                RunStats::default(),
+      recap:                    None,
+    };
+    serde_json::to_string(&new_game).unwrap()
+  }
+
+  /// Compares two versions of a map's TMX, for reviewing map edits without opening Tiled,
+  /// and describes what changed in how it plays, one change per line. This needs the
+  /// tilesets, so it has to happen before `unload_resources`.
+  pub fn diff_maps(&self, old_tmx: &str, new_tmx: &str) -> Result<String, JsValue> {
+    let diff = MapDiff::from_tmx(&self.resources, old_tmx.as_bytes(), new_tmx.as_bytes());
+    Ok(diff.to_js_error()?.report())
+  }
+
+  /// Starts recording a replay. The player is respawned first, so the recording has a
+  /// known starting point.
+  pub fn start_recording(&mut self) {
+    let save_data = self.get_save_data();
+    let settings = self.settings.clone();
+    self.restart_from(&save_data, settings.clone());
+    self.replay_recorder = Some(ReplayRecorder::new(save_data, settings));
+  }
+
+  /// Stops recording, returning the replay as JSON, if one was being recorded.
+  pub fn stop_recording(&mut self) -> Option<String> {
+    let replay = self.replay_recorder.take()?.finish();
+    Some(serde_json::to_string(&replay).unwrap())
+  }
+
+  /// Loads a replay for frame-by-frame inspection with `step_once` and `seek_replay`.
+  /// Live input and stepping are ignored until `close_replay`, which goes back to the
+  /// player's own game, ending any daily challenge or descent.
+  pub fn load_replay(&mut self, replay: &str) -> Result<(), JsValue> {
+    let replay = Replay::from_json(replay).to_js_error()?;
+    self.replay_recorder = None;
+    let (live_save_data, live_settings) = match self.replay_playback.take() {
+      Some(playback) => (playback.live_save_data, playback.live_settings),
+      None => (self.end_side_runs(), self.settings.clone()),
+    };
+    self.replay_playback = Some(ReplayPlayback {
+      replay,
+      next_frame: 0,
+      live_save_data,
+      live_settings,
+    });
+    self.seek_replay(0)
+  }
+
+  /// Freezes the game where it is, for examining it frame by frame with `step_once`.
+  pub fn pause_simulation(&mut self) {
+    self.simulation_paused = true;
+  }
+
+  pub fn resume_simulation(&mut self) {
+    self.simulation_paused = false;
+  }
+
+  /// Plays the next frame of the loaded replay or, with no replay loaded, steps the paused
+  /// game by one frame. Returns false if there's nothing to step: the replay has run out,
+  /// or the game isn't paused.
+  pub fn step_once(&mut self) -> Result<bool, JsValue> {
+    let Some(mut playback) = self.replay_playback.take() else {
+      if !self.simulation_paused {
+        return Ok(false);
+      }
+      if let Some(recorder) = &mut self.replay_recorder {
+        recorder.record_frame(FRAME_STEP_DT, None);
+      }
+      return self.simulate(FRAME_STEP_DT).map(|()| true);
+    };
+    let result = match playback.is_finished() {
+      true => Ok(false),
+      false => {
+        playback.next_frame += 1;
+        self.play_replay_frame(&playback.replay.frames[playback.next_frame - 1]).map(|()| true)
+      }
+    };
+    self.replay_playback = Some(playback);
+    result
+  }
+
+  /// Calls `step_once` up to `n` times, returning how many frames were actually stepped.
+  pub fn run_n_frames(&mut self, n: usize) -> Result<usize, JsValue> {
+    for i in 0..n {
+      if !self.step_once()? {
+        return Ok(i);
+      }
+    }
+    Ok(n)
+  }
+
+  fn play_replay_frame(&mut self, frame: &ReplayFrame) -> Result<(), JsValue> {
+    frame.events.iter().for_each(|event| self.handle_input_event(event.clone()));
+    match frame.timestamp {
+      Some(timestamp) => self.simulate_at(frame.dt, timestamp),
+      None => self.simulate(frame.dt),
+    }
+  }
+
+  /// Moves the loaded replay to just before the given frame. There are no snapshots, so
+  /// this replays everything from the start.
+  pub fn seek_replay(&mut self, frame: usize) -> Result<(), JsValue> {
+    let Some(playback) = &mut self.replay_playback else {
+      return Ok(());
+    };
+    playback.next_frame = 0;
+    let (save_data, settings) = (
+      playback.replay.save_data.clone(),
+      playback.replay.settings.clone(),
+    );
+    self.restart_from(&save_data, settings);
+    for _ in 0..frame {
+      if !self.step_once()? {
+        break;
+      }
+    }
+    Ok(())
+  }
+
+  /// The next frame of the loaded replay to play, or None if no replay is loaded.
+  pub fn replay_position(&self) -> Option<usize> {
+    self.replay_playback.as_ref().map(|playback| playback.next_frame)
+  }
+
+  pub fn replay_length(&self) -> Option<usize> {
+    self.replay_playback.as_ref().map(|playback| playback.frame_count())
+  }
+
+  /// Unloads the replay and puts the player's own save and settings back.
+  pub fn close_replay(&mut self) {
+    if let Some(playback) = self.replay_playback.take() {
+      self.restart_from(&playback.live_save_data, playback.live_settings);
+    }
+  }
+
+  fn movement_state(&self) -> MovementState {
+    let on_wall = self.blocked_left_last_frame || self.blocked_right_last_frame;
+    if self.char_state.hp.get() <= 0 {
+      MovementState::Dead
+    } else if self.dash_time > 0.0 && self.dash_direction.1 != 0.0 {
+      MovementState::AimedDashing
+    } else if self.dash_time > 0.0 {
+      MovementState::Dashing
+    } else if self.spring_launch_time > 0.0 {
+      MovementState::SpringLaunched
+    } else if self.shrunken {
+      MovementState::Shrunken
+    } else if self.hanging_from.is_some() {
+      MovementState::LedgeHanging
+    } else if self.touching_water {
+      MovementState::Swimming
+    } else if self.grounded_last_frame {
+      MovementState::Grounded
+    } else if on_wall && self.player_vel.1 > 0.0 {
+      MovementState::WallSliding
+    } else if self.player_vel.1 < 0.0 {
+      MovementState::Rising
+    } else {
+      MovementState::Falling
+    }
+  }
+
+  /// How many dashes the player gets between touching the ground: one with the dash, and
+  /// another with the extra dash on top of it.
+  fn max_dashes(&self) -> u32 {
+    match self.char_state.power_ups.contains(&PowerUp::Dash) {
+      false => 0,
+      true => 1 + self.char_state.power_ups.contains(&PowerUp::ExtraDash) as u32,
+    }
+  }
+
+  /// Which way a dash started now would go. With the omni dash, the held arrows aim it in
+  /// any of eight directions, and otherwise it's the way the player faces.
+  fn aimed_dash_direction(&self) -> Vec2 {
+    let facing = Vec2(if self.facing_right { 1.0 } else { -1.0 }, 0.0);
+    if !self.char_state.power_ups.contains(&PowerUp::OmniDash) {
+      return facing;
+    }
+    let held = |keys: &[&str]| keys.iter().any(|key| self.keys_held.contains(*key)) as i32;
+    let aim = Vec2(
+      (held(&input::RIGHT_KEYS) - held(&input::LEFT_KEYS)) as f32,
+      (held(&["ArrowDown", "s"]) - held(&["ArrowUp", "w"])) as f32,
+    );
+    match aim == Vec2(0.0, 0.0) {
+      true => facing,
+      false => (1.0 / aim.length()) * aim,
+    }
+  }
+
+  /// How long the player can stay underwater on a full breath.
+  fn full_air(&self) -> f32 {
+    match self.char_state.power_ups.contains(&PowerUp::Water) {
+      false => UNDERWATER_TIME,
+      true => HIGH_UNDERWATER_TIME,
+    }
+  }
+
+  /// How much of a full breath is left, while underwater and alive.
+  fn air_fraction(&self) -> Option<f32> {
+    if !self.submerged_in_water || self.char_state.hp.get() <= 0 {
+      return None;
+    }
+    // While drowning, the meter sits empty.
+    if self.suppress_air_meter {
+      return Some(0.0);
+    }
+    Some((self.air_remaining / self.full_air()).clamp(0.0, 1.0))
+  }
+
+  /// The game view, shaking for a moment after the player gets hurt.
+  fn camera(&self) -> Camera {
+    Camera::game_view(self.camera_pos, 1.0).with_shake(self.shake_offset)
+  }
+
+  fn map_camera(&self) -> Camera {
+    Camera::map_view(self.minimap.bounds(), self.map_shift_pos, self.map_zoom)
+  }
+
+  fn movement_readout(&self) -> MovementReadout {
+    MovementReadout {
+      frame:       self.frame_counter,
+      velocity:    self.player_vel,
+      state:       self.movement_state(),
+      grounded:    self.grounded_last_frame,
+      wall_left:   self.blocked_left_last_frame,
+      wall_right:  self.blocked_right_last_frame,
+      dashes_left: self.dashes_left,
+      double_jump: self.have_double_jump,
+      coyote_time: self.grounded_recently,
+    }
+  }
+
+  /// Puts the whole simulation back to a respawn from the given save, with no input held,
+  /// so that replays line up exactly.
+  fn restart_from(&mut self, save_data: &str, mut settings: Settings) {
+    settings.sanitize();
+    if settings.map_reveal_granularity != self.revealed_map.granularity() {
+      self.map_region_chunks = self.collision.region_chunks(settings.map_reveal_granularity);
+    }
+    self.quality.enabled = settings.auto_quality;
+    self.settings = settings;
+    // The save was exported by us, so there's nothing for it to fail on.
+    let _ = self.load_save_data(save_data);
+    // Replays and the like aren't the player coming back to their save.
+    self.showing_recap = None;
+    self.input = InputLayer::default();
+    self.sounds.drain();
+    self.game_events.drain();
+    self.keys_held.clear();
+    self.jump_hit = false;
+    self.dash_hit = false;
+    self.shoot_hit = false;
+    self.interact_hit = false;
+    self.retry_hit = false;
+    self.showing_map = false;
+    self.frame_counter = 0;
+    self.clock = GameClock::default();
+    self.rng = GameRng::default();
+    self.camera_focus_offset = Vec2::default();
+    self.peek_direction = 0.0;
+    self.peek_time = 0.0;
+    self.peek_offset = 0.0;
+    self.dashes_left = 0;
+    self.have_double_jump = false;
+    self.hanging_from = None;
+    self.ledge_regrab_time = 0.0;
+    self.dash_time = 0.0;
+    self.recently_blocked_to_left = 0.0;
+    self.recently_blocked_to_right = 0.0;
+    self.grounded_last_frame = false;
+    self.standing_on = None;
+    self.blocked_left_last_frame = false;
+    self.blocked_right_last_frame = false;
+    self.grounded_recently = 0.0;
+    self.touching_water = false;
+    self.submerged_in_water = false;
+    self.air_remaining = 0.0;
+    self.unaccounted_damage.set(0);
+    self.facing_right = true;
+    self.player_animation = PlayerAnimation::Idle;
+    self.animation_time = 0.0;
+    self.spring_launch_time = 0.0;
+    self.boost_time = 0.0;
+    self.boost_trail.clear();
+    self.player_stretch = 0.0;
+    self.shrink_time = 0.0;
+    self.last_trial_result = None;
+    self.lasers.clear();
+  }
+
+  pub fn respawn(&mut self) {
+    // A descent is over once the player dies, so there's nothing to respawn into.
+    if let Some(descent) = self.descent.take() {
+      self.restart_from(&descent.run.live_save_data, self.settings.clone());
+      return;
+    }
+    self.char_state = self.saved_char_state.clone();
+    self.char_events.revert_to_saved();
+    self.death = None;
+    self.damage_blink.set(0.0);
+    self.player_vel = Vec2::default();
+    self.hanging_from = None;
+    self.shrunken = false;
+    self.active_trial = None;
+    self.challenge_runs.clear();
+    self.chase = None;
+    self.dialogue = None;
+    self.interact_hold = InteractHold::Released;
+    self.particles.clear();
+    self.floaty_texts.clear();
+    self.status_effects.clear();
+    self.boost_time = 0.0;
+    self.boost_trail.clear();
+    if self.char_state.save_map != self.game_map.name {
+      let save_map = self.char_state.save_map.clone();
+      if let Err(e) = self.switch_map(&save_map) {
+        crate::log(&format!("Failed to load the save's map: {}", e));
+      }
+    }
+    self.reload_world(self.char_state.save_point);
+    // Dying right after saving shouldn't put the player back in whatever killed them, so
+    // they come back on the nearest safe ground, and can't be hurt for a moment.
+    let spawn_point = self.safe_respawn_point(self.char_state.save_point);
+    self.collision.set_position(&self.player_physics, spawn_point, true);
+    self.snap_camera(spawn_point);
+    self.last_safe_position = spawn_point;
+    self.spawn_time = SPAWN_ANIMATION_TIME;
+    self.respawn_immunity = RESPAWN_IMMUNITY_TIME;
+    self.touching_save_point = None;
+    self.save_exit_pulse = None;
+    self.spawn_particle_burst(spawn_point, 16, 4.0, "#8ff");
+  }
+
+  /// The nearest spot to `save_point` the player can stand without touching a hazard.
+  fn safe_respawn_point(&self, save_point: Vec2) -> Vec2 {
+    let lava_proof = self.char_state.power_ups.contains(&PowerUp::Lava);
+    self
+      .collision
+      .nearest_safe_standing(
+        save_point,
+        PLAYER_SIZE,
+        SAFE_RESPAWN_RADIUS,
+        |handle| match self.objects.get(&handle).map(|object| &object.data) {
+          Some(GameObjectData::Spike { .. } | GameObjectData::HazardSurface { .. }) => true,
+          Some(GameObjectData::Lava) => !lava_proof,
+          _ => false,
+        },
+      )
+  }
+
+  /// Rebuilds every object from the map, leaving out whatever char_state has collected,
+  /// and puts a fresh player body at `player_pos`.
+  fn reload_world(&mut self, player_pos: Vec2) {
+    self.objects = HashMap::new();
+    self.lod_pending_dt.clear();
+    self.splash_tracker.clear();
+    self.critters.clear();
+    self.player_room = None;
+    self.route_hint = None;
+    self.fragment_instances = 0;
+    self.placed_fragments.clear();
+    self.switch_channels.clear();
+    if let Some(draw_context) = &mut self.draw_context {
+      draw_context.tile_renderer.clear_fragments();
+    }
+    //let collision = Collision::from_game_map(&game_map);
+    self.collision = collision::CollisionWorld::new();
+    self.collision.load_game_map(&self.char_state, &self.game_map, &mut self.objects);
+    self.player_physics = self.collision.new_cuboid(
+      PhysicsKind::Sensor,
+      player_pos,
+      PLAYER_SIZE,
       0.25,
       false,
-      Some(InteractionGroups::new(
-        BASIC_GROUP,
-        WALLS_GROUP | PLAYER_GROUP,
-      )),
+      BASIC_INT_GROUPS,
     );
-    self.objects.insert(
-      physics_handle.collider,
-      GameObject {
-        physics_handle,
-        data: GameObjectData::Bee { lifespan: 12.0 },
+    // Only the starting map has a map screen, so only its regions can be revealed.
+    if self.on_start_map() {
+      self.map_region_chunks = self.collision.region_chunks(self.revealed_map.granularity());
+    }
+    // FIXME: This should maybe also run on the initial load.
+    self.redo_completed_interactions();
+  }
+
+  fn on_start_map(&self) -> bool {
+    self.game_map.name == START_MAP
+  }
+
+  /// Swaps in another map, along with everything drawn from it, without touching the world.
+  fn switch_map(&mut self, map_name: &str) -> Result<(), String> {
+    let game_map =
+      Rc::new(GameMap::from_resources(&self.resources, map_name).map_err(|e| e.to_string())?);
+    self.minimap = Minimap::from_game_map(&game_map);
+    if let Some(draw_context) = &mut self.draw_context {
+      draw_context.tile_renderer = TileRenderer::new(game_map.clone(), Vec2(2048.0, 1536.0));
+      let document = web_sys::window().unwrap().document().unwrap();
+      draw_context.minimap =
+        MinimapRenderer::new(&document, &self.minimap).map_err(|e| format!("{:?}", e))?;
+    }
+    self.apply_render_scale();
+    self.camera_bounds = CameraBounds::from_game_map(&game_map);
+    self.rooms = RoomMap::from_camera_bounds(&self.camera_bounds);
+    self.game_map = game_map;
+    Ok(())
+  }
+
+  /// Takes the player to the spawn named `spawn` in the map at the resource path `map_name`,
+  /// keeping all their progress. Doors lead here, and it's handy from the console for trying
+  /// out maps.
+  pub fn load_map(&mut self, map_name: &str, spawn: &str) -> Result<(), JsValue> {
+    if map_name != self.game_map.name {
+      self.switch_map(map_name).to_js_error()?;
+    }
+    self.reload_world(self.collision.spawn_point);
+    let spawn_point = match self.collision.named_spawns.get(spawn) {
+      Some(spawn_point) => *spawn_point,
+      None => {
+        crate::log(&format!("No spawn named {} in {}", spawn, map_name));
+        self.collision.spawn_point
+      }
+    };
+    self.collision.set_position(&self.player_physics, spawn_point, true);
+    if self.shrunken {
+      self.recreate_player_physics();
+    }
+    self.player_vel = Vec2::default();
+    self.hanging_from = None;
+    self.showing_map = false;
+    self.active_trial = None;
+    self.challenge_runs.clear();
+    self.chase = None;
+    self.dialogue = None;
+    self.interact_hold = InteractHold::Released;
+    self.particles.clear();
+    self.floaty_texts.clear();
+    self.touching_save_point = None;
+    self.save_exit_pulse = None;
+    self.snap_camera(spawn_point);
+    self.last_safe_position = spawn_point;
+    self.spawn_time = SPAWN_ANIMATION_TIME;
+    Ok(())
+  }
+
+  /// Whether the shrunken player has room above them to stand back up.
+  fn can_stand_up(&self) -> bool {
+    let stand_up_vector = Vec2(0.0, -(PLAYER_SIZE.1 - SHRUNKEN_SIZE.1));
+    // Check if the world is free right above us.
+    let stand_up_movement = self.collision.check_character_controller_movement(
+      1.0 / 60.0, // ficticious dt
+      &self.player_physics,
+      stand_up_vector,
+      false, // drop through platforms.
+    );
+    stand_up_movement.translation.y <= stand_up_vector.1 + 0.01
+  }
+
+  fn recreate_player_physics(&mut self) {
+    let old_player_position = self.collision.get_position(&self.player_physics);
+    self.collision.remove_object(self.player_physics.clone());
+    self.player_physics = self.collision.new_cuboid(
+      PhysicsKind::Sensor,
+      old_player_position.unwrap(),
+      match self.shrunken {
+        true => SHRUNKEN_SIZE,
+        false => PLAYER_SIZE,
       },
+      0.25,
+      false,
+      BASIC_INT_GROUPS,
     );
   }
 
-  fn create_floaty_text(&mut self, location: Option<Vec2>, text: String, color: String) {
+  fn create_bullet(&mut self, kind: ProjectileKind, location: Vec2, velocity: Vec2) {
+    // The player's own shots are theirs, so they pass through the player and only stop at walls.
+    let groups = match kind.is_players() {
+      true => InteractionGroups::new(PLAYER_GROUP, WALLS_GROUP),
+      false => InteractionGroups::new(BASIC_GROUP, WALLS_GROUP | PLAYER_GROUP),
+    };
     let physics_handle = self.collision.new_circle(
-      collision::PhysicsKind::Kinematic,
-      location.unwrap_or_else(|| self.collision.get_position(&self.player_physics).unwrap()),
-      0.25,
-      true,
-      Some(InteractionGroups::new(Group::NONE, Group::NONE)),
+      collision::PhysicsKind::Dynamic,
+      location,
+      kind.descriptor().radius,
+      false,
+      Some(groups),
     );
     // Set the velocity.
-    self.collision.set_velocity(&physics_handle, Vec2(0.0, -1.0));
+    self.collision.set_velocity(&physics_handle, velocity);
     self.objects.insert(
       physics_handle.collider,
       GameObject {
         physics_handle,
-        data: GameObjectData::FloatyText {
-          text,
-          color,
-          time_left: 2.0,
+        data: GameObjectData::Bullet {
+          kind,
+          velocity,
+          age: 0.0,
+          fading: None,
         },
       },
     );
   }
 
-  pub fn step(&mut self, dt: f32) -> Result<(), JsValue> {
-    if self.showing_map {
-      if self.keys_held.contains("ArrowUp") || self.keys_held.contains("w") {
-        self.map_shift_pos.1 -= 1.5 / self.map_zoom * dt;
-      }
-      if self.keys_held.contains("ArrowDown") || self.keys_held.contains("s") {
-        self.map_shift_pos.1 += 1.5 / self.map_zoom * dt;
+  /// Has each of the player's shots that's touching something damageable hit it, using up
+  /// the shot, and destroying the target if that was its last hp.
+  fn resolve_player_shots(&mut self) {
+    let shots: Vec<(ColliderHandle, Vec2, f32)> = self
+      .objects
+      .iter()
+      .filter_map(|(handle, object)| match object.data {
+        GameObjectData::Bullet { kind, .. } if kind.is_players() => Some((
+          *handle,
+          self.collision.get_position(&object.physics_handle)?,
+          kind.descriptor().radius,
+        )),
+        _ => None,
+      })
+      .collect();
+    let mut destroyed_hives = Vec::new();
+    for (shot, pos, radius) in shots {
+      let mut target = None;
+      // A little extra reach, so shots stopped against a stone still count as touching it.
+      self.collision.query_pipeline.intersections_with_shape(
+        &self.collision.rigid_body_set,
+        &self.collision.collider_set,
+        &Isometry::new(Vector2::new(pos.0, pos.1), 0.0),
+        &Ball::new(radius + 0.1),
+        QueryFilter::default(),
+        |handle| match self.objects.get(&handle) {
+          Some(object) if object.data.is_damageable() => {
+            target = Some(handle);
+            false
+          }
+          _ => true,
+        },
+      );
+      let Some(target) = target else {
+        continue;
+      };
+      if let Some(object) = self.objects.get_mut(&shot) {
+        object.data = GameObjectData::DeleteMe;
       }
-      if self.keys_held.contains("ArrowLeft") || self.keys_held.contains("a") {
-        self.map_shift_pos.0 -= 1.5 / self.map_zoom * dt;
+      let object = self.objects.get_mut(&target).unwrap();
+      if object.data.take_hit(PLAYER_SHOT_DAMAGE) {
+        if matches!(object.data, GameObjectData::BreakableBlock { .. }) {
+          self.break_block(target);
+          continue;
+        }
+        if let GameObjectData::Beehive { entity_id, .. } = object.data {
+          self.char_events.apply(&mut self.char_state, CharEvent::HiveDestroyed { entity_id });
+          destroyed_hives.push(entity_id);
+        }
+        object.data = GameObjectData::DeleteMe;
+        if let Some(location) = self.collision.get_position(&object.physics_handle) {
+          self.spawn_particle_burst(location, 12, 8.0, "#c80");
+        }
       }
-      if self.keys_held.contains("ArrowRight") || self.keys_held.contains("d") {
-        self.map_shift_pos.0 += 1.5 / self.map_zoom * dt;
+    }
+    self.remove_hive_bees(&destroyed_hives);
+  }
+
+  /// Breaks any breakable blocks the player is dashing into.
+  fn break_dashed_blocks(&mut self) {
+    if self.dash_time <= 0.0 {
+      return;
+    }
+    let Some((shape, pos)) = self.collision.get_shape_and_position(&self.player_physics) else {
+      return;
+    };
+    let reach = breakable::DASH_REACH * self.dash_direction;
+    let mut blocks = Vec::new();
+    self.collision.query_pipeline.intersections_with_shape(
+      &self.collision.rigid_body_set,
+      &self.collision.collider_set,
+      &Isometry::new(pos.translation.vector + Vector2::new(reach.0, reach.1), 0.0),
+      shape,
+      QueryFilter::default(),
+      |handle| {
+        if let Some(GameObjectData::BreakableBlock {
+          crumbling: None, ..
+        }) = self.objects.get(&handle).map(|object| &object.data)
+        {
+          blocks.push(handle);
+        }
+        true
+      },
+    );
+    for block in blocks {
+      self.break_block(block);
+    }
+  }
+
+  /// Opens any locked doors the player is up against, if they have keys of the doors' colors.
+  fn unlock_touched_doors(&mut self) {
+    let Some((shape, pos)) = self.collision.get_shape_and_position(&self.player_physics) else {
+      return;
+    };
+    let bounds = shape.compute_aabb(pos);
+    let reach = Cuboid::new(Vector2::new(
+      bounds.half_extents().x + keys::DOOR_REACH,
+      bounds.half_extents().y + keys::DOOR_REACH,
+    ));
+    let mut doors = Vec::new();
+    self.collision.query_pipeline.intersections_with_shape(
+      &self.collision.rigid_body_set,
+      &self.collision.collider_set,
+      pos,
+      &reach,
+      QueryFilter::default(),
+      |handle| {
+        if let Some(GameObjectData::LockedDoor {
+          entity_id,
+          color,
+          opening: None,
+        }) = self.objects.get(&handle).map(|object| &object.data)
+        {
+          doors.push((handle, *entity_id, color.clone()));
+        }
+        true
+      },
+    );
+    for (handle, entity_id, color) in doors {
+      if self.char_state.held_keys(&color) == 0 {
+        continue;
       }
-      if self.keys_held.contains("z") {
-        self.map_zoom *= 20.0f32.powf(dt);
+      self.char_events.apply(
+        &mut self.char_state,
+        CharEvent::DoorOpened { entity_id, color },
+      );
+      self.collision.collider_set[handle].set_enabled(false);
+      if let Some(GameObjectData::LockedDoor { opening, .. }) =
+        self.objects.get_mut(&handle).map(|object| &mut object.data)
+      {
+        *opening = Some(keys::DOOR_OPEN_TIME);
       }
-      if self.keys_held.contains("x") {
-        self.map_zoom /= 20.0f32.powf(dt);
+      self.sounds.emit(SoundEvent::DoorOpen);
+    }
+  }
+
+  /// Toggles the channels of the switches in `touched` that the player is hitting the way
+  /// they need to be hit, and hasn't been hitting already.
+  fn hit_switches(&mut self, touched: &[ColliderHandle]) {
+    let dashing = self.dash_time > 0.0;
+    for (handle, object) in self.objects.iter_mut() {
+      let GameObjectData::Switch {
+        channel,
+        hit_by,
+        pressed,
+        flash,
+      } = &mut object.data
+      else {
+        continue;
+      };
+      let hit = touched.contains(handle)
+        && match hit_by {
+          SwitchHit::Touch => true,
+          SwitchHit::Dash => dashing,
+        };
+      if hit && !*pressed {
+        self.switch_channels.toggle(channel);
+        *flash = switches::SWITCH_FLASH_TIME;
+        self.sounds.emit(SoundEvent::Switch);
       }
-      self.map_zoom = self.map_zoom.clamp(1.0, 10.0);
-      self.map_shift_pos.0 =
-        self.map_shift_pos.0.clamp(0.5 / self.map_zoom, 1.0 - 0.5 / self.map_zoom);
-      self.map_shift_pos.1 =
-        self.map_shift_pos.1.clamp(0.5 / self.map_zoom, 1.0 - 0.5 / self.map_zoom);
-      return Ok(());
+      *pressed = hit;
     }
+  }
 
-    self.int1_laser_time = (self.int1_laser_time - dt).max(0.0);
-    self.int2_laser_time = (self.int2_laser_time - dt).max(0.0);
+  /// Sets a breakable block crumbling, and leaves behind whatever it was holding. Breaking
+  /// it is saved like picking up a coin, so once the player saves it stays broken.
+  fn break_block(&mut self, handle: ColliderHandle) {
+    let Some(object) = self.objects.get_mut(&handle) else {
+      return;
+    };
+    let GameObjectData::BreakableBlock {
+      entity_id,
+      drops,
+      crumbling,
+      ..
+    } = &mut object.data
+    else {
+      return;
+    };
+    *crumbling = Some(breakable::CRUMBLE_TIME);
+    let (entity_id, drops) = (*entity_id, *drops);
+    let location = self.collision.get_position(&object.physics_handle);
+    // The pieces are only for show, so the player can go straight through.
+    self.collision.collider_set[handle].set_enabled(false);
+    self.char_events.apply(&mut self.char_state, CharEvent::BlockBroken { entity_id });
+    self.sounds.emit(SoundEvent::Crumble);
+    let Some(location) = location else {
+      return;
+    };
+    self.spawn_particle_burst(location, 10, 5.0, "#887");
+    if let Some(drop) = drops {
+      if !drop.is_collected(&self.char_state, entity_id) {
+        self.spawn_pickup(
+          PickupPhysics::Falling,
+          location,
+          breakable::DROP_RADIUS,
+          drop.pickup(entity_id),
+        );
+      }
+    }
+  }
+
+  /// Deals with the projectiles and bees left behind when the player moves into `new_room`,
+  /// as their kinds' room exit policies say. Anything already in the new room stays put.
+  fn apply_room_exit_policies(&mut self, new_room: Option<usize>) {
+    for object in self.objects.values_mut() {
+      let policy = match object.data {
+        GameObjectData::Bullet { kind, .. } => kind.descriptor().on_room_exit,
+        GameObjectData::Bee { .. } => BEE_ROOM_EXIT,
+        _ => continue,
+      };
+      let Some(pos) = self.collision.get_position(&object.physics_handle) else {
+        continue;
+      };
+      if self.rooms.room_at(pos) == new_room {
+        continue;
+      }
+      match (policy, &mut object.data) {
+        (RoomExitPolicy::Persist, _) => {}
+        (RoomExitPolicy::Despawn, _) => object.data = GameObjectData::DeleteMe,
+        (RoomExitPolicy::FadeOut, GameObjectData::Bullet { fading, .. }) => {
+          fading.get_or_insert(projectiles::FADE_OUT_TIME);
+        }
+        // Bees already fade out over the end of their lifespan.
+        (RoomExitPolicy::FadeOut, GameObjectData::Bee { lifespan, .. }) => {
+          *lifespan = lifespan.min(projectiles::FADE_OUT_TIME);
+        }
+        (RoomExitPolicy::FadeOut, _) => unreachable!(),
+      }
+    }
+  }
+
+  /// A destroyed hive's bees go with it.
+  fn remove_hive_bees(&mut self, hives: &[EntityId]) {
+    if hives.is_empty() {
+      return;
+    }
+    for object in self.objects.values_mut() {
+      if let GameObjectData::Bee { hive, .. } = &object.data {
+        if hives.contains(hive) {
+          object.data = GameObjectData::DeleteMe;
+        }
+      }
+    }
+  }
+
+  fn create_bee(&mut self, location: Vec2, velocity: Vec2, hive: EntityId) {
+    let physics_handle = self.collision.new_circle(
+      collision::PhysicsKind::Dynamic,
+      location,
+      0.25,
+      false,
+      Some(InteractionGroups::new(
+        BASIC_GROUP,
+        WALLS_GROUP | PLAYER_GROUP,
+      )),
+    );
+    self.objects.insert(
+      physics_handle.collider,
+      GameObject {
+        physics_handle,
+        data: GameObjectData::Bee {
+          lifespan: 12.0,
+          hive,
+        },
+      },
+    );
+  }
+
+  fn spawn_pickup(
+    &mut self,
+    physics: PickupPhysics,
+    location: Vec2,
+    radius: f32,
+    data: GameObjectData,
+  ) {
+    let physics_handle = self.collision.new_pickup(physics, location, radius);
+    if physics == PickupPhysics::Falling {
+      self.collision.set_velocity(&physics_handle, Vec2(0.0, -DROP_HOP_SPEED));
+    }
+    self.objects.insert(
+      physics_handle.collider,
+      GameObject {
+        physics_handle,
+        data,
+      },
+    );
+  }
+
+  /// Leaves a power-up in the world, as a reward for beating something. It drops to the
+  /// ground, so it can't be left hanging out of reach where a flying enemy died.
+  fn drop_power_up(&mut self, location: Vec2, power_up: PowerUp) {
+    if self.char_state.power_ups.contains(&power_up) {
+      return;
+    }
+    self.spawn_pickup(
+      PickupPhysics::Falling,
+      location,
+      0.45,
+      GameObjectData::PowerUp { power_up },
+    );
+  }
+
+  fn create_floaty_text(&mut self, location: Option<Vec2>, text: String, color: String) {
+    self.floaty_texts.push(FloatyText {
+      pos: location.unwrap_or_else(|| self.collision.get_position(&self.player_physics).unwrap()),
+      text,
+      color,
+      time_left: 2.0,
+    });
+  }
+
+  /// Like `step`, but given the timestamp (in the same clock as input events) of the
+  /// frame being stepped to, so buffered presses can take effect partway through the step.
+  pub fn step_at(&mut self, dt: f32, timestamp: f64) -> Result<(), JsValue> {
+    if self.replay_playback.is_some() || self.simulation_paused {
+      return Ok(());
+    }
+    if let Some(recorder) = &mut self.replay_recorder {
+      recorder.record_frame(dt, Some(timestamp));
+    }
+    self.simulate_at(dt, timestamp)
+  }
+
+  fn simulate_at(&mut self, dt: f32, timestamp: f64) -> Result<(), JsValue> {
+    let buffered = self.input.take_buffered_actions();
+    let Some(earliest) = buffered.iter().map(|b| b.timestamp).min_by(f64::total_cmp) else {
+      return self.simulate(dt);
+    };
+    // How long before the end of this step the earliest press came in.
+    let lead = (((timestamp - earliest) / 1000.0) as f32).clamp(0.0, dt);
+    if lead < MIN_SUB_STEP {
+      // The press came in right at the end, so it belongs to the next step.
+      self.simulate(dt)?;
+      buffered.iter().for_each(|b| self.apply_action(b.action));
+      return Ok(());
+    }
+    let remaining = match dt - lead >= MIN_SUB_STEP {
+      true => {
+        self.simulate(dt - lead)?;
+        lead
+      }
+      false => dt,
+    };
+    buffered.iter().for_each(|b| self.apply_action(b.action));
+    self.simulate(remaining)
+  }
+
+  pub fn step(&mut self, dt: f32) -> Result<(), JsValue> {
+    if self.replay_playback.is_some() || self.simulation_paused {
+      return Ok(());
+    }
+    if let Some(recorder) = &mut self.replay_recorder {
+      recorder.record_frame(dt, None);
+    }
+    self.simulate(dt)
+  }
+
+  fn simulate(&mut self, dt: f32) -> Result<(), JsValue> {
+    // Counted here rather than in step, so stepping with timestamps, one frame at a time, or
+    // through a replay all add up the same.
+    self.run_stats.play_time += dt;
+    let mut pauses = Vec::new();
+    if self.showing_map {
+      pauses.push(Pause::MapScreen);
+    }
+    if self.dialogue.is_some() {
+      pauses.push(Pause::Dialogue);
+    }
+    if self.death.as_ref().is_some_and(DeathSequence::is_hit_stopped) {
+      pauses.push(Pause::HitStop);
+    }
+    self.clock.tick(dt, &pauses);
+    let dt = self.clock.dt(Channel::Ui);
+    if let Some((_, time_left)) = &mut self.showing_recap {
+      *time_left -= dt;
+      if *time_left <= 0.0 {
+        self.showing_recap = None;
+      }
+    }
+    self.keys_held = self.input.effective_keys_held(&self.settings, self.showing_map);
+    if !self.showing_map {
+      let synthesized = self.input.synthesize_actions(&self.settings, dt, self.touching_water);
+      self.jump_hit |= synthesized.jump_hit;
+      self.dash_hit |= synthesized.dash_hit;
+    }
+
+    if self.showing_map {
+      if self.keys_held.contains("ArrowUp") || self.keys_held.contains("w") {
+        self.map_shift_pos.1 -= 1.5 / self.map_zoom * dt;
+      }
+      if self.keys_held.contains("ArrowDown") || self.keys_held.contains("s") {
+        self.map_shift_pos.1 += 1.5 / self.map_zoom * dt;
+      }
+      if self.keys_held.contains("ArrowLeft") || self.keys_held.contains("a") {
+        self.map_shift_pos.0 -= 1.5 / self.map_zoom * dt;
+      }
+      if self.keys_held.contains("ArrowRight") || self.keys_held.contains("d") {
+        self.map_shift_pos.0 += 1.5 / self.map_zoom * dt;
+      }
+      if self.keys_held.contains("z") {
+        self.map_zoom *= 20.0f32.powf(dt);
+      }
+      if self.keys_held.contains("x") {
+        self.map_zoom /= 20.0f32.powf(dt);
+      }
+      self.map_zoom = self.map_zoom.clamp(1.0, 10.0);
+      self.map_shift_pos.0 =
+        self.map_shift_pos.0.clamp(0.5 / self.map_zoom, 1.0 - 0.5 / self.map_zoom);
+      self.map_shift_pos.1 =
+        self.map_shift_pos.1.clamp(0.5 / self.map_zoom, 1.0 - 0.5 / self.map_zoom);
+      // The middle of the map screen is the cursor for picking where to fast travel to.
+      let target = match self.death {
+        None => fast_travel::pick(
+          self.discovered_save_points(),
+          &self.map_camera(),
+          Vec2(SCREEN_WIDTH / 2.0, SCREEN_HEIGHT / 2.0),
+        ),
+        Some(_) => None,
+      };
+      self.fast_travel.aim(target);
+      if std::mem::take(&mut self.interact_hit) {
+        if let Some(save_point) = self.fast_travel.press() {
+          self.travel_to_save_point(save_point);
+        }
+      }
+      return Ok(());
+    }
+    // Gameplay waits while a conversation is paged through.
+    if self.dialogue.is_some() {
+      self.update_dialogue();
+      return Ok(());
+    }
+    // Freeze everything for a moment when the player dies.
+    if let Some(death) = &mut self.death {
+      if death.is_hit_stopped() {
+        death.update(self.clock.dt(Channel::Always));
+        return Ok(());
+      }
+    }
+    // Everything from here on is gameplay.
+    let dt = self.clock.dt(Channel::Gameplay);
+    self.frame_counter += 1;
+
+    // Hold still while materializing.
+    if self.spawn_time > 0.0 {
+      self.spawn_time = (self.spawn_time - dt).max(0.0);
+      self.keys_held.clear();
+      self.jump_hit = false;
+      self.dash_hit = false;
+      self.shoot_hit = false;
+    }
+    if let Some((_, age)) = &mut self.save_exit_pulse {
+      *age += dt;
+      if *age >= SAVE_EXIT_PULSE_TIME {
+        self.save_exit_pulse = None;
+      }
+    }
+
+    for laser in &mut self.lasers {
+      laser.time_left -= dt;
+    }
+    self.lasers.retain(|laser| laser.time_left > 0.0);
+    if let Some(trial) = &mut self.active_trial {
+      trial.elapsed += dt;
+    }
+    if let Some(result) = &mut self.last_trial_result {
+      result.display_time -= dt;
+    }
+    if let Some(challenge) = self.daily_challenge.as_mut().filter(|c| c.result_code.is_none()) {
+      challenge.run.elapsed += dt;
+      let power_ups = &self.char_state.power_ups;
+      if self.save_repair_context.known_power_ups.iter().all(|p| power_ups.contains(p)) {
+        let power_ups: Vec<PowerUp> = power_ups.iter().copied().collect();
+        let code = challenge.finish(&power_ups, self.run_stats.deaths).to_string();
+        self.game_events.emit(GameEvent::DailyChallengeFinished {
+          date: challenge.date.clone(),
+          time: challenge.run.elapsed,
+          code,
+        });
+      }
+    }
+    self.update_descent(dt);
+    if self.retry_hit {
+      self.retry_hit = false;
+      self.retry_trial();
+    }
 
     //self.player_vel.1 += 1.0 * dt;
     // let (new_player_pos, collision_happened) = self.collision.try_move_rect(Rect {
@@ -671,24 +2934,57 @@ impl GameState {
 
     let player_pos = self.collision.get_position(&self.player_physics).unwrap();
     let player_y = player_pos.1;
+    if self.death.is_none() {
+      self.heatmap.update(dt, &self.game_map.name, player_pos);
+    }
 
-    let mrd = MAP_REVELATION_DISCRETIZATION;
-    let map_view_chunk = (
-      (player_pos.0 / mrd as f32).floor() as i32 * mrd,
-      (player_pos.1 / mrd as f32).floor() as i32 * MAP_REVELATION_DISCRETIZATION,
-    );
-    for dx in [-mrd, 0, mrd] {
-      for dy in [-mrd, 0, mrd] {
-        self.revealed_map.insert((map_view_chunk.0 + dx, map_view_chunk.1 + dy));
+    let room = self.rooms.room_at(player_pos);
+    if self.on_start_map() {
+      self.revealed_map.reveal_rect(
+        Vec2(
+          player_pos.0 - MAP_REVEAL_WALK_RADIUS,
+          player_pos.1 - MAP_REVEAL_WALK_RADIUS,
+        ),
+        Vec2(
+          player_pos.0 + MAP_REVEAL_WALK_RADIUS,
+          player_pos.1 + MAP_REVEAL_WALK_RADIUS,
+        ),
+      );
+      if let Some(room) = room {
+        self.visited_rooms.insert(room);
+      }
+    }
+    if room != self.player_room {
+      if self.player_room.is_some() {
+        self.apply_room_exit_policies(room);
       }
+      self.player_room = room;
     }
 
     let filter = QueryFilter::default();
 
     self.offered_interaction = None;
+    self.offered_dialogue = None;
+    self.locked_interaction = None;
+    self.offered_map_station = None;
+    self.offered_door = None;
     self.touching_water = false;
     self.submerged_in_water = false;
     let mut just_saved = false;
+    let mut touched_save_point = None;
+    let mut spring_launch = None;
+    let mut fatal_hazard = None;
+    let mut touched_trial_start = None;
+    let mut touched_trial_finish = None;
+    let mut touched_chase_start = None;
+    let mut touched_chase_end = None;
+    let mut touched_challenges = HashSet::new();
+    let mut touched_challenge_goal = None;
+    let mut touched_switches = Vec::new();
+    let mut pickup_bursts = Vec::new();
+    let mut destroyed_hives = Vec::new();
+    let mut power_up_drops = Vec::new();
+    let full_air = self.full_air();
     // Get the shape and pos of the player collider.
     if let Some((shape, pos)) = self.collision.get_shape_and_position(&self.player_physics) {
       self.collision.query_pipeline.intersections_with_shape(
@@ -704,24 +3000,60 @@ impl GameState {
             match object.data {
               GameObjectData::Coin { entity_id } => {
                 object.data = GameObjectData::DeleteMe;
-                self.char_state.coins.insert(entity_id);
+                self.char_events.apply(&mut self.char_state, CharEvent::Coin { entity_id });
+                self.sounds.emit(SoundEvent::Coin);
+                self.game_events.emit(GameEvent::CoinCollected {
+                  entity_id,
+                  rare: false,
+                });
+                pickup_bursts
+                  .extend(self.collision.get_position(&object.physics_handle).map(|p| (p, "#fd4")));
+              }
+              GameObjectData::Key {
+                entity_id,
+                ref color,
+              } => {
+                let color = color.clone();
+                object.data = GameObjectData::DeleteMe;
+                self.char_events.apply(&mut self.char_state, CharEvent::Key { entity_id, color });
+                self.sounds.emit(SoundEvent::Coin);
+                pickup_bursts
+                  .extend(self.collision.get_position(&object.physics_handle).map(|p| (p, "#fff")));
               }
               GameObjectData::RareCoin { entity_id } => {
                 object.data = GameObjectData::DeleteMe;
-                self.char_state.rare_coins.insert(entity_id);
+                self.char_events.apply(&mut self.char_state, CharEvent::RareCoin { entity_id });
+                self.sounds.emit(SoundEvent::Coin);
+                self.game_events.emit(GameEvent::CoinCollected {
+                  entity_id,
+                  rare: true,
+                });
+                pickup_bursts
+                  .extend(self.collision.get_position(&object.physics_handle).map(|p| (p, "#4df")));
               }
               GameObjectData::HpUp { entity_id } => {
                 object.data = GameObjectData::DeleteMe;
-                self.char_state.hp_ups.insert(entity_id);
-                self.char_state.reset_hp();
+                self.char_events.apply(&mut self.char_state, CharEvent::HpUp { entity_id });
+                self.game_events.emit(GameEvent::HpUpCollected { entity_id });
               }
               GameObjectData::PowerUp { .. } => {
                 match &object.data {
                   GameObjectData::PowerUp { power_up } => {
                     crate::log(&format!("Got power up: {:?}", power_up));
-                    self.char_state.power_ups.insert(power_up.clone());
+                    self.char_events.apply(
+                      &mut self.char_state,
+                      CharEvent::PowerUp {
+                        power_up: *power_up,
+                      },
+                    );
+                    self.game_events.emit(GameEvent::PowerUpObtained {
+                      power_up: *power_up,
+                    });
+                    self
+                      .game_events
+                      .emit(Split::PowerUp(*power_up).event(self.run_stats.play_time));
                     // If we got the water powerup, refresh air immediately.
-                    if power_up == "water" {
+                    if *power_up == PowerUp::Water {
                       self.air_remaining = HIGH_UNDERWATER_TIME;
                       self.suppress_air_meter = false;
                     }
@@ -730,7 +3062,14 @@ impl GameState {
                 }
                 object.data = GameObjectData::DeleteMe;
               }
-              GameObjectData::Spike => take_damage!(self, 2),
+              GameObjectData::Spike { damage } => take_damage!(self, damage),
+              GameObjectData::HazardSurface { effect } => self.status_effects.apply(effect),
+              GameObjectData::BoostRail { speed, duration } => {
+                self.boost_time = duration;
+                self.boost_speed = speed;
+              }
+              // The player's own shots go right through them.
+              GameObjectData::Bullet { kind, .. } if kind.is_players() => {}
               GameObjectData::Bullet { .. } => {
                 if self.char_state.hp.get() > 0 {
                   take_damage!(self, 1);
@@ -742,23 +3081,86 @@ impl GameState {
                   take_damage!(self, 1);
                 }
               }
+              // Dashing into a beehive knocks it down.
+              GameObjectData::Beehive { entity_id, .. } => {
+                if self.dash_time > 0.0 {
+                  object.data = GameObjectData::DeleteMe;
+                  self
+                    .char_events
+                    .apply(&mut self.char_state, CharEvent::HiveDestroyed { entity_id });
+                  destroyed_hives.push(entity_id);
+                  pickup_bursts.extend(
+                    self.collision.get_position(&object.physics_handle).map(|p| (p, "#c80")),
+                  );
+                }
+              }
+              GameObjectData::Enemy { ref enemy } => {
+                if self.dash_time > 0.0 {
+                  pickup_bursts.push((enemy.position, "#c33"));
+                  object.data = GameObjectData::DeleteMe;
+                } else if self.char_state.hp.get() > 0 {
+                  take_damage!(self, enemy.damage);
+                }
+              }
+              // The queen is hurt by dashes, and hurts the player on any other contact.
+              GameObjectData::BeeQueen { .. } => match &mut object.data {
+                GameObjectData::BeeQueen { queen } => {
+                  if self.dash_time > 0.0 {
+                    if queen.hit() {
+                      self
+                        .game_events
+                        .emit(Split::BossDefeated("bee_queen").event(self.run_stats.play_time));
+                      destroyed_hives.push(queen.entity_id);
+                      power_up_drops.push((queen.position, queen.drops));
+                      pickup_bursts.push((queen.position, "#fd4"));
+                      object.data = GameObjectData::DeleteMe;
+                    }
+                  } else if queen.invulnerable <= 0.0 && self.char_state.hp.get() > 0 {
+                    take_damage!(self, 2);
+                  }
+                }
+                _ => unreachable!(),
+              },
               GameObjectData::Water => {
                 self.touching_water = true;
               }
+              GameObjectData::AirBubble { .. } => match &mut object.data {
+                GameObjectData::AirBubble {
+                  respawn_time,
+                  cooldown,
+                } => {
+                  if *cooldown <= 0.0 && self.char_state.hp.get() > 0 {
+                    *cooldown = *respawn_time;
+                    self.air_remaining = full_air;
+                    self.suppress_air_meter = false;
+                    pickup_bursts.extend(
+                      self.collision.get_position(&object.physics_handle).map(|p| (p, "#aef")),
+                    );
+                  }
+                }
+                _ => unreachable!(),
+              },
               GameObjectData::Lava { .. } => {
-                if !self.char_state.power_ups.contains("lava") {
-                  take_damage!(self, 100);
+                if !self.char_state.power_ups.contains(&PowerUp::Lava) {
+                  fatal_hazard = Some(100);
                 }
               }
               GameObjectData::SavePoint => {
                 let save_point = &self.objects[&handle].physics_handle;
-                self.char_state.save_point =
-                  self.collision.get_position(save_point).unwrap() + Vec2(0.0, -1.0);
-                self.char_state.reset_hp();
+                touched_save_point = self.collision.get_position(save_point);
+                let save_point = self.collision.get_position(save_point).unwrap() + Vec2(0.0, -1.0);
+                self.char_events.apply(
+                  &mut self.char_state,
+                  CharEvent::Save {
+                    save_map: self.game_map.name.clone(),
+                    save_point,
+                  },
+                );
                 if self.char_state != self.saved_char_state {
                   just_saved = true;
                 }
                 self.saved_char_state = self.char_state.clone();
+                self.char_events.mark_saved();
               }
               // Let the player drop through platforms they're colliding with.
               // FIXME: Is there a better idiom here, maybe using @?
@@ -770,21 +3172,93 @@ impl GameState {
                 }
                 _ => unreachable!(),
               },
-              GameObjectData::Thwump { .. } => {
-                //take_damage!(self, 100);
+              // Only the leading face crushes, so the player can still ride on top.
+              GameObjectData::Thwump {
+                orientation,
+                half_size,
+                state: ThwumpState::Falling,
+                ..
+              } => {
+                let thwump_pos = self.collision.get_position(&object.physics_handle).unwrap();
+                if (player_pos - thwump_pos).dot(orientation) > half_size.1 {
+                  fatal_hazard = Some(thwump::CRUSH_DAMAGE);
+                }
+              }
+              GameObjectData::Thwump { .. } => {}
+              GameObjectData::Spring { .. } => match &mut object.data {
+                GameObjectData::Spring {
+                  direction,
+                  strength,
+                  squash,
+                } => {
+                  // Only fire when the player is moving into the spring, not when brushing past it.
+                  let approach = self.player_vel.0 * direction.0 + self.player_vel.1 * direction.1;
+                  if approach <= 0.0 && *squash <= 0.0 && self.char_state.hp.get() > 0 {
+                    *squash = SPRING_SQUASH_TIME;
+                    spring_launch = Some((*direction, *strength));
+                  }
+                }
+                _ => unreachable!(),
+              },
+              GameObjectData::Interaction {
+                interaction_number,
+                requires_powerup,
+                ref dialogue,
+              } => match requires_powerup {
+                Some(power_up) if !self.char_state.power_ups.contains(&power_up) => {
+                  self.locked_interaction = Some(power_up);
+                }
+                _ => {
+                  self.offered_interaction = Some(interaction_number);
+                  self.offered_dialogue = dialogue.clone();
+                }
+              },
+              GameObjectData::TrialStart {
+                ref trial,
+                medal_times,
+              } => {
+                let start_point = self.collision.get_position(&object.physics_handle).unwrap();
+                touched_trial_start = Some(TimeTrial::new(trial.clone(), start_point, medal_times));
+              }
+              GameObjectData::TrialFinish { ref trial } => {
+                touched_trial_finish = Some(trial.clone());
+              }
+              GameObjectData::ChaseStart { ref chase } => {
+                touched_chase_start = Some(chase.clone());
+              }
+              GameObjectData::ChaseEnd { ref chase } => {
+                touched_chase_end = Some(chase.clone());
+              }
+              GameObjectData::MapStation { ref region } => {
+                self.offered_map_station = Some(region.clone());
               }
-              GameObjectData::Interaction { interaction_number } => {
-                self.offered_interaction = Some(interaction_number);
+              GameObjectData::Door {
+                ref target_map,
+                ref target_spawn,
+              } => {
+                self.offered_door = Some((target_map.clone(), target_spawn.clone()));
               }
+              GameObjectData::ChallengeRegion { ref challenge } => {
+                touched_challenges.insert(challenge.clone());
+              }
+              GameObjectData::ChallengeGoal {
+                ref challenge,
+                reward,
+                entity_id,
+              } => {
+                touched_challenge_goal = Some((challenge.clone(), reward, entity_id));
+              }
+              GameObjectData::Switch { .. } => touched_switches.push(handle),
               GameObjectData::DestroyedDoor
-              | GameObjectData::Beehive { .. }
               | GameObjectData::VanishBlock { .. }
-              | GameObjectData::Stone
-              | GameObjectData::CoinWall { .. }
+              | GameObjectData::Stone { .. }
+              | GameObjectData::BreakableBlock { .. }
+              | GameObjectData::LockedDoor { .. }
+              | GameObjectData::ToggleBlock { .. }
+              | GameObjectData::CountGate { .. }
               | GameObjectData::Shooter1 { .. }
               | GameObjectData::TurnLaser { .. }
               | GameObjectData::MovingPlatform { .. }
-              | GameObjectData::FloatyText { .. }
               | GameObjectData::DeleteMe => {}
             }
           }
@@ -821,14 +3295,127 @@ impl GameState {
         );
       }
     }
+    // Leaving a save point sends a ripple out of it, to show it's been left in working order.
+    if let (Some(save_point), None) = (self.touching_save_point, touched_save_point) {
+      self.save_exit_pulse = Some((save_point, 0.0));
+    }
+    self.touching_save_point = touched_save_point;
     if just_saved {
+      self.sounds.emit(SoundEvent::Save);
+      self.game_events.emit(GameEvent::Saved {
+        save_map:   self.char_state.save_map.clone(),
+        save_point: self.char_state.save_point,
+      });
       self.create_floaty_text(None, "Saved!".to_string(), "yellow".to_string());
+      self.saved_recap = self.save_recap();
+    }
+    if let Some((direction, strength)) = spring_launch {
+      self.launch_from_spring(direction, strength);
+    }
+    self.hit_switches(&touched_switches);
+    let returned_to_safe_ground = match fatal_hazard {
+      Some(damage) => self.hit_fatal_hazard(damage),
+      None => false,
+    };
+    if self.char_state.hp.get() > 0 {
+      // Standing in the start zone keeps the timer armed at zero; it starts counting once we leave.
+      if let Some(trial) = touched_trial_start {
+        self.active_trial = Some(trial);
+      }
+      if let Some(finished) = touched_trial_finish {
+        if self.active_trial.as_ref().map(|t| t.name == finished) == Some(true) {
+          self.finish_trial();
+        }
+      }
+      // A chase only starts once, until the player escapes it or dies.
+      if let (Some(name), None) = (touched_chase_start, &self.chase) {
+        match self.collision.chase_paths.get(&name) {
+          Some(path) => self.chase = Some(Chase::new(name, path.clone())),
+          None => crate::log(&format!("No chase path named {}", name)),
+        }
+      }
+      if let Some(escaped) = touched_chase_end {
+        if self.chase.as_ref().map(|c| c.name == escaped) == Some(true) {
+          self.chase = None;
+        }
+      }
+      if let Some(chase) = &mut self.chase {
+        chase.update(dt);
+        let player_size = match self.shrunken {
+          true => SHRUNKEN_SIZE,
+          false => PLAYER_SIZE,
+        };
+        let player_rect = Rect::new(player_pos - player_size / 2.0, player_size);
+        if chase.hazard_rect().overlaps(player_rect) {
+          self.kill_player();
+        }
+      }
+    }
+    self.update_challenges(touched_challenges, touched_challenge_goal);
+    self.remove_hive_bees(&destroyed_hives);
+    for (location, color) in pickup_bursts {
+      self.spawn_particle_burst(location, 12, 8.0, color);
+    }
+    // The player and anything else that moves splashes going into or out of water.
+    let moving_objects = self.objects.iter().filter(|(_, object)| match object.data {
+      GameObjectData::Bullet { .. }
+      | GameObjectData::Bee { .. }
+      | GameObjectData::Enemy { .. }
+      | GameObjectData::Thwump { .. } => true,
+      _ => self.collision.is_dynamic(&object.physics_handle),
+    });
+    let splashes = self.splash_tracker.update(
+      &self.collision.water_surfaces,
+      std::iter::once((self.player_physics.collider, player_pos)).chain(moving_objects.filter_map(
+        |(handle, object)| {
+          Some((
+            *handle,
+            self.collision.get_position(&object.physics_handle)?,
+          ))
+        },
+      )),
+      dt,
+    );
+    if !splashes.is_empty() {
+      self.sounds.emit(SoundEvent::Splash);
+    }
+    for (location, speed) in splashes {
+      self.spawn_splash(location, speed);
+    }
+    for (location, power_up) in power_up_drops {
+      self.drop_power_up(location, power_up);
+    }
+    self.particles.update(dt);
+    self.spawn_wind_streaks(dt);
+    self.floaty_texts.retain_mut(|floaty_text| floaty_text.update(dt));
+    self.critters.update(
+      &self.collision.critter_zones,
+      Camera::game_view(self.camera_pos, 1.0).view_rect(),
+      self.settings.effects_scale(),
+    );
+    let mut focus_target = camera::focus_offset(&self.collision.camera_focuses, player_pos);
+    // Look ahead during a chase, since what's coming matters more than what's behind.
+    if let Some(chase) = &self.chase {
+      focus_target += chase.camera_lead();
+    }
+    self.camera_focus_offset +=
+      (1.0 - (-camera::FOCUS_BLEND_RATE * dt).exp()) * (focus_target - self.camera_focus_offset);
+
+    let water_movement =
+      self.touching_water && !self.char_state.power_ups.contains(&PowerUp::Water);
+
+    // Hazard surfaces keep hurting in ticks for as long as their effects last.
+    let tick_damage = self.status_effects.update(dt);
+    if tick_damage > 0 && self.char_state.hp.get() > 0 {
+      take_damage!(self, tick_damage);
     }
-    let water_movement = self.touching_water && !self.char_state.power_ups.contains("water");
 
     // Process damage blink.
     self.damage_blink.set(self.damage_blink.get() - dt);
+    self.respawn_immunity = (self.respawn_immunity - dt).max(0.0);
+    self.head_bump = (self.head_bump - dt).max(0.0);
     if let Some(amount) = self.queued_damage_text.get() {
+      self.sounds.emit(SoundEvent::Damage);
       self.create_floaty_text(None, format!("-{}", amount), "yellow".to_string());
       self.queued_damage_text.set(None);
     }
@@ -842,10 +3429,7 @@ impl GameState {
         self.suppress_air_meter = true;
       }
     } else {
-      self.air_remaining = match self.char_state.power_ups.contains("water") {
-        false => UNDERWATER_TIME,
-        true => HIGH_UNDERWATER_TIME,
-      };
+      self.air_remaining = self.full_air();
       self.suppress_air_meter = false;
     }
 
@@ -858,22 +3442,59 @@ impl GameState {
       _ => true,
     });
 
+    // Shots that hit something are used up here, before the update below sees them stop.
+    self.resolve_player_shots();
+    self.break_dashed_blocks();
+    self.unlock_touched_doors();
+
+    // Bees keep their distance from each other, so note where they all are first.
+    let bee_positions: Vec<Vec2> = self
+      .objects
+      .values()
+      .filter(|object| matches!(object.data, GameObjectData::Bee { .. }))
+      .filter_map(|object| self.collision.get_position(&object.physics_handle))
+      .collect();
+
     // Process object updates.
     let mut calls: Vec<Box<dyn FnMut(&mut Self)>> = Vec::new();
-    for object in self.objects.values_mut() {
+    let lod_view = Rect::new(
+      self.camera_pos,
+      (1.0 / TILE_SIZE) * Vec2(SCREEN_WIDTH, SCREEN_HEIGHT),
+    );
+    let player_bounds = self.collision.collider_bounds(self.player_physics.collider);
+    for (handle, object) in self.objects.iter_mut() {
+      // Far-off bees and bullets only update every few frames, catching up on the time they
+      // missed when they do.
+      let dt = match object.data {
+        GameObjectData::Bee { .. } | GameObjectData::Bullet { .. } => {
+          let pending = self.lod_pending_dt.entry(*handle).or_insert(0.0);
+          *pending += dt;
+          let pos = self.collision.get_position(&object.physics_handle).unwrap_or_default();
+          let phase = handle.into_raw_parts().0 as u64;
+          if !UpdateTier::for_position(lod_view, pos).updates_on(self.frame_counter, phase) {
+            continue;
+          }
+          std::mem::take(pending)
+        }
+        _ => dt,
+      };
       match &mut object.data {
         GameObjectData::Shooter1 {
           orientation,
           cooldown,
           shoot_period,
+          projectile,
+          ..
         } => {
-          cooldown.set(cooldown.get() - dt);
-          if cooldown.get() <= 0.0 {
-            cooldown.set(*shoot_period);
-            let velocity = 7.0 * *orientation;
+          *cooldown -= dt;
+          if *cooldown <= 0.0 {
+            *cooldown = *shoot_period;
+            let kind = *projectile;
+            let velocity = kind.launch_velocity(*orientation);
             let physics_handle = object.physics_handle.clone();
             calls.push(Box::new(move |this: &mut Self| {
               this.create_bullet(
+                kind,
                 this.collision.get_position(&physics_handle).unwrap(),
                 velocity,
               )
@@ -882,20 +3503,45 @@ impl GameState {
         }
         GameObjectData::Beehive {
           cooldown,
+          entity_id,
+          ..
         } => {
-          cooldown.set(cooldown.get() - dt);
-          if cooldown.get() <= 0.0 {
-            cooldown.set(2.0);
+          *cooldown -= dt;
+          if *cooldown <= 0.0 {
+            *cooldown = 2.0;
             let physics_handle = object.physics_handle.clone();
+            let hive = *entity_id;
             calls.push(Box::new(move |this: &mut Self| {
               this.create_bee(
                 this.collision.get_position(&physics_handle).unwrap() + Vec2(0.5, 0.5),
                 Vec2(0.0, 0.0),
+                hive,
               )
             }));
           }
         }
-        GameObjectData::Bee { lifespan } => {
+        GameObjectData::BeeQueen { queen } => {
+          let update = queen.update(dt, player_pos);
+          self.collision.set_position(&object.physics_handle, queen.position, false);
+          // Summoned bees count as the queen's, so they go when she does.
+          let (location, hive) = (queen.position, queen.entity_id);
+          let count = update.bees_to_summon;
+          if count > 0 {
+            calls.push(Box::new(move |this: &mut Self| {
+              for i in 0..count {
+                let angle = 2.0 * std::f32::consts::PI * i as f32 / count as f32;
+                this.create_bee(location, 3.0 * Vec2(angle.cos(), angle.sin()), hive);
+              }
+            }));
+          }
+        }
+        GameObjectData::Enemy { enemy } => {
+          enemy.update(dt, |origin, direction, distance| {
+            self.collision.ray_hits_wall(origin, direction, distance)
+          });
+          self.collision.set_position(&object.physics_handle, enemy.position, false);
+        }
+        GameObjectData::Bee { lifespan, .. } => {
           *lifespan -= dt;
           if *lifespan <= 0.0 {
             object.data = GameObjectData::DeleteMe;
@@ -916,17 +3562,80 @@ impl GameState {
             pos.1 = 6.5;
           }
           self.collision.set_position(&object.physics_handle, pos, false);
-          // Randomly adjust the velocity a bit.
-          let mut velocity = self.collision.get_velocity(&object.physics_handle).unwrap();
-          velocity.0 = (velocity.0 + dt.sqrt() * BEE_ACCEL * (rand::random::<f32>() - 0.5)).clamp(-BEE_TOP_SPEED, BEE_TOP_SPEED);
-          velocity.1 = (velocity.1 + dt.sqrt() * BEE_ACCEL * (rand::random::<f32>() - 0.5)).clamp(-BEE_TOP_SPEED, BEE_TOP_SPEED);
+          // Head for the player if they're close enough, with some jitter so swarms don't move in lockstep.
+          let mut accel = BEE_JITTER * Vec2(self.rng.next_f32() - 0.5, self.rng.next_f32() - 0.5);
+          let to_player = player_pos - pos;
+          if to_player.length() < BEE_SIGHT_RANGE {
+            accel += (BEE_ACCEL / to_player.length().max(0.01)) * to_player;
+          }
+          // Push away from any bees that are too close.
+          for other in &bee_positions {
+            let away = pos - *other;
+            let distance = away.length();
+            if distance > 0.001 && distance < BEE_SEPARATION_RADIUS {
+              let push =
+                BEE_SEPARATION_STRENGTH * BEE_ACCEL * (1.0 - distance / BEE_SEPARATION_RADIUS);
+              accel += (push / distance) * away;
+            }
+          }
+          if accel.length() > BEE_ACCEL {
+            accel = (BEE_ACCEL / accel.length()) * accel;
+          }
+          let mut velocity =
+            self.collision.get_velocity(&object.physics_handle).unwrap() + dt * accel;
+          if velocity.length() > BEE_TOP_SPEED {
+            velocity = (BEE_TOP_SPEED / velocity.length()) * velocity;
+          }
+          velocity += dt * wind::acceleration_at(&self.collision.wind_zones, pos);
           self.collision.set_velocity(&object.physics_handle, velocity);
         }
-        GameObjectData::Bullet { velocity } => {
-          // If the object's velocity has changed, delete it.
+        GameObjectData::Bullet {
+          kind,
+          velocity,
+          age,
+          fading,
+        } => {
+          let descriptor = kind.descriptor();
+          *age += dt;
+          if let Some(time_left) = fading {
+            *time_left -= dt;
+            if *time_left <= 0.0 {
+              object.data = GameObjectData::DeleteMe;
+              continue;
+            }
+          }
+          // If the object's velocity has changed, it hit something.
           let vel = self.collision.get_velocity(&object.physics_handle).unwrap();
-          if (vel - *velocity).length() > 0.01 {
-            object.data = GameObjectData::DeleteMe;
+          let end = match (vel - *velocity).length() > 0.01 {
+            true => Some(descriptor.on_impact),
+            false => match descriptor.fuse {
+              Some(fuse) if *age >= fuse => Some(descriptor.on_fuse),
+              _ => None,
+            },
+          };
+          match end {
+            Some(end) => {
+              let children = projectiles::end_children(end, *velocity);
+              object.data = GameObjectData::DeleteMe;
+              if !children.is_empty() {
+                let physics_handle = object.physics_handle.clone();
+                calls.push(Box::new(move |this: &mut Self| {
+                  let location = this.collision.get_position(&physics_handle).unwrap();
+                  for &(kind, velocity) in &children {
+                    this.create_bullet(kind, location, velocity);
+                  }
+                }));
+              }
+            }
+            None => {
+              let pos = self.collision.get_position(&object.physics_handle).unwrap();
+              let wind = wind::acceleration_at(&self.collision.wind_zones, pos);
+              if descriptor.gravity != 0.0 || wind != Vec2::default() {
+                velocity.1 += descriptor.gravity * dt;
+                *velocity += dt * wind;
+                self.collision.set_velocity(&object.physics_handle, *velocity);
+              }
+            }
           }
         }
         GameObjectData::Platform { currently_solid, y } => {
@@ -977,9 +3686,100 @@ impl GameState {
             }
           }
         }
-        GameObjectData::CoinWall { count } => {
-          if self.char_state.coins.len() as i32 >= *count {
-            crate::log(&format!("Deleting coin wall with {} coins", count));
+        GameObjectData::Thwump {
+          orientation,
+          half_size,
+          state,
+          home,
+        } => {
+          let pos = self.collision.get_position(&object.physics_handle).unwrap();
+          let player_pos = self.collision.get_position(&self.player_physics).unwrap();
+          match state {
+            ThwumpState::Idle => {
+              let clearance = thwump::clearance(
+                &self.collision,
+                &object.physics_handle,
+                pos,
+                *orientation,
+                *half_size,
+              );
+              if thwump::sees_player(pos, *orientation, *half_size, clearance, player_pos) {
+                *state = ThwumpState::Falling;
+              }
+            }
+            ThwumpState::Falling => {
+              let clearance = thwump::clearance(
+                &self.collision,
+                &object.physics_handle,
+                pos,
+                *orientation,
+                *half_size,
+              );
+              if clearance <= self.tuning.hazards.thwump_fall_speed * dt {
+                self.collision.set_position(
+                  &object.physics_handle,
+                  pos + clearance * *orientation,
+                  true,
+                );
+                *state = ThwumpState::Resting {
+                  time_left: self.tuning.hazards.thwump_rest_time,
+                };
+              } else {
+                self.collision.set_velocity(
+                  &object.physics_handle,
+                  self.tuning.hazards.thwump_fall_speed * *orientation,
+                );
+              }
+            }
+            ThwumpState::Resting { time_left } => {
+              *time_left -= dt;
+              if *time_left <= 0.0 {
+                *state = ThwumpState::Rising;
+              }
+            }
+            ThwumpState::Rising => {
+              let remaining = (*home - pos).length();
+              if remaining <= self.tuning.hazards.thwump_rise_speed * dt {
+                self.collision.set_position(&object.physics_handle, *home, true);
+                *state = ThwumpState::Idle;
+              } else {
+                self.collision.set_velocity(
+                  &object.physics_handle,
+                  (self.tuning.hazards.thwump_rise_speed / remaining) * (*home - pos),
+                );
+              }
+            }
+          }
+        }
+        // Dropped pickups are dynamic, and the world has no gravity of its own.
+        GameObjectData::Coin { .. }
+        | GameObjectData::RareCoin { .. }
+        | GameObjectData::HpUp { .. }
+        | GameObjectData::PowerUp { .. } => {
+          if self.collision.is_dynamic(&object.physics_handle) {
+            let velocity = self.collision.get_velocity(&object.physics_handle).unwrap();
+            self.collision.set_velocity(
+              &object.physics_handle,
+              velocity + Vec2(0.0, DROP_GRAVITY * dt),
+            );
+          }
+        }
+        GameObjectData::BreakableBlock {
+          crumbling: Some(time_left),
+          ..
+        }
+        | GameObjectData::LockedDoor {
+          opening: Some(time_left),
+          ..
+        } => {
+          *time_left -= dt;
+          if *time_left <= 0.0 {
+            object.data = GameObjectData::DeleteMe;
+          }
+        }
+        GameObjectData::CountGate { currency, count } => {
+          if currency.held(&self.char_state) as i32 >= *count {
+            crate::log(&format!("Opening gate needing {} {:?}", count, currency));
             object.data = GameObjectData::DeleteMe;
             let location = self.collision.get_position(&object.physics_handle).unwrap();
             calls.push(Box::new(move |this: &mut Self| {
@@ -1003,10 +3803,39 @@ impl GameState {
         GameObjectData::VanishBlock {
           vanish_timer,
           is_solid,
+          group: Some(group),
         } => {
-          // Check the distance to the player.
-          let block_pos = self.collision.get_position(&object.physics_handle).unwrap();
-          let distance = (player_pos - block_pos).length();
+          let pattern = match self.collision.vanish_groups.get(group) {
+            Some(pattern) => &pattern[..],
+            None => vanish::DEFAULT_VANISH_PATTERN,
+          };
+          // Look ahead by the fade time, so blocks are fully gone or back right on the beat.
+          let fade_step = dt / self.tuning.hazards.vanish_fade_time;
+          match vanish::is_solid_at(
+            pattern,
+            self.clock.time(Channel::Gameplay) + self.tuning.hazards.vanish_fade_time,
+          ) {
+            true => *vanish_timer = (*vanish_timer + fade_step).min(1.0),
+            false => *vanish_timer = (*vanish_timer - fade_step).max(0.0),
+          }
+          let collider = &mut self.collision.collider_set[object.physics_handle.collider];
+          if *vanish_timer <= 0.0 {
+            collider.set_enabled(false);
+            *is_solid = false;
+          }
+          if *vanish_timer >= 1.0 {
+            collider.set_enabled(true);
+            *is_solid = true;
+          }
+        }
+        GameObjectData::VanishBlock {
+          vanish_timer,
+          is_solid,
+          group: None,
+        } => {
+          // Check the distance to the player.
+          let block_pos = self.collision.get_position(&object.physics_handle).unwrap();
+          let distance = (player_pos - block_pos).length();
           if distance < 2.0 || (*is_solid && *vanish_timer < 1.0) {
             *vanish_timer = (*vanish_timer - dt * 1.2).max(0.0);
           } else {
@@ -1024,22 +3853,43 @@ impl GameState {
             *is_solid = true;
           }
         }
-        GameObjectData::FloatyText { time_left, .. } => {
-          *time_left -= dt;
-          if *time_left <= 0.0 {
-            object.data = GameObjectData::DeleteMe;
+        GameObjectData::ToggleBlock {
+          channel,
+          solid_when_on,
+          is_solid,
+        } => {
+          let solid = self.switch_channels.is_on(channel) == *solid_when_on;
+          // A block doesn't close up around the player; it waits until they're out of it.
+          let blocked = solid
+            && match (self.collision.collider_bounds(*handle), player_bounds) {
+              (Some(block), Some(player)) => block.overlaps(player),
+              _ => false,
+            };
+          if solid != *is_solid && !blocked {
+            self.collision.collider_set[*handle].set_enabled(solid);
+            *is_solid = solid;
           }
         }
+        GameObjectData::Switch { flash, .. } => {
+          *flash = (*flash - dt).max(0.0);
+        }
+        GameObjectData::Spring { squash, .. } => {
+          *squash = (*squash - dt).max(0.0);
+        }
+        GameObjectData::AirBubble { cooldown, .. } => {
+          *cooldown = (*cooldown - dt).max(0.0);
+        }
         _ => {}
       }
     }
+    self.lod_pending_dt.retain(|handle, _| self.objects.contains_key(handle));
     for mut f in calls {
       f(self);
     }
 
     // Don't do anything else if we're dead.
     if self.char_state.hp.get() <= 0 {
-      self.death_animation += dt;
+      self.update_death(dt);
       return Ok(());
     }
 
@@ -1060,12 +3910,19 @@ impl GameState {
     // if self.keys_held.contains("ArrowUp") {
     //   self.player_vel.1 -= 10.0;
     // }
-    let horizontal_decay_factor = match self.grounded_last_frame {
-      true => 0.5f32.powf(60.0 * dt),
-      false => 0.5f32.powf(5.0 * dt),
+    if self.hanging_from.is_some() {
+      self.update_ledge_hang(player_pos);
+    }
+    let boosted = self.boost_time > 0.0;
+    let grip = self.standing_on.map_or(1.0, Surface::grip);
+    let horizontal_decay_factor = match (self.grounded_last_frame, boosted) {
+      (true, _) => 0.5f32.powf(60.0 * grip * dt),
+      // Boosts carry the player's speed through jumps.
+      (false, true) => 1.0,
+      (false, false) => 0.5f32.powf(5.0 * dt),
     };
     let horizontal_dv = match self.grounded_last_frame {
-      true => 150.0,
+      true => 150.0 * grip,
       false => 25.0,
     } * match water_movement {
       true => 0.2,
@@ -1076,419 +3933,1623 @@ impl GameState {
     } else if self.player_vel.0 < 0.0 && self.dash_time <= 0.0 {
       self.player_vel.0 *= horizontal_decay_factor;
     }
-    if self.keys_held.contains("ArrowRight") || self.keys_held.contains("d") {
-      self.player_vel.0 += horizontal_dv * dt;
-    } else if self.player_vel.0 > 0.0 && self.dash_time <= 0.0 {
-      self.player_vel.0 *= horizontal_decay_factor;
+    if self.keys_held.contains("ArrowRight") || self.keys_held.contains("d") {
+      self.player_vel.0 += horizontal_dv * dt;
+    } else if self.player_vel.0 > 0.0 && self.dash_time <= 0.0 {
+      self.player_vel.0 *= horizontal_decay_factor;
+    }
+
+    if self.player_vel.1 < 0.0
+      && self.spring_launch_time <= 0.0
+      && !self.keys_held.contains("ArrowUp")
+      && !self.keys_held.contains("w")
+      && !self.keys_held.contains("z")
+    {
+      self.player_vel.1 *= 0.01f32.powf(dt);
+    }
+
+    let (mut max_horiz_speed, gravity_accel, terminal_velocity) = match water_movement {
+      true => (10.0, 20.0, 15.0),
+      false => (15.0, 60.0, 30.0),
+    };
+
+    max_horiz_speed *= match self.dash_time > 0.0 {
+      true => 2.0,
+      false => 1.0,
+    };
+    if boosted {
+      max_horiz_speed *= self.boost_speed;
+    }
+
+    self.player_vel.0 = self.player_vel.0.max(-max_horiz_speed).min(max_horiz_speed);
+    self.player_vel.1 = (self.player_vel.1 + gravity_accel * dt).min(terminal_velocity);
+    // Wind goes on after the clamps, so a strong enough gust can carry the player past top speed.
+    self.player_vel += dt * wind::acceleration_at(&self.collision.wind_zones, player_pos);
+    if self.dash_time > 0.0 {
+      self.player_vel = max_horiz_speed * self.dash_direction;
+    }
+    if self.hanging_from.is_some() {
+      self.player_vel = Vec2::default();
+    }
+    // Conveyor belts carry the player along, on top of their own movement.
+    let belt_speed = match self.grounded_last_frame {
+      true => self.standing_on.map_or(0.0, Surface::belt_speed),
+      false => 0.0,
+    };
+    let requested_motion = dt * (self.player_vel + Vec2(belt_speed, 0.0));
+    let effective_motion = self.collision.move_object_with_character_controller(
+      dt,
+      &self.player_physics,
+      requested_motion,
+      // drop through platforms
+      self.keys_held.contains("ArrowDown") || self.keys_held.contains("s"),
+    );
+    // For some reason effective_motion.grounded seems to always be false,
+    // so we instead consider ourselves grounded if we didn't move the full requested amount in y.
+    let grounded =
+      self.player_vel.1 > 0.0 && effective_motion.translation.y < dt * self.player_vel.1 * 0.95;
+    if grounded {
+      self.player_vel.1 = self.player_vel.1.min(0.0);
+    }
+    let blocked_to_left =
+      requested_motion.0 < 0.0 && effective_motion.translation.x > requested_motion.0 * 0.95;
+    let blocked_to_right =
+      requested_motion.0 > 0.0 && effective_motion.translation.x < requested_motion.0 * 0.95;
+    let blocked_to_top =
+      self.player_vel.1 < 0.0 && effective_motion.translation.y > dt * self.player_vel.1 * 0.95;
+    // Slick walls stop the player like any other, but can't be wall jumped off.
+    let moved_pos = player_pos
+      + Vec2(
+        effective_motion.translation.x,
+        effective_motion.translation.y,
+      );
+    let player_size = match self.shrunken {
+      true => SHRUNKEN_SIZE,
+      false => PLAYER_SIZE,
+    };
+    self.standing_on = match grounded {
+      true => self.collision.surface_below(moved_pos, player_size),
+      false => None,
+    };
+    if blocked_to_left {
+      if !self.collision.is_beside_slick_wall(moved_pos, player_size, -1.0) {
+        self.recently_blocked_to_left = WALL_JUMP_GRACE;
+      }
+      self.player_vel.0 = self.player_vel.0.max(0.0);
+    }
+    if blocked_to_right {
+      if !self.collision.is_beside_slick_wall(moved_pos, player_size, 1.0) {
+        self.recently_blocked_to_right = WALL_JUMP_GRACE;
+      }
+      self.player_vel.0 = self.player_vel.0.min(0.0);
+    }
+    if blocked_to_top {
+      self.player_vel.1 = self.player_vel.1.max(0.0);
+    }
+    // Falling while pushing against a wall grabs onto its top edge, if it's in reach.
+    let grab_side = match (blocked_to_left, blocked_to_right) {
+      (true, _) => Some(-1.0),
+      (_, true) => Some(1.0),
+      _ => None,
+    };
+    if let Some(side) = grab_side {
+      if self.hanging_from.is_none()
+        && !grounded
+        && !self.shrunken
+        && !water_movement
+        && self.player_vel.1 > 0.0
+        && self.dash_time <= 0.0
+        && self.ledge_regrab_time <= 0.0
+      {
+        if let Some(ledge) = self.collision.find_ledge(moved_pos, player_size, side, LEDGE_REACH) {
+          self.grab_ledge(ledge, moved_pos);
+        }
+      }
+    }
+    if grounded {
+      self.grounded_recently = JUMP_GRACE_PERIOD;
+      self.dashes_left = self.max_dashes();
+      self.have_double_jump = self.char_state.power_ups.contains(&PowerUp::DoubleJump);
+    }
+    // Allow wall jumps.
+    let wall_jump_allowed = self.char_state.power_ups.contains(&PowerUp::WallJump)
+      && (self.recently_blocked_to_left > 0.0 || self.recently_blocked_to_right > 0.0);
+    if !self.shrunken && self.jump_hit && (self.grounded_recently > 0.0 || wall_jump_allowed || self.have_double_jump) {
+      let abs_horizontal = self.player_vel.0.abs();
+      let jump_multiplier = match water_movement {
+        true => 0.5,
+        false => 1.0,
+      };
+      self.player_vel.1 = (-22.0 - 0.2 * abs_horizontal) * jump_multiplier;
+      self.sounds.emit(SoundEvent::Jump);
+      // Check if we're wall jumping for free.
+      if wall_jump_allowed && self.grounded_recently <= 0.0 {
+        let kick = self.tuning.movement.wall_jump_kick * max_horiz_speed;
+        self.player_vel.1 *= self.tuning.movement.wall_jump_lift;
+        if self.recently_blocked_to_left > 0.0 {
+          self.player_vel.0 = kick;
+        } else if self.recently_blocked_to_right > 0.0 {
+          self.player_vel.0 = -kick;
+        }
+      } else if self.grounded_recently <= 0.0 {
+        // Check if we're double jumping.
+        self.have_double_jump = false;
+      }
+      self.grounded_recently = 0.0;
+      self.recently_blocked_to_left = 0.0;
+      self.recently_blocked_to_right = 0.0;
+    }
+
+    if self.player_vel.0 > 0.1 {
+      self.facing_right = true;
+    } else if self.player_vel.0 < -0.1 {
+      self.facing_right = false;
+    }
+
+    let shocked = self.status_effects.has(StatusEffect::Shocked);
+    if shocked {
+      self.dash_time = 0.0;
+    }
+    if !self.shrunken && self.dash_hit && self.dashes_left > 0 && self.dash_time <= 0.0 && !shocked
+    {
+      // Perform a dash.
+      self.dashes_left -= 1;
+      self.dash_time = 0.3;
+      self.sounds.emit(SoundEvent::Dash);
+      self.dash_origin = player_pos;
+      self.dash_direction = self.aimed_dash_direction();
+      self.player_vel = 100.0 * self.dash_direction;
+    }
+    self.shoot_cooldown = (self.shoot_cooldown - dt).max(0.0);
+    if self.shoot_hit
+      && self.shoot_cooldown <= 0.0
+      && self.char_state.power_ups.contains(&PowerUp::Shoot)
+      && self.char_state.hp.get() > 0
+    {
+      self.shoot_cooldown = SHOOT_COOLDOWN;
+      let facing = match self.facing_right {
+        true => Vec2(1.0, 0.0),
+        false => Vec2(-1.0, 0.0),
+      };
+      let kind = ProjectileKind::PlayerShot;
+      self.create_bullet(
+        kind,
+        player_pos + 0.8 * facing,
+        kind.launch_velocity(facing),
+      );
+    }
+    // Check if the player is trying to use shrink.
+    if !self.shrunken
+      && grounded
+      && (self.keys_held.contains("ArrowDown") || self.keys_held.contains("s"))
+      && self.char_state.power_ups.contains(&PowerUp::Small)
+    {
+      self.shrink_time += dt;
+      if self.shrink_time > 0.25 {
+        self.shrunken = true;
+        self.recreate_player_physics();
+      }
+    } else {
+      self.shrink_time = 0.0;
+    }
+    if self.shrunken && (self.keys_held.contains("ArrowUp") || self.keys_held.contains("w")) {
+      if self.can_stand_up() {
+        self.collision.shift_object(
+          &self.player_physics,
+          Vec2(0.0, -(PLAYER_SIZE.1 - SHRUNKEN_SIZE.1) / 2.0),
+        );
+        self.shrunken = false;
+        self.recreate_player_physics();
+      } else if self.head_bump <= 0.0 {
+        self.head_bump = HEAD_BUMP_COOLDOWN;
+        self.sounds.emit(SoundEvent::HeadBump);
+        self.spawn_particle_burst(
+          player_pos - Vec2(0.0, SHRUNKEN_SIZE.1 / 2.0),
+          5,
+          2.0,
+          "#ddd",
+        );
+      }
+    }
+
+    match self.offered_interaction {
+      Some(interaction)
+        if self.offered_dialogue.is_none() && self.interaction_hold_time(interaction) > 0.0 =>
+      {
+        self.update_interact_hold(Some(interaction), dt);
+      }
+      Some(interaction) => {
+        if self.interact_hit {
+          self.interact_hit = false;
+          self.offered_interaction = None;
+          match self.offered_dialogue.take() {
+            Some(script) => self.start_dialogue(script, interaction),
+            None => self.apply_interaction(interaction),
+          }
+        }
+      }
+      None => self.update_interact_hold(None, dt),
+    }
+    if let Some(region) = self.offered_map_station.clone() {
+      if self.interact_hit {
+        self.interact_hit = false;
+        self.offered_map_station = None;
+        self.use_map_station(&region);
+      }
+    }
+    // Going through happens at the end of the step, once we're done with this map's world.
+    let mut door_taken = None;
+    if self.offered_door.is_some() && self.interact_hit {
+      self.interact_hit = false;
+      door_taken = self.offered_door.take();
+    }
+
+    // Lasers are instantly fatal.
+    let player_size = match self.shrunken {
+      true => SHRUNKEN_SIZE,
+      false => PLAYER_SIZE,
+    };
+    let player_rect = Rect::new(player_pos - player_size / 2.0, player_size);
+    if self.lasers.iter().any(|laser| laser.beam_rect().overlaps(player_rect)) {
+      take_damage!(self, 999999);
+    }
+
+    self.update_peek(dt, grounded, player_pos);
+    self.update_route_hint(dt, player_pos);
+    self.update_camera(dt, player_pos, grounded);
+
+    self.jump_hit = false;
+    self.dash_hit = false;
+    self.shoot_hit = false;
+    self.interact_hit = false;
+    // Somewhere to put the player back after a soft death, if they're standing safely.
+    if grounded && self.damage_blink.get() <= 0.0 && !self.touching_water && fatal_hazard.is_none()
+    {
+      self.last_safe_position = player_pos;
+    }
+    self.grounded_last_frame = grounded;
+    self.blocked_left_last_frame = blocked_to_left;
+    self.blocked_right_last_frame = blocked_to_right;
+    self.grounded_recently = (self.grounded_recently - dt).max(0.0);
+    self.recently_blocked_to_left = (self.recently_blocked_to_left - dt).max(0.0);
+    self.recently_blocked_to_right = (self.recently_blocked_to_right - dt).max(0.0);
+    self.dash_time = (self.dash_time - dt).max(0.0);
+    self.spring_launch_time = (self.spring_launch_time - dt).max(0.0);
+    self.ledge_regrab_time = (self.ledge_regrab_time - dt).max(0.0);
+    self.boost_time = (self.boost_time - dt).max(0.0);
+    // The trail follows the player while boosted, then shrinks away behind them.
+    if self.boost_time > 0.0 {
+      self.boost_trail.push(player_pos);
+    }
+    if self.boost_trail.len() > BOOST_TRAIL_LENGTH
+      || (self.boost_time <= 0.0 && !self.boost_trail.is_empty())
+    {
+      self.boost_trail.remove(0);
+    }
+    self.player_stretch = (self.player_stretch - 3.0 * dt).max(0.0);
+    self.update_player_animation(dt);
+    if returned_to_safe_ground {
+      self.return_to_safe_ground();
+    }
+    if let Some((target_map, target_spawn)) = door_taken {
+      self.load_map(&target_map, &target_spawn)?;
+    }
+    Ok(())
+  }
+
+  fn grab_ledge(&mut self, ledge: Ledge, player_pos: Vec2) {
+    // Hang with the top of the player's head level with the ledge.
+    let hang_y = ledge.top + PLAYER_SIZE.1 / 2.0;
+    self.collision.move_object_with_character_controller(
+      1.0 / 60.0, // ficticious dt
+      &self.player_physics,
+      Vec2(0.0, hang_y - player_pos.1),
+      false,
+    );
+    self.hanging_from = Some(ledge);
+    self.player_vel = Vec2::default();
+    self.facing_right = ledge.side > 0.0;
+    self.dashes_left = self.max_dashes();
+    self.have_double_jump = self.char_state.power_ups.contains(&PowerUp::DoubleJump);
+  }
+
+  /// While hanging from a ledge, up climbs onto it, down lets go, and jump or dash leave it
+  /// as they would from the ground.
+  fn update_ledge_hang(&mut self, player_pos: Vec2) {
+    let Some(ledge) = self.hanging_from else {
+      return;
+    };
+    let up = self.keys_held.contains("ArrowUp") || self.keys_held.contains("w");
+    let down = self.keys_held.contains("ArrowDown") || self.keys_held.contains("s");
+    // Check it's still there, in case it was a vanish block.
+    let still_there = self.collision.find_ledge(player_pos, PLAYER_SIZE, ledge.side, LEDGE_REACH);
+    if up && still_there.is_some() {
+      self.collision.set_position(&self.player_physics, ledge.climb_to, true);
+      self.hanging_from = None;
+      // Up is also a jump key, and we don't want to jump straight off the top.
+      self.jump_hit = false;
+      self.player_stretch = 1.0;
+    } else if down || still_there.is_none() {
+      self.hanging_from = None;
+      self.ledge_regrab_time = LEDGE_REGRAB_DELAY;
+    } else if self.jump_hit || self.dash_hit {
+      self.hanging_from = None;
+      self.grounded_recently = JUMP_GRACE_PERIOD;
+      self.ledge_regrab_time = LEDGE_REGRAB_DELAY;
+    }
+  }
+
+  /// Hurts the player for touching lava or getting crushed. With soft deaths on, this only
+  /// costs one heart, and returns true if they survived and should go back to safe ground.
+  fn hit_fatal_hazard(&mut self, damage: i32) -> bool {
+    if !self.settings.soft_deaths {
+      take_damage!(self, damage);
+      return false;
+    }
+    if self.damage_blink.get() > 0.0 || self.respawn_immunity > 0.0 || self.char_state.hp.get() <= 0
+    {
+      return false;
+    }
+    take_damage!(self, 1);
+    self.char_state.hp.get() > 0
+  }
+
+  /// Takes all of the player's HP at once. Unlike take_damage!, this goes through the blink
+  /// after a hit, so there's no getting away from a chase by being hurt just before it lands.
+  fn kill_player(&mut self) {
+    let hp = self.char_state.hp.get();
+    if hp <= 0 {
+      return;
+    }
+    self.char_events.apply(&mut self.char_state, CharEvent::Damage { amount: hp });
+    self.heatmap.record(HeatKind::Damage);
+    self.queued_damage_text.set(Some(hp));
+    self.unaccounted_damage.set(self.unaccounted_damage.get() + hp);
+    self.game_events.emit(GameEvent::Damaged {
+      amount: hp,
+      hp:     0,
+    });
+  }
+
+  fn return_to_safe_ground(&mut self) {
+    let position = self.last_safe_position;
+    self.collision.set_position(&self.player_physics, position, true);
+    self.player_vel = Vec2::default();
+    self.dash_time = 0.0;
+    self.spring_launch_time = 0.0;
+    self.snap_camera(position);
+    self.spawn_time = SPAWN_ANIMATION_TIME;
+    self.spawn_particle_burst(position, 16, 4.0, "#8ff");
+  }
+
+  fn update_player_animation(&mut self, dt: f32) {
+    let animation = PlayerAnimation::for_movement(self.movement_state(), self.player_vel.0);
+    match animation == self.player_animation {
+      true => self.animation_time += dt,
+      false => {
+        self.player_animation = animation;
+        self.animation_time = 0.0;
+      }
+    }
+  }
+
+  /// Pans the camera up or down once the player has stood still holding up or down for a moment.
+  fn update_peek(&mut self, dt: f32, grounded: bool, player_pos: Vec2) {
+    let up = self.keys_held.contains("ArrowUp") || self.keys_held.contains("w");
+    let down = self.keys_held.contains("ArrowDown") || self.keys_held.contains("s");
+    let direction = match (up, down) {
+      (true, false) => -1.0,
+      (false, true) => 1.0,
+      _ => 0.0,
+    };
+    let standing_still = grounded && self.player_vel.0.abs() < camera::PEEK_MAX_SPEED;
+    match standing_still && direction != 0.0 && direction == self.peek_direction {
+      true => self.peek_time += dt,
+      false => self.peek_time = 0.0,
+    }
+    self.peek_direction = direction;
+    let mut target = match self.peek_time >= camera::PEEK_DELAY {
+      true => direction * camera::PEEK_DISTANCE,
+      false => 0.0,
+    };
+    // Don't peek past the edge of the camera bounds.
+    if target != 0.0 {
+      let center = player_pos + self.camera_focus_offset;
+      target = self.camera_bounds.clamp_movement(center, center + Vec2(0.0, target)).1 - center.1;
+    }
+    self.peek_offset +=
+      (1.0 - (-self.tuning.camera.peek_blend_rate * dt).exp()) * (target - self.peek_offset);
+  }
+
+  /// Once the player has gone a long time without picking anything up, works out which
+  /// region the nearest power-up they can get to is in, to suggest heading there.
+  fn update_route_hint(&mut self, dt: f32, player_pos: Vec2) {
+    let char_state = &self.char_state;
+    let pickup_count = char_state.coins.len()
+      + char_state.rare_coins.len()
+      + char_state.hp_ups.len()
+      + char_state.power_ups.len();
+    if pickup_count != self.pickup_count {
+      self.pickup_count = pickup_count;
+      self.stuck_time = 0.0;
+      self.route_hint = None;
+    }
+    self.stuck_time += dt;
+    if !self.settings.route_hints
+      || self.stuck_time < progression::STUCK_TIME
+      || self.route_hint.is_some()
+    {
+      return;
+    }
+    self.route_hint = self.next_power_up_region(player_pos, &self.char_state.power_ups);
+  }
+
+  /// The region with the next power-up the player can reach from `from` with `power_ups`.
+  fn next_power_up_region(&self, from: Vec2, power_ups: &HashSet<PowerUp>) -> Option<String> {
+    let room = self.rooms.room_at(from)?;
+    // Bee queens count as where they drop their power-up.
+    let available: Vec<(PowerUp, Vec2)> = self
+      .objects
+      .values()
+      .filter_map(|object| match &object.data {
+        GameObjectData::PowerUp { power_up } => Some((
+          *power_up,
+          self.collision.get_position(&object.physics_handle)?,
+        )),
+        GameObjectData::BeeQueen { queen } => Some((queen.drops, queen.position)),
+        _ => None,
+      })
+      .collect();
+    let graph = ProgressionGraph::new(&self.rooms, &self.collision.gates);
+    let next = graph.next_power_up(&self.rooms, room, power_ups, &available);
+    next.and_then(|(_, pos)| self.collision.region_at(pos)).map(str::to_string)
+  }
+
+  /// Where the player is leaving off, as of just saving.
+  fn save_recap(&self) -> SaveRecap {
+    let char_state = &self.char_state;
+    let (region, next_goal) = match char_state.save_map == self.game_map.name {
+      true => (
+        self.collision.region_at(char_state.save_point).map(str::to_string),
+        self.next_power_up_region(char_state.save_point, &char_state.power_ups),
+      ),
+      false => (None, None),
+    };
+    // The saved history only goes back so far, so fall back to what the last recap said.
+    let latest_power_up = self
+      .char_events
+      .saved_history()
+      .iter()
+      .rev()
+      .find_map(|event| match event {
+        CharEvent::PowerUp { power_up } => Some(*power_up),
+        _ => None,
+      })
+      .or(self.saved_recap.latest_power_up);
+    SaveRecap {
+      region,
+      next_goal,
+      latest_power_up,
+      play_time: self.run_stats.play_time,
+    }
+  }
+
+  /// Starts the death sequence the first step after the player dies, bursting them into
+  /// particles and tossing their body, then moves the sequence along.
+  fn update_death(&mut self, dt: f32) {
+    let Some(death) = &mut self.death else {
+      let player_pos = self.collision.get_position(&self.player_physics).unwrap_or(Vec2(0.0, 0.0));
+      self.spawn_particle_burst(player_pos, 30, 12.0, "#f00");
+      let body = self.collision.new_cuboid(
+        PhysicsKind::Dynamic,
+        player_pos,
+        match self.shrunken {
+          true => SHRUNKEN_SIZE,
+          false => PLAYER_SIZE,
+        },
+        0.25,
+        false,
+        InteractionGroups::new(BASIC_GROUP, WALLS_GROUP),
+      );
+      // Thrown back the way the player was facing, tumbling over backwards.
+      let away = match self.facing_right {
+        true => -1.0,
+        false => 1.0,
+      };
+      self.collision.set_velocity(&body, Vec2(away * death::TOSS_SPEED.0, death::TOSS_SPEED.1));
+      self.collision.set_angular_velocity(&body, away * death::TOSS_SPIN);
+      self.death = Some(DeathSequence::new(body));
+      self.run_stats.deaths += 1;
+      self.heatmap.record(HeatKind::Death);
+      self.game_events.emit(GameEvent::Died);
+      if let Some(descent) = &mut self.descent {
+        let result = descent.finish(self.char_state.coins.len() as u32);
+        self.game_events.emit(GameEvent::DescentEnded {
+          seed:  result.seed,
+          depth: result.depth,
+          score: result.score,
+          code:  result.code.clone(),
+        });
+      }
+      return;
+    };
+    death.update(dt);
+    // The world has no gravity of its own, so the body gets it here.
+    if let Some(velocity) = self.collision.get_velocity(&death.body) {
+      self.collision.set_velocity(&death.body, velocity + Vec2(0.0, death::BODY_GRAVITY * dt));
+    }
+  }
+
+  /// Moves the descent's clock and depth along, and streams rooms in below the player and
+  /// out again once they're far enough above.
+  fn update_descent(&mut self, dt: f32) {
+    let Some(player_pos) = self.collision.get_position(&self.player_physics) else {
+      return;
+    };
+    // Taken out while rooms go in and out, which needs the rest of the game.
+    let Some(mut descent) = self.descent.take() else {
+      return;
+    };
+    if descent.result.is_none() {
+      descent.run.elapsed += dt;
+      descent.depth = descent.depth.max(player_pos.1 - descent.start_y);
+      while (descent.next_top as f32) < player_pos.1 + descent::LOOKAHEAD {
+        let (game_map, offset) = descent.next_room();
+        descent.rooms.push_back(PlacedRoom {
+          fragment: self.add_fragment(game_map, offset),
+          bottom:   descent.next_top,
+        });
+      }
+      while descent
+        .rooms
+        .front()
+        .is_some_and(|room| (room.bottom as f32) < player_pos.1 - descent::KEEP_BEHIND)
+      {
+        let room = descent.rooms.pop_front().unwrap();
+        self.remove_fragment(room.fragment);
+      }
+    }
+    self.descent = Some(descent);
+  }
+
+  /// Follows the player (plus any focus and peek), easing into wherever the camera bounds
+  /// push it.
+  fn update_camera(&mut self, dt: f32, player_pos: Vec2, grounded: bool) {
+    let facing = match self.facing_right {
+      true => 1.0,
+      false => -1.0,
+    };
+    self.camera_follow.update(
+      &self.tuning.camera,
+      dt,
+      player_pos,
+      self.player_vel,
+      facing,
+      grounded,
+    );
+    let center = self.camera_follow.center + self.camera_focus_offset + Vec2(0.0, self.peek_offset);
+    let size = (1.0 / TILE_SIZE) * Vec2(SCREEN_WIDTH, SCREEN_HEIGHT);
+    let view = Rect::new(
+      center - Vec2(size.0 / 2.0, size.1 / 2.0 + 50.0 / TILE_SIZE),
+      size,
+    );
+    let target = self.camera_bounds.constrain_view(view, player_pos).pos - view.pos;
+    self.camera_bounds_offset += (1.0 - (-self.tuning.camera.bounds_blend_rate * dt).exp())
+      * (target - self.camera_bounds_offset);
+    self.camera_pos = view.pos + self.camera_bounds_offset;
+  }
+
+  /// Starts the camera out on the player, wherever the bounds put it, rather than panning there.
+  fn snap_camera(&mut self, player_pos: Vec2) {
+    self.camera_follow.snap(player_pos);
+    self.update_camera(f32::INFINITY, player_pos, true);
+  }
+
+  fn finish_trial(&mut self) {
+    let trial = match self.active_trial.take() {
+      Some(trial) => trial,
+      None => return,
+    };
+    let time = trial.elapsed;
+    let previous_best = self.trial_best_times.get(&trial.name).copied();
+    let new_best = previous_best.map(|best| time < best).unwrap_or(true);
+    if new_best {
+      self.trial_best_times.insert(trial.name.clone(), time);
+    }
+    crate::log(&format!("Finished trial {} in {:.2}s", trial.name, time));
+    self.last_trial_result = Some(TrialResult {
+      medal: trial.medal_for(time),
+      trial,
+      time,
+      new_best,
+      display_time: time_trials::RESULT_DISPLAY_TIME,
+    });
+  }
+
+  /// Puts the player back at the start of the current (or most recently finished) trial.
+  fn retry_trial(&mut self) {
+    let trial = match (&self.active_trial, &self.last_trial_result) {
+      (Some(trial), _) => trial.clone(),
+      (None, Some(result)) => result.trial.clone(),
+      (None, None) => return,
+    };
+    if self.char_state.hp.get() <= 0 {
+      return;
+    }
+    self.collision.set_position(&self.player_physics, trial.start_point, true);
+    self.player_vel = Vec2::default();
+    self.dash_time = 0.0;
+    self.spring_launch_time = 0.0;
+    self.boost_time = 0.0;
+    self.boost_trail.clear();
+    self.last_trial_result = None;
+    self.active_trial = Some(TimeTrial::new(
+      trial.name,
+      trial.start_point,
+      trial.medal_times,
+    ));
+  }
+
+  fn update_challenges(
+    &mut self,
+    touched_challenges: HashSet<String>,
+    touched_goal: Option<(String, ChallengeReward, EntityId)>,
+  ) {
+    // Charge any damage taken since last frame to every room we were in.
+    let damage = self.unaccounted_damage.replace(0);
+    for run in self.challenge_runs.values_mut() {
+      run.damage_taken += damage;
+    }
+    // Leaving a room forfeits its run; re-entering starts a fresh one.
+    self.challenge_runs.retain(|name, _| touched_challenges.contains(name));
+    for name in touched_challenges {
+      if !self.char_state.completed_challenges.contains(&name) {
+        self.challenge_runs.entry(name).or_default();
+      }
+    }
+    let (name, reward, entity_id) = match touched_goal {
+      Some(goal) => goal,
+      None => return,
+    };
+    let flawless = self.challenge_runs.get(&name).map(|run| run.is_flawless()) == Some(true);
+    if !flawless || self.char_state.hp.get() <= 0 {
+      return;
+    }
+    self.challenge_runs.remove(&name);
+    self.char_events.apply(
+      &mut self.char_state,
+      CharEvent::Challenge { name: name.clone() },
+    );
+    if reward == ChallengeReward::RareCoin {
+      self.char_events.apply(&mut self.char_state, CharEvent::RareCoin { entity_id });
+      self.game_events.emit(GameEvent::CoinCollected {
+        entity_id,
+        rare: true,
+      });
+    }
+    crate::log(&format!("Completed challenge without damage: {}", name));
+    self.create_floaty_text(None, "Flawless!".to_string(), "#4df".to_string());
+  }
+
+  fn spawn_particle_burst(
+    &mut self,
+    location: Vec2,
+    count: usize,
+    speed: f32,
+    color: &'static str,
+  ) {
+    self.particles.burst(
+      location,
+      count,
+      speed,
+      color,
+      self.settings.effects_scale(),
+      self.quality.particle_budget(),
+    );
+  }
+
+  /// Splashes up from a water surface, bigger the faster whatever splashed was going.
+  fn spawn_splash(&mut self, location: Vec2, speed: f32) {
+    let speed = speed.min(MAX_SPLASH_SPEED);
+    self.particles.splash(
+      location,
+      (speed as usize).max(4),
+      0.5 * speed,
+      "#aef",
+      self.settings.effects_scale(),
+      self.quality.particle_budget(),
+    );
+  }
+
+  /// Gives off drifting streaks in the parts of wind zones that are on screen, so the wind
+  /// can be seen before it's felt.
+  fn spawn_wind_streaks(&mut self, dt: f32) {
+    let view = Camera::game_view(self.camera_pos, 1.0).view_rect();
+    let mut streaks = Vec::new();
+    for zone in &self.collision.wind_zones {
+      let Some(visible) = zone.visible_part(view) else {
+        continue;
+      };
+      let count = wind::streak_count(visible, dt, self.settings.effects_scale(), rand::random());
+      for _ in 0..count {
+        let origin = visible.pos
+          + Vec2(
+            rand::random::<f32>() * visible.size.0,
+            rand::random::<f32>() * visible.size.1,
+          );
+        streaks.push((origin, zone.streak_velocity()));
+      }
+    }
+    for (origin, vel) in streaks {
+      self.particles.streak(
+        origin,
+        vel,
+        WIND_STREAK_LIFE,
+        "#fff8",
+        self.quality.particle_budget(),
+      );
     }
+  }
 
-    if self.player_vel.1 < 0.0
-      && !self.keys_held.contains("ArrowUp")
-      && !self.keys_held.contains("w")
-      && !self.keys_held.contains("z")
-    {
-      self.player_vel.1 *= 0.01f32.powf(dt);
+  fn launch_from_spring(&mut self, direction: Vec2, strength: f32) {
+    // Only override the velocity components the spring actually pushes along,
+    // so a sideways spring keeps the player's vertical motion and vice versa.
+    if direction.0.abs() > 0.01 {
+      self.player_vel.0 = strength * direction.0;
+      self.dash_time = 0.0;
     }
+    if direction.1.abs() > 0.01 {
+      self.player_vel.1 = strength * direction.1;
+    }
+    self.spring_launch_time = SPRING_LAUNCH_TIME;
+    self.player_stretch = 1.0;
+    let player_pos = self.collision.get_position(&self.player_physics).unwrap();
+    self.spawn_particle_burst(player_pos, 10, 6.0, "#ddd");
+    // A launch is not ground: no coyote jump off the spring, and a jump pressed on the
+    // same frame is swallowed rather than spending the double jump.
+    self.grounded_recently = 0.0;
+    self.jump_hit = false;
+    self.have_double_jump = self.char_state.power_ups.contains(&PowerUp::DoubleJump);
+    self.dashes_left = self.max_dashes();
+  }
 
-    let (mut max_horiz_speed, gravity_accel, terminal_velocity) = match water_movement {
-      true => (10.0, 20.0, 15.0),
-      false => (15.0, 60.0, 30.0),
+  fn use_map_station(&mut self, region: &str) {
+    let Some(chunks) = self.map_region_chunks.get(region) else {
+      crate::log(&format!("No map_region rects for region: {}", region));
+      return;
     };
-
-    max_horiz_speed *= match self.dash_time > 0.0 {
-      true => 2.0,
-      false => 1.0,
+    let newly_revealed = self.revealed_map.reveal_chunks(chunks.iter().copied());
+    let text = match newly_revealed {
+      0 => "Already mapped",
+      _ => "Map updated!",
     };
+    self.create_floaty_text(None, text.to_string(), "#8cf".to_string());
+  }
 
-    self.player_vel.0 = self.player_vel.0.max(-max_horiz_speed).min(max_horiz_speed);
-    self.player_vel.1 = (self.player_vel.1 + gravity_accel * dt).min(terminal_velocity);
-    if self.dash_time > 0.0 {
-      self.player_vel.1 = 0.0;
+  fn click_interact(&mut self, world_pos: Vec2) {
+    let player_pos = self.collision.get_position(&self.player_physics).unwrap();
+    if (world_pos - player_pos).length() > CLICK_INTERACT_RANGE {
+      return;
     }
-    let effective_motion = self.collision.move_object_with_character_controller(
-      dt,
-      &self.player_physics,
-      dt * self.player_vel,
-      // drop through platforms
-      self.keys_held.contains("ArrowDown") || self.keys_held.contains("s"),
+    let mut clicked = None;
+    self.collision.query_pipeline.intersections_with_point(
+      &self.collision.rigid_body_set,
+      &self.collision.collider_set,
+      &Point::new(world_pos.0, world_pos.1),
+      QueryFilter::default(),
+      |handle| match self.objects.get(&handle).map(|object| &object.data) {
+        Some(
+          GameObjectData::Interaction { .. }
+          | GameObjectData::MapStation { .. }
+          | GameObjectData::Door { .. },
+        ) => {
+          clicked = Some(handle);
+          false
+        }
+        _ => true,
+      },
     );
-    // For some reason effective_motion.grounded seems to always be false,
-    // so we instead consider ourselves grounded if we didn't move the full requested amount in y.
-    let grounded =
-      self.player_vel.1 > 0.0 && effective_motion.translation.y < dt * self.player_vel.1 * 0.95;
-    if grounded {
-      self.player_vel.1 = self.player_vel.1.min(0.0);
-    }
-    let blocked_to_left =
-      self.player_vel.0 < 0.0 && effective_motion.translation.x > dt * self.player_vel.0 * 0.95;
-    let blocked_to_right =
-      self.player_vel.0 > 0.0 && effective_motion.translation.x < dt * self.player_vel.0 * 0.95;
-    let blocked_to_top =
-      self.player_vel.1 < 0.0 && effective_motion.translation.y > dt * self.player_vel.1 * 0.95;
-    if blocked_to_left {
-      self.recently_blocked_to_left = WALL_JUMP_GRACE;
-      self.player_vel.0 = self.player_vel.0.max(0.0);
-    }
-    if blocked_to_right {
-      self.recently_blocked_to_right = WALL_JUMP_GRACE;
-      self.player_vel.0 = self.player_vel.0.min(0.0);
-    }
-    if blocked_to_top {
-      self.player_vel.1 = self.player_vel.1.max(0.0);
-    }
-    if grounded {
-      self.grounded_recently = JUMP_GRACE_PERIOD;
-      self.have_dash = self.char_state.power_ups.contains("dash");
-      self.have_double_jump = self.char_state.power_ups.contains("double_jump");
+    match clicked.and_then(|handle| self.objects.get(&handle)).map(|object| &object.data) {
+      Some(GameObjectData::Interaction {
+        interaction_number,
+        requires_powerup,
+        dialogue,
+      }) if requires_powerup.iter().all(|p| self.char_state.power_ups.contains(p)) => {
+        match dialogue {
+          Some(script) => self.start_dialogue(script.clone(), *interaction_number),
+          // Ones that need E held can't be set off with a click, or holding would be moot.
+          None if self.interaction_hold_time(*interaction_number) > 0.0 => {}
+          None => self.apply_interaction(*interaction_number),
+        }
+      }
+      Some(GameObjectData::MapStation { region }) => self.use_map_station(&region.clone()),
+      Some(GameObjectData::Door {
+        target_map,
+        target_spawn,
+      }) => {
+        let (target_map, target_spawn) = (target_map.clone(), target_spawn.clone());
+        if let Err(e) = self.load_map(&target_map, &target_spawn) {
+          crate::log(&format!("Failed to go through door: {:?}", e));
+        }
+      }
+      _ => {}
     }
-    // Allow wall jumps.
-    let wall_jump_allowed = self.char_state.power_ups.contains("wall_jump")
-      && (self.recently_blocked_to_left > 0.0 || self.recently_blocked_to_right > 0.0);
-    if !self.shrunken && self.jump_hit && (self.grounded_recently > 0.0 || wall_jump_allowed || self.have_double_jump) {
-      let abs_horizontal = self.player_vel.0.abs();
-      let jump_multiplier = match water_movement {
-        true => 0.5,
-        false => 1.0,
+  }
+
+  /// The save points in parts of the map the player has revealed.
+  fn discovered_save_points(&self) -> impl Iterator<Item = Vec2> + '_ {
+    self
+      .collision
+      .save_points
+      .iter()
+      .copied()
+      .filter(|point| self.revealed_map.is_revealed(self.revealed_map.chunk_corner(*point)))
+  }
+
+  /// Everything marked on the map screen, in the parts of the map the player has revealed.
+  #[cfg(feature = "map-screen")]
+  fn map_markers(&self) -> Vec<(MapMarker, Vec2)> {
+    let mut markers = Vec::new();
+    for object in self.objects.values() {
+      let marker = match object.data {
+        GameObjectData::Coin { .. } if self.settings.map_coin_markers => MapMarker::Coin,
+        GameObjectData::RareCoin { .. } if self.settings.map_coin_markers => MapMarker::RareCoin,
+        GameObjectData::BeeQueen { .. } => MapMarker::Boss,
+        _ => continue,
       };
-      self.player_vel.1 = (-22.0 - 0.2 * abs_horizontal) * jump_multiplier;
-      // Check if we're wall jumping for free.
-      if wall_jump_allowed && self.grounded_recently <= 0.0 {
-        if self.recently_blocked_to_left > 0.0 {
-          self.player_vel.0 = max_horiz_speed;
-        } else if self.recently_blocked_to_right > 0.0 {
-          self.player_vel.0 = -max_horiz_speed;
+      if let Some(pos) = self.collision.get_position(&object.physics_handle) {
+        if self.revealed_map.is_revealed(self.revealed_map.chunk_corner(pos)) {
+          markers.push((marker, pos));
         }
-      } else if self.grounded_recently <= 0.0 {
-        // Check if we're double jumping.
-        self.have_double_jump = false;
       }
-      self.grounded_recently = 0.0;
-      self.recently_blocked_to_left = 0.0;
-      self.recently_blocked_to_right = 0.0;
     }
+    // Coins go first, so they're drawn under everything else.
+    markers.sort_by_key(|(marker, _)| *marker != MapMarker::Coin && *marker != MapMarker::RareCoin);
+    markers.extend(self.discovered_save_points().map(|pos| (MapMarker::SavePoint, pos)));
+    markers
+  }
 
-    if self.player_vel.0 > 0.1 {
-      self.facing_right = true;
-    } else if self.player_vel.0 < -0.1 {
-      self.facing_right = false;
+  /// Fast travels to the save point at `save_point`, closing the map screen.
+  fn travel_to_save_point(&mut self, save_point: Vec2) {
+    // Come out where saving there would have respawned the player.
+    let spawn_point = self.safe_respawn_point(save_point + Vec2(0.0, -1.0));
+    self.collision.set_position(&self.player_physics, spawn_point, true);
+    if self.shrunken {
+      self.shrunken = false;
+      self.recreate_player_physics();
     }
+    self.player_vel = Vec2::default();
+    self.hanging_from = None;
+    self.showing_map = false;
+    self.active_trial = None;
+    self.challenge_runs.clear();
+    self.chase = None;
+    self.touching_save_point = None;
+    self.save_exit_pulse = None;
+    self.snap_camera(spawn_point);
+    self.last_safe_position = spawn_point;
+    self.spawn_time = SPAWN_ANIMATION_TIME;
+    self.spawn_particle_burst(spawn_point, 16, 4.0, "#8ff");
+  }
 
-    if !self.shrunken && self.dash_hit && self.have_dash && self.dash_time <= 0.0 {
-      // Perform a dash.
-      self.have_dash = false;
-      self.dash_time = 0.3;
-      self.dash_origin = player_pos;
-      self.player_vel.0 = match self.facing_right {
-        true => 100.0,
-        false => -100.0,
-      };
-    }
-    // Check if the player is trying to use shrink.
-    if !self.shrunken
-      && grounded
-      && (self.keys_held.contains("ArrowDown") || self.keys_held.contains("s"))
-      && self.char_state.power_ups.contains("small")
+  fn toggle_map_pin(&mut self, screen_pos: Vec2) {
+    let camera = self.map_camera();
+    match self
+      .map_pins
+      .iter()
+      .position(|pin| (camera.world_to_screen(*pin) - screen_pos).length() < MAP_PIN_CLICK_RADIUS)
     {
-      self.shrink_time += dt;
-      if self.shrink_time > 0.25 {
-        self.shrunken = true;
-        self.recreate_player_physics();
+      Some(index) => {
+        self.map_pins.remove(index);
       }
-    } else {
-      self.shrink_time = 0.0;
-    }
-    if self.shrunken && (self.keys_held.contains("ArrowUp") || self.keys_held.contains("w")) {
-      let stand_up_vector = Vec2(0.0, -(PLAYER_SIZE.1 - SHRUNKEN_SIZE.1));
-      // Check if the world is free right above us.
-      let stand_up_movement = self.collision.check_character_controller_movement(
-        1.0/60.0, // ficticious dt
-        &self.player_physics,
-        stand_up_vector,
-        false, // drop through platforms.
-      );
-      if stand_up_movement.translation.y <= stand_up_vector.1 + 0.01 {
-        self.collision.shift_object(&self.player_physics, Vec2(
-          0.0,
-          -(PLAYER_SIZE.1 - SHRUNKEN_SIZE.1) / 2.0,
-        ));
-        self.shrunken = false;
-        self.recreate_player_physics();
-      } else {
-        self.damage_blink.set(0.35);
+      None if self.map_pins.len() < MAX_MAP_PINS => {
+        self.map_pins.push(camera.screen_to_world(screen_pos))
       }
+      None => {}
     }
+  }
 
-    if let Some(interaction) = self.offered_interaction {
-      if self.interact_hit {
-        self.interact_hit = false;
-        self.offered_interaction = None;
-        self.apply_interaction(interaction);
+  /// Picks what the compass should point at. There's no quest log, so this is
+  /// the nearest power-up that hasn't been picked up yet, in a straight line.
+  fn compass_target(
+    objects: &HashMap<ColliderHandle, GameObject>,
+    collision: &CollisionWorld,
+    from: Vec2,
+  ) -> Option<Vec2> {
+    objects
+      .values()
+      .filter(|object| matches!(object.data, GameObjectData::PowerUp { .. }))
+      .filter_map(|object| collision.get_position(&object.physics_handle))
+      .min_by(|a, b| (*a - from).length().total_cmp(&(*b - from).length()))
+  }
+
+  /// Moves a hold-to-interact along while E stays held at `interaction`, setting it off
+  /// once it's been held long enough, and cancelling it if E is let go or the player leaves.
+  fn update_interact_hold(&mut self, interaction: Option<i32>, dt: f32) {
+    let e_held = self.keys_held.contains("e");
+    self.interact_hold = match (self.interact_hold, interaction) {
+      (
+        InteractHold::Charging {
+          interaction: n,
+          held,
+        },
+        Some(current),
+      ) if e_held && n == current => {
+        let held = held + dt;
+        match held >= self.interaction_hold_time(current) {
+          true => {
+            self.offered_interaction = None;
+            self.apply_interaction(current);
+            InteractHold::Completed
+          }
+          false => InteractHold::Charging {
+            interaction: current,
+            held,
+          },
+        }
       }
-    }
+      (InteractHold::Charging { interaction: n, .. }, _) => {
+        self.game_events.emit(GameEvent::InteractionCancelled { number: n });
+        InteractHold::Released
+      }
+      (InteractHold::Completed, _) if e_held => InteractHold::Completed,
+      (_, Some(current)) if e_held => InteractHold::Charging {
+        interaction: current,
+        held:        dt,
+      },
+      _ => InteractHold::Released,
+    };
+  }
 
-    // If the laser is firing, and we're high enough up to get hit, take damage.
-    // FIXME: These are so hacky.
-    if self.int1_laser_time > 0.0 && player_y < 1070.0 / TILE_SIZE {
-      take_damage!(self, 999999);
-    }
-    if self.int2_laser_time > 0.0 && player_pos.0 > 40.0 && player_y > 93.5 {
-      take_damage!(self, 999999);
+  /// Opens the dialogue script named `script`, setting off `interaction` once it's been read.
+  fn start_dialogue(&mut self, script: String, interaction: i32) {
+    if self.dialogue_library.get(&script).is_none() {
+      crate::log(&format!("No dialogue named {}", script));
+      self.apply_interaction(interaction);
+      return;
     }
+    self.dialogue = Some(Dialogue::new(script, interaction));
+  }
 
+  /// Pages through the dialogue with E. Nothing else moves until it's over.
+  fn update_dialogue(&mut self) {
     self.jump_hit = false;
     self.dash_hit = false;
-    self.interact_hit = false;
-    self.grounded_last_frame = grounded;
-    self.grounded_recently = (self.grounded_recently - dt).max(0.0);
-    self.recently_blocked_to_left = (self.recently_blocked_to_left - dt).max(0.0);
-    self.recently_blocked_to_right = (self.recently_blocked_to_right - dt).max(0.0);
-    self.dash_time = (self.dash_time - dt).max(0.0);
-    Ok(())
+    self.shoot_hit = false;
+    self.retry_hit = false;
+    if !take(&mut self.interact_hit) {
+      return;
+    }
+    let Some(dialogue) = &mut self.dialogue else {
+      return;
+    };
+    if !dialogue.advance(&self.dialogue_library) {
+      let interaction = dialogue.interaction;
+      self.dialogue = None;
+      self.apply_interaction(interaction);
+    }
+  }
+
+  /// How long E has to be held to set off `interaction`, or zero if a tap does it. Firing a
+  /// laser is a big deal, so it shouldn't happen by accident.
+  fn interaction_hold_time(&self, interaction: i32) -> f32 {
+    let fires_laser = self.collision.interaction_scripts.get(&interaction).is_some_and(|actions| {
+      actions.iter().any(|action| matches!(action, TriggerAction::FireLaser { .. }))
+    });
+    match fires_laser {
+      true => INTERACT_HOLD_TIME,
+      false => 0.0,
+    }
   }
 
+  /// Runs the actions in `interaction`'s script, from its interact rect.
   pub fn apply_interaction(&mut self, interaction: i32) {
-    match interaction {
-      1 => {
-        if self.int1_laser_time <= 0.0 {
-          self.int1_laser_time = 0.8;
-          self.char_state.int1_completed = true;
-          self.interaction1_delete_stone();
+    // Interactions that only show dialogue.
+    if interaction == 0 {
+      return;
+    }
+    // An interaction can't go off again until its laser is done.
+    if self.lasers.iter().any(|laser| laser.interaction == interaction) {
+      return;
+    }
+    let actions = self.collision.interaction_scripts.get(&interaction).cloned().unwrap_or_default();
+    let mut finished_game = false;
+    for action in actions {
+      match action {
+        TriggerAction::FireLaser { origin, length } => {
+          self.lasers.push(Laser::new(origin, length, interaction));
+          self.sounds.emit(SoundEvent::Laser);
         }
-      }
-      2 => {
-        if self.int2_laser_time <= 0.0 {
-          self.int2_laser_time = 0.8;
-          self.char_state.int2_completed = true;
-          self.interaction2_delete_stone();
+        TriggerAction::SetFlag { flag } => {
+          finished_game |= flag == splits::GAME_FINISHED_FLAG;
+          self.char_events.apply(&mut self.char_state, CharEvent::Flag { name: flag });
+        }
+        TriggerAction::FindSecret { name } => {
+          self.char_events.apply(&mut self.char_state, CharEvent::Secret { name });
         }
+        action => self.apply_persistent_action(&action),
       }
-      3 => {}
-      _ => panic!("Unknown interaction: {}", interaction),
+    }
+    self.game_events.emit(GameEvent::InteractionTriggered {
+      number: interaction,
+    });
+    self.game_events.emit(Split::Interaction(interaction).event(self.run_stats.play_time));
+    if finished_game {
+      self.game_events.emit(Split::GameFinished.event(self.run_stats.play_time));
     }
   }
 
-  pub fn interaction1_delete_stone(&mut self) {
-    for object in self.objects.values_mut() {
-      match &mut object.data {
-        GameObjectData::Stone => {
-          let min_x = 17.0;
-          let max_x = 27.0;
-          let min_y = 28.0;
-          let max_y = 38.0;
-          let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
-          if pos.0 >= min_x && pos.0 <= max_x && pos.1 >= min_y && pos.1 <= max_y {
-            object.data = GameObjectData::DeleteMe;
-          }
-        }
-        _ => {}
-      }
+  /// Redoes the deletes and spawns of every interaction whose flags are already set, after
+  /// the world's been rebuilt from the map.
+  fn redo_completed_interactions(&mut self) {
+    let completed: Vec<TriggerAction> = self
+      .collision
+      .interaction_scripts
+      .values()
+      .filter(|actions| {
+        actions.iter().any(|action| {
+          matches!(action, TriggerAction::SetFlag { flag } if self.char_state.flags.contains(flag))
+        })
+      })
+      .flatten()
+      .filter(|action| action.is_persistent())
+      .cloned()
+      .collect();
+    for action in &completed {
+      self.apply_persistent_action(action);
     }
   }
 
-  pub fn interaction2_delete_stone(&mut self) {
-    for object in self.objects.values_mut() {
-      match &mut object.data {
-        GameObjectData::Stone => {
-          let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
-          if pos.1 >= 90.0 {
+  fn apply_persistent_action(&mut self, action: &TriggerAction) {
+    match action {
+      TriggerAction::DeleteObjects { kind, rect } => {
+        for object in self.objects.values_mut() {
+          if inspector::kind_name(&object.data) != *kind {
+            continue;
+          }
+          let pos = self.collision.get_position(&object.physics_handle).unwrap_or_default();
+          if rect.contains_point(pos) {
             object.data = GameObjectData::DeleteMe;
           }
         }
-        _ => {}
       }
+      TriggerAction::SpawnObject {
+        kind: SpawnKind::Stone,
+        rect,
+      } => {
+        let physics_handle = self.collision.new_cuboid(
+          PhysicsKind::Static,
+          rect.pos + rect.size / 2.0,
+          rect.size,
+          0.05,
+          false,
+          WALLS_INT_GROUPS,
+        );
+        self.objects.insert(
+          physics_handle.collider,
+          GameObject {
+            physics_handle,
+            data: GameObjectData::Stone { hp: None },
+          },
+        );
+      }
+      TriggerAction::FireLaser { .. }
+      | TriggerAction::SetFlag { .. }
+      | TriggerAction::FindSecret { .. } => {}
+    }
+  }
+
+  fn player_status(&self) -> PlayerStatus {
+    PlayerStatus {
+      hp:          self.char_state.hp.get(),
+      max_hp:      self.char_state.hp_ups.len() as i32 + 1,
+      coins:       self.char_state.coins.len(),
+      rare_coins:  self.char_state.rare_coins.len(),
+      keys:        self.char_state.held_key_counts(),
+      power_ups:   PowerUp::iter().filter(|p| self.char_state.power_ups.contains(p)).collect(),
+      dashes_left: self.dashes_left,
+      max_dashes:  self.max_dashes(),
     }
   }
 
   // FIXME: I don't remember what this return value is supposed to signify.
   pub fn draw_frame(&mut self) -> Result<bool, JsValue> {
-    let DrawContext {
-      canvases,
-      contexts,
+    let show_readout =
+      self.debug_mode || self.settings.show_movement_readout || self.replay_playback.is_some();
+    let movement_readout = match show_readout {
+      true => Some(self.movement_readout()),
+      false => None,
+    };
+    let air_fraction = self.air_fraction();
+    // Animations go by gameplay time, so they hold still along with everything else.
+    let game_time = self.clock.time(Channel::Gameplay);
+    #[cfg(feature = "map-screen")]
+    let map_camera = self.map_camera();
+    let status = self.player_status();
+    let hold_time = self.offered_interaction.map_or(0.0, |n| self.interaction_hold_time(n));
+    let ceiling_clear = self.shrunken && self.can_stand_up();
+    #[cfg(feature = "map-screen")]
+    let map_markers = match self.showing_map {
+      true => self.map_markers(),
+      false => Vec::new(),
+    };
+    let Some(DrawContext {
+      layers,
       images,
+      hd_images,
       tile_renderer,
-    } = &mut self.draw_context;
+      #[cfg(feature = "map-screen")]
+      minimap,
+      ..
+    }) = &mut self.draw_context
+    else {
+      return Ok(false);
+    };
+
+    layers[LayerId::Ui].clear();
+    lighting::begin(&layers[LayerId::Lighting]);
 
+    #[cfg(feature = "map-screen")]
     if self.showing_map {
-      let image = &images[&ImageResource::MapSmall];
       // Fill the main layer with red.
-      contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#334"));
-      contexts[MAIN_LAYER].fill_rect(0.0, 0.0, SCREEN_WIDTH as f64, SCREEN_HEIGHT as f64);
-
-      // There are three coordinate spaces:
-      // *) world space coordinates (ranging from like -300 to +300)
-      // *) uv coordinates in the map image (ranging from 0 to 1)
-      // *) screen coordinates (ranging from 0 to 1000ish)
-
-      let map_size = (image.width() as f32, image.height() as f32);
-      let map_bounds = ((-168, -120), (240, 160));
+      layers[LayerId::Main].set_fill_style(&JsValue::from_str("#334"));
+      layers[LayerId::Main].fill_rect(0.0, 0.0, SCREEN_WIDTH as f64, SCREEN_HEIGHT as f64);
 
-      let world_to_map_uv = |(world_x, world_y): (f32, f32)| {
-        let uv_x = (world_x - map_bounds.0 .0 as f32) / (map_bounds.1 .0 - map_bounds.0 .0) as f32;
-        let uv_y = (world_y - map_bounds.0 .1 as f32) / (map_bounds.1 .1 - map_bounds.0 .1) as f32;
-        (uv_x, uv_y)
-      };
-      let map_uv_to_screen = |(uv_x, uv_y): (f32, f32)| {
-        // Compute offsets from the center of the screen.
-        let dx = self.map_zoom * (uv_x - self.map_shift_pos.0) * SCREEN_WIDTH as f32;
-        let dy = self.map_zoom * (uv_y - self.map_shift_pos.1) * SCREEN_HEIGHT as f32;
-        let screen_x = SCREEN_WIDTH as f32 / 2.0 + dx;
-        let screen_y = SCREEN_HEIGHT as f32 / 2.0 + dy;
-        (screen_x as f64, screen_y as f64)
-      };
-      let world_delta_to_screen_factor = (
-        self.map_zoom * SCREEN_WIDTH / (map_bounds.1 .0 - map_bounds.0 .0) as f32,
-        self.map_zoom * SCREEN_HEIGHT / (map_bounds.1 .1 - map_bounds.0 .1) as f32,
+      // The minimap covers its bounds in world space, and the map camera maps that to the
+      // screen, given how the map screen is panned and zoomed.
+      layers.set_camera(map_camera);
+      let map_bounds = self.minimap.tile_bounds;
+      minimap.draw(
+        &layers[LayerId::Main],
+        map_camera.rect_to_screen(self.minimap.bounds()),
       );
-
-      // Copy over from the map image.
-      let screen_top_left = map_uv_to_screen((0.0, 0.0));
-      let screen_bottom_right = map_uv_to_screen((1.0, 1.0));
-      // We set the context to use nearest neighbor scaling.
-      contexts[MAIN_LAYER].set_image_smoothing_enabled(false);
-      contexts[MAIN_LAYER]
-        .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
-          image,
-          0.0,
-          0.0,
-          map_size.0 as f64,
-          map_size.1 as f64,
-          screen_top_left.0,
-          screen_top_left.1,
-          screen_bottom_right.0 - screen_top_left.0,
-          screen_bottom_right.1 - screen_top_left.1,
-        )
-        .unwrap();
-      contexts[MAIN_LAYER].set_image_smoothing_enabled(true);
-      // Black out everything that's not revealed.
-      contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#000"));
-      contexts[MAIN_LAYER].set_stroke_style(&JsValue::from_str("#444"));
-      contexts[MAIN_LAYER].set_line_width(1.0);
-      let mut chunk_y = map_bounds.0 .1;
+      // Cover up everything that's not revealed.
+      let granularity = self.revealed_map.granularity();
+      let chunk_screen_size =
+        map_camera.size_to_screen(Vec2(granularity as f32, granularity as f32));
+      layers[LayerId::Main].set_line_width(1.0);
+      let first_chunk =
+        self.revealed_map.chunk_corner(Vec2(map_bounds.0 .0 as f32, map_bounds.0 .1 as f32));
+      let mut chunk_y = first_chunk.1;
       while chunk_y < map_bounds.1 .1 {
-        let mut chunk_x = map_bounds.0 .0;
+        let mut chunk_x = first_chunk.0;
         while chunk_x < map_bounds.1 .0 {
-          if !self.revealed_map.contains(&(chunk_x, chunk_y)) {
-            let screen_pos = map_uv_to_screen(world_to_map_uv((chunk_x as f32, chunk_y as f32)));
-            contexts[MAIN_LAYER].begin_path();
-            contexts[MAIN_LAYER].rect(
-              screen_pos.0,
-              screen_pos.1,
-              world_delta_to_screen_factor.0 as f64 * MAP_REVELATION_DISCRETIZATION as f64,
-              world_delta_to_screen_factor.1 as f64 * MAP_REVELATION_DISCRETIZATION as f64,
+          if !self.revealed_map.is_revealed((chunk_x, chunk_y)) {
+            let screen_pos = map_camera.world_to_screen(Vec2(chunk_x as f32, chunk_y as f32));
+            map_reveal::draw_fog_chunk(
+              &layers[LayerId::Main],
+              self.settings.fog_style,
+              &self.revealed_map,
+              (chunk_x, chunk_y),
+              (screen_pos.0 as f64, screen_pos.1 as f64),
+              (chunk_screen_size.0 as f64, chunk_screen_size.1 as f64),
             );
-            contexts[MAIN_LAYER].fill();
-            contexts[MAIN_LAYER].stroke();
           }
-          chunk_x += MAP_REVELATION_DISCRETIZATION;
+          chunk_x += granularity;
         }
-        chunk_y += MAP_REVELATION_DISCRETIZATION;
+        chunk_y += granularity;
+      }
+      // Outline the rooms over the fog, so visited ones stand out from merely revealed chunks.
+      self.rooms.draw(
+        &layers[LayerId::Main],
+        &map_camera,
+        &self.visited_rooms,
+        |pos| self.revealed_map.is_revealed(self.revealed_map.chunk_corner(pos)),
+      );
+      for (marker, pos) in &map_markers {
+        marker.draw(
+          &layers[LayerId::Main],
+          &self.theme,
+          map_camera.world_to_screen(*pos),
+        );
       }
       // Draw where we are.
       let player_pos = self.collision.get_position(&self.player_physics).unwrap_or(Vec2(0.0, 0.0));
-      let screen_pos = map_uv_to_screen(world_to_map_uv((player_pos.0, player_pos.1)));
+      let screen_pos = map_camera.world_to_screen(player_pos);
       let dot_size = (4.0 * self.map_zoom).max(6.0) as f64;
-      contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#ff0"));
-      contexts[MAIN_LAYER].fill_rect(
-        screen_pos.0 - dot_size / 2.0,
-        screen_pos.1 - dot_size / 2.0,
+      layers[LayerId::Main].set_fill_style(&JsValue::from_str("#ff0"));
+      layers[LayerId::Main].fill_rect(
+        screen_pos.0 as f64 - dot_size / 2.0,
+        screen_pos.1 as f64 - dot_size / 2.0,
         dot_size,
         dot_size,
       );
+      // Draw the player's pins.
+      layers[LayerId::Main].set_fill_style(&JsValue::from_str("#f44"));
+      layers[LayerId::Main].set_stroke_style(&JsValue::from_str("#000"));
+      layers[LayerId::Main].set_line_width(2.0);
+      for pin in &self.map_pins {
+        let screen_pos = map_camera.world_to_screen(*pin);
+        layers[LayerId::Main].begin_path();
+        layers[LayerId::Main]
+          .arc(
+            screen_pos.0 as f64,
+            screen_pos.1 as f64,
+            6.0,
+            0.0,
+            2.0 * std::f64::consts::PI,
+          )
+          .unwrap();
+        layers[LayerId::Main].fill();
+        layers[LayerId::Main].stroke();
+      }
+      // Draw the cursor for picking a save point to fast travel to.
+      let ctx = &layers[LayerId::Main];
+      let center = Vec2(SCREEN_WIDTH / 2.0, SCREEN_HEIGHT / 2.0);
+      ctx.set_stroke_style(&JsValue::from_str("rgba(255, 255, 255, 0.6)"));
+      ctx.begin_path();
+      ctx.move_to(center.0 as f64 - 10.0, center.1 as f64);
+      ctx.line_to(center.0 as f64 + 10.0, center.1 as f64);
+      ctx.move_to(center.0 as f64, center.1 as f64 - 10.0);
+      ctx.line_to(center.0 as f64, center.1 as f64 + 10.0);
+      ctx.stroke();
+      if let Some(target) = self.fast_travel.target {
+        let screen_pos = map_camera.world_to_screen(target);
+        ctx.set_stroke_style(&JsValue::from_str("#8ff"));
+        ctx.begin_path();
+        ctx
+          .arc(
+            screen_pos.0 as f64,
+            screen_pos.1 as f64,
+            fast_travel::PICK_RADIUS as f64 / 2.0,
+            0.0,
+            std::f64::consts::TAU,
+          )
+          .unwrap();
+        ctx.stroke();
+        let prompt = match self.fast_travel.confirming {
+          true => "Press E again to travel here",
+          false => "E: Fast travel",
+        };
+        ctx.set_font("24px Arial");
+        ctx.set_text_align("center");
+        ctx.set_text_baseline("bottom");
+        ctx.set_fill_style(&JsValue::from_str("#fff"));
+        ctx.fill_text(prompt, center.0 as f64, SCREEN_HEIGHT as f64 - 20.0).unwrap();
+      }
+      if let Some(mouse_pos) = self.mouse_pos {
+        draw_cursor(&layers[LayerId::Main], mouse_pos);
+      }
 
       return Ok(true);
     }
 
-    // contexts[BACKGROUND_LAYER].begin_path();
-    // contexts[BACKGROUND_LAYER].move_to(10.0, 10.0);
-    // contexts[BACKGROUND_LAYER].line_to(100.0 * rand::random::<f64>(), 100.0);
-    // contexts[BACKGROUND_LAYER].stroke();
+    // layers[LayerId::Background].begin_path();
+    // layers[LayerId::Background].move_to(10.0, 10.0);
+    // layers[LayerId::Background].line_to(100.0 * rand::random::<f64>(), 100.0);
+    // layers[LayerId::Background].stroke();
 
     let player_pos = self.collision.get_position(&self.player_physics).unwrap_or(Vec2(0.0, 0.0));
 
-    // Recenter the gamera.
-    self.camera_pos = Vec2(
-      player_pos.0 - SCREEN_WIDTH / 2.0 / TILE_SIZE,
-      player_pos.1 - (SCREEN_HEIGHT / 2.0 + 50.0) / TILE_SIZE,
-    );
+    // Shake for a moment after taking damage, which starts the damage blink at 1.
+    let shake = (self.damage_blink.get() - (1.0 - SCREEN_SHAKE_TIME)).max(0.0) / SCREEN_SHAKE_TIME;
+    self.shake_offset = match shake > 0.0 {
+      true => {
+        let angle = rand::random::<f32>() * 2.0 * std::f32::consts::PI;
+        (shake * SCREEN_SHAKE_PIXELS * self.settings.effects_scale())
+          * Vec2(angle.cos(), angle.sin())
+      }
+      false => Vec2::default(),
+    };
+    // Bumping into a ceiling jolts the screen down, rather than every which way.
+    let bump = (self.head_bump - (HEAD_BUMP_COOLDOWN - HEAD_BUMP_TIME)).max(0.0) / HEAD_BUMP_TIME;
+    self.shake_offset += Vec2(0.0, bump * HEAD_BUMP_PIXELS * self.settings.effects_scale());
+    let camera = Camera::game_view(self.camera_pos, 1.0).with_shake(self.shake_offset);
+    layers.set_camera(camera);
 
-    // Draw the game background.
+    // Draw the game background, in the tile renderer's pixel space.
     let draw_rect = Rect {
-      pos:  TILE_SIZE * self.camera_pos,
+      pos:  TILE_SIZE * camera.view_rect().pos,
       size: Vec2(SCREEN_WIDTH, SCREEN_HEIGHT),
     };
     tile_renderer.draw(
       draw_rect,
-      &contexts[BACKGROUND_LAYER],
+      &layers[LayerId::Background],
       images,
-      &canvases[SCRATCH_LAYER],
-      &contexts[SCRATCH_LAYER],
+      hd_images,
+      &layers[LayerId::Scratch],
     );
 
     // Clear the main layer.
-    contexts[MAIN_LAYER].clear_rect(0.0, 0.0, SCREEN_WIDTH as f64, SCREEN_HEIGHT as f64);
+    layers[LayerId::Main].clear();
 
     // Draw all of the objects.
     for (_handle, object) in &self.objects {
       match object.data {
         GameObjectData::DestroyedDoor => {
           let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
-          // Draw a 1x3 darkened rectangle.
-          contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("rgba(0, 0, 0, 0.8)"));
-          contexts[MAIN_LAYER].fill_rect(
-            (TILE_SIZE * (pos.0 - self.camera_pos.0 - 0.5)) as f64 - 0.25,
-            (TILE_SIZE * (pos.1 - self.camera_pos.1 - 1.5)) as f64 - 0.25,
-            TILE_SIZE as f64 + 0.5,
-            (3.0 * TILE_SIZE) as f64 + 0.5,
+          // Draw a 1x3 darkened rectangle, a touch oversized to cover seams.
+          let rect = camera.rect_to_screen(Rect::new(pos - Vec2(0.5, 1.5), Vec2(1.0, 3.0)));
+          layers[LayerId::Main].set_fill_style(&JsValue::from_str("rgba(0, 0, 0, 0.8)"));
+          layers[LayerId::Main].fill_rect(
+            rect.pos.0 as f64 - 0.25,
+            rect.pos.1 as f64 - 0.25,
+            rect.size.0 as f64 + 0.5,
+            rect.size.1 as f64 + 0.5,
+          );
+        }
+        _ => {}
+      }
+    }
+
+    let view = camera.view_rect();
+    // Slick walls get a glossy sheen, to show they can't be wall jumped off.
+    let ctx = &layers[LayerId::Main];
+    self.theme.style("slick_wall").apply(ctx);
+    ctx.set_line_width(2.0);
+    let max_corner = view.max_corner();
+    for y in view.pos.1.floor() as i32..=max_corner.1.floor() as i32 {
+      for x in view.pos.0.floor() as i32..=max_corner.0.floor() as i32 {
+        if !self.collision.slick_cells.contains(&(x, y)) {
+          continue;
+        }
+        let rect = camera.rect_to_screen(Rect::new(Vec2(x as f32, y as f32), Vec2(1.0, 1.0)));
+        ctx.fill_rect(
+          rect.pos.0 as f64,
+          rect.pos.1 as f64,
+          rect.size.0 as f64,
+          rect.size.1 as f64,
+        );
+        ctx.begin_path();
+        ctx.move_to(
+          (rect.pos.0 + 0.2 * rect.size.0) as f64,
+          (rect.pos.1 + 0.8 * rect.size.1) as f64,
+        );
+        ctx.line_to(
+          (rect.pos.0 + 0.8 * rect.size.0) as f64,
+          (rect.pos.1 + 0.2 * rect.size.1) as f64,
+        );
+        ctx.stroke();
+      }
+    }
+    // Water surfaces ripple over the top of the water tiles.
+    self.theme.style("water_surface").apply(&layers[LayerId::Main]);
+    layers[LayerId::Main].set_line_width(camera.length_to_screen(0.08) as f64);
+    for surface in &self.collision.water_surfaces {
+      let max_corner = view.max_corner();
+      if surface.x1 < view.pos.0
+        || surface.x0 > max_corner.0
+        || surface.y < view.pos.1 - 1.0
+        || surface.y > max_corner.1 + 1.0
+      {
+        continue;
+      }
+      water::draw_surface(&layers[LayerId::Main], &camera, surface, game_time);
+    }
+    layers[LayerId::Main].set_global_alpha(1.0);
+
+    self.critters.draw(&layers[LayerId::Main], &camera, game_time);
+    if let Some(chase) = &self.chase {
+      chase.draw(&layers[LayerId::Main], &camera, &self.theme, game_time);
+    }
+    self.particles.draw(&layers[LayerId::Main], &camera);
+    for floaty_text in &self.floaty_texts {
+      floaty_text.draw(&layers[LayerId::Main], &camera);
+    }
+
+    // If we're dashing, draw lines from self.dash_origin.
+    if self.dash_time > 0.0 {
+      // Lower effects densities drop the outermost lines first.
+      let line_count = particles::scaled_count(6, self.settings.effects_scale());
+      let across = Vec2(-self.dash_direction.1, self.dash_direction.0);
+      for i in [2, 3, 1, 4, 0, 5].into_iter().take(line_count) {
+        // Spread out across the dash in screen pixels, so the lines look the same at any zoom.
+        let offset = 10.0 * (i as f32 - 2.5) * across;
+        let t = [0.8, 0.4, 0.2, 0.2, 0.4, 0.8][i as usize];
+        let pos = self.dash_origin + t * (player_pos - self.dash_origin);
+        let from = camera.world_to_screen(pos) + offset;
+        let to = camera.world_to_screen(player_pos) + offset;
+        layers[LayerId::Main].set_stroke_style(&JsValue::from_str("#fff"));
+        layers[LayerId::Main].set_line_width(3.0);
+        layers[LayerId::Main].set_global_alpha((self.dash_time / 0.3).clamp(0.0, 1.0) as f64);
+        layers[LayerId::Main].begin_path();
+        layers[LayerId::Main].move_to(from.0 as f64, from.1 as f64);
+        layers[LayerId::Main].line_to(to.0 as f64, to.1 as f64);
+        layers[LayerId::Main].stroke();
+        layers[LayerId::Main].set_global_alpha(1.0);
+      }
+    }
+
+    // Boosted players leave a trail, fading out toward its tail.
+    if self.boost_trail.len() >= 2 && self.settings.effects_scale() > 0.0 {
+      let ctx = &layers[LayerId::Main];
+      let style = self.theme.style("boost_trail");
+      style.apply(ctx);
+      ctx.set_line_width(camera.length_to_screen(0.25) as f64);
+      ctx.set_line_cap("round");
+      for (i, pair) in self.boost_trail.windows(2).enumerate() {
+        let fraction = (i + 1) as f32 / self.boost_trail.len() as f32;
+        let (from, to) = (
+          camera.world_to_screen(pair[0]),
+          camera.world_to_screen(pair[1]),
+        );
+        ctx.set_global_alpha((fraction * style.alpha * self.settings.effects_scale()) as f64);
+        ctx.begin_path();
+        ctx.move_to(from.0 as f64, from.1 as f64);
+        ctx.line_to(to.0 as f64, to.1 as f64);
+        ctx.stroke();
+      }
+      ctx.set_line_cap("butt");
+      ctx.set_global_alpha(1.0);
+    }
+
+    // Draw the player, flickering while they're invulnerable after taking damage or respawning.
+    let player_sheet = &images[&ImageResource::PlayerSprites];
+    let blink_alpha = match self.damage_blink.get().max(self.respawn_immunity) % 0.2 > 0.1 {
+      true => 1.0,
+      false => 0.5,
+    };
+    let current_player_height = match self.shrunken {
+      true => SHRUNKEN_SIZE.1,
+      false => PLAYER_SIZE.1,
+    };
+    // Stretch the player along their height after a spring launch, keeping the area roughly constant.
+    let draw_width = PLAYER_SIZE.0 * (1.0 - 0.2 * self.player_stretch);
+    let draw_height = current_player_height * (1.0 + 0.25 * self.player_stretch);
+    match &self.death {
+      None => {
+        // Materializing grows the player out from their middle, in a column of light.
+        let materialized = 1.0 - self.spawn_time / SPAWN_ANIMATION_TIME;
+        let draw_height = materialized * draw_height;
+        layers[LayerId::Main].set_global_alpha((blink_alpha * materialized) as f64);
+        sprites::draw_player(
+          &layers[LayerId::Main],
+          player_sheet,
+          self.player_animation,
+          self.animation_time,
+          self.facing_right,
+          camera.world_to_screen(player_pos),
+          camera.size_to_screen(Vec2(draw_width, draw_height)),
+        );
+        layers[LayerId::Main].set_global_alpha(1.0);
+        // While shrunken, a caret where the player's head would be once they stand up, red
+        // if there's a ceiling in the way.
+        if self.shrunken {
+          let ctx = &layers[LayerId::Main];
+          let tip = camera
+            .world_to_screen(player_pos + Vec2(0.0, SHRUNKEN_SIZE.1 / 2.0 - PLAYER_SIZE.1 + 0.1));
+          let unit = camera.length_to_screen(0.15) as f64;
+          ctx.set_stroke_style(&JsValue::from_str(match ceiling_clear {
+            true => "rgba(255, 255, 255, 0.4)",
+            false => "rgba(255, 90, 90, 0.8)",
+          }));
+          ctx.set_line_width(2.0);
+          ctx.begin_path();
+          ctx.move_to(tip.0 as f64 - unit, tip.1 as f64 + unit);
+          ctx.line_to(tip.0 as f64, tip.1 as f64);
+          ctx.line_to(tip.0 as f64 + unit, tip.1 as f64 + unit);
+          ctx.stroke();
+        }
+        if self.spawn_time > 0.0 {
+          lighting::draw_beam_glow(
+            &layers[LayerId::Lighting],
+            camera.world_to_screen(player_pos - Vec2(0.0, 4.0)),
+            camera.world_to_screen(player_pos + Vec2(0.0, PLAYER_SIZE.1 / 2.0)),
+            camera.length_to_screen(draw_width),
+            lighting::SPAWN_GLOW,
+            1.0 - materialized,
+          );
+        }
+      }
+      // Once dead, the tossed body stands in for the player.
+      Some(death) => {
+        let ctx = &layers[LayerId::Main];
+        let pos = self.collision.get_position(&death.body).unwrap_or(player_pos);
+        let angle = self.collision.get_rotation(&death.body).unwrap_or(0.0);
+        let size = camera.size_to_screen(Vec2(draw_width, current_player_height));
+        let center = camera.world_to_screen(pos);
+        ctx.scoped(|ctx| {
+          ctx.translate(center.0 as f64, center.1 as f64).unwrap();
+          ctx.rotate(angle as f64).unwrap();
+          sprites::draw_player(
+            ctx,
+            player_sheet,
+            PlayerAnimation::Fall,
+            0.0,
+            self.facing_right,
+            Vec2(0.0, 0.0),
+            size,
           );
+        });
+      }
+    }
+
+    if let Some((save_point, age)) = self.save_exit_pulse {
+      let t = age / SAVE_EXIT_PULSE_TIME;
+      let center = camera.world_to_screen(save_point);
+      layers[LayerId::Main].set_global_alpha((1.0 - t) as f64);
+      layers[LayerId::Main].set_stroke_style(&JsValue::from_str("#8ff"));
+      layers[LayerId::Main].set_line_width(3.0);
+      layers[LayerId::Main].begin_path();
+      layers[LayerId::Main]
+        .arc(
+          center.0 as f64,
+          center.1 as f64,
+          camera.length_to_screen(0.5 + 1.5 * t) as f64,
+          0.0,
+          2.0 * std::f64::consts::PI,
+        )
+        .unwrap();
+      layers[LayerId::Main].stroke();
+      layers[LayerId::Main].set_global_alpha(1.0);
+    }
+
+    // Assist previews go under the hazards they're for.
+    if self.settings.hazard_previews {
+      let ctx = &layers[LayerId::Main];
+      let style = self.theme.style("hazard_preview");
+      style.apply(ctx);
+      for object in self.objects.values() {
+        let Some(pos) = self.collision.get_position(&object.physics_handle) else {
+          continue;
+        };
+        let fade = hazard_preview::fade(pos, player_pos);
+        if fade <= 0.0 {
+          continue;
+        }
+        ctx.set_global_alpha((style.alpha * fade) as f64);
+        match &object.data {
+          GameObjectData::Shooter1 {
+            orientation,
+            projectile,
+            ..
+          } => {
+            let path =
+              hazard_preview::projectile_path(&self.collision, *projectile, pos, *orientation);
+            hazard_preview::draw_path(ctx, &camera, &path);
+          }
+          GameObjectData::Thwump {
+            orientation,
+            half_size,
+            state: ThwumpState::Idle,
+            ..
+          } => {
+            let clearance = thwump::clearance(
+              &self.collision,
+              &object.physics_handle,
+              pos,
+              *orientation,
+              *half_size,
+            );
+            hazard_preview::draw_fall_zone(ctx, &camera, pos, *orientation, *half_size, clearance);
+          }
+          _ => {}
         }
-        _ => {}
-      }
-    }
-
-    // If we're dashing, draw lines from self.dash_origin.
-    if self.dash_time > 0.0 {
-      for i in 0..6 {
-        let dy = 5.0 * (i as f32 - 2.5);
-        let t = [0.8, 0.4, 0.2, 0.2, 0.4, 0.8][i as usize];
-        let pos = self.dash_origin + t * (player_pos - self.dash_origin);
-        let width = player_pos.0 - pos.0;
-        let screen_pos = Vec2(
-          TILE_SIZE * (pos.0 - self.camera_pos.0),
-          TILE_SIZE * (pos.1 - self.camera_pos.1) + dy,
-        );
-        contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#fff"));
-        contexts[MAIN_LAYER].set_global_alpha((self.dash_time / 0.3).clamp(0.0, 1.0) as f64);
-        contexts[MAIN_LAYER].fill_rect(
-          screen_pos.0 as f64 - 1.0,
-          (screen_pos.1 + dy) as f64 - 1.5,
-          (TILE_SIZE * width) as f64,
-          3.0,
-        );
-        contexts[MAIN_LAYER].set_global_alpha(1.0);
       }
+      ctx.set_global_alpha(1.0);
     }
 
-    // Draw a red rectangle for the player.
-    if self.damage_blink.get() % 0.2 > 0.1 {
-      contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#f00"));
-    } else {
-      contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#800"));
-    }
-    let current_player_height = match self.shrunken {
-      true => SHRUNKEN_SIZE.1,
-      false => PLAYER_SIZE.1,
-    };
-    contexts[MAIN_LAYER].fill_rect(
-      (TILE_SIZE * (player_pos.0 - self.camera_pos.0 - PLAYER_SIZE.0 / 2.0)) as f64,
-      (TILE_SIZE
-        * (player_pos.1 - self.camera_pos.1 - current_player_height / 2.0 + 10.0 * self.death_animation))
-        as f64,
-      (TILE_SIZE * PLAYER_SIZE.0) as f64,
-      (TILE_SIZE * (current_player_height - 10.0 * self.death_animation).max(0.0)) as f64,
+    // Draw all of the objects, skipping the ones well off screen. Enemies and the queen keep
+    // their own positions, and lasers reach far from their turrets, so those are checked
+    // separately.
+    let object_positions: Vec<Vec2> = self
+      .objects
+      .values()
+      .map(|object| self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0)))
+      .collect();
+    let mut on_screen = vec![false; object_positions.len()];
+    let view = camera.view_rect();
+    batch::points_in_rect(
+      &object_positions,
+      Rect::new(
+        view.pos - Vec2(DRAW_CULL_MARGIN, DRAW_CULL_MARGIN),
+        view.size + Vec2(2.0 * DRAW_CULL_MARGIN, 2.0 * DRAW_CULL_MARGIN),
+      ),
+      &mut on_screen,
     );
-
-    // Draw all of the objects.
-    for (_handle, object) in &self.objects {
+    for ((handle, object), (pos, on_screen)) in
+      self.objects.iter().zip(object_positions.iter().zip(on_screen))
+    {
+      let culled = match &object.data {
+        GameObjectData::Enemy { .. } | GameObjectData::BeeQueen { .. } => false,
+        GameObjectData::TurnLaser { hit_point, .. } => {
+          !on_screen && !beam_crosses(view, *pos, *hit_point)
+        }
+        _ => !on_screen,
+      };
+      if culled {
+        continue;
+      }
       match &object.data {
         GameObjectData::Coin { .. }
         | GameObjectData::RareCoin { .. }
@@ -1497,354 +5558,1151 @@ impl GameState {
           // Draw a circle, with a different color outside.
           let radius_mult = match object.data {
             GameObjectData::Coin { .. } => {
-              contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#ff0"));
-              contexts[MAIN_LAYER].set_stroke_style(&JsValue::from_str("#aa0"));
+              self.theme.style("coin").apply(&layers[LayerId::Main]);
               1.0
             }
             GameObjectData::RareCoin { .. } => {
-              contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#04a"));
-              contexts[MAIN_LAYER].set_stroke_style(&JsValue::from_str("#026"));
+              self.theme.style("rare_coin").apply(&layers[LayerId::Main]);
               1.0
             }
-            GameObjectData::Bullet { .. } => {
-              contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#f00"));
-              contexts[MAIN_LAYER].set_stroke_style(&JsValue::from_str("#a00"));
-              0.5
+            GameObjectData::Bullet { kind, fading, .. } => {
+              let descriptor = kind.descriptor();
+              let style = self.theme.style(descriptor.style);
+              style.apply(&layers[LayerId::Main]);
+              if let Some(time_left) = fading {
+                let fade = (time_left / projectiles::FADE_OUT_TIME).clamp(0.0, 1.0);
+                layers[LayerId::Main].set_global_alpha((style.alpha * fade) as f64);
+              }
+              2.0 * descriptor.radius
             }
             _ => unreachable!(),
           };
-          contexts[MAIN_LAYER].set_line_width(5.0);
-          contexts[MAIN_LAYER].begin_path();
-          contexts[MAIN_LAYER]
+          layers[LayerId::Main].set_line_width(5.0);
+          layers[LayerId::Main].begin_path();
+          layers[LayerId::Main]
             .arc(
-              (TILE_SIZE * (pos.0 - self.camera_pos.0)) as f64,
-              (TILE_SIZE * (pos.1 - self.camera_pos.1)) as f64,
-              (radius_mult * TILE_SIZE / 2.0) as f64,
+              camera.world_to_screen(pos).0 as f64,
+              camera.world_to_screen(pos).1 as f64,
+              camera.length_to_screen(radius_mult / 2.0) as f64,
               0.0,
               2.0 * std::f64::consts::PI,
             )
             .unwrap();
-          contexts[MAIN_LAYER].fill();
-          contexts[MAIN_LAYER].stroke();
+          layers[LayerId::Main].fill();
+          layers[LayerId::Main].stroke();
+          layers[LayerId::Main].set_global_alpha(1.0);
         }
-        GameObjectData::Bee { lifespan } => {
+        GameObjectData::Bee { lifespan, .. } => {
           // Draw a little yellow rectangle.
-          contexts[MAIN_LAYER].set_global_alpha(
-            (*lifespan).clamp(0.0, 1.0) as f64
-          );
+          layers[LayerId::Main].set_global_alpha((*lifespan).clamp(0.0, 1.0) as f64);
           let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
           let screen_pos = (
-            (TILE_SIZE * (pos.0 - self.camera_pos.0 - BEE_SIZE / 2.0)) as f64,
-            (TILE_SIZE * (pos.1 - self.camera_pos.1 - BEE_SIZE / 2.0)) as f64,
+            camera.world_to_screen(pos - Vec2(BEE_SIZE / 2.0, BEE_SIZE / 2.0)).0 as f64,
+            camera.world_to_screen(pos - Vec2(BEE_SIZE / 2.0, BEE_SIZE / 2.0)).1 as f64,
           );
-          contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#ff0"));
-          contexts[MAIN_LAYER].fill_rect(
+          layers[LayerId::Main].set_fill_style(&JsValue::from_str("#ff0"));
+          layers[LayerId::Main].fill_rect(
             screen_pos.0,
             screen_pos.1,
-            (BEE_SIZE * TILE_SIZE) as f64,
-            (BEE_SIZE * TILE_SIZE) as f64,
+            camera.length_to_screen(BEE_SIZE) as f64,
+            camera.length_to_screen(BEE_SIZE) as f64,
           );
-          contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#000"));
+          layers[LayerId::Main].set_fill_style(&JsValue::from_str("#000"));
           // Draw black stripes.
           for i in 1..=3 {
             let t = i as f32 / 4.0;
-            
-            contexts[MAIN_LAYER].fill_rect(
-              screen_pos.0 + (t * TILE_SIZE * BEE_SIZE as f32) as f64 - 1.0,
+
+            layers[LayerId::Main].fill_rect(
+              screen_pos.0 + camera.length_to_screen(t * BEE_SIZE) as f64 - 1.0,
               screen_pos.1,
               2.0,
-              (BEE_SIZE * TILE_SIZE) as f64,
+              camera.length_to_screen(BEE_SIZE) as f64,
             );
           }
-          contexts[MAIN_LAYER].set_global_alpha(1.0);
+          layers[LayerId::Main].set_global_alpha(1.0);
         }
         GameObjectData::HpUp { .. } => {
           let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
           // Draw a circle, with a different color outside.
-          contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#0f0"));
-          contexts[MAIN_LAYER].set_stroke_style(&JsValue::from_str("#0a0"));
-          contexts[MAIN_LAYER].set_line_width(5.0);
-          contexts[MAIN_LAYER].begin_path();
-          contexts[MAIN_LAYER]
+          let style = self.theme.style("hp_up");
+          style.apply(&layers[LayerId::Main]);
+          layers[LayerId::Main].set_line_width(5.0);
+          layers[LayerId::Main].begin_path();
+          layers[LayerId::Main]
             .arc(
-              (TILE_SIZE * (pos.0 - self.camera_pos.0)) as f64,
-              (TILE_SIZE * (pos.1 - self.camera_pos.1)) as f64,
-              (TILE_SIZE * 0.75) as f64,
+              camera.world_to_screen(pos).0 as f64,
+              camera.world_to_screen(pos).1 as f64,
+              camera.length_to_screen(0.75) as f64,
               0.0,
               2.0 * std::f64::consts::PI,
             )
             .unwrap();
-          contexts[MAIN_LAYER].fill();
-          contexts[MAIN_LAYER].stroke();
+          layers[LayerId::Main].fill();
+          layers[LayerId::Main].stroke();
           // Put text in the middle.
-          contexts[MAIN_LAYER].set_font("24px Arial");
-          contexts[MAIN_LAYER].set_text_align("center");
-          contexts[MAIN_LAYER].set_text_baseline("middle");
-          contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#040"));
-          contexts[MAIN_LAYER]
+          layers[LayerId::Main].set_font("24px Arial");
+          layers[LayerId::Main].set_text_align("center");
+          layers[LayerId::Main].set_text_baseline("middle");
+          layers[LayerId::Main].set_fill_style(&JsValue::from_str(&style.stroke));
+          layers[LayerId::Main]
             .fill_text(
               "+HP",
-              (TILE_SIZE * (pos.0 - self.camera_pos.0)) as f64,
-              (TILE_SIZE * (pos.1 - self.camera_pos.1)) as f64,
+              camera.world_to_screen(pos).0 as f64,
+              camera.world_to_screen(pos).1 as f64,
             )
             .unwrap();
+          layers[LayerId::Main].set_global_alpha(1.0);
         }
         GameObjectData::PowerUp { power_up } => {
           let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
           // Draw a circle, with a different color outside.
-          contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#00f"));
-          contexts[MAIN_LAYER].set_stroke_style(&JsValue::from_str("#002"));
-          contexts[MAIN_LAYER].set_line_width(5.0);
-          contexts[MAIN_LAYER].begin_path();
-          contexts[MAIN_LAYER]
+          let style = self.theme.style("power_up");
+          style.apply(&layers[LayerId::Main]);
+          layers[LayerId::Main].set_line_width(5.0);
+          layers[LayerId::Main].begin_path();
+          layers[LayerId::Main]
             .arc(
-              (TILE_SIZE * (pos.0 - self.camera_pos.0)) as f64,
-              (TILE_SIZE * (pos.1 - self.camera_pos.1)) as f64,
-              (TILE_SIZE * 0.75) as f64,
+              camera.world_to_screen(pos).0 as f64,
+              camera.world_to_screen(pos).1 as f64,
+              camera.length_to_screen(0.75) as f64,
               0.0,
               2.0 * std::f64::consts::PI,
             )
             .unwrap();
-          contexts[MAIN_LAYER].fill();
-          contexts[MAIN_LAYER].stroke();
+          layers[LayerId::Main].fill();
+          layers[LayerId::Main].stroke();
           // Put text in the middle.
-          contexts[MAIN_LAYER].set_font("24px Arial");
-          contexts[MAIN_LAYER].set_text_align("center");
-          contexts[MAIN_LAYER].set_text_baseline("middle");
-          contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#002"));
-          contexts[MAIN_LAYER]
+          layers[LayerId::Main].set_font("24px Arial");
+          layers[LayerId::Main].set_text_align("center");
+          layers[LayerId::Main].set_text_baseline("middle");
+          layers[LayerId::Main].set_fill_style(&JsValue::from_str(&style.stroke));
+          layers[LayerId::Main]
             .fill_text(
-              match &power_up[..] {
-                "wall_jump" => "WJ",
-                "dash" => "D",
-                "water" => "W",
-                "lava" => "F",
-                "small" => "S",
-                "double_jump" => "DJ",
-                _ => panic!("Unknown power up: {}", power_up),
-              },
-              (TILE_SIZE * (pos.0 - self.camera_pos.0)) as f64,
-              (TILE_SIZE * (pos.1 - self.camera_pos.1)) as f64,
+              power_up.icon(),
+              camera.world_to_screen(pos).0 as f64,
+              camera.world_to_screen(pos).1 as f64,
             )
             .unwrap();
+          layers[LayerId::Main].set_global_alpha(1.0);
+        }
+        GameObjectData::CountGate { currency, count } => {
+          let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
+          let ctx = &layers[LayerId::Main];
+          // The icon of what it takes, with how many, across the middle of the gate.
+          let icon = camera.world_to_screen(pos - Vec2(0.15, 0.0));
+          self.theme.style(currency.style()).apply(ctx);
+          ctx.set_line_width(2.0);
+          match currency {
+            // Secrets have no pickup to match, so they get a question mark.
+            Currency::Secrets => {
+              ctx.set_font("bold 18px Arial");
+              ctx.set_text_align("center");
+              ctx.set_text_baseline("middle");
+              ctx.stroke_text("?", icon.0 as f64, icon.1 as f64).unwrap();
+              ctx.fill_text("?", icon.0 as f64, icon.1 as f64).unwrap();
+            }
+            _ => {
+              ctx.begin_path();
+              ctx
+                .arc(
+                  icon.0 as f64,
+                  icon.1 as f64,
+                  camera.length_to_screen(0.12) as f64,
+                  0.0,
+                  std::f64::consts::TAU,
+                )
+                .unwrap();
+              ctx.fill();
+              ctx.stroke();
+            }
+          }
+          let text = camera.world_to_screen(pos + Vec2(0.02, 0.0));
+          ctx.set_font("16px Arial");
+          ctx.set_text_align("left");
+          ctx.set_text_baseline("middle");
+          ctx.set_fill_style(&JsValue::from_str("#fff"));
+          ctx.fill_text(&count.to_string(), text.0 as f64, text.1 as f64).unwrap();
+          ctx.set_global_alpha(1.0);
         }
         GameObjectData::TurnLaser {
           angle, hit_point, ..
         } => {
           let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
-          contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#777"));
-          contexts[MAIN_LAYER].set_stroke_style(&JsValue::from_str("#222"));
-          contexts[MAIN_LAYER].set_line_width(5.0);
-          contexts[MAIN_LAYER].begin_path();
-          contexts[MAIN_LAYER]
+          self.theme.style("laser_turret").apply(&layers[LayerId::Main]);
+          layers[LayerId::Main].set_line_width(5.0);
+          layers[LayerId::Main].begin_path();
+          layers[LayerId::Main]
             .arc(
-              (TILE_SIZE * (pos.0 - self.camera_pos.0)) as f64,
-              (TILE_SIZE * (pos.1 - self.camera_pos.1)) as f64,
-              (TILE_SIZE * 0.45) as f64,
+              camera.world_to_screen(pos).0 as f64,
+              camera.world_to_screen(pos).1 as f64,
+              camera.length_to_screen(0.45) as f64,
               0.0,
               2.0 * std::f64::consts::PI,
             )
             .unwrap();
-          contexts[MAIN_LAYER].fill();
-          contexts[MAIN_LAYER].stroke();
+          layers[LayerId::Main].fill();
+          layers[LayerId::Main].stroke();
           // Draw the laser.
-          contexts[MAIN_LAYER].set_stroke_style(&JsValue::from_str("#f00"));
-          contexts[MAIN_LAYER].set_line_width(5.0);
-          contexts[MAIN_LAYER].begin_path();
-          contexts[MAIN_LAYER].move_to(
-            (TILE_SIZE * (pos.0 - self.camera_pos.0)) as f64,
-            (TILE_SIZE * (pos.1 - self.camera_pos.1)) as f64,
+          self.theme.style("laser").apply(&layers[LayerId::Main]);
+          layers[LayerId::Main].set_line_width(5.0);
+          layers[LayerId::Main].begin_path();
+          layers[LayerId::Main].move_to(
+            camera.world_to_screen(pos).0 as f64,
+            camera.world_to_screen(pos).1 as f64,
           );
-          contexts[MAIN_LAYER].line_to(
-            (TILE_SIZE * (hit_point.0 - self.camera_pos.0)) as f64,
-            (TILE_SIZE * (hit_point.1 - self.camera_pos.1)) as f64,
+          layers[LayerId::Main].line_to(
+            camera.world_to_screen(*hit_point).0 as f64,
+            camera.world_to_screen(*hit_point).1 as f64,
           );
-          contexts[MAIN_LAYER].stroke();
+          layers[LayerId::Main].stroke();
+          layers[LayerId::Main].set_global_alpha(1.0);
         }
-        GameObjectData::FloatyText {
-          text,
-          color,
-          time_left,
+        GameObjectData::Stone { .. } => {
+          let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
+          self.theme.style("stone").apply(&layers[LayerId::Main]);
+          layers[LayerId::Main].set_line_width(3.0);
+          layers[LayerId::Main].begin_path();
+          layers[LayerId::Main].rect(
+            camera.world_to_screen(pos - Vec2(0.45, 0.45)).0 as f64,
+            camera.world_to_screen(pos - Vec2(0.45, 0.45)).1 as f64,
+            camera.length_to_screen(0.9) as f64,
+            camera.length_to_screen(0.9) as f64,
+          );
+          layers[LayerId::Main].fill();
+          layers[LayerId::Main].stroke();
+          layers[LayerId::Main].set_global_alpha(1.0);
+        }
+        GameObjectData::Key { color, .. } => {
+          let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
+          let ctx = &layers[LayerId::Main];
+          let center = camera.world_to_screen(pos);
+          let unit = camera.length_to_screen(0.1) as f64;
+          ctx.set_fill_style(&JsValue::from_str(color));
+          ctx.set_stroke_style(&JsValue::from_str("#222"));
+          ctx.set_line_width(2.0);
+          // A ring for the bow, and a shaft with a tooth off to the right.
+          ctx.begin_path();
+          ctx
+            .arc(
+              center.0 as f64 - 1.5 * unit,
+              center.1 as f64,
+              1.5 * unit,
+              0.0,
+              std::f64::consts::TAU,
+            )
+            .unwrap();
+          ctx.rect(
+            center.0 as f64,
+            center.1 as f64 - 0.5 * unit,
+            3.0 * unit,
+            unit,
+          );
+          ctx.rect(
+            center.0 as f64 + 2.0 * unit,
+            center.1 as f64 + 0.5 * unit,
+            unit,
+            unit,
+          );
+          ctx.fill();
+          ctx.stroke();
+        }
+        GameObjectData::LockedDoor { color, opening, .. } => {
+          let Some(bounds) = self.collision.collider_bounds(*handle) else {
+            continue;
+          };
+          let ctx = &layers[LayerId::Main];
+          // An opening door slides up into its top tile.
+          let remaining = opening.map_or(1.0, keys::door_remaining);
+          let rect = camera.rect_to_screen(Rect::new(
+            bounds.pos,
+            Vec2(bounds.size.0, remaining * bounds.size.1),
+          ));
+          ctx.set_fill_style(&JsValue::from_str(color));
+          ctx.set_stroke_style(&JsValue::from_str("#222"));
+          ctx.set_line_width(3.0);
+          ctx.fill_rect(
+            rect.pos.0 as f64,
+            rect.pos.1 as f64,
+            rect.size.0 as f64,
+            rect.size.1 as f64,
+          );
+          ctx.stroke_rect(
+            rect.pos.0 as f64,
+            rect.pos.1 as f64,
+            rect.size.0 as f64,
+            rect.size.1 as f64,
+          );
+          // A keyhole in the middle, while it's still shut.
+          if opening.is_none() {
+            let center = camera.world_to_screen(bounds.pos + 0.5 * bounds.size);
+            let unit = camera.length_to_screen(0.1) as f64;
+            ctx.set_fill_style(&JsValue::from_str("#222"));
+            ctx.begin_path();
+            ctx
+              .arc(
+                center.0 as f64,
+                center.1 as f64 - unit,
+                unit,
+                0.0,
+                std::f64::consts::TAU,
+              )
+              .unwrap();
+            ctx.rect(
+              center.0 as f64 - 0.5 * unit,
+              center.1 as f64 - unit,
+              unit,
+              2.5 * unit,
+            );
+            ctx.fill();
+          }
+        }
+        GameObjectData::BreakableBlock {
+          hp,
+          max_hp,
+          crumbling,
+          ..
         } => {
           let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
-          contexts[MAIN_LAYER].set_font("32px Arial");
-          contexts[MAIN_LAYER].set_text_align("center");
-          contexts[MAIN_LAYER].set_text_baseline("middle");
-          contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str(color));
-          contexts[MAIN_LAYER].set_global_alpha(*time_left as f64);
-          contexts[MAIN_LAYER]
-            .fill_text(
-              text,
-              (TILE_SIZE * (pos.0 - self.camera_pos.0)) as f64,
-              (TILE_SIZE * (pos.1 - self.camera_pos.1)) as f64,
+          let ctx = &layers[LayerId::Main];
+          let style = self.theme.style("breakable_block");
+          style.apply(ctx);
+          ctx.set_line_width(2.0);
+          match crumbling {
+            Some(time_left) => {
+              let progress = 1.0 - time_left / breakable::CRUMBLE_TIME;
+              ctx.set_global_alpha(((1.0 - progress) * style.alpha) as f64);
+              for (offset, size) in breakable::crumble_pieces(progress) {
+                let corner = camera.world_to_screen(pos + offset - Vec2(size / 2.0, size / 2.0));
+                let side = camera.length_to_screen(size) as f64;
+                ctx.fill_rect(corner.0 as f64, corner.1 as f64, side, side);
+                ctx.stroke_rect(corner.0 as f64, corner.1 as f64, side, side);
+              }
+            }
+            None => {
+              let corner = camera.world_to_screen(pos - Vec2(0.45, 0.45));
+              let side = camera.length_to_screen(0.9) as f64;
+              ctx.set_global_alpha(style.alpha as f64);
+              ctx.fill_rect(corner.0 as f64, corner.1 as f64, side, side);
+              ctx.stroke_rect(corner.0 as f64, corner.1 as f64, side, side);
+              // A crack across it, and another for each hit it's taken.
+              let center = camera.world_to_screen(pos);
+              let reach = camera.length_to_screen(0.4) as f64;
+              ctx.begin_path();
+              for crack in 0..=(max_hp - hp).min(3) {
+                let angle = 0.6 + 1.7 * crack as f64;
+                ctx.move_to(center.0 as f64, center.1 as f64);
+                ctx.line_to(
+                  center.0 as f64 + reach * angle.cos(),
+                  center.1 as f64 + reach * angle.sin(),
+                );
+              }
+              ctx.stroke();
+            }
+          }
+          ctx.set_global_alpha(1.0);
+        }
+        GameObjectData::Switch { channel, flash, .. } => {
+          let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
+          let screen_pos = camera.world_to_screen(pos);
+          let ctx = &layers[LayerId::Main];
+          let style = match self.switch_channels.is_on(channel) {
+            true => self.theme.style("switch_on"),
+            false => self.theme.style("switch"),
+          };
+          style.apply(ctx);
+          ctx.set_global_alpha(style.alpha as f64);
+          ctx.set_line_width(3.0);
+          ctx.begin_path();
+          ctx
+            .arc(
+              screen_pos.0 as f64,
+              screen_pos.1 as f64,
+              camera.length_to_screen(0.3) as f64,
+              0.0,
+              std::f64::consts::TAU,
             )
             .unwrap();
-          contexts[MAIN_LAYER].set_global_alpha(1.0);
+          ctx.fill();
+          ctx.stroke();
+          // A ring that spreads out and fades just after it's hit.
+          if *flash > 0.0 {
+            let progress = 1.0 - flash / switches::SWITCH_FLASH_TIME;
+            ctx.set_global_alpha(((1.0 - progress) * style.alpha) as f64);
+            ctx.begin_path();
+            ctx
+              .arc(
+                screen_pos.0 as f64,
+                screen_pos.1 as f64,
+                camera.length_to_screen(0.3 + 0.3 * progress) as f64,
+                0.0,
+                std::f64::consts::TAU,
+              )
+              .unwrap();
+            ctx.stroke();
+          }
+          ctx.set_global_alpha(1.0);
         }
-        GameObjectData::Stone => {
+        GameObjectData::ToggleBlock { is_solid, .. } => {
           let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
-          contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#888"));
-          contexts[MAIN_LAYER].set_stroke_style(&JsValue::from_str("#444"));
-          contexts[MAIN_LAYER].set_line_width(3.0);
-          contexts[MAIN_LAYER].begin_path();
-          contexts[MAIN_LAYER].rect(
-            (TILE_SIZE * (pos.0 - self.camera_pos.0 - 0.45)) as f64,
-            (TILE_SIZE * (pos.1 - self.camera_pos.1 - 0.45)) as f64,
-            (TILE_SIZE * 0.9) as f64,
-            (TILE_SIZE * 0.9) as f64,
-          );
-          contexts[MAIN_LAYER].fill();
-          contexts[MAIN_LAYER].stroke();
+          let ctx = &layers[LayerId::Main];
+          let style = self.theme.style("toggle_block");
+          let corner = camera.world_to_screen(pos - Vec2(0.45, 0.45));
+          let side = camera.length_to_screen(0.9) as f64;
+          style.apply(ctx);
+          ctx.set_line_width(2.0);
+          // Blocks that are switched off are just a faint outline of where they'll be.
+          ctx.set_global_alpha(match *is_solid {
+            true => style.alpha as f64,
+            false => 0.3 * style.alpha as f64,
+          });
+          if *is_solid {
+            ctx.fill_rect(corner.0 as f64, corner.1 as f64, side, side);
+          }
+          ctx.stroke_rect(corner.0 as f64, corner.1 as f64, side, side);
+          ctx.set_global_alpha(1.0);
         }
         GameObjectData::VanishBlock {
           vanish_timer,
           is_solid,
+          ..
         } => {
           let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
-          // If we're solid draw a block turning red.
-          let mut size = 0.9;
+          let style = self.theme.style("vanish_block");
+          let timer = vanish_timer.clamp(0.0, 1.0);
+          // If we're solid draw a block turning to the warning color; otherwise, show a block
+          // fading back in.
+          let (size, alpha) = match *is_solid {
+            true => (0.9, style.alpha),
+            false => (0.3 + 0.6 * timer, 0.5 * timer * style.alpha),
+          };
+          let rect = (
+            camera.world_to_screen(pos - Vec2(size / 2.0, size / 2.0)).0 as f64,
+            camera.world_to_screen(pos - Vec2(size / 2.0, size / 2.0)).1 as f64,
+            camera.length_to_screen(size) as f64,
+          );
+          style.apply(&layers[LayerId::Main]);
+          layers[LayerId::Main].set_global_alpha(alpha as f64);
+          layers[LayerId::Main].fill_rect(rect.0, rect.1, rect.2, rect.2);
           if *is_solid {
-            contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str(&format!(
-              "rgb({},{},{})",
-              255 - (119.0 * vanish_timer.clamp(0.0, 1.0)) as i32,
-              136 - (136.0 * (1.0 - vanish_timer.clamp(0.0, 1.0))) as i32,
-              136 - (136.0 * (1.0 - vanish_timer.clamp(0.0, 1.0))) as i32,
-            )));
-            contexts[MAIN_LAYER].set_stroke_style(&JsValue::from_str("#444"));
-          } else {
-            // Otherwise, show a block fading back in.
-            contexts[MAIN_LAYER].set_global_alpha(0.5 * vanish_timer.clamp(0.0, 1.0) as f64);
-            size = 0.3 + 0.6 * vanish_timer.clamp(0.0, 1.0);
-            contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#888"));
-            contexts[MAIN_LAYER].set_stroke_style(&JsValue::from_str("#444"));
+            let warning = self.theme.style("vanish_block_warning");
+            layers[LayerId::Main].set_fill_style(&JsValue::from_str(&warning.fill));
+            layers[LayerId::Main].set_global_alpha(((1.0 - timer) * warning.alpha) as f64);
+            layers[LayerId::Main].fill_rect(rect.0, rect.1, rect.2, rect.2);
+            layers[LayerId::Main].set_global_alpha(alpha as f64);
+          }
+          layers[LayerId::Main].set_line_width(3.0);
+          layers[LayerId::Main].stroke_rect(rect.0, rect.1, rect.2, rect.2);
+          layers[LayerId::Main].set_global_alpha(1.0);
+        }
+        GameObjectData::AirBubble {
+          respawn_time,
+          cooldown,
+        } => {
+          let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
+          let screen_pos = camera.world_to_screen(pos);
+          // While respawning, the bubble regrows from nothing.
+          let growth = match *respawn_time > 0.0 {
+            true => 1.0 - (*cooldown / *respawn_time).clamp(0.0, 1.0),
+            false => 1.0,
+          };
+          let ctx = &layers[LayerId::Main];
+          ctx.set_global_alpha(if *cooldown > 0.0 { 0.3 } else { 0.8 });
+          ctx.set_fill_style(&JsValue::from_str("rgba(180, 230, 255, 0.5)"));
+          ctx.set_stroke_style(&JsValue::from_str("#def"));
+          ctx.set_line_width(2.0);
+          ctx.begin_path();
+          ctx
+            .arc(
+              screen_pos.0 as f64,
+              screen_pos.1 as f64,
+              camera.length_to_screen(0.4 * growth) as f64,
+              0.0,
+              2.0 * std::f64::consts::PI,
+            )
+            .unwrap();
+          ctx.fill();
+          ctx.stroke();
+          ctx.set_global_alpha(1.0);
+        }
+        GameObjectData::Spring {
+          direction, squash, ..
+        } => {
+          let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
+          // The spring is drawn in its own frame, with -y pointing along the launch direction.
+          let compression = (*squash / SPRING_SQUASH_TIME).clamp(0.0, 1.0);
+          let coil_height = 0.55 * (1.0 - 0.6 * compression);
+          layers[LayerId::Main].save();
+          layers[LayerId::Main]
+            .translate(
+              camera.world_to_screen(pos).0 as f64,
+              camera.world_to_screen(pos).1 as f64,
+            )
+            .unwrap();
+          layers[LayerId::Main]
+            .rotate((direction.1.atan2(direction.0) + std::f32::consts::FRAC_PI_2) as f64)
+            .unwrap();
+          // Base.
+          layers[LayerId::Main].set_fill_style(&JsValue::from_str("#555"));
+          layers[LayerId::Main].fill_rect(
+            camera.length_to_screen(-0.45) as f64,
+            camera.length_to_screen(0.3) as f64,
+            camera.length_to_screen(0.9) as f64,
+            camera.length_to_screen(0.2) as f64,
+          );
+          // Coil, squashed while the spring is recoiling.
+          layers[LayerId::Main].set_stroke_style(&JsValue::from_str("#ccc"));
+          layers[LayerId::Main].set_line_width(3.0);
+          layers[LayerId::Main].begin_path();
+          for i in 0..=4 {
+            let y = 0.3 - coil_height * i as f32 / 4.0;
+            let x = if i % 2 == 0 { -0.25 } else { 0.25 };
+            if i == 0 {
+              layers[LayerId::Main].move_to(
+                camera.length_to_screen(x) as f64,
+                camera.length_to_screen(y) as f64,
+              );
+            } else {
+              layers[LayerId::Main].line_to(
+                camera.length_to_screen(x) as f64,
+                camera.length_to_screen(y) as f64,
+              );
+            }
           }
-          contexts[MAIN_LAYER].set_line_width(3.0);
-          contexts[MAIN_LAYER].begin_path();
-          contexts[MAIN_LAYER].rect(
-            (TILE_SIZE * (pos.0 - self.camera_pos.0 - size / 2.0)) as f64,
-            (TILE_SIZE * (pos.1 - self.camera_pos.1 - size / 2.0)) as f64,
-            (TILE_SIZE * size) as f64,
-            (TILE_SIZE * size) as f64,
+          layers[LayerId::Main].stroke();
+          // Pad.
+          layers[LayerId::Main].set_fill_style(&JsValue::from_str("#e44"));
+          layers[LayerId::Main].fill_rect(
+            camera.length_to_screen(-0.45) as f64,
+            camera.length_to_screen(0.3 - coil_height - 0.15) as f64,
+            camera.length_to_screen(0.9) as f64,
+            camera.length_to_screen(0.15) as f64,
+          );
+          layers[LayerId::Main].restore();
+        }
+        GameObjectData::Thwump {
+          orientation,
+          half_size,
+          ..
+        } => {
+          let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
+          let across = Vec2(-orientation.1, orientation.0);
+          // Inset slightly from the collider, like the moving platforms.
+          let half_extent = Vec2(
+            (half_size.0 * across.0 + half_size.1 * orientation.0).abs(),
+            (half_size.0 * across.1 + half_size.1 * orientation.1).abs(),
+          ) - Vec2(0.05, 0.05);
+          let body = camera.rect_to_screen(Rect::new(pos - half_extent, 2.0 * half_extent));
+          layers[LayerId::Main].set_fill_style(&JsValue::from_str("#666"));
+          layers[LayerId::Main].set_stroke_style(&JsValue::from_str("#222"));
+          layers[LayerId::Main].begin_path();
+          layers[LayerId::Main].rect(
+            body.pos.0 as f64,
+            body.pos.1 as f64,
+            body.size.0 as f64,
+            body.size.1 as f64,
+          );
+          layers[LayerId::Main].fill();
+          layers[LayerId::Main].stroke();
+          // Draw the damage side, on the face it falls toward.
+          let face = pos + (half_size.1 - 0.05) * *orientation;
+          let (a, b) = (
+            camera.world_to_screen(face - (half_size.0 - 0.05) * across),
+            camera.world_to_screen(face + (half_size.0 - 0.05) * across),
           );
-          contexts[MAIN_LAYER].fill();
-          contexts[MAIN_LAYER].stroke();
-          contexts[MAIN_LAYER].set_global_alpha(1.0);
+          layers[LayerId::Main].set_stroke_style(&JsValue::from_str("#8cf"));
+          layers[LayerId::Main].begin_path();
+          layers[LayerId::Main].move_to(a.0 as f64, a.1 as f64);
+          layers[LayerId::Main].line_to(b.0 as f64, b.1 as f64);
+          layers[LayerId::Main].stroke();
         }
-        GameObjectData::Thwump { orientation, .. }
-        | GameObjectData::MovingPlatform { orientation } => {
+        GameObjectData::MovingPlatform { .. } => {
           let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
-          contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("#666"));
-          contexts[MAIN_LAYER].set_stroke_style(&JsValue::from_str("#222"));
-          contexts[MAIN_LAYER].begin_path();
-          contexts[MAIN_LAYER].rect(
-            (TILE_SIZE * (pos.0 - self.camera_pos.0 - 1.45)) as f64,
-            (TILE_SIZE * (pos.1 - self.camera_pos.1 - 0.45)) as f64,
-            (TILE_SIZE * 3.0) as f64,
-            (TILE_SIZE * 1.0) as f64,
+          layers[LayerId::Main].set_fill_style(&JsValue::from_str("#666"));
+          layers[LayerId::Main].set_stroke_style(&JsValue::from_str("#222"));
+          layers[LayerId::Main].begin_path();
+          layers[LayerId::Main].rect(
+            camera.world_to_screen(pos - Vec2(1.45, 0.45)).0 as f64,
+            camera.world_to_screen(pos - Vec2(1.45, 0.45)).1 as f64,
+            camera.length_to_screen(3.0) as f64,
+            camera.length_to_screen(1.0) as f64,
           );
-          contexts[MAIN_LAYER].fill();
-          contexts[MAIN_LAYER].stroke();
+          layers[LayerId::Main].fill();
+          layers[LayerId::Main].stroke();
           // Draw the damage side.
-          contexts[MAIN_LAYER].set_stroke_style(&JsValue::from_str("#8cf"));
-          contexts[MAIN_LAYER].begin_path();
-          contexts[MAIN_LAYER].move_to(
-            (TILE_SIZE * (pos.0 - self.camera_pos.0 - 1.45)) as f64,
-            (TILE_SIZE * (pos.1 - self.camera_pos.1 + 0.45)) as f64,
+          layers[LayerId::Main].set_stroke_style(&JsValue::from_str("#8cf"));
+          layers[LayerId::Main].begin_path();
+          layers[LayerId::Main].move_to(
+            camera.world_to_screen(pos + Vec2(-1.45, 0.45)).0 as f64,
+            camera.world_to_screen(pos + Vec2(-1.45, 0.45)).1 as f64,
+          );
+          layers[LayerId::Main].line_to(
+            camera.world_to_screen(pos + Vec2(1.45, 0.45)).0 as f64,
+            camera.world_to_screen(pos + Vec2(1.45, 0.45)).1 as f64,
+          );
+          layers[LayerId::Main].stroke();
+        }
+        GameObjectData::Enemy { enemy } => {
+          let ctx = &layers[LayerId::Main];
+          let top_left = camera.world_to_screen(enemy.position - enemy.size / 2.0);
+          let size = (
+            camera.length_to_screen(enemy.size.0) as f64,
+            camera.length_to_screen(enemy.size.1) as f64,
+          );
+          // A bob in its step, in time with how fast it walks.
+          let bob = 0.04 * size.1 * (8.0 * enemy.speed * game_time).sin().abs() as f64;
+          self.theme.style("walker").apply(ctx);
+          ctx.set_line_width(3.0);
+          ctx.fill_rect(top_left.0 as f64, top_left.1 as f64 - bob, size.0, size.1);
+          ctx.stroke_rect(top_left.0 as f64, top_left.1 as f64 - bob, size.0, size.1);
+          // An eye on the side it's walking toward.
+          let eye = camera.world_to_screen(
+            enemy.position + Vec2(0.25 * enemy.facing * enemy.size.0, -0.2 * enemy.size.1),
+          );
+          ctx.set_fill_style(&JsValue::from_str("#fff"));
+          ctx.begin_path();
+          ctx
+            .arc(
+              eye.0 as f64,
+              eye.1 as f64 - bob,
+              0.12 * size.0,
+              0.0,
+              std::f64::consts::TAU,
+            )
+            .unwrap();
+          ctx.fill();
+          ctx.set_global_alpha(1.0);
+        }
+        GameObjectData::BeeQueen { queen } => {
+          let ctx = &layers[LayerId::Main];
+          // Shake in place while winding up a dive, with a line showing where it'll go.
+          let mut center = camera.world_to_screen(queen.position);
+          if let Some(target) = queen.telegraphing() {
+            center.0 += if (self.frame_counter / 2) % 2 == 0 {
+              3.0
+            } else {
+              -3.0
+            };
+            let target = camera.world_to_screen(target);
+            ctx.set_stroke_style(&JsValue::from_str("rgba(255, 60, 60, 0.7)"));
+            ctx.set_line_width(3.0);
+            ctx.begin_path();
+            ctx.move_to(center.0 as f64, center.1 as f64);
+            ctx.line_to(target.0 as f64, target.1 as f64);
+            ctx.stroke();
+          }
+          let radius = camera.length_to_screen(bee_queen::QUEEN_RADIUS) as f64;
+          // Flicker after being hit.
+          ctx.push();
+          if queen.invulnerable > 0.0 && (self.frame_counter / 3) % 2 == 0 {
+            ctx.set_global_alpha(0.4);
+          }
+          ctx.set_fill_style(&JsValue::from_str("#fc0"));
+          ctx.set_stroke_style(&JsValue::from_str("#000"));
+          ctx.set_line_width(4.0);
+          ctx.begin_path();
+          ctx
+            .arc(
+              center.0 as f64,
+              center.1 as f64,
+              radius,
+              0.0,
+              2.0 * std::f64::consts::PI,
+            )
+            .unwrap();
+          ctx.fill();
+          ctx.stroke();
+          ctx.set_fill_style(&JsValue::from_str("#000"));
+          for i in -1..=1 {
+            ctx.fill_rect(
+              center.0 as f64 + 0.45 * radius * i as f64 - 4.0,
+              center.1 as f64 - 0.8 * radius,
+              8.0,
+              1.6 * radius,
+            );
+          }
+          ctx.pop();
+          // Health bar.
+          let bar_width = 2.0 * radius;
+          ctx.set_fill_style(&JsValue::from_str("rgba(0, 0, 0, 0.6)"));
+          ctx.fill_rect(
+            center.0 as f64 - radius,
+            center.1 as f64 - radius - 16.0,
+            bar_width,
+            8.0,
           );
-          contexts[MAIN_LAYER].line_to(
-            (TILE_SIZE * (pos.0 - self.camera_pos.0 + 1.45)) as f64,
-            (TILE_SIZE * (pos.1 - self.camera_pos.1 + 0.45)) as f64,
+          ctx.set_fill_style(&JsValue::from_str("#f44"));
+          ctx.fill_rect(
+            center.0 as f64 - radius,
+            center.1 as f64 - radius - 16.0,
+            bar_width * queen.hp.max(0) as f64 / queen.max_hp.max(1) as f64,
+            8.0,
+          );
+        }
+        GameObjectData::BoostRail { .. } => {
+          let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
+          let top_left = camera.world_to_screen(pos - Vec2(0.5, 0.5));
+          let tile = camera.length_to_screen(1.0) as f64;
+          let ctx = &layers[LayerId::Main];
+          let style = self.theme.style("boost_rail");
+          style.apply(ctx);
+          ctx.fill_rect(top_left.0 as f64, top_left.1 as f64, tile, tile);
+          // Streaks run along the rail, lined up from tile to tile.
+          ctx.set_line_width(2.0);
+          ctx.begin_path();
+          for row in [0.3, 0.7] {
+            let start = (2.0 * (pos.0 + game_time + row)).rem_euclid(1.0) as f64;
+            let y = top_left.1 as f64 + row as f64 * tile;
+            ctx.move_to(top_left.0 as f64 + start * tile, y);
+            ctx.line_to(top_left.0 as f64 + (start + 0.25).min(1.0) * tile, y);
+          }
+          ctx.stroke();
+          ctx.set_global_alpha(1.0);
+        }
+        GameObjectData::HazardSurface { effect } => {
+          let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
+          let top_left = camera.world_to_screen(pos - Vec2(0.5, 0.5));
+          let ctx = &layers[LayerId::Main];
+          ctx.set_fill_style(&JsValue::from_str(match effect {
+            StatusEffect::Poisoned => "rgba(80, 200, 40, 0.5)",
+            StatusEffect::Shocked => "rgba(255, 240, 80, 0.35)",
+          }));
+          ctx.fill_rect(
+            top_left.0 as f64,
+            top_left.1 as f64,
+            camera.length_to_screen(1.0) as f64,
+            camera.length_to_screen(1.0) as f64,
           );
-          contexts[MAIN_LAYER].stroke();
+          if *effect == StatusEffect::Shocked && (self.frame_counter / 4) % 2 == 0 {
+            // A flickering spark across the tile.
+            ctx.set_stroke_style(&JsValue::from_str("#ffa"));
+            ctx.set_line_width(2.0);
+            ctx.begin_path();
+            ctx.move_to(
+              top_left.0 as f64,
+              (top_left.1 + camera.length_to_screen(0.5)) as f64,
+            );
+            for i in 1..=4 {
+              let x = top_left.0 + camera.length_to_screen(0.25 * i as f32);
+              let y = top_left.1 + camera.length_to_screen(if i % 2 == 0 { 0.5 } else { 0.2 });
+              ctx.line_to(x as f64, y as f64);
+            }
+            ctx.stroke();
+          }
         }
         _ => {}
       }
     }
 
-    if self.int1_laser_time > 0.0 || self.int2_laser_time > 0.0 {
-      let laser_time = self.int1_laser_time.max(self.int2_laser_time);
-      let (laser_origin, laser_dx, laser_angle) = match self.int1_laser_time > 0.0 {
-        true => ((1200.0, 1024.0), -800.0, std::f32::consts::PI),
-        false => ((1300.0, 3040.0), 800.0, 0.0),
-      };
-      // Draw the laser.
-      contexts[MAIN_LAYER].set_stroke_style(&JsValue::from_str("#ff0"));
-      contexts[MAIN_LAYER].set_line_width(20.0 * laser_time as f64);
-      contexts[MAIN_LAYER].begin_path();
-      contexts[MAIN_LAYER].move_to(
-        (laser_origin.0 - self.camera_pos.0 * TILE_SIZE) as f64,
-        (laser_origin.1 - self.camera_pos.1 * TILE_SIZE) as f64,
+    // Glow from lava and lasers. Lasers stop at the wall their raycast hit, so walls block
+    // their glow, with a splash of light where they hit.
+    let glow_intensity = match self.quality.lighting_enabled() {
+      true => self.settings.effects_scale(),
+      false => 0.0,
+    };
+    if glow_intensity > 0.0 {
+      let ctx = &layers[LayerId::Lighting];
+      let glow_view = Rect::new(
+        camera.view_rect().pos - Vec2(2.0, 2.0),
+        camera.view_rect().size + Vec2(4.0, 4.0),
       );
-      contexts[MAIN_LAYER].line_to(
-        (laser_origin.0 - self.camera_pos.0 * TILE_SIZE + laser_dx) as f64,
-        (laser_origin.1 - self.camera_pos.1 * TILE_SIZE) as f64,
+      // There can be a lot of lava, so cull and place it all in one batch.
+      let lava: Vec<Vec2> = self
+        .objects
+        .values()
+        .filter(|object| matches!(object.data, GameObjectData::Lava))
+        .map(|object| self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0)))
+        .collect();
+      let mut lava_visible = vec![false; lava.len()];
+      batch::points_in_rect(&lava, glow_view, &mut lava_visible);
+      let mut lava_screen = vec![Vec2::default(); lava.len()];
+      camera.points_to_screen(&lava, &mut lava_screen);
+      for ((pos, screen_pos), visible) in lava.iter().zip(&lava_screen).zip(lava_visible) {
+        if !visible {
+          continue;
+        }
+        // A slow flicker, out of step from tile to tile.
+        let flicker = 0.05 * (3.0 * game_time + 1.7 * pos.0 + pos.1).sin();
+        lighting::draw_point_glow(
+          ctx,
+          *screen_pos,
+          camera.length_to_screen(1.5),
+          lighting::LAVA_GLOW,
+          (0.3 + flicker) * glow_intensity,
+        );
+      }
+      for object in self.objects.values() {
+        let GameObjectData::TurnLaser { hit_point, .. } = &object.data else {
+          continue;
+        };
+        let pos = self.collision.get_position(&object.physics_handle).unwrap_or(Vec2(0.0, 0.0));
+        if !beam_crosses(glow_view, pos, *hit_point) {
+          continue;
+        }
+        let (start, end) = (
+          camera.world_to_screen(pos),
+          camera.world_to_screen(*hit_point),
+        );
+        lighting::draw_beam_glow(
+          ctx,
+          start,
+          end,
+          camera.length_to_screen(0.2),
+          lighting::LASER_GLOW,
+          glow_intensity,
+        );
+        lighting::draw_point_glow(
+          ctx,
+          end,
+          camera.length_to_screen(1.0),
+          lighting::LASER_GLOW,
+          0.6 * glow_intensity,
+        );
+      }
+    }
+
+    for laser in &self.lasers {
+      let laser_time = laser.time_left;
+      let laser_angle = match laser.length < 0.0 {
+        true => std::f32::consts::PI,
+        false => 0.0,
+      };
+      let start = camera.world_to_screen(laser.origin);
+      let end = camera.world_to_screen(laser.end());
+      lighting::draw_beam_glow(
+        &layers[LayerId::Lighting],
+        start,
+        end,
+        30.0 * laser_time,
+        lighting::INT_LASER_GLOW,
+        glow_intensity,
       );
-      contexts[MAIN_LAYER].stroke();
-      contexts[MAIN_LAYER].set_line_width(10.0 * laser_time as f64);
-      for _ in 0..12 {
+      // Draw the laser.
+      layers[LayerId::Main].set_stroke_style(&JsValue::from_str("#ff0"));
+      layers[LayerId::Main].set_line_width(20.0 * laser_time as f64);
+      layers[LayerId::Main].begin_path();
+      layers[LayerId::Main].move_to(start.0 as f64, start.1 as f64);
+      layers[LayerId::Main].line_to(end.0 as f64, end.1 as f64);
+      layers[LayerId::Main].stroke();
+      layers[LayerId::Main].set_line_width(10.0 * laser_time as f64);
+      for _ in 0..particles::scaled_count(12, self.settings.effects_scale()) {
         let angle = (rand::random::<f32>() - 0.5) * 1.0 + laser_angle;
         let distance = (40.0 + rand::random::<f32>() * 120.0) * laser_time;
         let endpoint = (
-          (laser_origin.0 - self.camera_pos.0 * TILE_SIZE + angle.cos() * distance) as f64,
-          (laser_origin.1 - self.camera_pos.1 * TILE_SIZE + angle.sin() * distance) as f64,
-        );
-        contexts[MAIN_LAYER].begin_path();
-        contexts[MAIN_LAYER].move_to(
-          (laser_origin.0 - self.camera_pos.0 * TILE_SIZE) as f64,
-          (laser_origin.1 - self.camera_pos.1 * TILE_SIZE) as f64,
+          (start.0 + angle.cos() * distance) as f64,
+          (start.1 + angle.sin() * distance) as f64,
         );
-        contexts[MAIN_LAYER].line_to(endpoint.0, endpoint.1);
-        contexts[MAIN_LAYER].stroke();
+        layers[LayerId::Main].begin_path();
+        layers[LayerId::Main].move_to(start.0 as f64, start.1 as f64);
+        layers[LayerId::Main].line_to(endpoint.0, endpoint.1);
+        layers[LayerId::Main].stroke();
       }
     }
 
+    // Tint the screen for each effect the player is under.
+    for effect in self.status_effects.active() {
+      layers[LayerId::Main].set_fill_style(&JsValue::from_str(effect.overlay_color()));
+      layers[LayerId::Main].fill_rect(0.0, 0.0, SCREEN_WIDTH as f64, SCREEN_HEIGHT as f64);
+    }
+
     // If we're under water, draw a blue rectangle over the screen.
     if self.submerged_in_water {
-      contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("rgba(0, 0, 255, 0.4)"));
-      contexts[MAIN_LAYER].fill_rect(0.0, 0.0, SCREEN_WIDTH as f64, SCREEN_HEIGHT as f64);
-      // Draw our air meter.
-      let air_bubbles = if self.suppress_air_meter || self.char_state.hp.get() <= 0 {
-        0
-      } else {
-        self.air_remaining.round() as i32
-      };
-      contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("rgba(0, 0, 255, 0.5)"));
-      contexts[MAIN_LAYER].set_stroke_style(&JsValue::from_str("rgba(128, 128, 255, 0.5)"));
-      contexts[MAIN_LAYER].set_line_width(2.0);
-      let player_center = (
-        (TILE_SIZE * (player_pos.0 - self.camera_pos.0)) as f64,
-        (TILE_SIZE * (player_pos.1 - self.camera_pos.1)) as f64,
+      layers[LayerId::Main].set_fill_style(&JsValue::from_str("rgba(0, 0, 255, 0.4)"));
+      layers[LayerId::Main].fill_rect(0.0, 0.0, SCREEN_WIDTH as f64, SCREEN_HEIGHT as f64);
+    }
+
+    // Keep the HUD out of any notches, plus the player's chosen margin.
+    let hud = self.safe_area.with_margin(HUD_MARGIN + self.settings.hud_margin as f32);
+    let top_left = hud.anchor(HudAnchor::TopLeft);
+
+    // Show how much air is left, closing in the edges of the screen as it runs out.
+    if let Some(fraction) = air_fraction {
+      hud::draw_vignette(&layers[LayerId::Main], 1.0 - fraction / 0.25);
+      let pulse = 0.5 + 0.5 * (8.0 * self.air_remaining).sin();
+      hud::draw_air_bar(
+        &layers[LayerId::Main],
+        hud.anchor(HudAnchor::TopCenter),
+        fraction,
+        pulse,
       );
-      for i in 0..air_bubbles {
-        // Draw circles.
-        contexts[MAIN_LAYER].begin_path();
-        contexts[MAIN_LAYER]
-          .arc(
-            player_center.0 - 87.5 + 25.0 * (i % 8) as f64,
-            player_center.1 - 110.0 + 25.0 * (i / 8) as f64,
-            10.0,
-            0.0,
-            2.0 * std::f64::consts::PI,
-          )
-          .unwrap();
-        contexts[MAIN_LAYER].fill();
-        contexts[MAIN_LAYER].stroke();
-      }
     }
 
     // If the user is offered an interaction, show it.
-    if let Some(interaction_number) = self.offered_interaction {
-      let text = match interaction_number {
-        1 => "Press E to shoot laser",
-        2 => "Press E to shoot laser",
-        3 => "You win the game!",
-        _ => "Unknown interaction!",
+    if self.offered_interaction.is_some() {
+      let script = self.offered_dialogue.as_ref().and_then(|name| self.dialogue_library.get(name));
+      let text = match script {
+        Some(script) => script.prompt.as_str(),
+        None if hold_time > 0.0 => "Hold E to shoot laser",
+        None => "Press E to interact",
+      };
+      layers[LayerId::Main].set_font("32px Arial");
+      layers[LayerId::Main].set_fill_style(&JsValue::from_str("white"));
+      layers[LayerId::Main].set_text_align("left");
+      layers[LayerId::Main].set_text_baseline("top");
+      layers[LayerId::Main]
+        .fill_text(text, top_left.0 as f64, top_left.1 as f64 + 20.0)
+        .unwrap();
+      // A ring after the prompt fills up while E is held.
+      if let InteractHold::Charging { held, .. } = self.interact_hold {
+        let fraction = (held / hold_time).min(1.0) as f64;
+        let text_width = layers[LayerId::Main].measure_text(text).unwrap().width();
+        hud::draw_progress_ring(
+          &layers[LayerId::Main],
+          Vec2(top_left.0 + text_width as f32 + 30.0, top_left.1 + 36.0),
+          14.0,
+          fraction,
+        );
+      }
+    }
+    if let Some(power_up) = &self.locked_interaction {
+      layers[LayerId::Main].set_font("32px Arial");
+      layers[LayerId::Main].set_fill_style(&JsValue::from_str("#888"));
+      layers[LayerId::Main].set_text_align("left");
+      layers[LayerId::Main].set_text_baseline("top");
+      layers[LayerId::Main]
+        .fill_text(
+          &format!("Requires {}", power_up.display_name()),
+          top_left.0 as f64,
+          top_left.1 as f64 + 20.0,
+        )
+        .unwrap();
+    }
+    if self.offered_map_station.is_some() {
+      layers[LayerId::Main].set_font("32px Arial");
+      layers[LayerId::Main].set_fill_style(&JsValue::from_str("white"));
+      layers[LayerId::Main].set_text_align("left");
+      layers[LayerId::Main].set_text_baseline("top");
+      layers[LayerId::Main]
+        .fill_text(
+          "Press E to read the map",
+          top_left.0 as f64,
+          top_left.1 as f64 + 20.0,
+        )
+        .unwrap();
+    }
+    if self.offered_door.is_some() {
+      layers[LayerId::Main].set_font("32px Arial");
+      layers[LayerId::Main].set_fill_style(&JsValue::from_str("white"));
+      layers[LayerId::Main].set_text_align("left");
+      layers[LayerId::Main].set_text_baseline("top");
+      layers[LayerId::Main]
+        .fill_text(
+          "Press E to go through",
+          top_left.0 as f64,
+          top_left.1 as f64 + 20.0,
+        )
+        .unwrap();
+    }
+
+    // Show how any no-damage challenges we're in are going.
+    let bottom_left = hud.anchor(HudAnchor::BottomLeft);
+    for (i, (name, run)) in self.challenge_runs.iter().enumerate() {
+      let (text, color) = match run.is_flawless() {
+        true => (format!("{}: no damage", name), "#4df"),
+        false => (format!("{}: failed", name), "#f44"),
+      };
+      layers[LayerId::Main].set_font("20px Arial");
+      layers[LayerId::Main].set_fill_style(&JsValue::from_str(color));
+      layers[LayerId::Main].set_text_align("left");
+      layers[LayerId::Main].set_text_baseline("bottom");
+      layers[LayerId::Main]
+        .fill_text(
+          &text,
+          bottom_left.0 as f64,
+          bottom_left.1 as f64 - 24.0 * i as f64,
+        )
+        .unwrap();
+    }
+
+    // Point the compass at the current target, fading it out as the target comes on screen.
+    if self.settings.show_compass {
+      if let Some(target) = Self::compass_target(&self.objects, &self.collision, player_pos) {
+        let screen_target = layers[LayerId::Main].world_to_screen(target);
+        let outside = (-screen_target.0)
+          .max(screen_target.0 - SCREEN_WIDTH)
+          .max(-screen_target.1)
+          .max(screen_target.1 - SCREEN_HEIGHT);
+        let alpha = (outside / COMPASS_FADE_DISTANCE).clamp(0.0, 1.0);
+        if alpha > 0.0 {
+          let delta = target - player_pos;
+          let ctx = &layers[LayerId::Main];
+          ctx.save();
+          ctx.set_global_alpha(alpha as f64);
+          let top_center = hud.anchor(HudAnchor::TopCenter);
+          ctx.translate(top_center.0 as f64, top_center.1 as f64 + 50.0).unwrap();
+          ctx.rotate(delta.1.atan2(delta.0) as f64).unwrap();
+          ctx.set_fill_style(&JsValue::from_str("#fd4"));
+          ctx.set_stroke_style(&JsValue::from_str("#000"));
+          ctx.set_line_width(2.0);
+          ctx.begin_path();
+          ctx.move_to(22.0, 0.0);
+          ctx.line_to(-14.0, -14.0);
+          ctx.line_to(-6.0, 0.0);
+          ctx.line_to(-14.0, 14.0);
+          ctx.close_path();
+          ctx.fill();
+          ctx.stroke();
+          ctx.restore();
+        }
+      }
+    }
+
+    if let Some(region) = self.route_hint.as_ref().filter(|_| self.settings.route_hints) {
+      let top_center = hud.anchor(HudAnchor::TopCenter);
+      layers[LayerId::Main].set_font("24px Arial");
+      layers[LayerId::Main].set_fill_style(&JsValue::from_str("#fd4"));
+      layers[LayerId::Main].set_text_align("center");
+      layers[LayerId::Main].set_text_baseline("top");
+      layers[LayerId::Main]
+        .fill_text(
+          &format!("Stuck? Try exploring {}", region),
+          top_center.0 as f64,
+          top_center.1 as f64 + 80.0,
+        )
+        .unwrap();
+    }
+
+    // Draw the daily challenge's clock, and the code to share once it's done.
+    if let Some(challenge) = &self.daily_challenge {
+      let top_left = hud.anchor(HudAnchor::TopLeft) + Vec2(0.0, hud::STATUS_HEIGHT);
+      layers[LayerId::Main].set_font("24px monospace");
+      layers[LayerId::Main].set_fill_style(&JsValue::from_str("white"));
+      layers[LayerId::Main].set_text_align("left");
+      layers[LayerId::Main].set_text_baseline("top");
+      let text = match &challenge.result_code {
+        Some(code) => format!("Daily complete! {}", code),
+        None => format!(
+          "Daily {} {}",
+          challenge.date,
+          time_trials::format_time(challenge.run.elapsed)
+        ),
       };
-      contexts[MAIN_LAYER].set_font("32px Arial");
-      contexts[MAIN_LAYER].set_fill_style(&JsValue::from_str("white"));
-      contexts[MAIN_LAYER].set_text_align("left");
-      contexts[MAIN_LAYER].set_text_baseline("top");
-      contexts[MAIN_LAYER].fill_text(text, 10.0, 30.0).unwrap();
+      layers[LayerId::Main].fill_text(&text, top_left.0 as f64, top_left.1 as f64).unwrap();
+    }
+
+    // Draw how deep the descent has got, and the score once it's over.
+    if let Some(descent) = &self.descent {
+      let top_left = hud.anchor(HudAnchor::TopLeft) + Vec2(0.0, hud::STATUS_HEIGHT);
+      layers[LayerId::Main].set_font("24px monospace");
+      layers[LayerId::Main].set_fill_style(&JsValue::from_str("white"));
+      layers[LayerId::Main].set_text_align("left");
+      layers[LayerId::Main].set_text_baseline("top");
+      let text = match &descent.result {
+        Some(result) => format!("Descent over! Score {} ({})", result.score, result.code),
+        None => format!(
+          "Depth {} {}",
+          descent.depth as u32,
+          time_trials::format_time(descent.run.elapsed)
+        ),
+      };
+      layers[LayerId::Main].fill_text(&text, top_left.0 as f64, top_left.1 as f64).unwrap();
+    }
+
+    // Draw the time trial timer and results.
+    if let Some(trial) = &self.active_trial {
+      let top_right = hud.anchor(HudAnchor::TopRight) + Vec2(0.0, hud::STATUS_HEIGHT);
+      layers[LayerId::Main].set_font("32px monospace");
+      layers[LayerId::Main].set_fill_style(&JsValue::from_str("white"));
+      layers[LayerId::Main].set_text_align("right");
+      layers[LayerId::Main].set_text_baseline("top");
+      layers[LayerId::Main]
+        .fill_text(
+          &time_trials::format_time(trial.elapsed),
+          top_right.0 as f64,
+          top_right.1 as f64,
+        )
+        .unwrap();
+      if let Some(best) = self.trial_best_times.get(&trial.name) {
+        layers[LayerId::Main].set_font("20px monospace");
+        layers[LayerId::Main]
+          .fill_text(
+            &format!("Best {}", time_trials::format_time(*best)),
+            top_right.0 as f64,
+            top_right.1 as f64 + 36.0,
+          )
+          .unwrap();
+      }
+    }
+    if let Some(result) = &self.last_trial_result {
+      if result.display_time > 0.0 {
+        layers[LayerId::Main].set_global_alpha(result.display_time.clamp(0.0, 1.0) as f64);
+        layers[LayerId::Main].set_fill_style(&JsValue::from_str("rgba(0, 0, 0, 0.7)"));
+        layers[LayerId::Main].fill_rect(
+          SCREEN_WIDTH as f64 / 2.0 - 220.0,
+          SCREEN_HEIGHT as f64 / 2.0 - 140.0,
+          440.0,
+          130.0,
+        );
+        layers[LayerId::Main].set_text_align("center");
+        layers[LayerId::Main].set_text_baseline("middle");
+        layers[LayerId::Main].set_font("32px Arial");
+        layers[LayerId::Main].set_fill_style(&JsValue::from_str("white"));
+        layers[LayerId::Main]
+          .fill_text(
+            &format!(
+              "{}{}",
+              time_trials::format_time(result.time),
+              if result.new_best { " - New best!" } else { "" },
+            ),
+            SCREEN_WIDTH as f64 / 2.0,
+            SCREEN_HEIGHT as f64 / 2.0 - 110.0,
+          )
+          .unwrap();
+        let (medal_text, medal_color) = match result.medal {
+          Some(medal) => (format!("{} medal", medal.name()), medal.color()),
+          None => ("No medal".to_string(), "#888"),
+        };
+        layers[LayerId::Main].set_fill_style(&JsValue::from_str(medal_color));
+        layers[LayerId::Main]
+          .fill_text(
+            &medal_text,
+            SCREEN_WIDTH as f64 / 2.0,
+            SCREEN_HEIGHT as f64 / 2.0 - 70.0,
+          )
+          .unwrap();
+        layers[LayerId::Main].set_font("20px Arial");
+        layers[LayerId::Main].set_fill_style(&JsValue::from_str("#aaa"));
+        layers[LayerId::Main]
+          .fill_text(
+            "Press R to retry",
+            SCREEN_WIDTH as f64 / 2.0,
+            SCREEN_HEIGHT as f64 / 2.0 - 35.0,
+          )
+          .unwrap();
+        layers[LayerId::Main].set_global_alpha(1.0);
+      }
+    }
+    // Remind a returning player where they were, fading out after a few seconds.
+    if let Some((recap, time_left)) = &self.showing_recap {
+      let ctx = &layers[LayerId::Main];
+      let lines = recap.lines();
+      let height = 30.0 * lines.len() as f64 + 20.0;
+      ctx.set_global_alpha((time_left / recap::RECAP_FADE_TIME).clamp(0.0, 1.0) as f64);
+      ctx.set_fill_style(&JsValue::from_str("rgba(0, 0, 0, 0.7)"));
+      ctx.fill_rect(
+        SCREEN_WIDTH as f64 / 2.0 - 220.0,
+        SCREEN_HEIGHT as f64 / 2.0 - 140.0 - height,
+        440.0,
+        height,
+      );
+      ctx.set_text_align("center");
+      ctx.set_text_baseline("middle");
+      for (i, line) in lines.iter().enumerate() {
+        // The first line says where, so it stands out from the rest.
+        let (font, color) = match i {
+          0 => ("26px Arial", "white"),
+          _ => ("20px Arial", "#ccc"),
+        };
+        ctx.set_font(font);
+        ctx.set_fill_style(&JsValue::from_str(color));
+        ctx
+          .fill_text(
+            line,
+            SCREEN_WIDTH as f64 / 2.0,
+            SCREEN_HEIGHT as f64 / 2.0 - 140.0 - height + 25.0 + 30.0 * i as f64,
+          )
+          .unwrap();
+      }
+      ctx.set_global_alpha(1.0);
     }
 
     // // Draw all of the game objects.
@@ -1854,7 +6712,7 @@ impl GameState {
     //     None => continue,
     //   };
     //   let pos = game_object.get_position(&self.game_world);
-    //   contexts[MAIN_LAYER]
+    //   layers[LayerId::Main]
     //     .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
     //       &images.get(&draw_info.image).expect("Image not found"),
     //       draw_info.x as f64,
@@ -1870,7 +6728,7 @@ impl GameState {
 
     // Copy a bit of the sprite sheet to the canvas
     // for i in 0..1_000 {
-    //   contexts[MAIN_LAYER].draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+    //   layers[LayerId::Main].draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
     //     &images[&ImageResource::MainSpriteSheet],
     //     200.0 * rand::random::<f64>(),
     //     200.0 * rand::random::<f64>(),
@@ -1882,6 +6740,174 @@ impl GameState {
     //     32.0,
     //   )?;
     // }
+    if !self.showing_map {
+      hud::draw_status(
+        &layers[LayerId::Ui],
+        hud.anchor(HudAnchor::TopRight),
+        &status,
+      );
+      if let Some(dialogue) = &self.dialogue {
+        dialogue.draw(&layers[LayerId::Ui], &self.dialogue_library);
+      }
+    }
+    if let Some(death) = &self.death {
+      let ctx = &layers[LayerId::Ui];
+      let fade = death.fade_amount();
+      if fade > 0.0 {
+        ctx.set_global_alpha((0.85 * fade) as f64);
+        ctx.set_fill_style(&JsValue::from_str("#000"));
+        ctx.fill_rect(0.0, 0.0, SCREEN_WIDTH as f64, SCREEN_HEIGHT as f64);
+        ctx.set_global_alpha(1.0);
+      }
+      if death.phase == DeathPhase::GameOver {
+        ctx.set_text_align("center");
+        ctx.set_text_baseline("middle");
+        ctx.set_fill_style(&JsValue::from_str("#f44"));
+        ctx.set_font("64px Arial");
+        let center = Vec2(SCREEN_WIDTH / 2.0, SCREEN_HEIGHT / 2.0);
+        ctx.fill_text("Game Over", center.0 as f64, center.1 as f64 - 30.0).unwrap();
+        ctx.set_fill_style(&JsValue::from_str("#ccc"));
+        ctx.set_font("24px Arial");
+        ctx
+          .fill_text(
+            "Press space to respawn",
+            center.0 as f64,
+            center.1 as f64 + 30.0,
+          )
+          .unwrap();
+      }
+    }
+    #[cfg(feature = "editor")]
+    if self.debug_mode {
+      self.camera_bounds.draw(&layers[LayerId::Ui], &camera, camera.view_rect());
+    }
+    if self.debug_mode || self.replay_playback.is_some() {
+      debug_hud::draw_hitboxes(
+        &layers[LayerId::Ui],
+        &self.objects,
+        &self.collision,
+        &self.player_physics,
+        self.player_vel,
+        &camera,
+      );
+    }
+    #[cfg(feature = "debug-tools")]
+    if self.debug_mode {
+      debug_hud::draw_object_labels(
+        &layers[LayerId::Ui],
+        &self.objects,
+        &self.collision,
+        &self.debug_label_categories,
+        &camera,
+      );
+    }
+    if let Some(readout) = &movement_readout {
+      debug_hud::draw_movement_readout(&layers[LayerId::Ui], top_left + Vec2(-4.0, 60.0), readout);
+    }
+    if let Some(playback) = &self.replay_playback {
+      debug_hud::draw_replay_position(
+        &layers[LayerId::Ui],
+        hud.anchor(HudAnchor::BottomRight),
+        playback.next_frame,
+        playback.frame_count(),
+      );
+    }
+    if !self.stale_assets.is_empty() {
+      hud::draw_notice(
+        &layers[LayerId::Ui],
+        hud.anchor(HudAnchor::TopCenter),
+        "Some game files are out of date. Try reloading the page.",
+      );
+    }
+    if let Some(mouse_pos) = self.mouse_pos {
+      draw_cursor(&layers[LayerId::Main], mouse_pos);
+    }
     Ok(true)
   }
 }
+
+/// An object's full state, for savestates and for checking that runs play back identically.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectSnapshot {
+  pub position: Option<Vec2>,
+  pub velocity: Option<Vec2>,
+  pub data:     GameObjectData,
+}
+
+// Native entry points, for driving the simulation without a browser.
+impl GameState {
+  /// A game state with nothing to draw to. `draw_frame` does nothing, but everything else works.
+  pub fn new_headless(resources: HashMap<String, Vec<u8>>) -> Self {
+    let game_map =
+      Rc::new(GameMap::from_resources(&resources, START_MAP).expect("Failed to load map"));
+    Self::from_parts(resources, game_map, None)
+  }
+
+  /// Feeds in a key event, as if it came from the page.
+  pub fn send_input(&mut self, event: InputEvent) {
+    self.handle_input_event(event);
+  }
+
+  pub fn player_position(&self) -> Vec2 {
+    self.collision.get_position(&self.player_physics).unwrap_or(Vec2(0.0, 0.0))
+  }
+
+  pub fn player_velocity(&self) -> Vec2 {
+    self.player_vel
+  }
+
+  pub fn char_state(&self) -> &CharState {
+    &self.char_state
+  }
+
+  /// Plays a whole replay from its start, returning checkpoints taken along the way and at the end.
+  pub fn play_replay(&mut self, replay: &Replay) -> Vec<ReplayCheckpoint> {
+    self.restart_from(&replay.save_data, replay.settings.clone());
+    let mut checkpoints = Vec::new();
+    for (i, frame) in replay.frames.iter().enumerate() {
+      if self.play_replay_frame(frame).is_err() {
+        panic!("Simulation failed on frame {}", i);
+      }
+      let frame_number = i + 1;
+      if frame_number % replay::CHECKPOINT_INTERVAL == 0 || frame_number == replay.frames.len() {
+        checkpoints.push(self.replay_checkpoint(frame_number));
+      }
+    }
+    checkpoints
+  }
+
+  /// Every object's state, in a stable order.
+  pub fn object_snapshots(&self) -> Vec<ObjectSnapshot> {
+    let mut handles: Vec<&ColliderHandle> = self.objects.keys().collect();
+    handles.sort_by_key(|handle| handle.into_raw_parts());
+    handles
+      .into_iter()
+      .map(|handle| {
+        let object = &self.objects[handle];
+        ObjectSnapshot {
+          position: self.collision.get_position(&object.physics_handle),
+          velocity: self.collision.get_velocity(&object.physics_handle),
+          data:     object.data.clone(),
+        }
+      })
+      .collect()
+  }
+
+  fn replay_checkpoint(&self, frame: usize) -> ReplayCheckpoint {
+    let mut power_ups: Vec<String> =
+      self.char_state.power_ups.iter().map(|p| p.name().to_string()).collect();
+    power_ups.sort();
+    ReplayCheckpoint {
+      frame,
+      position: replay::round_for_checkpoint(
+        self.collision.get_position(&self.player_physics).unwrap_or_default(),
+      ),
+      velocity: replay::round_for_checkpoint(self.player_vel),
+      hp: self.char_state.hp.get(),
+      power_ups,
+      coins: self.char_state.coins.len(),
+      rare_coins: self.char_state.rare_coins.len(),
+      save_point: replay::round_for_checkpoint(self.char_state.save_point),
+    }
+  }
+}
@@ -0,0 +1,76 @@
+use wasm_bindgen::JsValue;
+use web_sys::CanvasRenderingContext2d;
+
+use crate::math::Vec2;
+
+// Glow colors, as the "r, g, b" part of an rgba() color.
+pub const LAVA_GLOW: &str = "255, 110, 20";
+pub const LASER_GLOW: &str = "255, 40, 40";
+pub const INT_LASER_GLOW: &str = "255, 230, 80";
+pub const SPAWN_GLOW: &str = "140, 255, 255";
+
+fn rgba(rgb: &str, alpha: f32) -> JsValue {
+  JsValue::from_str(&format!("rgba({}, {})", rgb, alpha.clamp(0.0, 1.0)))
+}
+
+/// Clears the lighting layer and sets it up so that glows add together. The page blends
+/// the whole layer over the main layer, so anything drawn here brightens what's under it.
+pub fn begin(ctx: &CanvasRenderingContext2d) {
+  ctx.set_global_composite_operation("source-over").unwrap();
+  ctx.clear_rect(
+    0.0,
+    0.0,
+    crate::SCREEN_WIDTH as f64,
+    crate::SCREEN_HEIGHT as f64,
+  );
+  ctx.set_global_composite_operation("lighter").unwrap();
+}
+
+/// A soft round glow centered on `center`, in screen pixels, fading out to nothing at `radius`.
+pub fn draw_point_glow(
+  ctx: &CanvasRenderingContext2d,
+  center: Vec2,
+  radius: f32,
+  rgb: &str,
+  intensity: f32,
+) {
+  if intensity <= 0.0 || radius <= 0.0 {
+    return;
+  }
+  let (x, y) = (center.0 as f64, center.1 as f64);
+  let gradient = ctx.create_radial_gradient(x, y, 0.0, x, y, radius as f64).unwrap();
+  gradient.add_color_stop(0.0, &format!("rgba({}, {})", rgb, intensity.min(1.0))).unwrap();
+  gradient.add_color_stop(1.0, &format!("rgba({}, 0)", rgb)).unwrap();
+  ctx.set_fill_style(&gradient);
+  ctx.fill_rect(
+    x - radius as f64,
+    y - radius as f64,
+    2.0 * radius as f64,
+    2.0 * radius as f64,
+  );
+}
+
+/// A glow along a beam from `from` to `to`, in screen pixels, built up from a few strokes
+/// of widening width and falling alpha. Beams stop where they hit a wall, so the glow does too.
+pub fn draw_beam_glow(
+  ctx: &CanvasRenderingContext2d,
+  from: Vec2,
+  to: Vec2,
+  width: f32,
+  rgb: &str,
+  intensity: f32,
+) {
+  if intensity <= 0.0 {
+    return;
+  }
+  ctx.set_line_cap("round");
+  for (width_mult, alpha_mult) in [(1.0, 0.5), (2.5, 0.25), (5.0, 0.1)] {
+    ctx.set_stroke_style(&rgba(rgb, intensity * alpha_mult));
+    ctx.set_line_width((width * width_mult) as f64);
+    ctx.begin_path();
+    ctx.move_to(from.0 as f64, from.1 as f64);
+    ctx.line_to(to.0 as f64, to.1 as f64);
+    ctx.stroke();
+  }
+  ctx.set_line_cap("butt");
+}
@@ -0,0 +1,35 @@
+use crate::math::{Rect, Vec2};
+
+// Objects more than this many tiles outside the camera's view update less often.
+pub const FULL_RATE_MARGIN: f32 = 10.0;
+// Objects out of range update once every this many frames.
+pub const REDUCED_RATE_INTERVAL: u64 = 4;
+
+/// How often an object's update logic runs, depending on how far it is from the camera.
+/// Physics still moves everything every frame, so skipped updates just mean steering and
+/// timers lag behind a little, which nobody can see offscreen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateTier {
+  Full,
+  Reduced,
+}
+
+impl UpdateTier {
+  pub fn for_position(view: Rect, pos: Vec2) -> Self {
+    let margin = Vec2(FULL_RATE_MARGIN, FULL_RATE_MARGIN);
+    let nearby = Rect::new(view.pos - margin, view.size + 2.0 * margin);
+    match nearby.contains_point(pos) {
+      true => UpdateTier::Full,
+      false => UpdateTier::Reduced,
+    }
+  }
+
+  /// Whether an object in this tier updates on the given frame. Reduced rate objects are
+  /// spread out across frames by `phase`, so they don't all update at once.
+  pub fn updates_on(self, frame: u64, phase: u64) -> bool {
+    match self {
+      UpdateTier::Full => true,
+      UpdateTier::Reduced => (frame + phase) % REDUCED_RATE_INTERVAL == 0,
+    }
+  }
+}
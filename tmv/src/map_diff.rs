@@ -0,0 +1,452 @@
+use std::{
+  collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+  fmt,
+};
+
+use anyhow::Error;
+use serde::Serialize;
+use tiled::{Chunk, Properties, PropertyValue};
+
+use crate::{collision, game_maps::GameMap, math::Vec2, tile_rendering::TILE_SIZE};
+
+// Objects that moved less than this, in tiles, count as staying put.
+const MOVE_EPSILON: f32 = 0.01;
+
+/// One gameplay-relevant difference between two versions of a map. Objects are matched up
+/// by their Tiled object ids, which stay the same across edits.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MapChange {
+  ObjectAdded {
+    layer:    String,
+    id:       u32,
+    name:     String,
+    position: Vec2,
+  },
+  ObjectRemoved {
+    layer:    String,
+    id:       u32,
+    name:     String,
+    position: Vec2,
+  },
+  ObjectMoved {
+    layer: String,
+    id:    u32,
+    name:  String,
+    from:  Vec2,
+    to:    Vec2,
+  },
+  // Its properties, type, or shape changed.
+  ObjectEdited {
+    layer:   String,
+    id:      u32,
+    name:    String,
+    details: Vec<String>,
+  },
+  // The tile in a cell of the main layer now has different properties, or is flipped.
+  TileEdited {
+    cell:    (i32, i32),
+    details: Vec<String>,
+  },
+  // A connected patch of cells that became solid, or stopped being solid.
+  WallsAdded {
+    top_left: (i32, i32),
+    size:     (i32, i32),
+    cells:    usize,
+  },
+  WallsRemoved {
+    top_left: (i32, i32),
+    size:     (i32, i32),
+    cells:    usize,
+  },
+}
+
+impl fmt::Display for MapChange {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      MapChange::ObjectAdded {
+        layer,
+        id,
+        name,
+        position,
+      } => write!(
+        f,
+        "Added {} #{} to {} at ({:.1}, {:.1})",
+        name, id, layer, position.0, position.1
+      ),
+      MapChange::ObjectRemoved {
+        layer,
+        id,
+        name,
+        position,
+      } => write!(
+        f,
+        "Removed {} #{} from {} at ({:.1}, {:.1})",
+        name, id, layer, position.0, position.1
+      ),
+      MapChange::ObjectMoved {
+        layer,
+        id,
+        name,
+        from,
+        to,
+      } => write!(
+        f,
+        "Moved {} #{} in {} from ({:.1}, {:.1}) to ({:.1}, {:.1})",
+        name, id, layer, from.0, from.1, to.0, to.1
+      ),
+      MapChange::ObjectEdited {
+        layer,
+        id,
+        name,
+        details,
+      } => write!(
+        f,
+        "Edited {} #{} in {}: {}",
+        name,
+        id,
+        layer,
+        details.join("; ")
+      ),
+      MapChange::TileEdited { cell, details } => write!(
+        f,
+        "Edited the tile at ({}, {}): {}",
+        cell.0,
+        cell.1,
+        details.join("; ")
+      ),
+      MapChange::WallsAdded {
+        top_left,
+        size,
+        cells,
+      } => write!(
+        f,
+        "Added {} wall tiles within {}x{} at ({}, {})",
+        cells, size.0, size.1, top_left.0, top_left.1
+      ),
+      MapChange::WallsRemoved {
+        top_left,
+        size,
+        cells,
+      } => write!(
+        f,
+        "Removed {} wall tiles within {}x{} at ({}, {})",
+        cells, size.0, size.1, top_left.0, top_left.1
+      ),
+    }
+  }
+}
+
+/// Everything about two versions of a map that matters to how it plays, for reviewing map
+/// edits without opening Tiled. Purely visual changes, like swapping one wall tile for
+/// another, or anything on the background layers, are left out.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MapDiff {
+  pub changes:       Vec<MapChange>,
+  // How many segments the walls are made of, before and after, as a rough measure of how
+  // much their shape changed.
+  pub wall_segments: (usize, usize),
+}
+
+impl MapDiff {
+  pub fn between(old: &GameMap, new: &GameMap) -> Self {
+    let mut changes = diff_objects(&map_objects(old), &map_objects(new));
+    let (old_tiles, new_tiles) = (main_layer_tiles(old), main_layer_tiles(new));
+    changes.extend(diff_tiles(&old_tiles, &new_tiles));
+    let solid_cells = |tiles: &HashMap<(i32, i32), TileSummary>| -> HashSet<(i32, i32)> {
+      tiles.iter().filter(|(_, tile)| tile.is_solid()).map(|(cell, _)| *cell).collect()
+    };
+    let (old_solid, new_solid) = (solid_cells(&old_tiles), solid_cells(&new_tiles));
+    for patch in connected_patches(new_solid.difference(&old_solid).copied().collect()) {
+      let (top_left, size) = bounding_box(&patch);
+      changes.push(MapChange::WallsAdded {
+        top_left,
+        size,
+        cells: patch.len(),
+      });
+    }
+    for patch in connected_patches(old_solid.difference(&new_solid).copied().collect()) {
+      let (top_left, size) = bounding_box(&patch);
+      changes.push(MapChange::WallsRemoved {
+        top_left,
+        size,
+        cells: patch.len(),
+      });
+    }
+    Self {
+      changes,
+      wall_segments: (
+        collision::generate_walls(&old_solid).len(),
+        collision::generate_walls(&new_solid).len(),
+      ),
+    }
+  }
+
+  /// Loads both versions of a TMX, using the tilesets in `resources`, and compares them.
+  pub fn from_tmx(
+    resources: &HashMap<String, Vec<u8>>,
+    old_tmx: &[u8],
+    new_tmx: &[u8],
+  ) -> Result<Self, Error> {
+    // Next to the tilesets, so the maps' relative tileset paths resolve.
+    const MAP_NAME: &str = "/assets/map_diff.tmx";
+    let load = |tmx: &[u8]| {
+      let mut map_resources: HashMap<String, Vec<u8>> = resources
+        .iter()
+        .filter(|(name, _)| name.ends_with(".tsx"))
+        .map(|(name, data)| (name.clone(), data.clone()))
+        .collect();
+      map_resources.insert(MAP_NAME.to_string(), tmx.to_vec());
+      GameMap::from_resources(&map_resources, MAP_NAME)
+    };
+    Ok(Self::between(&load(old_tmx)?, &load(new_tmx)?))
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.changes.is_empty()
+  }
+
+  /// Every change, one per line.
+  pub fn report(&self) -> String {
+    let mut lines: Vec<String> = self.changes.iter().map(|change| change.to_string()).collect();
+    if self.wall_segments.0 != self.wall_segments.1 {
+      lines.push(format!(
+        "Walls went from {} segments to {}",
+        self.wall_segments.0, self.wall_segments.1
+      ));
+    }
+    match lines.is_empty() {
+      true => "No gameplay changes".to_string(),
+      false => lines.join("\n"),
+    }
+  }
+}
+
+struct ObjectSummary {
+  name:       String,
+  position:   Vec2,
+  user_type:  String,
+  // Compared as text, since that's all a reviewer needs from it.
+  shape:      String,
+  properties: Properties,
+}
+
+/// Every object in the map's object layers, by layer name and object id.
+fn map_objects(game_map: &GameMap) -> BTreeMap<(String, u32), ObjectSummary> {
+  let mut objects = BTreeMap::new();
+  for layer in game_map.map.layers() {
+    let tiled::LayerType::ObjectLayer(object_layer) = layer.layer_type() else {
+      continue;
+    };
+    for object in object_layer.objects() {
+      // Like the game, go by the name property, which is what says what a rect does.
+      let name = match object.properties.get("name") {
+        Some(PropertyValue::StringValue(s)) => s.clone(),
+        _ if !object.name.is_empty() => object.name.clone(),
+        _ => "object".to_string(),
+      };
+      objects.insert(
+        (layer.name.clone(), object.id()),
+        ObjectSummary {
+          name,
+          position: Vec2(object.x / TILE_SIZE, object.y / TILE_SIZE),
+          user_type: object.user_type.clone(),
+          shape: format!("{:?}", object.shape),
+          properties: object.properties.clone(),
+        },
+      );
+    }
+  }
+  objects
+}
+
+fn diff_objects(
+  old: &BTreeMap<(String, u32), ObjectSummary>,
+  new: &BTreeMap<(String, u32), ObjectSummary>,
+) -> Vec<MapChange> {
+  let mut changes = Vec::new();
+  for ((layer, id), object) in old {
+    if !new.contains_key(&(layer.clone(), *id)) {
+      changes.push(MapChange::ObjectRemoved {
+        layer:    layer.clone(),
+        id:       *id,
+        name:     object.name.clone(),
+        position: object.position,
+      });
+    }
+  }
+  for ((layer, id), object) in new {
+    let Some(before) = old.get(&(layer.clone(), *id)) else {
+      changes.push(MapChange::ObjectAdded {
+        layer:    layer.clone(),
+        id:       *id,
+        name:     object.name.clone(),
+        position: object.position,
+      });
+      continue;
+    };
+    if (object.position - before.position).length() > MOVE_EPSILON {
+      changes.push(MapChange::ObjectMoved {
+        layer: layer.clone(),
+        id:    *id,
+        name:  object.name.clone(),
+        from:  before.position,
+        to:    object.position,
+      });
+    }
+    let mut details = property_changes(&before.properties, &object.properties);
+    if object.user_type != before.user_type {
+      details.push(format!(
+        "type: {:?} -> {:?}",
+        before.user_type, object.user_type
+      ));
+    }
+    if object.shape != before.shape {
+      details.push("reshaped".to_string());
+    }
+    if !details.is_empty() {
+      changes.push(MapChange::ObjectEdited {
+        layer: layer.clone(),
+        id: *id,
+        name: object.name.clone(),
+        details,
+      });
+    }
+  }
+  changes
+}
+
+#[derive(Default)]
+struct TileSummary {
+  // Empty for plain walls.
+  user_type:  String,
+  properties: Properties,
+  // Flipping a tile turns things like shooters and springs around.
+  flips:      (bool, bool, bool),
+}
+
+impl TileSummary {
+  fn is_solid(&self) -> bool {
+    collision::is_solid_tile(&self.user_type)
+  }
+}
+
+/// Every tile in the map's main layer, by cell. Empty cells are left out.
+fn main_layer_tiles(game_map: &GameMap) -> HashMap<(i32, i32), TileSummary> {
+  let mut tiles = HashMap::new();
+  let tiled::LayerType::TileLayer(tiled::TileLayer::Infinite(data)) =
+    game_map.get_main_layer().layer_type()
+  else {
+    return tiles;
+  };
+  for (chunk_pos, chunk) in data.chunks() {
+    for x in 0..Chunk::WIDTH as i32 {
+      for y in 0..Chunk::HEIGHT as i32 {
+        let Some(tile) = chunk.get_tile(x, y) else {
+          continue;
+        };
+        let Some(base_tile) = tile.get_tile() else {
+          continue;
+        };
+        let cell = (
+          chunk_pos.0 * Chunk::WIDTH as i32 + x,
+          chunk_pos.1 * Chunk::HEIGHT as i32 + y,
+        );
+        tiles.insert(
+          cell,
+          TileSummary {
+            user_type:  base_tile.user_type.clone().unwrap_or_default(),
+            properties: base_tile.properties.clone(),
+            flips:      (tile.flip_h, tile.flip_v, tile.flip_d),
+          },
+        );
+      }
+    }
+  }
+  tiles
+}
+
+fn diff_tiles(
+  old: &HashMap<(i32, i32), TileSummary>,
+  new: &HashMap<(i32, i32), TileSummary>,
+) -> Vec<MapChange> {
+  let empty = TileSummary::default();
+  let cells: BTreeSet<(i32, i32)> = old.keys().chain(new.keys()).copied().collect();
+  let mut changes = Vec::new();
+  for cell in cells {
+    let before = old.get(&cell).unwrap_or(&empty);
+    let after = new.get(&cell).unwrap_or(&empty);
+    let mut details = property_changes(&before.properties, &after.properties);
+    // A tile that was added or removed has no type to compare against.
+    if old.contains_key(&cell) && new.contains_key(&cell) && before.user_type != after.user_type {
+      details.push(format!(
+        "type: {:?} -> {:?}",
+        before.user_type, after.user_type
+      ));
+    }
+    // Plain walls look the same to the game whichever way they're flipped.
+    if details.is_empty() && !after.properties.is_empty() && before.flips != after.flips {
+      details.push("flipped".to_string());
+    }
+    if !details.is_empty() {
+      changes.push(MapChange::TileEdited { cell, details });
+    }
+  }
+  changes
+}
+
+/// Each property that differs, like "damage: 2 -> 3", in name order.
+fn property_changes(old: &Properties, new: &Properties) -> Vec<String> {
+  let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+  let text = |value: Option<&PropertyValue>| match value {
+    None => "none".to_string(),
+    Some(PropertyValue::StringValue(s)) => s.clone(),
+    Some(PropertyValue::IntValue(i)) => i.to_string(),
+    Some(PropertyValue::FloatValue(x)) => x.to_string(),
+    Some(PropertyValue::BoolValue(b)) => b.to_string(),
+    Some(other) => format!("{:?}", other),
+  };
+  names
+    .into_iter()
+    .filter(|name| old.get(*name) != new.get(*name))
+    .map(|name| {
+      format!(
+        "{}: {} -> {}",
+        name,
+        text(old.get(name)),
+        text(new.get(name))
+      )
+    })
+    .collect()
+}
+
+/// Splits cells into groups that touch along an edge, each sorted, in order of their first
+/// cell.
+fn connected_patches(mut cells: HashSet<(i32, i32)>) -> Vec<Vec<(i32, i32)>> {
+  let mut patches = Vec::new();
+  while let Some(&start) = cells.iter().min() {
+    cells.remove(&start);
+    let mut patch = vec![start];
+    let mut frontier = vec![start];
+    while let Some((x, y)) = frontier.pop() {
+      for neighbor in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+        if cells.remove(&neighbor) {
+          patch.push(neighbor);
+          frontier.push(neighbor);
+        }
+      }
+    }
+    patch.sort();
+    patches.push(patch);
+  }
+  patches
+}
+
+// The top-left cell and size, in cells, of the smallest box around them all.
+fn bounding_box(cells: &[(i32, i32)]) -> ((i32, i32), (i32, i32)) {
+  let min_x = cells.iter().map(|c| c.0).min().unwrap_or(0);
+  let max_x = cells.iter().map(|c| c.0).max().unwrap_or(0);
+  let min_y = cells.iter().map(|c| c.1).min().unwrap_or(0);
+  let max_y = cells.iter().map(|c| c.1).max().unwrap_or(0);
+  ((min_x, min_y), (max_x - min_x + 1, max_y - min_y + 1))
+}
@@ -0,0 +1,56 @@
+use wasm_bindgen::JsValue;
+use web_sys::CanvasRenderingContext2d;
+
+use crate::{math::Vec2, theme::Theme};
+
+/// Something marked on the map screen, in a part of the map the player has revealed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapMarker {
+  SavePoint,
+  // Only marked with the map_coin_markers setting on.
+  Coin,
+  RareCoin,
+  Boss,
+}
+
+impl MapMarker {
+  /// Draws the marker's icon centered on `pos`, in screen pixels. Icons stay the same size
+  /// however far the map is zoomed, so they stay readable zoomed out.
+  pub fn draw(self, ctx: &CanvasRenderingContext2d, theme: &Theme, pos: Vec2) {
+    let (x, y) = (pos.0 as f64, pos.1 as f64);
+    ctx.set_line_width(2.0);
+    match self {
+      MapMarker::SavePoint => {
+        ctx.set_fill_style(&JsValue::from_str("#8ff"));
+        ctx.set_stroke_style(&JsValue::from_str("#000"));
+        ctx.fill_rect(x - 5.0, y - 5.0, 10.0, 10.0);
+        ctx.stroke_rect(x - 5.0, y - 5.0, 10.0, 10.0);
+      }
+      MapMarker::Coin | MapMarker::RareCoin => {
+        let (style, radius) = match self {
+          MapMarker::Coin => ("coin", 3.0),
+          _ => ("rare_coin", 4.0),
+        };
+        theme.style(style).apply(ctx);
+        ctx.begin_path();
+        ctx.arc(x, y, radius, 0.0, std::f64::consts::TAU).unwrap();
+        ctx.fill();
+        ctx.stroke();
+        ctx.set_global_alpha(1.0);
+      }
+      // A diamond, to stand out from the round and square icons.
+      MapMarker::Boss => {
+        ctx.set_fill_style(&JsValue::from_str("#f80"));
+        ctx.set_stroke_style(&JsValue::from_str("#000"));
+        ctx.begin_path();
+        ctx.move_to(x, y - 8.0);
+        ctx.line_to(x + 8.0, y);
+        ctx.line_to(x, y + 8.0);
+        ctx.line_to(x - 8.0, y);
+        ctx.close_path();
+        ctx.fill();
+        ctx.stroke();
+      }
+    }
+  }
+}
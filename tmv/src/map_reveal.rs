@@ -0,0 +1,134 @@
+use std::collections::HashSet;
+
+use wasm_bindgen::JsValue;
+use web_sys::CanvasRenderingContext2d;
+
+use crate::{math::Vec2, settings::FogStyle};
+
+/// The parts of the map the player has uncovered, tracked as square chunks
+/// keyed by their top-left corner in tiles (so always multiples of the granularity).
+#[derive(Debug, Clone)]
+pub struct RevealedMap {
+  granularity: i32,
+  chunks:      HashSet<(i32, i32)>,
+}
+
+impl RevealedMap {
+  pub fn new(granularity: i32) -> Self {
+    Self::from_chunks(granularity, HashSet::new())
+  }
+
+  pub fn from_chunks(granularity: i32, chunks: HashSet<(i32, i32)>) -> Self {
+    Self {
+      granularity,
+      chunks,
+    }
+  }
+
+  pub fn granularity(&self) -> i32 {
+    self.granularity
+  }
+
+  pub fn chunks(&self) -> &HashSet<(i32, i32)> {
+    &self.chunks
+  }
+
+  pub fn chunk_corner(&self, pos: Vec2) -> (i32, i32) {
+    let g = self.granularity;
+    (
+      (pos.0 / g as f32).floor() as i32 * g,
+      (pos.1 / g as f32).floor() as i32 * g,
+    )
+  }
+
+  pub fn is_revealed(&self, corner: (i32, i32)) -> bool {
+    self.chunks.contains(&corner)
+  }
+
+  /// Reveals the given chunks, returning how many weren't already revealed.
+  pub fn reveal_chunks(&mut self, chunks: impl IntoIterator<Item = (i32, i32)>) -> usize {
+    chunks.into_iter().filter(|chunk| self.chunks.insert(*chunk)).count()
+  }
+
+  /// Reveals every chunk overlapping the box from `top_left` (inclusive) to `bottom_right` (exclusive).
+  pub fn reveal_rect(&mut self, top_left: Vec2, bottom_right: Vec2) -> usize {
+    let g = self.granularity;
+    let (x0, y0) = self.chunk_corner(top_left);
+    let x1 = (bottom_right.0 / g as f32).ceil() as i32 * g;
+    let y1 = (bottom_right.1 / g as f32).ceil() as i32 * g;
+    let chunks: Vec<_> = (x0..x1)
+      .step_by(g as usize)
+      .flat_map(|x| (y0..y1).step_by(g as usize).map(move |y| (x, y)))
+      .collect();
+    self.reveal_chunks(chunks)
+  }
+
+  /// Switches to a new chunk size, remapping what's been revealed so far.
+  /// Any new chunk overlapping an old revealed chunk counts as revealed, so
+  /// changing granularity never hides anything the player has already seen.
+  pub fn set_granularity(&mut self, granularity: i32) {
+    if granularity == self.granularity {
+      return;
+    }
+    let old_granularity = self.granularity;
+    let old_chunks = std::mem::take(&mut self.chunks);
+    self.granularity = granularity;
+    for (x, y) in old_chunks {
+      self.reveal_rect(
+        Vec2(x as f32, y as f32),
+        Vec2((x + old_granularity) as f32, (y + old_granularity) as f32),
+      );
+    }
+  }
+}
+
+/// Covers up one unrevealed chunk on the map screen in the given fog style.
+pub fn draw_fog_chunk(
+  context: &CanvasRenderingContext2d,
+  style: FogStyle,
+  revealed_map: &RevealedMap,
+  chunk: (i32, i32),
+  screen_pos: (f64, f64),
+  size: (f64, f64),
+) {
+  match style {
+    FogStyle::HardBlack => {
+      context.set_fill_style(&JsValue::from_str("#000"));
+      context.set_stroke_style(&JsValue::from_str("#444"));
+      context.begin_path();
+      context.rect(screen_pos.0, screen_pos.1, size.0, size.1);
+      context.fill();
+      context.stroke();
+    }
+    FogStyle::SoftGradient => {
+      // Chunks bordering revealed ones are only partly covered, which feathers the edge of the fog.
+      let g = revealed_map.granularity();
+      let revealed_neighbors = (-1..=1)
+        .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
+        .filter(|(dx, dy)| revealed_map.is_revealed((chunk.0 + dx * g, chunk.1 + dy * g)))
+        .count();
+      let alpha = 1.0 - 0.08 * revealed_neighbors as f64;
+      context.set_fill_style(&JsValue::from_str(&format!("rgba(0, 0, 0, {})", alpha)));
+      context.fill_rect(screen_pos.0, screen_pos.1, size.0, size.1);
+    }
+    FogStyle::Hatched => {
+      context.set_fill_style(&JsValue::from_str("#222"));
+      context.fill_rect(screen_pos.0, screen_pos.1, size.0, size.1);
+      context.save();
+      context.begin_path();
+      context.rect(screen_pos.0, screen_pos.1, size.0, size.1);
+      context.clip();
+      context.set_stroke_style(&JsValue::from_str("#555"));
+      context.begin_path();
+      let spacing = 8.0;
+      let mut offset = -size.1;
+      while offset < size.0 {
+        context.move_to(screen_pos.0 + offset, screen_pos.1 + size.1);
+        context.line_to(screen_pos.0 + offset + size.1, screen_pos.1);
+        offset += spacing;
+      }
+      context.stroke();
+      context.restore();
+    }
+  }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashSet;
+
+use wasm_bindgen::JsValue;
+use web_sys::CanvasRenderingContext2d;
+
+use crate::{
+  camera::{Camera, CameraBounds},
+  math::{geometry::point_in_polygon, Vec2},
+};
+
+// Edges within this distance of lying on the same line, and overlapping by at least this
+// much, are a door between their rooms.
+const DOOR_EPSILON: f32 = 0.05;
+// How much of each end of a shared edge to leave off when drawing its door.
+const DOOR_INSET: f32 = 0.25;
+
+/// A room on the map: one of the closed polygons from the CameraBounds layer.
+pub struct Room {
+  pub outline: Vec<Vec2>,
+}
+
+/// Where two rooms share a stretch of wall, which the player can pass through.
+pub struct Door {
+  pub rooms: (usize, usize),
+  pub a:     Vec2,
+  pub b:     Vec2,
+}
+
+/// The rooms and the doors between them, for drawing the map screen.
+pub struct RoomMap {
+  pub rooms: Vec<Room>,
+  pub doors: Vec<Door>,
+}
+
+// The stretch of `edge` that `other` lies along, if they're collinear and overlap.
+fn shared_span(edge: (Vec2, Vec2), other: (Vec2, Vec2)) -> Option<(Vec2, Vec2)> {
+  let length = (edge.1 - edge.0).length();
+  if length < DOOR_EPSILON {
+    return None;
+  }
+  let along = (edge.1 - edge.0).to_unit();
+  let off_line = |p: Vec2| along.cross(p - edge.0).abs() > DOOR_EPSILON;
+  if off_line(other.0) || off_line(other.1) {
+    return None;
+  }
+  let (t0, t1) = (along.dot(other.0 - edge.0), along.dot(other.1 - edge.0));
+  let start = t0.min(t1).max(0.0);
+  let end = t0.max(t1).min(length);
+  match end - start > DOOR_EPSILON {
+    true => Some((edge.0 + start * along, edge.0 + end * along)),
+    false => None,
+  }
+}
+
+fn edges(outline: &[Vec2]) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+  (0..outline.len()).map(move |i| (outline[i], outline[(i + 1) % outline.len()]))
+}
+
+impl RoomMap {
+  /// Open polylines in the bounds only constrain the camera, so they aren't rooms.
+  pub fn from_camera_bounds(bounds: &CameraBounds) -> Self {
+    let rooms: Vec<Room> = bounds
+      .polylines
+      .iter()
+      .filter(|polyline| polyline.closed && polyline.points.len() >= 3)
+      .map(|polyline| Room {
+        outline: polyline.points.clone(),
+      })
+      .collect();
+    let mut doors = Vec::new();
+    for i in 0..rooms.len() {
+      for j in i + 1..rooms.len() {
+        for edge in edges(&rooms[i].outline) {
+          for other in edges(&rooms[j].outline) {
+            if let Some((a, b)) = shared_span(edge, other) {
+              doors.push(Door {
+                rooms: (i, j),
+                a,
+                b,
+              });
+            }
+          }
+        }
+      }
+    }
+    Self { rooms, doors }
+  }
+
+  /// The room containing `pos`, if any. Where rooms overlap, the first one wins.
+  pub fn room_at(&self, pos: Vec2) -> Option<usize> {
+    self.rooms.iter().position(|room| point_in_polygon(pos, &room.outline))
+  }
+
+  /// Draws the visited rooms filled in, and the outlines of rooms that are at least partly
+  /// revealed, with the doors out of visited rooms.
+  pub fn draw(
+    &self,
+    ctx: &CanvasRenderingContext2d,
+    camera: &Camera,
+    visited: &HashSet<usize>,
+    is_revealed: impl Fn(Vec2) -> bool,
+  ) {
+    ctx.set_line_width(2.0);
+    for (i, room) in self.rooms.iter().enumerate() {
+      let is_visited = visited.contains(&i);
+      if !is_visited && !room.outline.iter().any(|p| is_revealed(*p)) {
+        continue;
+      }
+      ctx.begin_path();
+      for (j, point) in room.outline.iter().enumerate() {
+        let p = camera.world_to_screen(*point);
+        match j {
+          0 => ctx.move_to(p.0 as f64, p.1 as f64),
+          _ => ctx.line_to(p.0 as f64, p.1 as f64),
+        }
+      }
+      ctx.close_path();
+      if is_visited {
+        ctx.set_fill_style(&JsValue::from_str("rgba(90, 140, 255, 0.25)"));
+        ctx.fill();
+      }
+      ctx.set_stroke_style(&JsValue::from_str(match is_visited {
+        true => "#9cf",
+        false => "rgba(150, 160, 180, 0.6)",
+      }));
+      ctx.stroke();
+    }
+    ctx.set_stroke_style(&JsValue::from_str("#fc4"));
+    ctx.set_line_width(4.0);
+    for door in &self.doors {
+      if !visited.contains(&door.rooms.0) && !visited.contains(&door.rooms.1) {
+        continue;
+      }
+      let along = door.b - door.a;
+      let inset = (DOOR_INSET / along.length()).min(0.5);
+      let (a, b) = (
+        camera.world_to_screen(door.a + inset * along),
+        camera.world_to_screen(door.b - inset * along),
+      );
+      ctx.begin_path();
+      ctx.move_to(a.0 as f64, a.1 as f64);
+      ctx.line_to(b.0 as f64, b.1 as f64);
+      ctx.stroke();
+    }
+  }
+}
@@ -0,0 +1,42 @@
+//! Operations over whole slices of points, written as plain loops over flat data so
+//! they autovectorize, for culling and drawing many things at once.
+
+use super::{Rect, Vec2};
+
+/// Writes `scale * (p - origin)` for each point into `out`. With `origin` the camera
+/// position and `scale` the tile size, this is world to screen coordinates.
+pub fn transform_points(points: &[Vec2], origin: Vec2, scale: f32, out: &mut [Vec2]) {
+  assert_eq!(points.len(), out.len());
+  for (p, o) in points.iter().zip(out.iter_mut()) {
+    *o = Vec2(scale * (p.0 - origin.0), scale * (p.1 - origin.1));
+  }
+}
+
+/// The inverse of `transform_points`: writes `origin + p / scale` for each point into `out`.
+pub fn untransform_points(points: &[Vec2], origin: Vec2, scale: f32, out: &mut [Vec2]) {
+  assert_eq!(points.len(), out.len());
+  let inv_scale = 1.0 / scale;
+  for (p, o) in points.iter().zip(out.iter_mut()) {
+    *o = Vec2(origin.0 + inv_scale * p.0, origin.1 + inv_scale * p.1);
+  }
+}
+
+/// The smallest rect containing every point, or None for no points.
+pub fn bounding_rect(points: &[Vec2]) -> Option<Rect> {
+  let first = *points.first()?;
+  let (mut min, mut max) = (first, first);
+  for p in &points[1..] {
+    min = Vec2(min.0.min(p.0), min.1.min(p.1));
+    max = Vec2(max.0.max(p.0), max.1.max(p.1));
+  }
+  Some(Rect::new(min, max - min))
+}
+
+/// Sets each entry of `mask` to whether the matching point is in `rect` (by `Rect::contains_point`).
+pub fn points_in_rect(points: &[Vec2], rect: Rect, mask: &mut [bool]) {
+  assert_eq!(points.len(), mask.len());
+  let max = rect.max_corner();
+  for (p, m) in points.iter().zip(mask.iter_mut()) {
+    *m = (p.0 >= rect.pos.0) & (p.0 < max.0) & (p.1 >= rect.pos.1) & (p.1 < max.1);
+  }
+}
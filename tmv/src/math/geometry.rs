@@ -0,0 +1,117 @@
+//! Intersection tests between moving boxes and line segments.
+
+use super::{Rect, Vec2};
+
+/// Where a moving box first touches another, from `sweep_rect`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SweepHit {
+  // Fraction of the movement completed at first contact, from 0 to 1.
+  pub time:   f32,
+  // The face of the target that was hit, pointing back at the moving box. Zero if they
+  // already overlapped.
+  pub normal: Vec2,
+}
+
+/// Sweeps `moving` along `delta`, and reports when it first touches `target`, if it does
+/// before the end of the movement. Boxes that only end up touching along an edge don't count.
+pub fn sweep_rect(moving: Rect, delta: Vec2, target: Rect) -> Option<SweepHit> {
+  if moving.overlaps(target) {
+    return Some(SweepHit {
+      time:   0.0,
+      normal: Vec2::default(),
+    });
+  }
+  let mut entry = f32::NEG_INFINITY;
+  let mut exit = f32::INFINITY;
+  let mut normal = Vec2::default();
+  let axes = [
+    (
+      moving.pos.0,
+      moving.size.0,
+      delta.0,
+      target.pos.0,
+      target.size.0,
+      Vec2(1.0, 0.0),
+    ),
+    (
+      moving.pos.1,
+      moving.size.1,
+      delta.1,
+      target.pos.1,
+      target.size.1,
+      Vec2(0.0, 1.0),
+    ),
+  ];
+  for (pos, size, d, target_pos, target_size, axis) in axes {
+    if d == 0.0 {
+      // Not moving on this axis, so we have to already be within the target's span.
+      if pos + size <= target_pos || target_pos + target_size <= pos {
+        return None;
+      }
+      continue;
+    }
+    let (near, far, face) = match d > 0.0 {
+      true => (
+        target_pos - (pos + size),
+        target_pos + target_size - pos,
+        -axis,
+      ),
+      false => (
+        target_pos + target_size - pos,
+        target_pos - (pos + size),
+        axis,
+      ),
+    };
+    let (axis_entry, axis_exit) = (near / d, far / d);
+    if axis_entry > entry {
+      entry = axis_entry;
+      normal = face;
+    }
+    exit = exit.min(axis_exit);
+  }
+  match entry < exit && (0.0..=1.0).contains(&entry) {
+    true => Some(SweepHit {
+      time: entry,
+      normal,
+    }),
+    false => None,
+  }
+}
+
+/// Where segments `a0 -> a1` and `b0 -> b1` cross, as the fraction along each of them.
+/// Parallel segments never intersect, even if they overlap.
+pub fn segment_intersection(a0: Vec2, a1: Vec2, b0: Vec2, b1: Vec2) -> Option<(f32, f32)> {
+  let (da, db) = (a1 - a0, b1 - b0);
+  let denominator = da.cross(db);
+  if denominator == 0.0 {
+    return None;
+  }
+  let offset = b0 - a0;
+  let t = offset.cross(db) / denominator;
+  let u = offset.cross(da) / denominator;
+  match (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+    true => Some((t, u)),
+    false => None,
+  }
+}
+
+/// The point where two segments cross, if they do.
+pub fn segment_intersection_point(a0: Vec2, a1: Vec2, b0: Vec2, b1: Vec2) -> Option<Vec2> {
+  segment_intersection(a0, a1, b0, b1).map(|(t, _)| a0 + t * (a1 - a0))
+}
+
+/// Whether `p` is inside the polygon with the given vertices (in either winding order), by
+/// counting edge crossings of a ray going right from it.
+pub fn point_in_polygon(p: Vec2, polygon: &[Vec2]) -> bool {
+  let mut inside = false;
+  for i in 0..polygon.len() {
+    let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+    if (a.1 > p.1) != (b.1 > p.1) {
+      let crossing_x = a.0 + (p.1 - a.1) / (b.1 - a.1) * (b.0 - a.0);
+      if p.0 < crossing_x {
+        inside = !inside;
+      }
+    }
+  }
+  inside
+}
@@ -1,7 +1,14 @@
+//! Geometry primitives. Nothing in here depends on the rest of the crate.
+
 use serde::{Deserialize, Serialize};
 
+pub mod batch;
+pub mod geometry;
+
+// repr(C) so that a slice of Vec2s is a flat run of f32s, which the batch ops vectorize over.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, ts_rs::TS)]
 #[ts(export)]
+#[repr(C)]
 pub struct Vec2(pub f32, pub f32);
 
 impl Vec2 {
@@ -9,6 +16,15 @@ impl Vec2 {
     (self.0 * self.0 + self.1 * self.1).sqrt()
   }
 
+  pub fn dot(self, other: Self) -> f32 {
+    self.0 * other.0 + self.1 * other.1
+  }
+
+  /// The z component of the 3D cross product.
+  pub fn cross(self, other: Self) -> f32 {
+    self.0 * other.1 - self.1 * other.0
+  }
+
   pub fn to_unit(self) -> Self {
     let c = 1.0 / self.length();
     Self(c * self.0, c * self.1)
@@ -107,7 +123,7 @@ impl std::ops::Neg for Vec2 {
   }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, ts_rs::TS)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, ts_rs::TS)]
 #[ts(export)]
 pub struct Rect {
   pub pos:  Vec2,
@@ -126,11 +142,22 @@ impl Rect {
       && p.1 < self.pos.1 + self.size.1
   }
 
+  // Unlike contains_point, the far edges are inclusive here, so a rect contains itself.
   pub fn contains_rect(self, r: Rect) -> bool {
-    // FIXME: Simplify this
-    self.contains_point(r.pos)
-      && self.contains_point(r.pos + Vec2(r.size.0, 0.0))
-      && self.contains_point(r.pos + Vec2(0.0, r.size.1))
-      && self.contains_point(r.pos + r.size)
+    r.pos.0 >= self.pos.0
+      && r.pos.1 >= self.pos.1
+      && r.pos.0 + r.size.0 <= self.pos.0 + self.size.0
+      && r.pos.1 + r.size.1 <= self.pos.1 + self.size.1
+  }
+
+  pub fn max_corner(self) -> Vec2 {
+    self.pos + self.size
+  }
+
+  pub fn overlaps(self, r: Rect) -> bool {
+    r.pos.0 < self.pos.0 + self.size.0
+      && self.pos.0 < r.pos.0 + r.size.0
+      && r.pos.1 < self.pos.1 + self.size.1
+      && self.pos.1 < r.pos.1 + r.size.1
   }
 }
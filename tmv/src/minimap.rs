@@ -0,0 +1,149 @@
+use tiled::Chunk;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{CanvasRenderingContext2d, Document, HtmlCanvasElement};
+
+use crate::{
+  collision,
+  game_maps::{GameMap, Placement},
+  math::{Rect, Vec2},
+};
+
+// Open space left around the map's outermost tiles on the map screen, in tiles.
+pub const MARGIN: i32 = 8;
+const EMPTY_COLOR: &str = "#223";
+const SOLID_COLOR: &str = "#778";
+
+/// Which of a map's main layer tiles are solid, for drawing on the map screen. This is
+/// worked out from the map itself, so the map screen always matches it.
+#[derive(Debug, Clone, Default)]
+pub struct Minimap {
+  // The part of the world the map screen covers, as the top left and bottom right corners,
+  // in tiles.
+  pub tile_bounds: ((i32, i32), (i32, i32)),
+  // Horizontal runs of solid tiles, as (x, y, length), in tiles.
+  pub solid_runs:  Vec<(i32, i32, i32)>,
+}
+
+impl Minimap {
+  pub fn from_game_map(game_map: &GameMap) -> Self {
+    Self::from_game_map_at(game_map, Placement::default())
+  }
+
+  /// The minimap of a map put wherever `placement` puts it, as with
+  /// `CollisionWorld::load_game_map_at`.
+  pub fn from_game_map_at(game_map: &GameMap, placement: Placement) -> Self {
+    let offset = placement.offset;
+    let mut solid_cells = Vec::new();
+    if let tiled::LayerType::TileLayer(tiled::TileLayer::Infinite(data)) =
+      game_map.get_main_layer().layer_type()
+    {
+      for (chunk_pos, chunk) in data.chunks() {
+        for x in 0..Chunk::WIDTH as i32 {
+          for y in 0..Chunk::HEIGHT as i32 {
+            let Some(tile) = chunk.get_tile(x, y) else {
+              continue;
+            };
+            let base_tile = tile.get_tile().unwrap();
+            if !collision::is_solid_tile(base_tile.user_type.as_deref().unwrap_or("")) {
+              continue;
+            }
+            solid_cells.push((
+              chunk_pos.0 * Chunk::WIDTH as i32 + x + offset.0,
+              chunk_pos.1 * Chunk::HEIGHT as i32 + y + offset.1,
+            ));
+          }
+        }
+      }
+    }
+    if solid_cells.is_empty() {
+      return Self {
+        tile_bounds: ((0, 0), (1, 1)),
+        solid_runs:  Vec::new(),
+      };
+    }
+
+    let mut tile_bounds = (solid_cells[0], solid_cells[0]);
+    for &(x, y) in &solid_cells {
+      tile_bounds.0 = (tile_bounds.0 .0.min(x), tile_bounds.0 .1.min(y));
+      tile_bounds.1 = (tile_bounds.1 .0.max(x + 1), tile_bounds.1 .1.max(y + 1));
+    }
+    let tile_bounds = (
+      (tile_bounds.0 .0 - MARGIN, tile_bounds.0 .1 - MARGIN),
+      (tile_bounds.1 .0 + MARGIN, tile_bounds.1 .1 + MARGIN),
+    );
+
+    // Merge neighboring cells in each row, so drawing takes far fewer rects.
+    solid_cells.sort_by_key(|&(x, y)| (y, x));
+    let mut solid_runs: Vec<(i32, i32, i32)> = Vec::new();
+    for (x, y) in solid_cells {
+      match solid_runs.last_mut() {
+        Some(run) if run.1 == y && run.0 + run.2 == x => run.2 += 1,
+        _ => solid_runs.push((x, y, 1)),
+      }
+    }
+
+    Self {
+      tile_bounds,
+      solid_runs,
+    }
+  }
+
+  /// The part of the world the map screen covers.
+  pub fn bounds(&self) -> Rect {
+    let ((x0, y0), (x1, y1)) = self.tile_bounds;
+    Rect::new(
+      Vec2(x0 as f32, y0 as f32),
+      Vec2((x1 - x0) as f32, (y1 - y0) as f32),
+    )
+  }
+
+  /// World space to uv coordinates in the map screen's bounds (ranging from 0 to 1).
+  pub fn world_to_uv(&self, world: Vec2) -> Vec2 {
+    let bounds = self.bounds();
+    Vec2(
+      (world.0 - bounds.pos.0) / bounds.size.0,
+      (world.1 - bounds.pos.1) / bounds.size.1,
+    )
+  }
+}
+
+/// The map screen's picture of a minimap, rasterized into an offscreen canvas once at load,
+/// one pixel per tile.
+pub struct MinimapRenderer {
+  canvas: HtmlCanvasElement,
+}
+
+impl MinimapRenderer {
+  pub fn new(document: &Document, minimap: &Minimap) -> Result<Self, JsValue> {
+    let canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+    let ((x0, y0), (x1, y1)) = minimap.tile_bounds;
+    canvas.set_width((x1 - x0) as u32);
+    canvas.set_height((y1 - y0) as u32);
+    let ctx = canvas
+      .get_context("2d")?
+      .ok_or_else(|| JsValue::from_str("No 2d context"))?
+      .dyn_into::<CanvasRenderingContext2d>()?;
+    ctx.set_fill_style(&JsValue::from_str(EMPTY_COLOR));
+    ctx.fill_rect(0.0, 0.0, canvas.width() as f64, canvas.height() as f64);
+    ctx.set_fill_style(&JsValue::from_str(SOLID_COLOR));
+    for &(x, y, length) in &minimap.solid_runs {
+      ctx.fill_rect((x - x0) as f64, (y - y0) as f64, length as f64, 1.0);
+    }
+    Ok(Self { canvas })
+  }
+
+  /// Draws the whole minimap stretched over `rect`, in screen pixels, keeping tiles sharp.
+  pub fn draw(&self, ctx: &CanvasRenderingContext2d, rect: Rect) {
+    ctx.set_image_smoothing_enabled(false);
+    ctx
+      .draw_image_with_html_canvas_element_and_dw_and_dh(
+        &self.canvas,
+        rect.pos.0 as f64,
+        rect.pos.1 as f64,
+        rect.size.0 as f64,
+        rect.size.1 as f64,
+      )
+      .unwrap();
+    ctx.set_image_smoothing_enabled(true);
+  }
+}
@@ -0,0 +1,192 @@
+use wasm_bindgen::JsValue;
+use web_sys::CanvasRenderingContext2d;
+
+use crate::{camera::Camera, math::Vec2};
+
+const PARTICLE_GRAVITY: f32 = 20.0;
+// Streaks are drawn as long as the distance they cover in this many seconds.
+const STREAK_TRAIL_TIME: f32 = 0.08;
+
+#[derive(Debug, Clone)]
+pub struct Particle {
+  pub pos:      Vec2,
+  pub vel:      Vec2,
+  pub life:     f32,
+  pub max_life: f32,
+  pub size:     f32,
+  pub color:    &'static str,
+  // Streaks float along without falling, drawn as lines along their velocity, like wind.
+  pub streak:   bool,
+}
+
+// How fast floaty text drifts upward, in tiles per second.
+const FLOATY_TEXT_SPEED: f32 = 1.0;
+
+/// Text that drifts up and fades out, like damage numbers. It's purely visual, so unlike
+/// game objects it has no physics body.
+#[derive(Debug, Clone)]
+pub struct FloatyText {
+  pub pos:       Vec2,
+  pub text:      String,
+  pub color:     String,
+  pub time_left: f32,
+}
+
+impl FloatyText {
+  /// Returns false once the text has faded out.
+  pub fn update(&mut self, dt: f32) -> bool {
+    self.pos.1 -= FLOATY_TEXT_SPEED * dt;
+    self.time_left -= dt;
+    self.time_left > 0.0
+  }
+
+  pub fn draw(&self, context: &CanvasRenderingContext2d, camera: &Camera) {
+    let screen_pos = camera.world_to_screen(self.pos);
+    context.set_font("32px Arial");
+    context.set_text_align("center");
+    context.set_text_baseline("middle");
+    context.set_fill_style(&JsValue::from_str(&self.color));
+    context.set_global_alpha(self.time_left.min(1.0) as f64);
+    context.fill_text(&self.text, screen_pos.0 as f64, screen_pos.1 as f64).unwrap();
+    context.set_global_alpha(1.0);
+  }
+}
+
+#[derive(Debug, Default)]
+pub struct ParticleSystem {
+  particles: Vec<Particle>,
+}
+
+/// Scales how many of some effect element to draw by the effects density (0 to 1).
+pub fn scaled_count(count: usize, density: f32) -> usize {
+  (count as f32 * density).round() as usize
+}
+
+impl ParticleSystem {
+  pub fn len(&self) -> usize {
+    self.particles.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.particles.is_empty()
+  }
+
+  /// Sprays particles out from `origin` in all directions. The count is scaled by
+  /// the effects density, and particles past `budget` live ones are dropped.
+  pub fn burst(
+    &mut self,
+    origin: Vec2,
+    count: usize,
+    speed: f32,
+    color: &'static str,
+    density: f32,
+    budget: usize,
+  ) {
+    for _ in 0..scaled_count(count, density) {
+      if self.particles.len() >= budget {
+        break;
+      }
+      let angle = rand::random::<f32>() * 2.0 * std::f32::consts::PI;
+      let speed = speed * (0.5 + 0.5 * rand::random::<f32>());
+      let life = 0.3 + 0.3 * rand::random::<f32>();
+      self.particles.push(Particle {
+        pos: origin,
+        vel: Vec2(speed * angle.cos(), speed * angle.sin()),
+        life,
+        max_life: life,
+        size: 0.15,
+        color,
+        streak: false,
+      });
+    }
+  }
+
+  /// Throws particles up and out from a point on a water surface, like `burst` but only
+  /// upward.
+  pub fn splash(
+    &mut self,
+    origin: Vec2,
+    count: usize,
+    speed: f32,
+    color: &'static str,
+    density: f32,
+    budget: usize,
+  ) {
+    for _ in 0..scaled_count(count, density) {
+      if self.particles.len() >= budget {
+        break;
+      }
+      // Within 60 degrees either side of straight up.
+      let angle = -std::f32::consts::FRAC_PI_2 + (rand::random::<f32>() - 0.5) * 2.0 * 1.05;
+      let speed = speed * (0.5 + 0.5 * rand::random::<f32>());
+      let life = 0.3 + 0.3 * rand::random::<f32>();
+      self.particles.push(Particle {
+        pos: origin,
+        vel: Vec2(speed * angle.cos(), speed * angle.sin()),
+        life,
+        max_life: life,
+        size: 0.12,
+        color,
+        streak: false,
+      });
+    }
+  }
+
+  /// Adds one streak drifting at `vel` from `origin`, unless there are already `budget`
+  /// live particles.
+  pub fn streak(&mut self, origin: Vec2, vel: Vec2, life: f32, color: &'static str, budget: usize) {
+    if self.particles.len() >= budget {
+      return;
+    }
+    self.particles.push(Particle {
+      pos: origin,
+      vel,
+      life,
+      max_life: life,
+      size: 0.05,
+      color,
+      streak: true,
+    });
+  }
+
+  pub fn update(&mut self, dt: f32) {
+    for particle in &mut self.particles {
+      if !particle.streak {
+        particle.vel.1 += PARTICLE_GRAVITY * dt;
+      }
+      particle.pos += dt * particle.vel;
+      particle.life -= dt;
+    }
+    self.particles.retain(|particle| particle.life > 0.0);
+  }
+
+  pub fn clear(&mut self) {
+    self.particles.clear();
+  }
+
+  pub fn draw(&self, context: &CanvasRenderingContext2d, camera: &Camera) {
+    for particle in &self.particles {
+      let size = camera.length_to_screen(particle.size);
+      let screen_pos = camera.world_to_screen(particle.pos);
+      context.set_global_alpha((particle.life / particle.max_life) as f64);
+      if particle.streak {
+        let tail = camera.world_to_screen(particle.pos - STREAK_TRAIL_TIME * particle.vel);
+        context.set_stroke_style(&JsValue::from_str(particle.color));
+        context.set_line_width(size as f64);
+        context.begin_path();
+        context.move_to(tail.0 as f64, tail.1 as f64);
+        context.line_to(screen_pos.0 as f64, screen_pos.1 as f64);
+        context.stroke();
+        continue;
+      }
+      context.set_fill_style(&JsValue::from_str(particle.color));
+      context.fill_rect(
+        (screen_pos.0 - size / 2.0) as f64,
+        (screen_pos.1 - size / 2.0) as f64,
+        size as f64,
+        size as f64,
+      );
+    }
+    context.set_global_alpha(1.0);
+  }
+}
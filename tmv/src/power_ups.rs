@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+/// An ability the player can pick up. These serialize as the snake_case names used in map
+/// properties, so saves from when power-ups were plain strings still load.
+#[derive(
+  Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, strum_macros::EnumIter,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerUp {
+  WallJump,
+  Dash,
+  Water,
+  Small,
+  Lava,
+  DoubleJump,
+  ExtraDash,
+  OmniDash,
+  Shoot,
+}
+
+impl PowerUp {
+  /// Parses the name used for the `powerup`, `requires_powerup`, and `drops` map properties.
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "wall_jump" => Some(PowerUp::WallJump),
+      "dash" => Some(PowerUp::Dash),
+      "water" => Some(PowerUp::Water),
+      "small" => Some(PowerUp::Small),
+      "lava" => Some(PowerUp::Lava),
+      "double_jump" => Some(PowerUp::DoubleJump),
+      "extra_dash" => Some(PowerUp::ExtraDash),
+      "omni_dash" => Some(PowerUp::OmniDash),
+      "shoot" => Some(PowerUp::Shoot),
+      _ => None,
+    }
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      PowerUp::WallJump => "wall_jump",
+      PowerUp::Dash => "dash",
+      PowerUp::Water => "water",
+      PowerUp::Small => "small",
+      PowerUp::Lava => "lava",
+      PowerUp::DoubleJump => "double_jump",
+      PowerUp::ExtraDash => "extra_dash",
+      PowerUp::OmniDash => "omni_dash",
+      PowerUp::Shoot => "shoot",
+    }
+  }
+
+  /// How a power-up is named to the player, matching the labels on the page.
+  pub fn display_name(self) -> &'static str {
+    match self {
+      PowerUp::WallJump => "Wall Jump",
+      PowerUp::Dash => "Dash",
+      PowerUp::Water => "Water Power",
+      PowerUp::Small => "Shrink",
+      PowerUp::Lava => "Fireproof",
+      PowerUp::DoubleJump => "Double Jump",
+      PowerUp::ExtraDash => "Extra Dash",
+      PowerUp::OmniDash => "Omni Dash",
+      PowerUp::Shoot => "Shoot",
+    }
+  }
+
+  /// The letters drawn on the pickup.
+  pub fn icon(self) -> &'static str {
+    match self {
+      PowerUp::WallJump => "WJ",
+      PowerUp::Dash => "D",
+      PowerUp::Water => "W",
+      PowerUp::Small => "S",
+      PowerUp::Lava => "F",
+      PowerUp::DoubleJump => "DJ",
+      PowerUp::ExtraDash => "D+",
+      PowerUp::OmniDash => "D*",
+      PowerUp::Shoot => "G",
+    }
+  }
+}
@@ -0,0 +1,102 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::{
+  camera::Boundary,
+  map_rooms::RoomMap,
+  math::{Rect, Vec2},
+  power_ups::PowerUp,
+};
+
+/// How long, in seconds, the player has to go without picking anything up before the route
+/// hint appears.
+pub const STUCK_TIME: f32 = 300.0;
+
+/// A gate rect, marking that passing through any door it covers needs a power-up (water to
+/// swim through, lava to wade through, and so on). Water and lava make gates of their own,
+/// and more can be drawn in the map as gate rects.
+#[derive(Debug, Clone)]
+pub struct Gate {
+  pub bounds:   Rect,
+  pub requires: PowerUp,
+}
+
+/// Gates over `cells`, which all need `requires`. Neighboring cells in a row share a gate,
+/// so there are fewer to check doors against.
+pub fn gates_from_cells(cells: &HashSet<(i32, i32)>, requires: PowerUp) -> Vec<Gate> {
+  let mut cells: Vec<(i32, i32)> = cells.iter().copied().collect();
+  cells.sort_by_key(|&(x, y)| (y, x));
+  let mut gates: Vec<Gate> = Vec::new();
+  for (x, y) in cells {
+    match gates.last_mut() {
+      Some(gate) if gate.bounds.pos.1 == y as f32 && gate.bounds.max_corner().0 == x as f32 => {
+        gate.bounds.size.0 += 1.0;
+      }
+      _ => gates.push(Gate {
+        bounds: Rect::new(Vec2(x as f32, y as f32), Vec2(1.0, 1.0)),
+        requires,
+      }),
+    }
+  }
+  gates
+}
+
+/// Which rooms lead to which, and what it takes to get through each door between them.
+pub struct ProgressionGraph {
+  // For each room, the rooms its doors lead to, with what each door needs.
+  neighbors: Vec<Vec<(usize, Vec<PowerUp>)>>,
+}
+
+impl ProgressionGraph {
+  pub fn new(rooms: &RoomMap, gates: &[Gate]) -> Self {
+    let mut neighbors = vec![Vec::new(); rooms.rooms.len()];
+    for door in &rooms.doors {
+      let segment = Boundary {
+        a: door.a,
+        b: door.b,
+      };
+      let requires: Vec<PowerUp> = gates
+        .iter()
+        .filter(|gate| segment.touches_rect(gate.bounds))
+        .map(|gate| gate.requires)
+        .collect();
+      let (i, j) = door.rooms;
+      neighbors[i].push((j, requires.clone()));
+      neighbors[j].push((i, requires));
+    }
+    Self { neighbors }
+  }
+
+  /// The rooms that can be got to from `start` with `power_ups`, nearest first by how many
+  /// doors away they are.
+  pub fn reachable_rooms(&self, start: usize, power_ups: &HashSet<PowerUp>) -> Vec<usize> {
+    let mut seen = HashSet::from([start]);
+    let mut order = Vec::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(room) = queue.pop_front() {
+      order.push(room);
+      for (next, requires) in &self.neighbors[room] {
+        if requires.iter().all(|p| power_ups.contains(p)) && seen.insert(*next) {
+          queue.push_back(*next);
+        }
+      }
+    }
+    order
+  }
+
+  /// Of the power-ups still out in the world (`available`, with where each is), the one in
+  /// the nearest room that can be got to from `start`.
+  pub fn next_power_up(
+    &self,
+    rooms: &RoomMap,
+    start: usize,
+    power_ups: &HashSet<PowerUp>,
+    available: &[(PowerUp, Vec2)],
+  ) -> Option<(PowerUp, Vec2)> {
+    self.reachable_rooms(start, power_ups).into_iter().find_map(|room| {
+      available
+        .iter()
+        .copied()
+        .find(|(power_up, pos)| !power_ups.contains(power_up) && rooms.room_at(*pos) == Some(room))
+    })
+  }
+}
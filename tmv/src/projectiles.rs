@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::Vec2;
+
+/// The kinds of enemy projectile. Turrets pick theirs with a `projectile` property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProjectileKind {
+  Bullet,
+  // Lobbed in an arc, bursting into fragments where it lands.
+  Spore,
+  Fragment,
+  // Splits into two bullets heading off at angles after a moment.
+  Splitter,
+  // Fired by the player with the shoot power-up. These hurt damageable objects rather than
+  // the player.
+  PlayerShot,
+}
+
+/// Things that fade out when the player leaves their room take this many seconds to go.
+pub const FADE_OUT_TIME: f32 = 1.0;
+
+/// What happens to a projectile or bee when the player leaves the room it's in. Respawning
+/// rebuilds the whole world, so everything goes then whatever its policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoomExitPolicy {
+  Persist,
+  Despawn,
+  FadeOut,
+}
+
+/// What a projectile turns into when it ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProjectileEnd {
+  Vanish,
+  // `count` children fanned out evenly over the upward half circle.
+  Burst {
+    kind:  ProjectileKind,
+    count: usize,
+    speed: f32,
+  },
+  // Two children, turned `angle` radians to either side of the projectile's heading.
+  Split {
+    kind:  ProjectileKind,
+    angle: f32,
+  },
+}
+
+pub struct ProjectileDescriptor {
+  pub launch_speed: f32,
+  // Extra upward speed at launch, for lobbed projectiles.
+  pub loft:         f32,
+  pub gravity:      f32,
+  pub radius:       f32,
+  // Seconds until the projectile ends on its own, if it doesn't hit anything first.
+  pub fuse:         Option<f32>,
+  pub on_fuse:      ProjectileEnd,
+  pub on_impact:    ProjectileEnd,
+  pub on_room_exit: RoomExitPolicy,
+  // The theme style it's drawn with.
+  pub style:        &'static str,
+}
+
+impl ProjectileKind {
+  pub fn from_property(name: &str) -> Option<Self> {
+    match name {
+      "bullet" => Some(ProjectileKind::Bullet),
+      "spore" => Some(ProjectileKind::Spore),
+      "splitter" => Some(ProjectileKind::Splitter),
+      _ => None,
+    }
+  }
+
+  pub fn descriptor(self) -> ProjectileDescriptor {
+    match self {
+      ProjectileKind::Bullet => ProjectileDescriptor {
+        launch_speed: 7.0,
+        loft:         0.0,
+        gravity:      0.0,
+        radius:       0.25,
+        fuse:         None,
+        on_fuse:      ProjectileEnd::Vanish,
+        on_impact:    ProjectileEnd::Vanish,
+        on_room_exit: RoomExitPolicy::Despawn,
+        style:        "bullet",
+      },
+      ProjectileKind::Spore => ProjectileDescriptor {
+        launch_speed: 5.0,
+        loft:         8.0,
+        gravity:      20.0,
+        radius:       0.3,
+        fuse:         None,
+        on_fuse:      ProjectileEnd::Vanish,
+        on_impact:    ProjectileEnd::Burst {
+          kind:  ProjectileKind::Fragment,
+          count: 5,
+          speed: 6.0,
+        },
+        // Spores are slow, so they'd visibly pop out of existence.
+        on_room_exit: RoomExitPolicy::FadeOut,
+        style:        "spore",
+      },
+      ProjectileKind::Fragment => ProjectileDescriptor {
+        launch_speed: 6.0,
+        loft:         0.0,
+        gravity:      0.0,
+        radius:       0.15,
+        fuse:         Some(0.35),
+        on_fuse:      ProjectileEnd::Vanish,
+        on_impact:    ProjectileEnd::Vanish,
+        on_room_exit: RoomExitPolicy::Despawn,
+        style:        "fragment",
+      },
+      ProjectileKind::Splitter => ProjectileDescriptor {
+        launch_speed: 5.0,
+        loft:         0.0,
+        gravity:      0.0,
+        radius:       0.3,
+        fuse:         Some(0.6),
+        on_fuse:      ProjectileEnd::Split {
+          kind:  ProjectileKind::Bullet,
+          angle: 0.5,
+        },
+        on_impact:    ProjectileEnd::Vanish,
+        on_room_exit: RoomExitPolicy::Despawn,
+        style:        "splitter",
+      },
+      ProjectileKind::PlayerShot => ProjectileDescriptor {
+        launch_speed: 16.0,
+        loft:         0.0,
+        gravity:      0.0,
+        radius:       0.15,
+        fuse:         Some(0.5),
+        on_fuse:      ProjectileEnd::Vanish,
+        on_impact:    ProjectileEnd::Vanish,
+        // The player's shots follow them through doors, since they're already on their way.
+        on_room_exit: RoomExitPolicy::Persist,
+        style:        "player_shot",
+      },
+    }
+  }
+
+  pub fn is_players(self) -> bool {
+    self == ProjectileKind::PlayerShot
+  }
+
+  /// The velocity to fire this kind of projectile with from a turret facing `orientation`.
+  pub fn launch_velocity(self, orientation: Vec2) -> Vec2 {
+    let descriptor = self.descriptor();
+    descriptor.launch_speed * orientation + Vec2(0.0, -descriptor.loft)
+  }
+}
+
+fn rotate(v: Vec2, angle: f32) -> Vec2 {
+  let (sin, cos) = angle.sin_cos();
+  Vec2(cos * v.0 - sin * v.1, sin * v.0 + cos * v.1)
+}
+
+/// The projectiles left behind by `end`, as kinds and velocities, given the velocity the
+/// ending projectile had.
+pub fn end_children(end: ProjectileEnd, velocity: Vec2) -> Vec<(ProjectileKind, Vec2)> {
+  match end {
+    ProjectileEnd::Vanish => Vec::new(),
+    ProjectileEnd::Burst { kind, count, speed } => (0..count)
+      .map(|i| {
+        let angle = -std::f32::consts::PI * (i as f32 + 0.5) / count as f32;
+        (kind, speed * Vec2(angle.cos(), angle.sin()))
+      })
+      .collect(),
+    ProjectileEnd::Split { kind, angle } => {
+      let heading = match velocity.length() > 0.0 {
+        true => (kind.descriptor().launch_speed / velocity.length()) * velocity,
+        false => Vec2(kind.descriptor().launch_speed, 0.0),
+      };
+      vec![
+        (kind, rotate(heading, angle)),
+        (kind, rotate(heading, -angle)),
+      ]
+    }
+  }
+}
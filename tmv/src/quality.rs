@@ -0,0 +1,101 @@
+/// Render scale multipliers for each quality level, best first.
+const RENDER_SCALE_FACTORS: [f32; 3] = [1.0, 0.75, 0.5];
+/// Particle budgets for each quality level, best first.
+const PARTICLE_BUDGETS: [usize; 3] = [400, 200, 80];
+/// Whether lava and lasers glow, for each quality level, best first.
+const LIGHTING_ENABLED: [bool; 3] = [true, true, false];
+// A bit of slack over 60fps, so ordinary jitter doesn't count as going over budget.
+const FRAME_BUDGET: f32 = 1.15 / 60.0;
+// How long frame times have to stay over budget before we drop a level.
+const DOWNGRADE_AFTER: f32 = 2.0;
+// How long frame times have to stay in budget before we try going back up a level.
+// This doubles every time a restore doesn't stick, so we don't flip-flop.
+const INITIAL_RESTORE_DELAY: f32 = 5.0;
+const MAX_RESTORE_DELAY: f32 = 60.0;
+const SMOOTHING: f32 = 0.1;
+
+/// Watches frame times and trades render quality for speed when the game can't keep up.
+#[derive(Debug)]
+pub struct QualityController {
+  pub enabled:        bool,
+  level:              usize,
+  average_frame_time: f32,
+  over_budget_time:   f32,
+  within_budget_time: f32,
+  restore_delay:      f32,
+  // Set right after going up a level, so a quick drop back down counts as a failed restore.
+  recently_restored:  bool,
+}
+
+impl Default for QualityController {
+  fn default() -> Self {
+    Self {
+      enabled:            true,
+      level:              0,
+      average_frame_time: 1.0 / 60.0,
+      over_budget_time:   0.0,
+      within_budget_time: 0.0,
+      restore_delay:      INITIAL_RESTORE_DELAY,
+      recently_restored:  false,
+    }
+  }
+}
+
+impl QualityController {
+  pub fn render_scale_factor(&self) -> f32 {
+    RENDER_SCALE_FACTORS[self.level]
+  }
+
+  pub fn particle_budget(&self) -> usize {
+    PARTICLE_BUDGETS[self.level]
+  }
+
+  pub fn lighting_enabled(&self) -> bool {
+    LIGHTING_ENABLED[self.level]
+  }
+
+  /// Feeds in how long the last frame took, returning true if the quality level changed.
+  pub fn report_frame_time(&mut self, frame_time: f32) -> bool {
+    if !self.enabled {
+      return self.reset();
+    }
+    self.average_frame_time += SMOOTHING * (frame_time - self.average_frame_time);
+    if self.average_frame_time > FRAME_BUDGET {
+      self.over_budget_time += frame_time;
+      self.within_budget_time = 0.0;
+    } else {
+      self.within_budget_time += frame_time;
+      self.over_budget_time = 0.0;
+    }
+
+    if self.over_budget_time > DOWNGRADE_AFTER && self.level + 1 < RENDER_SCALE_FACTORS.len() {
+      if self.recently_restored {
+        self.restore_delay = (2.0 * self.restore_delay).min(MAX_RESTORE_DELAY);
+      }
+      self.level += 1;
+      self.recently_restored = false;
+      self.over_budget_time = 0.0;
+      return true;
+    }
+    if self.within_budget_time > self.restore_delay && self.level > 0 {
+      self.level -= 1;
+      self.recently_restored = true;
+      self.within_budget_time = 0.0;
+      return true;
+    }
+    // A restore that's held up for a full delay period counts as successful.
+    if self.within_budget_time > self.restore_delay {
+      self.recently_restored = false;
+    }
+    false
+  }
+
+  fn reset(&mut self) -> bool {
+    let changed = self.level != 0;
+    *self = Self {
+      enabled: self.enabled,
+      ..Self::default()
+    };
+    changed
+  }
+}
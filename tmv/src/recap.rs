@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+use crate::power_ups::PowerUp;
+
+// How long the recap stays up after loading a save, in seconds, the last of which it
+// spends fading out.
+pub const RECAP_TIME: f32 = 6.0;
+pub const RECAP_FADE_TIME: f32 = 1.0;
+
+/// A few words about where the player left off, kept with the save so that someone coming
+/// back to it can pick up where they were.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SaveRecap {
+  // The map region the player saved in, if it's in one.
+  pub region:          Option<String>,
+  // There's no quest log, so this is the region with the next power-up in reach from the
+  // save point, like the route hints.
+  pub next_goal:       Option<String>,
+  pub latest_power_up: Option<PowerUp>,
+  // In seconds.
+  pub play_time:       f32,
+}
+
+impl SaveRecap {
+  /// The recap's text, a line at a time.
+  pub fn lines(&self) -> Vec<String> {
+    let mut lines = vec![match &self.region {
+      Some(region) => format!("Resting in {}", region),
+      None => "Resting".to_string(),
+    }];
+    if let Some(power_up) = self.latest_power_up {
+      lines.push(format!("Last found: {}", power_up.display_name()));
+    }
+    if let Some(goal) = &self.next_goal {
+      lines.push(format!("Next: explore {}", goal));
+    }
+    lines.push(format!("Played {}", format_play_time(self.play_time)));
+    lines
+  }
+}
+
+/// Hours and minutes, like `3:07`.
+pub fn format_play_time(seconds: f32) -> String {
+  let minutes = (seconds / 60.0).floor() as i32;
+  format!("{}:{:02}", minutes / 60, minutes % 60)
+}
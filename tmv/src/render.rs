@@ -0,0 +1,165 @@
+use std::ops::{Deref, Index, IndexMut};
+
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{CanvasRenderingContext2d, Document, HtmlCanvasElement};
+
+use crate::{
+  camera::Camera,
+  math::{Rect, Vec2},
+  SCREEN_HEIGHT, SCREEN_WIDTH,
+};
+
+/// The canvases stacked up on the page, from the top down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum_macros::EnumIter)]
+pub enum LayerId {
+  Ui,
+  // Glows, which the page blends over the main layer.
+  Lighting,
+  Main,
+  Background,
+  // Offscreen, for the tile renderer to assemble chunks on.
+  Scratch,
+}
+
+impl LayerId {
+  /// The layers that are actually shown, and so get sized to the display.
+  pub const VISIBLE: [LayerId; 4] = [
+    LayerId::Ui,
+    LayerId::Lighting,
+    LayerId::Main,
+    LayerId::Background,
+  ];
+
+  pub fn element_id(self) -> &'static str {
+    match self {
+      LayerId::Ui => "uiCanvas",
+      LayerId::Lighting => "lightingCanvas",
+      LayerId::Main => "mainCanvas",
+      LayerId::Background => "backgroundCanvas",
+      LayerId::Scratch => "scratchCanvas",
+    }
+  }
+}
+
+/// One canvas and its 2D context, which it derefs to for drawing. It also knows the camera
+/// for this frame, for converting between world and screen coordinates.
+pub struct Layer {
+  pub canvas: HtmlCanvasElement,
+  ctx:        CanvasRenderingContext2d,
+  pub camera: Camera,
+}
+
+impl Deref for Layer {
+  type Target = CanvasRenderingContext2d;
+
+  fn deref(&self) -> &CanvasRenderingContext2d {
+    &self.ctx
+  }
+}
+
+impl Layer {
+  pub fn from_document(document: &Document, id: LayerId) -> Result<Self, JsValue> {
+    let canvas = document
+      .get_element_by_id(id.element_id())
+      .ok_or_else(|| JsValue::from_str(&format!("Missing canvas: {}", id.element_id())))?
+      .dyn_into::<HtmlCanvasElement>()?;
+    let ctx = canvas
+      .get_context("2d")?
+      .ok_or_else(|| JsValue::from_str("No 2d context"))?
+      .dyn_into::<CanvasRenderingContext2d>()?;
+    Ok(Self {
+      canvas,
+      ctx,
+      camera: Camera::game_view(Vec2::default(), 1.0),
+    })
+  }
+
+  pub fn world_to_screen(&self, world: Vec2) -> Vec2 {
+    self.camera.world_to_screen(world)
+  }
+
+  pub fn screen_to_world(&self, screen: Vec2) -> Vec2 {
+    self.camera.screen_to_world(screen)
+  }
+
+  /// Saves the style, transform, and clip, to be put back by the matching `pop`.
+  pub fn push(&self) {
+    self.ctx.save();
+  }
+
+  pub fn pop(&self) {
+    self.ctx.restore();
+  }
+
+  /// Runs `f` with the state pushed, so any styles it sets don't leak into later drawing.
+  pub fn scoped<R>(&self, f: impl FnOnce(&Self) -> R) -> R {
+    self.push();
+    let result = f(self);
+    self.pop();
+    result
+  }
+
+  /// Restricts drawing to a rectangle in screen coordinates, until the next `pop`.
+  pub fn clip_screen_rect(&self, rect: Rect) {
+    self.ctx.begin_path();
+    self.ctx.rect(
+      rect.pos.0 as f64,
+      rect.pos.1 as f64,
+      rect.size.0 as f64,
+      rect.size.1 as f64,
+    );
+    self.ctx.clip();
+  }
+
+  /// Restricts drawing to a rectangle in world coordinates, until the next `pop`.
+  pub fn clip_world_rect(&self, rect: Rect) {
+    self.clip_screen_rect(self.camera.rect_to_screen(rect));
+  }
+
+  pub fn clear(&self) {
+    self.ctx.clear_rect(0.0, 0.0, SCREEN_WIDTH as f64, SCREEN_HEIGHT as f64);
+  }
+
+  /// Sizes the backing store for `ratio` device pixels per screen pixel, keeping the
+  /// transform such that drawing is still in screen pixels.
+  pub fn set_pixel_ratio(&self, ratio: f32) {
+    self.canvas.set_width((SCREEN_WIDTH * ratio) as u32);
+    self.canvas.set_height((SCREEN_HEIGHT * ratio) as u32);
+    // Resizing resets the transform.
+    self.ctx.set_transform(ratio as f64, 0.0, 0.0, ratio as f64, 0.0, 0.0).unwrap();
+  }
+}
+
+/// Every layer, indexed by `LayerId`.
+pub struct Layers {
+  layers: Vec<Layer>,
+}
+
+impl Layers {
+  pub fn from_document(document: &Document) -> Result<Self, JsValue> {
+    use strum::IntoEnumIterator;
+    let layers =
+      LayerId::iter().map(|id| Layer::from_document(document, id)).collect::<Result<_, _>>()?;
+    Ok(Self { layers })
+  }
+
+  pub fn set_camera(&mut self, camera: Camera) {
+    for layer in &mut self.layers {
+      layer.camera = camera;
+    }
+  }
+}
+
+impl Index<LayerId> for Layers {
+  type Output = Layer;
+
+  fn index(&self, id: LayerId) -> &Layer {
+    &self.layers[id as usize]
+  }
+}
+
+impl IndexMut<LayerId> for Layers {
+  fn index_mut(&mut self, id: LayerId) -> &mut Layer {
+    &mut self.layers[id as usize]
+  }
+}
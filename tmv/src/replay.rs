@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{math::Vec2, settings::Settings, InputEvent};
+
+/// Bumped whenever a change to the format would make old replays misread.
+pub const REPLAY_VERSION: u32 = 1;
+
+/// How many frames apart `GameState::play_replay` takes checkpoints.
+pub const CHECKPOINT_INTERVAL: usize = 60;
+
+/// The input events that arrived before one simulation step, and how long that step was.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayFrame {
+  pub events:    Vec<InputEvent>,
+  pub dt:        f32,
+  // Set if the step came from `step_at`, so buffered presses land at the same point within it.
+  #[serde(default)]
+  pub timestamp: Option<f64>,
+}
+
+/// A recording of play from a respawn, which reproduces the run when fed back through the simulation.
+/// Per-object timers (like shooter cooldowns) aren't recorded, so they start from their map defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+  pub version:   u32,
+  // The save data and settings in effect when recording started. Playback starts by loading them.
+  pub save_data: String,
+  pub settings:  Settings,
+  pub frames:    Vec<ReplayFrame>,
+}
+
+impl Replay {
+  pub fn from_json(json: &str) -> Result<Self, String> {
+    let replay: Replay = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    if replay.version != REPLAY_VERSION {
+      return Err(format!(
+        "Replay is version {}, but only version {} is supported",
+        replay.version, REPLAY_VERSION
+      ));
+    }
+    Ok(replay)
+  }
+}
+
+#[derive(Debug)]
+pub struct ReplayRecorder {
+  replay:         Replay,
+  pending_events: Vec<InputEvent>,
+}
+
+impl ReplayRecorder {
+  pub fn new(save_data: String, settings: Settings) -> Self {
+    Self {
+      replay:         Replay {
+        version: REPLAY_VERSION,
+        save_data,
+        settings,
+        frames: Vec::new(),
+      },
+      pending_events: Vec::new(),
+    }
+  }
+
+  pub fn record_event(&mut self, event: InputEvent) {
+    self.pending_events.push(event);
+  }
+
+  pub fn record_frame(&mut self, dt: f32, timestamp: Option<f64>) {
+    self.replay.frames.push(ReplayFrame {
+      events: std::mem::take(&mut self.pending_events),
+      dt,
+      timestamp,
+    });
+  }
+
+  /// Events after the last step are dropped, as they never got to affect anything.
+  pub fn finish(self) -> Replay {
+    self.replay
+  }
+}
+
+/// A replay loaded for frame-by-frame inspection. While one is loaded, live input and
+/// stepping are ignored, and the live save and settings are kept aside until it's closed.
+#[derive(Debug)]
+pub struct ReplayPlayback {
+  pub replay:         Replay,
+  pub next_frame:     usize,
+  pub live_save_data: String,
+  pub live_settings:  Settings,
+}
+
+impl ReplayPlayback {
+  pub fn frame_count(&self) -> usize {
+    self.replay.frames.len()
+  }
+
+  pub fn is_finished(&self) -> bool {
+    self.next_frame >= self.frame_count()
+  }
+}
+
+/// A summary of the gameplay state at some frame of a replay, for regression tests to compare.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayCheckpoint {
+  pub frame:      usize,
+  pub position:   Vec2,
+  pub velocity:   Vec2,
+  pub hp:         i32,
+  pub power_ups:  Vec<String>,
+  pub coins:      usize,
+  pub rare_coins: usize,
+  pub save_point: Vec2,
+}
+
+/// Rounds to the nearest thousandth, so checkpoints don't flip on float noise.
+pub fn round_for_checkpoint(v: Vec2) -> Vec2 {
+  Vec2(
+    (v.0 * 1000.0).round() / 1000.0,
+    (v.1 * 1000.0).round() / 1000.0,
+  )
+}
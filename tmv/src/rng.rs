@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+// Replays and savestates start every run from this seed.
+pub const DEFAULT_SEED: u64 = 0x746d_7672_6e67;
+
+/// The random number generator for anything that affects gameplay, like bee jitter. Unlike
+/// `rand::random`, its state is plain data that can be saved and restored along with
+/// everything else, so hazards behave the same way every time a run is played back.
+/// Purely cosmetic randomness (particles, laser sparks) doesn't need to go through this.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameRng {
+  state: u64,
+}
+
+impl GameRng {
+  pub fn new(seed: u64) -> Self {
+    // Xorshift gets stuck at zero.
+    Self { state: seed.max(1) }
+  }
+
+  pub fn next_u64(&mut self) -> u64 {
+    // xorshift64*
+    self.state ^= self.state >> 12;
+    self.state ^= self.state << 25;
+    self.state ^= self.state >> 27;
+    self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+  }
+
+  /// Uniform in [0, 1).
+  pub fn next_f32(&mut self) -> f32 {
+    (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+  }
+}
+
+impl Default for GameRng {
+  fn default() -> Self {
+    Self::new(DEFAULT_SEED)
+  }
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{power_ups::PowerUp, save_repair::SaveRepairContext, CharEvent, LocalStorageSaveData};
+
+/// Running totals kept with a save. Unlike char_state these aren't rolled back on respawn.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunStats {
+  // In seconds, not counting replays.
+  pub play_time: f32,
+  pub deaths:    u32,
+}
+
+/// What one save says about how a run went, for comparing runs in a spreadsheet. It leaves
+/// out anything that could pick out a player, like where they saved or dropped pins.
+#[derive(Debug, Clone, Serialize)]
+pub struct SaveStatistics {
+  // Which of the saves passed in this came from, counting from 0.
+  pub slot:                 usize,
+  pub completion_percent:   f32,
+  pub play_time:            f32,
+  pub deaths:               u32,
+  // The order the power-ups were picked up in. Any picked up before the oldest event in the
+  // save's history are missing, since their order is lost.
+  pub power_up_route:       Vec<PowerUp>,
+  pub coins:                usize,
+  pub rare_coins:           usize,
+  pub hp_ups:               usize,
+  pub challenges_completed: usize,
+}
+
+impl SaveStatistics {
+  /// Summarizes a save that has already been repaired against `context`, so everything it
+  /// has collected is something the maps actually have.
+  pub fn from_save(slot: usize, save: &LocalStorageSaveData, context: &SaveRepairContext) -> Self {
+    let char_state = &save.char_state;
+    let collected = char_state.coins.len()
+      + char_state.rare_coins.len()
+      + char_state.hp_ups.len()
+      + char_state.power_ups.len();
+    let mut power_up_route = Vec::new();
+    for event in &save.char_history {
+      if let CharEvent::PowerUp { power_up } = event {
+        if char_state.power_ups.contains(power_up) && !power_up_route.contains(power_up) {
+          power_up_route.push(*power_up);
+        }
+      }
+    }
+    Self {
+      slot,
+      completion_percent: match context.collectible_count() {
+        0 => 0.0,
+        total => 100.0 * collected as f32 / total as f32,
+      },
+      play_time: save.run_stats.play_time,
+      deaths: save.run_stats.deaths,
+      power_up_route,
+      coins: char_state.coins.len(),
+      rare_coins: char_state.rare_coins.len(),
+      hp_ups: char_state.hp_ups.len(),
+      challenges_completed: char_state.completed_challenges.len(),
+    }
+  }
+}
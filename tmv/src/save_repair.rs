@@ -0,0 +1,399 @@
+use std::collections::{HashMap, HashSet};
+
+use rapier2d::prelude::ColliderHandle;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+  collision::CollisionWorld, math::Vec2, power_ups::PowerUp, run_stats::RunStats, CharState,
+  EntityId, GameObject, GameObjectData, LocalStorageSaveData,
+};
+
+/// What loading a save had to fix, so the player can be told rather than silently losing progress.
+#[derive(Debug, Default, Serialize)]
+pub struct RepairReport {
+  pub fixes: Vec<String>,
+}
+
+impl RepairReport {
+  fn note(&mut self, fix: String) {
+    crate::log(&format!("Repaired save: {}", fix));
+    self.fixes.push(fix);
+  }
+}
+
+/// The version of the save format we write. Bump it and add a migration whenever a change
+/// to the format needs old saves rewritten to keep their meaning; new fields with sensible
+/// defaults don't, since parsing fills those in.
+pub const SAVE_VERSION: u32 = 2;
+
+// MIGRATIONS[i] upgrades a save from version i to version i + 1. Saves from before the
+// format was versioned count as version 0.
+const MIGRATIONS: [fn(&mut Map<String, Value>); SAVE_VERSION as usize] =
+  [migrate_0_to_1, migrate_1_to_2];
+
+// Unversioned saves were all written before the map reveal granularity was configurable, so
+// any that don't say otherwise used the original one, whatever the default is now.
+fn migrate_0_to_1(object: &mut Map<String, Value>) {
+  object.entry("revealed_map_granularity").or_insert_with(|| Value::from(8));
+}
+
+// The two laser puzzles used to be hard-coded, with a bool each for whether they'd been
+// done. Now interactions set flags, so those become flags, and so do the events that set them.
+fn migrate_1_to_2(object: &mut Map<String, Value>) {
+  if let Some(Value::Object(char_state)) = object.get_mut("char_state") {
+    let flags: Vec<Value> = ["int1_completed", "int2_completed"]
+      .into_iter()
+      .filter(|name| char_state.remove(*name) == Some(Value::Bool(true)))
+      .map(Value::from)
+      .collect();
+    char_state.insert("flags".to_string(), Value::Array(flags));
+  }
+  if let Some(Value::Array(history)) = object.get_mut("char_history") {
+    for event in history.iter_mut().filter_map(Value::as_object_mut) {
+      if event.get("kind").and_then(Value::as_str) != Some("interaction") {
+        continue;
+      }
+      let number = event.remove("number").unwrap_or_default();
+      event.insert("kind".to_string(), Value::from("flag"));
+      event.insert(
+        "name".to_string(),
+        Value::from(format!("int{}_completed", number)),
+      );
+    }
+  }
+}
+
+/// Brings save JSON up to the current version. Saves from a newer version of the game are
+/// refused, since loading them would rewrite them in the old format and lose whatever the
+/// newer version added.
+fn migrate(object: &mut Map<String, Value>, report: &mut RepairReport) -> Result<(), String> {
+  let version = match object.get("version") {
+    None => 0,
+    Some(value) => match value.as_u64() {
+      Some(version) => version,
+      None => {
+        report.note(format!(
+          "Unreadable save version {}, so it was treated as 0",
+          value
+        ));
+        0
+      }
+    },
+  };
+  if version > SAVE_VERSION as u64 {
+    return Err(format!(
+      "Save is from a newer version of the game (version {}, but this is version {})",
+      version, SAVE_VERSION
+    ));
+  }
+  for migration in &MIGRATIONS[version as usize..] {
+    migration(object);
+  }
+  object.insert("version".to_string(), Value::from(SAVE_VERSION));
+  Ok(())
+}
+
+/// Facts about the maps that a save gets checked against.
+#[derive(Debug, Default)]
+pub struct SaveRepairContext {
+  pub known_entity_ids:  HashSet<EntityId>,
+  pub known_power_ups:   HashSet<PowerUp>,
+  // Each save point, along with the map it's on.
+  pub valid_save_points: Vec<(String, Vec2)>,
+  pub spawn_map:         String,
+  pub spawn_point:       Vec2,
+  pub room_count:        usize,
+  // How many coins, rare coins, and hp ups there are to collect across the maps.
+  pub known_item_count:  usize,
+}
+
+impl SaveRepairContext {
+  /// Gathers the context from a freshly loaded map that a new game starts on, before any save
+  /// has removed collected items.
+  pub fn from_objects(
+    map_name: &str,
+    objects: &HashMap<ColliderHandle, GameObject>,
+    collision: &CollisionWorld,
+  ) -> Self {
+    let mut context = Self {
+      spawn_map: map_name.to_string(),
+      spawn_point: collision.spawn_point,
+      ..Default::default()
+    };
+    context.add_map(map_name, objects, collision);
+    context
+  }
+
+  /// Adds what's in another freshly loaded map, since a save can hold progress from any of them.
+  pub fn add_map(
+    &mut self,
+    map_name: &str,
+    objects: &HashMap<ColliderHandle, GameObject>,
+    collision: &CollisionWorld,
+  ) {
+    for object in objects.values() {
+      match &object.data {
+        GameObjectData::Coin { entity_id }
+        | GameObjectData::RareCoin { entity_id }
+        | GameObjectData::HpUp { entity_id } => {
+          self.known_entity_ids.insert(*entity_id);
+          self.known_item_count += 1;
+        }
+        GameObjectData::ChallengeGoal { entity_id, .. } => {
+          self.known_entity_ids.insert(*entity_id);
+        }
+        // What a block drops shares its id, so collecting the drop is saved under it too.
+        GameObjectData::BreakableBlock {
+          entity_id, drops, ..
+        } => {
+          self.known_entity_ids.insert(*entity_id);
+          if drops.is_some() {
+            self.known_item_count += 1;
+          }
+        }
+        GameObjectData::PowerUp { power_up } => {
+          self.known_power_ups.insert(*power_up);
+        }
+        GameObjectData::SavePoint => {
+          // This matches where touching a save point puts the save point.
+          if let Some(pos) = collision.get_position(&object.physics_handle) {
+            self.valid_save_points.push((map_name.to_string(), pos + Vec2(0.0, -1.0)));
+          }
+        }
+        _ => {}
+      }
+    }
+  }
+
+  /// Everything there is to collect, for working out how complete a save is.
+  pub fn collectible_count(&self) -> usize {
+    self.known_item_count + self.known_power_ups.len()
+  }
+
+  fn is_valid_save_point(&self, save_map: &str, save_point: Vec2) -> bool {
+    std::iter::once((&self.spawn_map, &self.spawn_point))
+      .chain(self.valid_save_points.iter().map(|(map, p)| (map, p)))
+      .any(|(map, p)| map == save_map && (*p - save_point).length() < 0.01)
+  }
+}
+
+fn take_field<T: DeserializeOwned>(
+  object: &Map<String, Value>,
+  path: &str,
+  key: &str,
+  default: T,
+  report: &mut RepairReport,
+) -> T {
+  match object.get(key) {
+    None => default,
+    Some(value) => T::deserialize(value).unwrap_or_else(|e| {
+      report.note(format!(
+        "{}{} was unreadable ({}), so it was reset",
+        path, key, e
+      ));
+      default
+    }),
+  }
+}
+
+/// Reads an array field element by element, dropping just the elements that don't parse.
+fn take_list<T: DeserializeOwned, C: FromIterator<T>>(
+  object: &Map<String, Value>,
+  path: &str,
+  key: &str,
+  report: &mut RepairReport,
+) -> C {
+  let Some(value) = object.get(key) else {
+    return std::iter::empty().collect();
+  };
+  let Value::Array(elements) = value else {
+    report.note(format!("{}{} wasn't a list, so it was reset", path, key));
+    return std::iter::empty().collect();
+  };
+  let parsed: Vec<T> = elements.iter().filter_map(|e| T::deserialize(e).ok()).collect();
+  if parsed.len() != elements.len() {
+    report.note(format!(
+      "Dropped {} unreadable entries from {}{}",
+      elements.len() - parsed.len(),
+      path,
+      key
+    ));
+  }
+  parsed.into_iter().collect()
+}
+
+/// Reads a map field entry by entry, dropping just the entries that don't parse.
+fn take_map<T: DeserializeOwned>(
+  object: &Map<String, Value>,
+  path: &str,
+  key: &str,
+  report: &mut RepairReport,
+) -> HashMap<String, T> {
+  let Some(value) = object.get(key) else {
+    return HashMap::new();
+  };
+  let Value::Object(entries) = value else {
+    report.note(format!("{}{} wasn't a map, so it was reset", path, key));
+    return HashMap::new();
+  };
+  let parsed: HashMap<String, T> =
+    entries.iter().filter_map(|(k, v)| Some((k.clone(), T::deserialize(v).ok()?))).collect();
+  if parsed.len() != entries.len() {
+    report.note(format!(
+      "Dropped {} unreadable entries from {}{}",
+      entries.len() - parsed.len(),
+      path,
+      key
+    ));
+  }
+  parsed
+}
+
+fn parse_char_state(value: Option<&Value>, report: &mut RepairReport) -> CharState {
+  let defaults = CharState::default();
+  let Some(Value::Object(object)) = value else {
+    report.note("char_state was missing or unreadable, so progress was reset".to_string());
+    return defaults;
+  };
+  let path = "char_state.";
+  CharState {
+    save_map:             take_field(object, path, "save_map", defaults.save_map, report),
+    save_point:           take_field(object, path, "save_point", defaults.save_point, report),
+    hp:                   take_field(object, path, "hp", defaults.hp, report),
+    power_ups:            take_list(object, path, "power_ups", report),
+    coins:                take_list(object, path, "coins", report),
+    rare_coins:           take_list(object, path, "rare_coins", report),
+    hp_ups:               take_list(object, path, "hp_ups", report),
+    flags:                take_list(object, path, "flags", report),
+    completed_challenges: take_list(object, path, "completed_challenges", report),
+    secrets:              take_list(object, path, "secrets", report),
+    broken_blocks:        take_list(object, path, "broken_blocks", report),
+    destroyed_hives:      take_list(object, path, "destroyed_hives", report),
+    keys:                 take_field(object, path, "keys", HashMap::new(), report),
+    opened_doors:         take_field(object, path, "opened_doors", HashMap::new(), report),
+  }
+}
+
+/// Parses save JSON as forgivingly as possible, after migrating it to the current version:
+/// fields are read one at a time, and anything unreadable falls back to its default instead
+/// of failing the whole load. Only JSON that isn't an object at all, or that's from a newer
+/// version of the game, is rejected.
+pub fn parse_leniently(
+  json: &str,
+  report: &mut RepairReport,
+) -> Result<LocalStorageSaveData, String> {
+  let value: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+  let Value::Object(mut object) = value else {
+    return Err("Save data isn't a JSON object".to_string());
+  };
+  migrate(&mut object, report)?;
+  Ok(LocalStorageSaveData {
+    version:                  SAVE_VERSION,
+    char_state:               parse_char_state(object.get("char_state"), report),
+    revealed_map:             take_list(&object, "", "revealed_map", report),
+    revealed_map_granularity: take_field(
+      &object,
+      "",
+      "revealed_map_granularity",
+      crate::MAP_REVELATION_DISCRETIZATION,
+      report,
+    ),
+    trial_best_times:         take_map(&object, "", "trial_best_times", report),
+    map_pins:                 take_list(&object, "", "map_pins", report),
+    visited_rooms:            take_list(&object, "", "visited_rooms", report),
+    char_history:             take_list(&object, "", "char_history", report),
+    run_stats:                take_field(&object, "", "run_stats", RunStats::default(), report),
+    recap:                    take_field(&object, "", "recap", None, report),
+  })
+}
+
+/// Fixes values that parsed fine but don't make sense for this map.
+pub fn repair(
+  save: &mut LocalStorageSaveData,
+  context: &SaveRepairContext,
+  report: &mut RepairReport,
+) {
+  let char_state = &mut save.char_state;
+  for (name, ids) in [
+    ("coins", &mut char_state.coins),
+    ("rare_coins", &mut char_state.rare_coins),
+    ("hp_ups", &mut char_state.hp_ups),
+  ] {
+    let before = ids.len();
+    ids.retain(|id| context.known_entity_ids.contains(id));
+    if ids.len() != before {
+      report.note(format!("Dropped {} unknown {}", before - ids.len(), name));
+    }
+  }
+  let before = char_state.power_ups.len();
+  char_state.power_ups.retain(|power_up| context.known_power_ups.contains(power_up));
+  if char_state.power_ups.len() != before {
+    report.note(format!(
+      "Dropped {} unknown power ups",
+      before - char_state.power_ups.len()
+    ));
+  }
+
+  let max_hp = char_state.hp_ups.len() as i32 + 1;
+  let hp = char_state.hp.get();
+  if !(1..=max_hp).contains(&hp) {
+    char_state.hp.set(hp.clamp(1, max_hp));
+    report.note(format!("Clamped hp from {} to {}", hp, char_state.hp.get()));
+  }
+
+  if !context.is_valid_save_point(&char_state.save_map, char_state.save_point) {
+    report.note(format!(
+      "Save point {:?} in {} isn't a save point, so it was moved to the spawn",
+      char_state.save_point, char_state.save_map
+    ));
+    char_state.save_map = context.spawn_map.clone();
+    char_state.save_point = context.spawn_point;
+  }
+
+  if !(1..=64).contains(&save.revealed_map_granularity) {
+    report.note(format!(
+      "Invalid map reveal granularity {}, so the revealed map was reset",
+      save.revealed_map_granularity
+    ));
+    save.revealed_map_granularity = crate::MAP_REVELATION_DISCRETIZATION;
+    save.revealed_map.clear();
+  } else {
+    let g = save.revealed_map_granularity;
+    let before = save.revealed_map.len();
+    save.revealed_map.retain(|(x, y)| x % g == 0 && y % g == 0);
+    if save.revealed_map.len() != before {
+      report.note(format!(
+        "Dropped {} misaligned revealed map chunks",
+        before - save.revealed_map.len()
+      ));
+    }
+  }
+
+  let before = save.trial_best_times.len();
+  save.trial_best_times.retain(|_, time| time.is_finite() && *time >= 0.0);
+  if save.trial_best_times.len() != before {
+    report.note(format!(
+      "Dropped {} invalid time trial best times",
+      before - save.trial_best_times.len()
+    ));
+  }
+
+  let before = save.map_pins.len();
+  save.map_pins.retain(|pin| pin.0.is_finite() && pin.1.is_finite());
+  if save.map_pins.len() != before {
+    report.note(format!(
+      "Dropped {} invalid map pins",
+      before - save.map_pins.len()
+    ));
+  }
+
+  let before = save.visited_rooms.len();
+  save.visited_rooms.retain(|room| *room < context.room_count);
+  if save.visited_rooms.len() != before {
+    report.note(format!(
+      "Dropped {} unknown visited rooms",
+      before - save.visited_rooms.len()
+    ));
+  }
+}
@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+/// How unrevealed parts of the map screen are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FogStyle {
+  HardBlack,
+  SoftGradient,
+  Hatched,
+}
+
+/// Whether dashing takes a fresh press each time, or keeps going while the key is held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DashMode {
+  Tap,
+  Hold,
+}
+
+/// Player-facing options, persisted separately from the save file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+  // Side length, in tiles, of the chunks the map is revealed in.
+  pub map_reveal_granularity: i32,
+  pub fog_style:              FogStyle,
+  // Assist: mark coins not yet picked up in revealed parts of the map screen.
+  pub map_coin_markers:       bool,
+  // Assist: point a HUD arrow at the nearest power-up still out in the world.
+  pub show_compass:           bool,
+  // Assist: faintly show where nearby turrets' shots will fly and where thwumps will fall.
+  pub hazard_previews:        bool,
+  // Assist: after a long time without finding anything, suggest where the next power-up
+  // in reach is.
+  pub route_hints:            bool,
+  // Assist: lava and crushing hazards cost one heart and put the player back on the last
+  // safe ground, rather than killing them outright.
+  pub soft_deaths:            bool,
+  // Input modes.
+  pub auto_run:               bool,
+  pub dash_mode:              DashMode,
+  // Tapping left or right twice quickly dashes that way, as well as the dash key.
+  pub double_tap_dash:        bool,
+  // Holding jump in water keeps re-pressing it.
+  pub swim_assist:            bool,
+  // Apply jump and dash presses at the point within the frame they happened, rather than at the next step.
+  pub immediate_input:        bool,
+  // Lower the render resolution when frames are running long.
+  pub auto_quality:           bool,
+  // Percentage (0 to 100) of the usual particles, sparks, and trails to draw.
+  pub effects_density:        i32,
+  // Speedrun aid: show velocity, movement state, and a frame counter (always on in debug mode).
+  pub show_movement_readout:  bool,
+  // Extra space, in screen pixels, to keep between the HUD and the edges of the screen.
+  pub hud_margin:             i32,
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    Self {
+      map_reveal_granularity: crate::MAP_REVELATION_DISCRETIZATION,
+      fog_style:              FogStyle::HardBlack,
+      map_coin_markers:       false,
+      show_compass:           false,
+      hazard_previews:        false,
+      route_hints:            false,
+      soft_deaths:            false,
+      auto_run:               false,
+      dash_mode:              DashMode::Tap,
+      double_tap_dash:        false,
+      swim_assist:            false,
+      immediate_input:        false,
+      auto_quality:           true,
+      effects_density:        100,
+      show_movement_readout:  false,
+      hud_margin:             0,
+    }
+  }
+}
+
+impl Settings {
+  pub fn sanitize(&mut self) {
+    self.map_reveal_granularity = self.map_reveal_granularity.clamp(1, 64);
+    self.effects_density = self.effects_density.clamp(0, 100);
+    self.hud_margin = self.hud_margin.clamp(0, 100);
+  }
+
+  pub fn effects_scale(&self) -> f32 {
+    self.effects_density as f32 / 100.0
+  }
+}
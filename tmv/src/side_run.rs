@@ -0,0 +1,24 @@
+use std::fmt::Display;
+
+use crate::assets;
+
+/// What daily challenges and descents have in common: each is a fresh game on the clock,
+/// played apart from the player's own game, which is put aside until the run is over.
+#[derive(Debug, Clone, Default)]
+pub struct SideRun {
+  // The player's own game, to go back to afterwards.
+  pub live_save_data: String,
+  pub elapsed:        f32,
+}
+
+impl SideRun {
+  /// A short hash of how the run ended, given by `fields` and the time taken, so that shared
+  /// results can be checked for edits.
+  pub fn result_hash(&self, fields: &[&dyn Display]) -> String {
+    // Whole centiseconds, as shown, so the same time always gives the same hash.
+    let centiseconds = (100.0 * self.elapsed).round() as u64;
+    let mut state: Vec<String> = fields.iter().map(|field| field.to_string()).collect();
+    state.push(centiseconds.to_string());
+    assets::content_hash(state.join("|").as_bytes())[..8].to_string()
+  }
+}
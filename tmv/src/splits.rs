@@ -0,0 +1,36 @@
+use crate::{game_events::GameEvent, power_ups::PowerUp};
+
+/// An interaction that sets this flag finishes the game.
+pub const GAME_FINISHED_FLAG: &str = "game_finished";
+
+/// A milestone on the critical path, for speedrun timers to split on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Split {
+  PowerUp(PowerUp),
+  // By the interaction's number in the map.
+  Interaction(i32),
+  BossDefeated(&'static str),
+  GameFinished,
+}
+
+impl Split {
+  /// The signal id autosplitters match on. These are built only from names that are already
+  /// fixed by maps and saves, so they stay the same from version to version: `power_up:dash`,
+  /// `interaction:12`, `boss:bee_queen`, and `game_finished`.
+  pub fn id(self) -> String {
+    match self {
+      Split::PowerUp(power_up) => format!("power_up:{}", power_up.name()),
+      Split::Interaction(number) => format!("interaction:{}", number),
+      Split::BossDefeated(boss) => format!("boss:{}", boss),
+      Split::GameFinished => "game_finished".to_string(),
+    }
+  }
+
+  /// The event that signals this split, at `game_time` seconds of play.
+  pub fn event(self, game_time: f32) -> GameEvent {
+    GameEvent::Split {
+      id: self.id(),
+      game_time,
+    }
+  }
+}
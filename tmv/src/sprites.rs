@@ -0,0 +1,97 @@
+use web_sys::HtmlImageElement;
+
+use crate::{debug_hud::MovementState, math::Vec2, render::Layer};
+
+// Frames in the player sheet are this many pixels across.
+const FRAME_WIDTH: f32 = 40.0;
+// Below this horizontal speed a grounded player stands still rather than running.
+const RUN_MIN_SPEED: f32 = 1.0;
+
+/// Which row of the player spritesheet to draw from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerAnimation {
+  Idle,
+  Run,
+  Jump,
+  Fall,
+  Dash,
+  Shrunken,
+}
+
+struct SheetRow {
+  top:            f32,
+  height:         f32,
+  frame_count:    u32,
+  frames_per_sec: f32,
+}
+
+impl PlayerAnimation {
+  pub fn for_movement(state: MovementState, horizontal_speed: f32) -> Self {
+    match state {
+      MovementState::Dashing | MovementState::AimedDashing => PlayerAnimation::Dash,
+      MovementState::Shrunken => PlayerAnimation::Shrunken,
+      MovementState::Grounded if horizontal_speed.abs() >= RUN_MIN_SPEED => PlayerAnimation::Run,
+      MovementState::Grounded => PlayerAnimation::Idle,
+      MovementState::Rising | MovementState::SpringLaunched | MovementState::LedgeHanging => {
+        PlayerAnimation::Jump
+      }
+      MovementState::Falling
+      | MovementState::WallSliding
+      | MovementState::Swimming
+      | MovementState::Dead => PlayerAnimation::Fall,
+    }
+  }
+
+  // Must match the layout of player.png.
+  fn row(self) -> SheetRow {
+    let (top, height, frame_count, frames_per_sec) = match self {
+      PlayerAnimation::Idle => (0.0, 80.0, 2, 2.0),
+      PlayerAnimation::Run => (80.0, 80.0, 6, 12.0),
+      PlayerAnimation::Jump => (160.0, 80.0, 1, 0.0),
+      PlayerAnimation::Fall => (240.0, 80.0, 1, 0.0),
+      PlayerAnimation::Dash => (320.0, 80.0, 2, 16.0),
+      PlayerAnimation::Shrunken => (400.0, 32.0, 2, 3.0),
+    };
+    SheetRow {
+      top,
+      height,
+      frame_count,
+      frames_per_sec,
+    }
+  }
+}
+
+/// Draws the frame of `animation` that's showing `time` seconds in, stretched to `size` and
+/// centered on `center` (in screen pixels). The sheet faces right, so facing left mirrors it.
+pub fn draw_player(
+  ctx: &Layer,
+  sheet: &HtmlImageElement,
+  animation: PlayerAnimation,
+  time: f32,
+  facing_right: bool,
+  center: Vec2,
+  size: Vec2,
+) {
+  let row = animation.row();
+  let frame = (time * row.frames_per_sec) as u32 % row.frame_count;
+  ctx.scoped(|ctx| {
+    ctx.translate(center.0 as f64, center.1 as f64).unwrap();
+    if !facing_right {
+      ctx.scale(-1.0, 1.0).unwrap();
+    }
+    ctx.set_image_smoothing_enabled(false);
+    ctx
+      .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+        sheet,
+        (frame as f32 * FRAME_WIDTH) as f64,
+        row.top as f64,
+        FRAME_WIDTH as f64,
+        row.height as f64,
+        -size.0 as f64 / 2.0,
+        -size.1 as f64 / 2.0,
+        size.0 as f64,
+        size.1 as f64,
+      )
+      .unwrap();
+  });
+}
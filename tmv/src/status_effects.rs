@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// A lingering effect on the player, picked up from a hazard surface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StatusEffect {
+  Poisoned,
+  // Also keeps the player from dashing.
+  Shocked,
+}
+
+impl StatusEffect {
+  /// How long the effect lasts after leaving the surface.
+  pub fn linger_time(self) -> f32 {
+    match self {
+      StatusEffect::Poisoned => 1.5,
+      StatusEffect::Shocked => 0.75,
+    }
+  }
+
+  /// Seconds between damage ticks. These are longer than the blink after taking damage,
+  /// so that no tick gets swallowed by the previous one.
+  pub fn tick_period(self) -> f32 {
+    match self {
+      StatusEffect::Poisoned => 1.25,
+      StatusEffect::Shocked => 1.1,
+    }
+  }
+
+  /// The tint over the screen while the effect is active.
+  pub fn overlay_color(self) -> &'static str {
+    match self {
+      StatusEffect::Poisoned => "rgba(60, 200, 60, 0.15)",
+      StatusEffect::Shocked => "rgba(255, 240, 80, 0.15)",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ActiveEffect {
+  remaining:  f32,
+  until_tick: f32,
+}
+
+/// The effects currently on the player, with their timers.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatusEffects {
+  effects: HashMap<StatusEffect, ActiveEffect>,
+}
+
+impl StatusEffects {
+  /// Starts or refreshes an effect. A fresh effect does its first tick of damage right away.
+  pub fn apply(&mut self, effect: StatusEffect) {
+    let active = self.effects.entry(effect).or_insert(ActiveEffect {
+      remaining:  0.0,
+      until_tick: 0.0,
+    });
+    active.remaining = effect.linger_time();
+  }
+
+  /// Runs the timers forward, returning the damage due this frame.
+  pub fn update(&mut self, dt: f32) -> i32 {
+    let mut damage = 0;
+    for (effect, active) in &mut self.effects {
+      active.until_tick -= dt;
+      if active.until_tick <= 0.0 {
+        active.until_tick += effect.tick_period();
+        damage += 1;
+      }
+      active.remaining -= dt;
+    }
+    self.effects.retain(|_, active| active.remaining > 0.0);
+    damage
+  }
+
+  pub fn has(&self, effect: StatusEffect) -> bool {
+    self.effects.contains_key(&effect)
+  }
+
+  pub fn active(&self) -> impl Iterator<Item = StatusEffect> + '_ {
+    self.effects.keys().copied()
+  }
+
+  pub fn clear(&mut self) {
+    self.effects.clear();
+  }
+}
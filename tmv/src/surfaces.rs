@@ -0,0 +1,43 @@
+// How fast conveyor belts carry whatever's standing on them, in tiles per second.
+pub const CONVEYOR_SPEED: f32 = 5.0;
+// How much of their usual grip on the ground players keep on ice, for speeding up and for
+// slowing down.
+pub const ICE_GRIP: f32 = 0.15;
+
+/// Solid tiles that do something to the player standing on them, picked by the tile's
+/// user_type in the tileset. Each kind gets its own wall collider, so the ground underfoot
+/// can be told apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Surface {
+  ConveyorLeft,
+  ConveyorRight,
+  Ice,
+}
+
+impl Surface {
+  pub fn from_user_type(user_type: &str) -> Option<Self> {
+    match user_type {
+      "conveyor_left" => Some(Surface::ConveyorLeft),
+      "conveyor_right" => Some(Surface::ConveyorRight),
+      "ice" => Some(Surface::Ice),
+      _ => None,
+    }
+  }
+
+  /// How fast the surface carries the player to the right (or left, if negative).
+  pub fn belt_speed(self) -> f32 {
+    match self {
+      Surface::ConveyorLeft => -CONVEYOR_SPEED,
+      Surface::ConveyorRight => CONVEYOR_SPEED,
+      Surface::Ice => 0.0,
+    }
+  }
+
+  /// Multiplies how quickly the player can change speed while standing on the surface.
+  pub fn grip(self) -> f32 {
+    match self {
+      Surface::Ice => ICE_GRIP,
+      Surface::ConveyorLeft | Surface::ConveyorRight => 1.0,
+    }
+  }
+}
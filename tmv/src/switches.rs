@@ -0,0 +1,46 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+// How long a switch lights up for after it's hit, in seconds.
+pub const SWITCH_FLASH_TIME: f32 = 0.3;
+
+/// What it takes to hit a switch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SwitchHit {
+  Touch,
+  Dash,
+}
+
+impl SwitchHit {
+  pub fn from_property(name: &str) -> Option<Self> {
+    match name {
+      "touch" => Some(SwitchHit::Touch),
+      "dash" => Some(SwitchHit::Dash),
+      _ => None,
+    }
+  }
+}
+
+/// Which switch channels are on. Every channel starts off whenever the world is rebuilt, so
+/// a puzzle left half done resets on respawn.
+#[derive(Debug, Clone, Default)]
+pub struct SwitchChannels {
+  on: HashSet<String>,
+}
+
+impl SwitchChannels {
+  pub fn is_on(&self, channel: &str) -> bool {
+    self.on.contains(channel)
+  }
+
+  pub fn toggle(&mut self, channel: &str) {
+    if !self.on.remove(channel) {
+      self.on.insert(channel.to_string());
+    }
+  }
+
+  pub fn clear(&mut self) {
+    self.on.clear();
+  }
+}
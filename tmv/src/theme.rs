@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use wasm_bindgen::JsValue;
+use web_sys::CanvasRenderingContext2d;
+
+/// How one kind of object is drawn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectStyle {
+  pub fill:   String,
+  pub stroke: String,
+  pub alpha:  f32,
+}
+
+impl ObjectStyle {
+  fn new(fill: &str, stroke: &str) -> Self {
+    Self {
+      fill:   fill.to_string(),
+      stroke: stroke.to_string(),
+      alpha:  1.0,
+    }
+  }
+
+  /// Sets the fill, stroke, and alpha. Callers reset the global alpha when they're done.
+  pub fn apply(&self, ctx: &CanvasRenderingContext2d) {
+    ctx.set_fill_style(&JsValue::from_str(&self.fill));
+    ctx.set_stroke_style(&JsValue::from_str(&self.stroke));
+    ctx.set_global_alpha(self.alpha as f64);
+  }
+}
+
+// A theme file only needs to mention the parts of a style it changes.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StyleOverride {
+  fill:   Option<String>,
+  stroke: Option<String>,
+  alpha:  Option<f32>,
+}
+
+/// Colors for objects, keyed by kind name, as loaded from a theme JSON file like
+/// `{"coin": {"fill": "#ff0", "stroke": "#aa0"}}`.
+#[derive(Debug, Clone)]
+pub struct Theme {
+  styles: HashMap<&'static str, ObjectStyle>,
+}
+
+impl Default for Theme {
+  fn default() -> Self {
+    let styles = [
+      ("coin", ObjectStyle::new("#ff0", "#aa0")),
+      ("rare_coin", ObjectStyle::new("#04a", "#026")),
+      ("hp_up", ObjectStyle::new("#0f0", "#0a0")),
+      ("power_up", ObjectStyle::new("#00f", "#002")),
+      ("bullet", ObjectStyle::new("#f00", "#a00")),
+      ("spore", ObjectStyle::new("#a4d", "#527")),
+      ("fragment", ObjectStyle::new("#c6f", "#527")),
+      ("splitter", ObjectStyle::new("#f80", "#a40")),
+      ("player_shot", ObjectStyle::new("#8ff", "#088")),
+      // Only drawn as the icon on gates that open for found secrets.
+      ("secret", ObjectStyle::new("#eef", "#88a")),
+      ("stone", ObjectStyle::new("#888", "#444")),
+      ("breakable_block", ObjectStyle::new("#a97", "#543")),
+      ("switch", ObjectStyle::new("#a44", "#422")),
+      ("switch_on", ObjectStyle::new("#4c4", "#242")),
+      ("toggle_block", ObjectStyle::new("#c8f", "#537")),
+      ("vanish_block", ObjectStyle::new("#888", "#444")),
+      // Blended in over the block as it's about to vanish.
+      ("vanish_block_warning", ObjectStyle::new("#f00", "#444")),
+      ("laser_turret", ObjectStyle::new("#777", "#222")),
+      ("laser", ObjectStyle::new("#f00", "#f00")),
+      ("walker", ObjectStyle::new("#a33", "#411")),
+      // Only the fill is used, for both the dotted shot paths and the thwump fall zones.
+      // The alpha fades further with distance from the player.
+      (
+        "hazard_preview",
+        ObjectStyle {
+          alpha: 0.3,
+          ..ObjectStyle::new("#f66", "#f66")
+        },
+      ),
+      // Only the stroke and alpha are used, for the line along the top of water.
+      (
+        "water_surface",
+        ObjectStyle {
+          alpha: 0.7,
+          ..ObjectStyle::new("#aef", "#cff")
+        },
+      ),
+      // The fill is the rail itself, and the stroke the streaks running along it.
+      (
+        "boost_rail",
+        ObjectStyle {
+          alpha: 0.5,
+          ..ObjectStyle::new("#4af", "#dff")
+        },
+      ),
+      // The fill tints the tile, and the stroke is a glint across it.
+      (
+        "slick_wall",
+        ObjectStyle {
+          alpha: 0.35,
+          ..ObjectStyle::new("#bdf", "#fff")
+        },
+      ),
+      // The fill is the body of the chase hazard, and the stroke its leading edge.
+      ("chase_wall", ObjectStyle::new("#555", "#ccc")),
+      ("chase_lava", ObjectStyle::new("#e40", "#fc0")),
+      // Only the stroke and alpha are used.
+      (
+        "boost_trail",
+        ObjectStyle {
+          alpha: 0.6,
+          ..ObjectStyle::new("#4af", "#8df")
+        },
+      ),
+    ];
+    Self {
+      styles: styles.into_iter().collect(),
+    }
+  }
+}
+
+impl Theme {
+  /// Parses a theme file, over the top of the default theme. Unknown kinds are errors, so
+  /// typos don't go unnoticed.
+  pub fn from_json(json: &str) -> Result<Self, String> {
+    let overrides: HashMap<String, StyleOverride> =
+      serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let mut theme = Self::default();
+    for (kind, style_override) in overrides {
+      let style = theme
+        .styles
+        .get_mut(kind.as_str())
+        .ok_or_else(|| format!("Unknown object kind in theme: {:?}", kind))?;
+      if let Some(fill) = style_override.fill {
+        style.fill = fill;
+      }
+      if let Some(stroke) = style_override.stroke {
+        style.stroke = stroke;
+      }
+      if let Some(alpha) = style_override.alpha {
+        style.alpha = alpha.clamp(0.0, 1.0);
+      }
+    }
+    Ok(theme)
+  }
+
+  pub fn style(&self, kind: &str) -> &ObjectStyle {
+    self.styles.get(kind).unwrap_or_else(|| panic!("No theme style for {:?}", kind))
+  }
+}
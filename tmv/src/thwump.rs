@@ -0,0 +1,69 @@
+use rapier2d::{
+  na::Vector2,
+  prelude::{Group, InteractionGroups, Point, QueryFilter, Ray},
+};
+
+use crate::{
+  collision::{CollisionWorld, PhysicsObjectHandle, WALLS_GROUP},
+  math::Vec2,
+};
+
+// How far ahead a thwump can see the player, in tiles.
+pub const SIGHT_RANGE: f32 = 12.0;
+pub const FALL_SPEED: f32 = 25.0;
+// How long a thwump sits where it landed before heading back.
+pub const REST_TIME: f32 = 1.0;
+pub const RISE_SPEED: f32 = 3.0;
+// Getting crushed is fatal, like lava: there's nowhere for the player to go.
+pub const CRUSH_DAMAGE: i32 = 100;
+
+/// How far the thwump at `pos` can move along `direction` before its leading face hits a
+/// wall, up to SIGHT_RANGE. Casts from the middle and both ends of the face, so a ledge
+/// under just one end still stops it. `half_size` is half the thwump's width across
+/// `direction`, then half its depth along it.
+pub fn clearance(
+  collision: &CollisionWorld,
+  handle: &PhysicsObjectHandle,
+  pos: Vec2,
+  direction: Vec2,
+  half_size: Vec2,
+) -> f32 {
+  let across = Vec2(-direction.1, direction.0);
+  let filter = QueryFilter::default()
+    .exclude_collider(handle.collider)
+    .exclude_sensors()
+    .groups(InteractionGroups::new(Group::ALL, WALLS_GROUP));
+  let mut clearance = SIGHT_RANGE;
+  for side in [-0.95, 0.0, 0.95] {
+    let origin = pos + (side * half_size.0) * across;
+    let ray = Ray::new(
+      Point::new(origin.0, origin.1),
+      Vector2::new(direction.0, direction.1),
+    );
+    if let Some((_, toi)) = collision.query_pipeline.cast_ray(
+      &collision.rigid_body_set,
+      &collision.collider_set,
+      &ray,
+      SIGHT_RANGE + half_size.1,
+      true,
+      filter,
+    ) {
+      clearance = clearance.min(toi - half_size.1);
+    }
+  }
+  clearance.max(0.0)
+}
+
+/// Whether the player is in the thwump's path, within `clearance` of its leading face.
+pub fn sees_player(
+  pos: Vec2,
+  direction: Vec2,
+  half_size: Vec2,
+  clearance: f32,
+  player_pos: Vec2,
+) -> bool {
+  let offset = player_pos - pos;
+  let ahead = offset.dot(direction) - half_size.1;
+  let across = offset.dot(Vec2(-direction.1, direction.0)).abs();
+  ahead > 0.0 && ahead < clearance && across < half_size.0 + crate::PLAYER_SIZE.0 / 2.0
+}
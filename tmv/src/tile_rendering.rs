@@ -4,20 +4,156 @@ use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 
 // use crate::game::ImageResource;
-use crate::game_maps::GameMap;
+use crate::game_maps::{GameMap, ParallaxDepth};
 use crate::math::{Rect, Vec2};
+use crate::render::Layer;
 use crate::ImageResource;
 // use crate::web::IntoJsError;
 
 pub const TILE_SIZE: f32 = 32.0;
+// Past this the scratch canvas gets too big to be worth it.
+pub const MAX_RENDER_SCALE: f32 = 2.0;
+pub const MIN_RENDER_SCALE: f32 = 0.5;
 const CHUNK_SIZE_IN_PIXELS: f32 = TILE_SIZE * tiled::Chunk::WIDTH as f32;
 
 // Statically assert that tiled::Chunk::WIDTH == tiled::Chunk::HEIGHT.
 const _: () = [()][(tiled::Chunk::WIDTH != tiled::Chunk::HEIGHT) as usize];
 
+/// Higher resolution copies of image resources, keyed by how many times TILE_SIZE their tiles are.
+pub type HdImages = HashMap<(ImageResource, u32), web_sys::HtmlImageElement>;
+
+/// An image to draw tiles from, and how many times TILE_SIZE its tiles are.
+type TileSource<'a> = (&'a web_sys::HtmlImageElement, f32);
+
+/// Another map drawn along with the main one, shifted over by whole chunks, like the rooms
+/// streamed in below the player in descent mode.
+pub struct MapFragment {
+  pub game_map:       Rc<GameMap>,
+  pub chunk_offset:   (i32, i32),
+  pub tileset_images: HashMap<usize, ImageResource>,
+}
+
 pub struct TileRenderer {
-  pub current_rect: Rect,
-  pub game_map:     Rc<GameMap>,
+  pub current_rect:   Rect,
+  pub game_map:       Rc<GameMap>,
+  // The image each of the map's tilesets comes from, looked up once rather than per redraw.
+  pub tileset_images: HashMap<usize, ImageResource>,
+  // Drawn after the main map, in the order they were added.
+  pub fragments:      Vec<MapFragment>,
+  // Canvas pixels per world pixel in the scratch canvas, e.g. the devicePixelRatio.
+  pub render_scale:   f32,
+  // The scale of the source each image's tiles in the scratch canvas were drawn from.
+  pub source_scales:  HashMap<ImageResource, f32>,
+  // How many map chunks (across all rendered layers) are currently drawn into the scratch canvas.
+  pub cached_chunks:  usize,
+}
+
+/// The image each tileset's tiles come from, by tileset index.
+fn tileset_image_resources(map: &tiled::Map) -> HashMap<usize, ImageResource> {
+  let mut tileset_index_to_imag_resource = HashMap::new();
+  for (tileset_index, tileset) in map.tilesets().iter().enumerate() {
+    if let Some(image) = &tileset.image {
+      let image_resource = ImageResource::from_path(image.source.to_str().unwrap()).expect(
+        &format!("Failed to find image resource for path: {:?}", image.source),
+      );
+      tileset_index_to_imag_resource.insert(tileset_index, image_resource);
+    }
+  }
+  tileset_index_to_imag_resource
+}
+
+/// Picks the version of each image that best matches the render scale: the smallest one
+/// that's at least as detailed as needed, or failing that the most detailed. HD variants
+/// that haven't loaded, or aren't their scale times the size of the 1x image, are skipped,
+/// so without any HD art every tile comes from its 1x image.
+fn pick_sources<'a>(
+  render_scale: f32,
+  images: &'a HashMap<ImageResource, web_sys::HtmlImageElement>,
+  hd_images: &'a HdImages,
+) -> HashMap<ImageResource, TileSource<'a>> {
+  let mut sources = HashMap::new();
+  for (image_resource, image) in images {
+    let mut candidates = vec![(image, 1.0)];
+    for (scale, _) in image_resource.hd_variants() {
+      let Some(hd_image) = hd_images.get(&(image_resource.clone(), *scale)) else {
+        continue;
+      };
+      let loaded = hd_image.complete() && hd_image.natural_width() > 0;
+      let matches_scale = hd_image.natural_width() == scale * image.natural_width()
+        && hd_image.natural_height() == scale * image.natural_height();
+      if loaded && matches_scale {
+        candidates.push((hd_image, *scale as f32));
+      }
+    }
+    candidates.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let best = candidates.iter().find(|(_, scale)| *scale >= render_scale);
+    sources.insert(
+      image_resource.clone(),
+      *best.unwrap_or_else(|| candidates.last().unwrap()),
+    );
+  }
+  sources
+}
+
+/// Draws one tile with its top left corner at `dest_pos`, in the context's current units,
+/// flipped however the map says. Marker tiles are only for the game, and aren't drawn.
+fn draw_tile(
+  ctx: &web_sys::CanvasRenderingContext2d,
+  tile: &tiled::LayerTile,
+  dest_pos: Vec2,
+  tileset_images: &HashMap<usize, ImageResource>,
+  sources: &HashMap<ImageResource, TileSource>,
+) {
+  let base_tile = tile.get_tile().unwrap();
+  if let Some(user_type) = &base_tile.user_type {
+    if user_type == "marker" {
+      return;
+    }
+  }
+  let ts = tile.get_tileset();
+  let ts_index = tile.id() as u32;
+  let ts_x = ts_index % ts.columns;
+  let ts_y = ts_index / ts.columns;
+  let image_resource = tileset_images.get(&tile.tileset_index()).expect("Missing image resource");
+  let (source_image, source_scale) = sources[image_resource];
+  let source_tile_size = TILE_SIZE * source_scale;
+  let ts_pos = Vec2(
+    ts_x as f32 * source_tile_size,
+    ts_y as f32 * source_tile_size,
+  );
+  // Flips are done around the middle of the tile.
+  ctx.save();
+  ctx
+    .translate(
+      (dest_pos.0 + TILE_SIZE / 2.0) as f64,
+      (dest_pos.1 + TILE_SIZE / 2.0) as f64,
+    )
+    .unwrap();
+  if tile.flip_h {
+    ctx.scale(-1.0, 1.0).unwrap();
+  }
+  if tile.flip_v {
+    ctx.scale(1.0, -1.0).unwrap();
+  }
+  // Flip diagonally
+  if tile.flip_d {
+    ctx.rotate(std::f64::consts::FRAC_PI_2).unwrap();
+    ctx.scale(1.0, -1.0).unwrap();
+  }
+  ctx
+    .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+      source_image,
+      ts_pos.0 as f64,
+      ts_pos.1 as f64,
+      source_tile_size as f64,
+      source_tile_size as f64,
+      -TILE_SIZE as f64 / 2.0,
+      -TILE_SIZE as f64 / 2.0,
+      TILE_SIZE as f64,
+      TILE_SIZE as f64,
+    )
+    .unwrap();
+  ctx.restore();
 }
 
 impl TileRenderer {
@@ -25,145 +161,177 @@ impl TileRenderer {
     Self {
       // Our starting rect is far away, forcing a rerender on the first .draw().
       current_rect: Rect::new(Vec2(-f32::MAX, -f32::MAX), scratch_dims),
+      tileset_images: tileset_image_resources(&game_map.map),
       game_map,
+      fragments: Vec::new(),
+      render_scale: 1.0,
+      source_scales: HashMap::new(),
+      cached_chunks: 0,
     }
   }
 
+  pub fn add_fragment(&mut self, game_map: Rc<GameMap>, chunk_offset: (i32, i32)) {
+    self.fragments.push(MapFragment {
+      tileset_images: tileset_image_resources(&game_map.map),
+      game_map,
+      chunk_offset,
+    });
+    // The fragment may land on chunks that are already drawn, so everything gets redrawn.
+    self.current_rect.pos = Vec2(-f32::MAX, -f32::MAX);
+  }
+
+  pub fn remove_fragment(&mut self, chunk_offset: (i32, i32)) {
+    self.fragments.retain(|fragment| fragment.chunk_offset != chunk_offset);
+    self.current_rect.pos = Vec2(-f32::MAX, -f32::MAX);
+  }
+
+  pub fn clear_fragments(&mut self) {
+    self.fragments.clear();
+    self.current_rect.pos = Vec2(-f32::MAX, -f32::MAX);
+  }
+
+  pub fn set_render_scale(&mut self, render_scale: f32) {
+    let render_scale = render_scale.clamp(MIN_RENDER_SCALE, MAX_RENDER_SCALE);
+    if render_scale != self.render_scale {
+      self.render_scale = render_scale;
+      // Force a rerender on the next .draw().
+      self.current_rect.pos = Vec2(-f32::MAX, -f32::MAX);
+    }
+  }
+
+  /// Frees the scratch canvas's pixels. The next .draw() regrows and rerenders it.
+  pub fn clear_cache(&mut self, scratch_canvas: &web_sys::HtmlCanvasElement) {
+    scratch_canvas.set_width(0);
+    scratch_canvas.set_height(0);
+    self.current_rect.pos = Vec2(-f32::MAX, -f32::MAX);
+    self.cached_chunks = 0;
+  }
+
+  /// Moves the scratch canvas to start at the given chunk. Whatever it already has that's
+  /// still in range is shifted over rather than redrawn, so only the newly exposed strips
+  /// of chunks get rendered.
   fn redraw(
     &mut self,
     (chunk_x, chunk_y): (i32, i32),
-    images: &HashMap<ImageResource, web_sys::HtmlImageElement>,
+    sources: &HashMap<ImageResource, TileSource>,
+    scratch_canvas: &web_sys::HtmlCanvasElement,
     scratch_ctx: &web_sys::CanvasRenderingContext2d,
   ) {
-    // Fill the scratch canvas with pink.
-    scratch_ctx.set_fill_style(&JsValue::from_str("black"));
-    scratch_ctx.fill_rect(
-      0.0,
-      0.0,
-      self.current_rect.size.0 as f64,
-      self.current_rect.size.1 as f64,
+    let scale = self.render_scale;
+    let scratch_pixels = (
+      (self.current_rect.size.0 * scale) as u32,
+      (self.current_rect.size.1 * scale) as u32,
     );
-    // FIXME: It's possible to reuse much of the existing image, by shifting it.
-    for render_layer in [
-      self.game_map.get_background_layer(),
-      self.game_map.get_main_layer(),
-    ] {
-      //let main_layer = self.game_map.get_main_layer();
-      let chunk_count_x = (self.current_rect.size.0 / CHUNK_SIZE_IN_PIXELS).floor() as i32;
-      let chunk_count_y = (self.current_rect.size.1 / CHUNK_SIZE_IN_PIXELS).floor() as i32;
-      self.current_rect = Rect::new(
-        Vec2(
-          chunk_x as f32 * CHUNK_SIZE_IN_PIXELS,
-          chunk_y as f32 * CHUNK_SIZE_IN_PIXELS,
-        ),
-        self.current_rect.size,
-      );
-      let mut tileset_index_to_imag_resource = HashMap::new();
-      //let mut tileset_index_and_id_to_pos = HashMap::new();
-      for (tileset_index, tileset) in self.game_map.map.tilesets().iter().enumerate() {
-        if let Some(image) = &tileset.image {
-          let image_resource = ImageResource::from_path(image.source.to_str().unwrap()).expect(
-            &format!("Failed to find image resource for path: {:?}", image.source),
-          );
-          tileset_index_to_imag_resource.insert(tileset_index, image_resource);
-        }
-        // crate::log(&format!("Tileset {} has {} tiles in {} columns", tileset_index, tileset.tiles().len(), tileset.columns));
-        // for (tile_index, (tile_id, _)) in tileset.tiles().enumerate() {
-        //   crate::log(&format!("Index: {}, ID: {}", tile_index, tile_id));
-        //   let ts_x = tile_index as u32 % tileset.columns;
-        //   let ts_y = tile_index as u32 / tileset.columns;
-        //   tileset_index_and_id_to_pos.insert((tileset_index, tile_id), (ts_x, ts_y));
-        // }
+    let resized = (scratch_canvas.width(), scratch_canvas.height()) != scratch_pixels;
+    if resized {
+      scratch_canvas.set_width(scratch_pixels.0);
+      scratch_canvas.set_height(scratch_pixels.1);
+    }
+    let chunk_count_x = (self.current_rect.size.0 / CHUNK_SIZE_IN_PIXELS).floor() as i32;
+    let chunk_count_y = (self.current_rect.size.1 / CHUNK_SIZE_IN_PIXELS).floor() as i32;
+    // How many chunks over the contents move, if enough of them are still good to keep.
+    let old_pos = self.current_rect.pos;
+    let shift = match resized || old_pos.0 == -f32::MAX {
+      true => None,
+      false => Some((
+        chunk_x - (old_pos.0 / CHUNK_SIZE_IN_PIXELS).round() as i32,
+        chunk_y - (old_pos.1 / CHUNK_SIZE_IN_PIXELS).round() as i32,
+      )),
+    }
+    .filter(|(dx, dy)| dx.abs() < chunk_count_x && dy.abs() < chunk_count_y);
+    self.current_rect = Rect::new(
+      Vec2(
+        chunk_x as f32 * CHUNK_SIZE_IN_PIXELS,
+        chunk_y as f32 * CHUNK_SIZE_IN_PIXELS,
+      ),
+      self.current_rect.size,
+    );
+    let chunk_pixels = (scale * CHUNK_SIZE_IN_PIXELS) as f64;
+    let (width, height) = (scratch_pixels.0 as f64, scratch_pixels.1 as f64);
+    match shift {
+      // Leave gaps transparent, so background parallax layers show through them.
+      None => scratch_ctx.clear_rect(0.0, 0.0, width, height),
+      Some((dx, dy)) => {
+        // Drawing a canvas onto itself reads from a copy, so overlapping is fine. It has to
+        // replace what's there rather than draw over it, or the old tiles would show through
+        // the gaps between the moved ones.
+        scratch_ctx.set_global_composite_operation("copy").unwrap();
+        scratch_ctx
+          .draw_image_with_html_canvas_element(
+            scratch_canvas,
+            -dx as f64 * chunk_pixels,
+            -dy as f64 * chunk_pixels,
+          )
+          .unwrap();
+        scratch_ctx.set_global_composite_operation("source-over").unwrap();
+        // Then clear everything around the part that was kept, for the new chunks.
+        let kept_x0 = (-dx).max(0) as f64 * chunk_pixels;
+        let kept_x1 = (chunk_count_x - dx).min(chunk_count_x) as f64 * chunk_pixels;
+        let kept_y0 = (-dy).max(0) as f64 * chunk_pixels;
+        let kept_y1 = (chunk_count_y - dy).min(chunk_count_y) as f64 * chunk_pixels;
+        scratch_ctx.clear_rect(0.0, 0.0, width, kept_y0);
+        scratch_ctx.clear_rect(0.0, kept_y1, width, height - kept_y1);
+        scratch_ctx.clear_rect(0.0, kept_y0, kept_x0, kept_y1 - kept_y0);
+        scratch_ctx.clear_rect(kept_x1, kept_y0, width - kept_x1, kept_y1 - kept_y0);
       }
-
-      match render_layer.layer_type() {
-        tiled::LayerType::TileLayer(tiled::TileLayer::Infinite(data)) => {
-          //println!("Infinite tile layer");
-          // We iterate over the chunks in the desired rect.
-          for y in 0..chunk_count_y {
-            for x in 0..chunk_count_x {
-              if let Some(chunk) = data.get_chunk(chunk_x + x, chunk_y + y) {
-                // Draw the chunk.
-                for tile_y in 0..tiled::Chunk::HEIGHT as i32 {
-                  for tile_x in 0..tiled::Chunk::WIDTH as i32 {
-                    if let Some(tile) = chunk.get_tile(tile_x, tile_y) {
-                      let base_tile = tile.get_tile().unwrap();
-                      if let Some(user_type) = &base_tile.user_type {
-                        if user_type == "marker" {
-                          continue;
-                        }
-                      }
-
-                      let tileset_index = tile.tileset_index();
-                      //let (ts_x, ts_y) = tileset_index_and_id_to_pos[&(tileset_index, tile.id())];
-                      let ts = tile.get_tileset();
-                      // //let ts_index = tile.tileset_index() as u32;
-                      let ts_index = tile.id() as u32;
-                      let ts_x = ts_index % ts.columns;
-                      let ts_y = ts_index / ts.columns;
-                      let ts_pos = Vec2(ts_x as f32 * TILE_SIZE, ts_y as f32 * TILE_SIZE);
-                      let chunk_pos = Vec2(
-                        x as f32 * CHUNK_SIZE_IN_PIXELS,
-                        y as f32 * CHUNK_SIZE_IN_PIXELS,
-                      );
-                      let tile_pos = Vec2(tile_x as f32 * TILE_SIZE, tile_y as f32 * TILE_SIZE);
-                      let dest_pos = chunk_pos + tile_pos;
-                      // let image_resource = tileset_index_to_imag_resource
-                      //   .entry(tile.tileset_index())
-                      //   .or_insert_with(|| {
-                      //     let image_resource = ImageResource::Tileset(ts.name.clone());
-                      //     images
-                      //       .get(&image_resource)
-                      //       .expect("Missing image resource")
-                      //       .clone()
-                      //   });
-                      let image_resource = tileset_index_to_imag_resource
-                        .get(&tileset_index)
-                        .expect("Missing image resource");
-                      scratch_ctx
-                        .translate(
-                          (dest_pos.0 + TILE_SIZE / 2.0) as f64,
-                          (dest_pos.1 + TILE_SIZE / 2.0) as f64,
-                        )
-                        .unwrap();
-                      if tile.flip_h {
-                        // Mirror around dest_pos.0 + TILE_SIZE / 2
-                        scratch_ctx.scale(-1.0, 1.0).unwrap();
-                      }
-                      if tile.flip_v {
-                        scratch_ctx.scale(1.0, -1.0).unwrap();
-                        //scratch_ctx.translate(0.0, TILE_SIZE as f64).unwrap();
-                      }
-                      // Flip diagonally
-                      if tile.flip_d {
-                        scratch_ctx.rotate(std::f64::consts::FRAC_PI_2).unwrap();
-                        scratch_ctx.scale(1.0, -1.0).unwrap();
-                        // scratch_ctx.rotate(std::f64::consts::FRAC_PI_2);
-                        // scratch_ctx.translate(0.0, -TILE_SIZE as f64);
+    }
+    let was_drawn = |x: i32, y: i32| match shift {
+      Some((dx, dy)) => {
+        (0..chunk_count_x).contains(&(x + dx)) && (0..chunk_count_y).contains(&(y + dy))
+      }
+      None => false,
+    };
+    self.cached_chunks = 0;
+    let placed_maps = std::iter::once((&self.game_map, &self.tileset_images, (0, 0))).chain(
+      self.fragments.iter().map(|fragment| {
+        (
+          &fragment.game_map,
+          &fragment.tileset_images,
+          fragment.chunk_offset,
+        )
+      }),
+    );
+    for (game_map, tileset_images, (offset_x, offset_y)) in placed_maps {
+      for render_layer in [game_map.get_background_layer(), game_map.get_main_layer()] {
+        match render_layer.layer_type() {
+          tiled::LayerType::TileLayer(tiled::TileLayer::Infinite(data)) => {
+            // Tiles are placed in world pixels.
+            scratch_ctx.set_transform(scale as f64, 0.0, 0.0, scale as f64, 0.0, 0.0).unwrap();
+            // We iterate over the chunks in the desired rect.
+            for y in 0..chunk_count_y {
+              for x in 0..chunk_count_x {
+                let chunk = data.get_chunk(chunk_x + x - offset_x, chunk_y + y - offset_y);
+                if let Some(chunk) = chunk {
+                  self.cached_chunks += 1;
+                  if was_drawn(x, y) {
+                    continue;
+                  }
+                  // Draw the chunk.
+                  for tile_y in 0..tiled::Chunk::HEIGHT as i32 {
+                    for tile_x in 0..tiled::Chunk::WIDTH as i32 {
+                      if let Some(tile) = chunk.get_tile(tile_x, tile_y) {
+                        let chunk_pos = Vec2(
+                          x as f32 * CHUNK_SIZE_IN_PIXELS,
+                          y as f32 * CHUNK_SIZE_IN_PIXELS,
+                        );
+                        let tile_pos = Vec2(tile_x as f32 * TILE_SIZE, tile_y as f32 * TILE_SIZE);
+                        draw_tile(
+                          scratch_ctx,
+                          &tile,
+                          chunk_pos + tile_pos,
+                          tileset_images,
+                          sources,
+                        );
                       }
-                      scratch_ctx
-                        .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
-                          &images[&image_resource],
-                          ts_pos.0 as f64,
-                          ts_pos.1 as f64,
-                          TILE_SIZE as f64,
-                          TILE_SIZE as f64,
-                          -TILE_SIZE as f64 / 2.0, //dest_pos.0 as f64,
-                          -TILE_SIZE as f64 / 2.0, //dest_pos.1 as f64,
-                          TILE_SIZE as f64,
-                          TILE_SIZE as f64,
-                        )
-                        .unwrap();
-                      // Reset the transform.
-                      scratch_ctx.set_transform(1.0, 0.0, 0.0, 1.0, 0.0, 0.0).unwrap();
                     }
                   }
                 }
               }
             }
+            scratch_ctx.set_transform(1.0, 0.0, 0.0, 1.0, 0.0, 0.0).unwrap();
           }
+          _ => panic!("Unexpected layer type"),
         }
-        _ => panic!("Unexpected layer type"),
       }
     }
   }
@@ -173,11 +341,25 @@ impl TileRenderer {
     draw_rect: Rect,
     dest: &web_sys::CanvasRenderingContext2d,
     images: &HashMap<ImageResource, web_sys::HtmlImageElement>,
-    scratch_canvas: &web_sys::HtmlCanvasElement,
-    scratch_ctx: &web_sys::CanvasRenderingContext2d,
+    hd_images: &HdImages,
+    scratch: &Layer,
   ) {
-    // Clear the destination canvas.
-    dest.clear_rect(0.0, 0.0, draw_rect.size.0 as f64, draw_rect.size.1 as f64);
+    let (scratch_canvas, scratch_ctx): (_, &web_sys::CanvasRenderingContext2d) =
+      (&scratch.canvas, scratch);
+    let sources = pick_sources(self.render_scale, images, hd_images);
+    let source_scales: HashMap<_, _> = sources
+      .iter()
+      .map(|(image_resource, (_, scale))| (image_resource.clone(), *scale))
+      .collect();
+    // HD art that finishes loading after the first draw replaces what was drawn without it.
+    if source_scales != self.source_scales {
+      self.source_scales = source_scales;
+      self.current_rect.pos = Vec2(-f32::MAX, -f32::MAX);
+    }
+    // Clear the destination canvas to black, then put down anything behind the map.
+    dest.set_fill_style(&JsValue::from_str("black"));
+    dest.fill_rect(0.0, 0.0, draw_rect.size.0 as f64, draw_rect.size.1 as f64);
+    self.draw_parallax_layers(ParallaxDepth::Background, draw_rect, dest, images, &sources);
     //crate::log(&format!("Starting rect: {:?} -- Request rect: {:?}", self.current_rect, draw_rect));
     // Determine if the desired rect is contained entirely within the current rect.
     if !self.current_rect.contains_rect(draw_rect) {
@@ -198,7 +380,7 @@ impl TileRenderer {
       //  self.current_rect.size,
       //);
       // Redraw ourself.
-      self.redraw((chunk_x, chunk_y), images, scratch_ctx);
+      self.redraw((chunk_x, chunk_y), &sources, scratch_canvas, scratch_ctx);
     }
     //crate::log(&format!("New rect: {:?} -- Request rect: {:?}", self.current_rect, draw_rect));
     assert!(self.current_rect.contains_rect(draw_rect));
@@ -211,15 +393,92 @@ impl TileRenderer {
         // draw_rect.size.0 as f64,
         // draw_rect.size.1 as f64,
         &scratch_canvas,
-        (draw_rect.pos.0 - self.current_rect.pos.0) as f64,
-        (draw_rect.pos.1 - self.current_rect.pos.1) as f64,
-        draw_rect.size.0 as f64,
-        draw_rect.size.1 as f64,
+        (self.render_scale * (draw_rect.pos.0 - self.current_rect.pos.0)) as f64,
+        (self.render_scale * (draw_rect.pos.1 - self.current_rect.pos.1)) as f64,
+        (self.render_scale * draw_rect.size.0) as f64,
+        (self.render_scale * draw_rect.size.1) as f64,
         0.0,
         0.0,
         draw_rect.size.0 as f64,
         draw_rect.size.1 as f64,
       )
       .unwrap();
+    self.draw_parallax_layers(ParallaxDepth::Foreground, draw_rect, dest, images, &sources);
+  }
+
+  /// Draws the parallax layers at `depth` straight to `dest`. They each scroll at their own
+  /// rate, so unlike the main tiles they can't share the scratch canvas, and are redrawn
+  /// every frame, only as much as is on screen.
+  fn draw_parallax_layers(
+    &self,
+    depth: ParallaxDepth,
+    draw_rect: Rect,
+    dest: &web_sys::CanvasRenderingContext2d,
+    images: &HashMap<ImageResource, web_sys::HtmlImageElement>,
+    sources: &HashMap<ImageResource, TileSource>,
+  ) {
+    for parallax_layer in &self.game_map.parallax_layers {
+      if parallax_layer.depth != depth {
+        continue;
+      }
+      let layer = self.game_map.get_parallax_layer(parallax_layer);
+      if !layer.visible {
+        continue;
+      }
+      // Where the top left of the screen falls in the layer, as Tiled places it: a factor of 1
+      // moves with the map, and 0 stays fixed on screen.
+      let origin = Vec2(
+        layer.parallax_x * draw_rect.pos.0 - layer.offset_x,
+        layer.parallax_y * draw_rect.pos.1 - layer.offset_y,
+      );
+      dest.set_global_alpha(layer.opacity as f64);
+      match layer.layer_type() {
+        tiled::LayerType::TileLayer(tile_layer) => {
+          let (x0, y0) = (
+            (origin.0 / TILE_SIZE).floor() as i32,
+            (origin.1 / TILE_SIZE).floor() as i32,
+          );
+          let (x1, y1) = (
+            ((origin.0 + draw_rect.size.0) / TILE_SIZE).ceil() as i32,
+            ((origin.1 + draw_rect.size.1) / TILE_SIZE).ceil() as i32,
+          );
+          for tile_y in y0..y1 {
+            for tile_x in x0..x1 {
+              let tile = match tile_layer {
+                tiled::TileLayer::Finite(data) => data.get_tile(tile_x, tile_y),
+                tiled::TileLayer::Infinite(data) => data.get_tile(tile_x, tile_y),
+              };
+              if let Some(tile) = tile {
+                draw_tile(
+                  dest,
+                  &tile,
+                  Vec2(tile_x as f32 * TILE_SIZE, tile_y as f32 * TILE_SIZE) - origin,
+                  &self.tileset_images,
+                  sources,
+                );
+              }
+            }
+          }
+        }
+        tiled::LayerType::ImageLayer(image_layer) => {
+          if let Some(image) = &image_layer.image {
+            let image_resource = ImageResource::from_path(image.source.to_str().unwrap())
+              .unwrap_or_else(|| panic!("No image resource for path: {:?}", image.source));
+            dest
+              .draw_image_with_html_image_element(
+                &images[&image_resource],
+                -origin.0 as f64,
+                -origin.1 as f64,
+              )
+              .unwrap();
+          }
+        }
+        _ => panic!(
+          "Parallax layer {} must be a tile or image layer",
+          layer.name
+        ),
+      }
+    }
+    dest.set_global_alpha(1.0);
   }
 }
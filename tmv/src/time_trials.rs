@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::Vec2;
+
+/// How long the results popup stays up after finishing a trial.
+pub const RESULT_DISPLAY_TIME: f32 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Medal {
+  Gold,
+  Silver,
+  Bronze,
+}
+
+impl Medal {
+  pub fn name(self) -> &'static str {
+    match self {
+      Medal::Gold => "Gold",
+      Medal::Silver => "Silver",
+      Medal::Bronze => "Bronze",
+    }
+  }
+
+  pub fn color(self) -> &'static str {
+    match self {
+      Medal::Gold => "#fd0",
+      Medal::Silver => "#ccc",
+      Medal::Bronze => "#c83",
+    }
+  }
+}
+
+/// A trial as defined by its trial_start rect in the map.
+#[derive(Debug, Clone)]
+pub struct TimeTrial {
+  pub name:        String,
+  pub start_point: Vec2,
+  // Gold, silver, and bronze thresholds in seconds. Missing thresholds are infinite.
+  pub medal_times: [f32; 3],
+  pub elapsed:     f32,
+}
+
+impl TimeTrial {
+  pub fn new(name: String, start_point: Vec2, medal_times: [f32; 3]) -> Self {
+    Self {
+      name,
+      start_point,
+      medal_times,
+      elapsed: 0.0,
+    }
+  }
+
+  pub fn medal_for(&self, time: f32) -> Option<Medal> {
+    [Medal::Gold, Medal::Silver, Medal::Bronze]
+      .into_iter()
+      .zip(self.medal_times)
+      .find(|(_, threshold)| time <= *threshold)
+      .map(|(medal, _)| medal)
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrialResult {
+  pub trial:        TimeTrial,
+  pub time:         f32,
+  pub medal:        Option<Medal>,
+  pub new_best:     bool,
+  pub display_time: f32,
+}
+
+pub fn format_time(seconds: f32) -> String {
+  let minutes = (seconds / 60.0).floor() as i32;
+  format!("{}:{:05.2}", minutes, seconds - 60.0 * minutes as f32)
+}
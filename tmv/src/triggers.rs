@@ -0,0 +1,170 @@
+use crate::math::{Rect, Vec2};
+
+/// How long a laser fires for, in seconds.
+pub const LASER_TIME: f32 = 0.8;
+// How thick a laser beam is where it hurts, in tiles.
+const LASER_THICKNESS: f32 = 0.6;
+
+/// What a spawn_object action can make.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnKind {
+  // A solid block, which can't be broken.
+  Stone,
+}
+
+impl SpawnKind {
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "stone" => Some(SpawnKind::Stone),
+      _ => None,
+    }
+  }
+}
+
+/// One step of what an interaction does, as written in its interact rect's actions property.
+/// Positions and sizes are in tiles.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriggerAction {
+  // Removes every object of `kind` (as the inspector names kinds, like "Stone") whose
+  // middle is in `rect`.
+  DeleteObjects { kind: String, rect: Rect },
+  // A horizontal beam from `origin`, `length` tiles long (negative to go left), which kills
+  // the player if it touches them.
+  FireLaser { origin: Vec2, length: f32 },
+  SpawnObject { kind: SpawnKind, rect: Rect },
+  // Records progress in the save. Deletes and spawns in a script that sets a flag are
+  // redone whenever the world is rebuilt once the flag is set, so they stay done. Setting
+  // game_finished ends the game, as far as speedrun timers are concerned.
+  SetFlag { flag: String },
+  // Marks the secret called `name` as found, for count gates that count secrets.
+  FindSecret { name: String },
+}
+
+impl TriggerAction {
+  /// Whether this changes the world in a way that has to be redone when it's rebuilt.
+  pub fn is_persistent(&self) -> bool {
+    matches!(
+      self,
+      TriggerAction::DeleteObjects { .. } | TriggerAction::SpawnObject { .. }
+    )
+  }
+
+  /// The same action, for a copy of its map moved over by `shift` tiles.
+  pub fn shifted(self, shift: Vec2) -> Self {
+    match self {
+      TriggerAction::DeleteObjects { kind, rect } => TriggerAction::DeleteObjects {
+        kind,
+        rect: Rect::new(rect.pos + shift, rect.size),
+      },
+      TriggerAction::FireLaser { origin, length } => TriggerAction::FireLaser {
+        origin: origin + shift,
+        length,
+      },
+      TriggerAction::SpawnObject { kind, rect } => TriggerAction::SpawnObject {
+        kind,
+        rect: Rect::new(rect.pos + shift, rect.size),
+      },
+      TriggerAction::SetFlag { .. } | TriggerAction::FindSecret { .. } => self,
+    }
+  }
+}
+
+fn parse_numbers<const N: usize>(args: &[&str], action: &str) -> Result<[f32; N], String> {
+  if args.len() != N {
+    return Err(format!(
+      "{} takes {} numbers, but got {}",
+      action,
+      N,
+      args.len()
+    ));
+  }
+  let mut numbers = [0.0; N];
+  for (number, arg) in numbers.iter_mut().zip(args) {
+    *number = arg.parse().map_err(|_| format!("Bad number in {}: {}", action, arg))?;
+  }
+  Ok(numbers)
+}
+
+fn parse_rect(args: &[&str], action: &str) -> Result<Rect, String> {
+  let [x, y, width, height] = parse_numbers(args, action)?;
+  Ok(Rect::new(Vec2(x, y), Vec2(width, height)))
+}
+
+/// Parses an actions property: actions separated by semicolons, each a name followed by its
+/// arguments separated by spaces, like
+/// `fire_laser 37.5 32 -25; delete_objects Stone 17 28 10 10; set_flag int1_completed`.
+/// The actions are:
+///
+///   delete_objects <kind> <x> <y> <width> <height>
+///   fire_laser <x> <y> <length>
+///   spawn_object <kind> <x> <y> <width> <height>
+///   set_flag <name>
+///   find_secret <name>
+pub fn parse_actions(actions: &str) -> Result<Vec<TriggerAction>, String> {
+  let mut parsed = Vec::new();
+  for action in actions.split(';').map(str::trim).filter(|action| !action.is_empty()) {
+    let words: Vec<&str> = action.split_whitespace().collect();
+    let (name, args) = (words[0], &words[1..]);
+    parsed.push(match (name, args) {
+      ("delete_objects", [kind, rect @ ..]) => TriggerAction::DeleteObjects {
+        kind: kind.to_string(),
+        rect: parse_rect(rect, name)?,
+      },
+      ("fire_laser", _) => {
+        let [x, y, length] = parse_numbers(args, name)?;
+        TriggerAction::FireLaser {
+          origin: Vec2(x, y),
+          length,
+        }
+      }
+      ("spawn_object", [kind, rect @ ..]) => TriggerAction::SpawnObject {
+        kind: SpawnKind::from_name(kind).ok_or_else(|| format!("Can't spawn {}", kind))?,
+        rect: parse_rect(rect, name)?,
+      },
+      ("set_flag", [flag]) => TriggerAction::SetFlag {
+        flag: flag.to_string(),
+      },
+      ("find_secret", [secret]) => TriggerAction::FindSecret {
+        name: secret.to_string(),
+      },
+      _ => return Err(format!("Bad action: {}", action)),
+    });
+  }
+  Ok(parsed)
+}
+
+/// A laser an interaction fired, for as long as it lasts.
+#[derive(Debug, Clone)]
+pub struct Laser {
+  pub origin:      Vec2,
+  pub length:      f32,
+  pub time_left:   f32,
+  // The interaction that fired it, which can't fire again until it's done.
+  pub interaction: i32,
+}
+
+impl Laser {
+  pub fn new(origin: Vec2, length: f32, interaction: i32) -> Self {
+    Self {
+      origin,
+      length,
+      time_left: LASER_TIME,
+      interaction,
+    }
+  }
+
+  pub fn end(&self) -> Vec2 {
+    self.origin + Vec2(self.length, 0.0)
+  }
+
+  /// The area the beam hurts.
+  pub fn beam_rect(&self) -> Rect {
+    Rect::new(
+      Vec2(
+        self.origin.0.min(self.end().0),
+        self.origin.1 - LASER_THICKNESS / 2.0,
+      ),
+      Vec2(self.length.abs(), LASER_THICKNESS),
+    )
+  }
+}
@@ -0,0 +1,204 @@
+use std::{collections::BTreeMap, ops::RangeInclusive};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{camera, thwump, vanish};
+
+/// Movement knobs for level design, loaded from JSON like `{"wall_jump_kick": 1.2}`. Unlike
+/// settings these change how the game plays, so they're for designers rather than players.
+/// Anything left out keeps its default, and unknown names are errors, so typos don't go
+/// unnoticed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MovementTuning {
+  // How fast a wall jump kicks the player away from the wall, in multiples of their top
+  // running speed, and how fast it sends them up, in multiples of a normal jump. Between
+  // them they set the angle of the kick.
+  pub wall_jump_kick: f32,
+  pub wall_jump_lift: f32,
+}
+
+impl Default for MovementTuning {
+  fn default() -> Self {
+    Self {
+      wall_jump_kick: 1.0,
+      wall_jump_lift: 1.0,
+    }
+  }
+}
+
+impl MovementTuning {
+  pub fn from_json(json: &str) -> Result<Self, String> {
+    let mut tuning: Self = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    tuning.sanitize();
+    Ok(tuning)
+  }
+
+  fn sanitize(&mut self) {
+    self.wall_jump_kick = self.wall_jump_kick.clamp(0.0, 4.0);
+    self.wall_jump_lift = self.wall_jump_lift.clamp(0.0, 4.0);
+  }
+}
+
+/// Timings for the hazards that move on their own.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HazardTuning {
+  // In tiles per second.
+  pub thwump_fall_speed: f32,
+  pub thwump_rise_speed: f32,
+  pub thwump_rest_time:  f32,
+  pub vanish_fade_time:  f32,
+}
+
+impl Default for HazardTuning {
+  fn default() -> Self {
+    Self {
+      thwump_fall_speed: thwump::FALL_SPEED,
+      thwump_rise_speed: thwump::RISE_SPEED,
+      thwump_rest_time:  thwump::REST_TIME,
+      vanish_fade_time:  vanish::VANISH_FADE_TIME,
+    }
+  }
+}
+
+/// How the camera follows the player. See the defaults in camera.rs for what each does.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraTuning {
+  pub follow_blend_rate:    f32,
+  pub lookahead_distance:   f32,
+  pub lookahead_full_speed: f32,
+  pub lookahead_blend_rate: f32,
+  pub vertical_deadzone:    f32,
+  pub peek_blend_rate:      f32,
+  pub bounds_blend_rate:    f32,
+}
+
+impl Default for CameraTuning {
+  fn default() -> Self {
+    Self {
+      follow_blend_rate:    camera::FOLLOW_BLEND_RATE,
+      lookahead_distance:   camera::LOOKAHEAD_DISTANCE,
+      lookahead_full_speed: camera::LOOKAHEAD_FULL_SPEED,
+      lookahead_blend_rate: camera::LOOKAHEAD_BLEND_RATE,
+      vertical_deadzone:    camera::VERTICAL_DEADZONE,
+      peek_blend_rate:      camera::PEEK_BLEND_RATE,
+      bounds_blend_rate:    camera::BOUNDS_BLEND_RATE,
+    }
+  }
+}
+
+/// Every gameplay constant that can be tuned live, as loaded from tuning.json like
+/// `{"movement": {"wall_jump_kick": 1.2}, "camera": {"vertical_deadzone": 2}}`. From the
+/// console, each is named by its section and field, like `camera.vertical_deadzone`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Tunables {
+  pub movement: MovementTuning,
+  pub hazards:  HazardTuning,
+  pub camera:   CameraTuning,
+}
+
+impl Tunables {
+  pub fn from_json(json: &str) -> Result<Self, String> {
+    let mut tunables: Self = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    for (_, value, range) in tunables.registry() {
+      *value = value.clamp(*range.start(), *range.end());
+    }
+    Ok(tunables)
+  }
+
+  /// Each tunable's name, value, and the range it's kept within, so a stray value can't
+  /// freeze or fling the game.
+  fn registry(&mut self) -> Vec<(&'static str, &mut f32, RangeInclusive<f32>)> {
+    vec![
+      (
+        "movement.wall_jump_kick",
+        &mut self.movement.wall_jump_kick,
+        0.0..=4.0,
+      ),
+      (
+        "movement.wall_jump_lift",
+        &mut self.movement.wall_jump_lift,
+        0.0..=4.0,
+      ),
+      (
+        "hazards.thwump_fall_speed",
+        &mut self.hazards.thwump_fall_speed,
+        0.1..=100.0,
+      ),
+      (
+        "hazards.thwump_rise_speed",
+        &mut self.hazards.thwump_rise_speed,
+        0.1..=100.0,
+      ),
+      (
+        "hazards.thwump_rest_time",
+        &mut self.hazards.thwump_rest_time,
+        0.0..=10.0,
+      ),
+      (
+        "hazards.vanish_fade_time",
+        &mut self.hazards.vanish_fade_time,
+        0.01..=2.0,
+      ),
+      (
+        "camera.follow_blend_rate",
+        &mut self.camera.follow_blend_rate,
+        0.1..=100.0,
+      ),
+      (
+        "camera.lookahead_distance",
+        &mut self.camera.lookahead_distance,
+        0.0..=10.0,
+      ),
+      (
+        "camera.lookahead_full_speed",
+        &mut self.camera.lookahead_full_speed,
+        0.1..=100.0,
+      ),
+      (
+        "camera.lookahead_blend_rate",
+        &mut self.camera.lookahead_blend_rate,
+        0.1..=100.0,
+      ),
+      (
+        "camera.vertical_deadzone",
+        &mut self.camera.vertical_deadzone,
+        0.0..=10.0,
+      ),
+      (
+        "camera.peek_blend_rate",
+        &mut self.camera.peek_blend_rate,
+        0.1..=100.0,
+      ),
+      (
+        "camera.bounds_blend_rate",
+        &mut self.camera.bounds_blend_rate,
+        0.1..=100.0,
+      ),
+    ]
+  }
+
+  /// Sets the tunable called `name`, clamped to its range.
+  pub fn set(&mut self, name: &str, value: f32) -> Result<(), String> {
+    if !value.is_finite() {
+      return Err(format!("{} isn't a valid value for {}", value, name));
+    }
+    match self.registry().into_iter().find(|(n, _, _)| *n == name) {
+      Some((_, field, range)) => {
+        *field = value.clamp(*range.start(), *range.end());
+        Ok(())
+      }
+      None => Err(format!("Unknown tunable: {}", name)),
+    }
+  }
+
+  /// Every tunable's current value, by name.
+  pub fn values(&self) -> BTreeMap<&'static str, f32> {
+    let mut tunables = self.clone();
+    let values = tunables.registry().into_iter().map(|(name, value, _)| (name, *value)).collect();
+    values
+  }
+}
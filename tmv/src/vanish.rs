@@ -0,0 +1,31 @@
+// How long each character of a vanish block pattern lasts, in seconds.
+pub const VANISH_BEAT: f32 = 0.5;
+// How long synchronized blocks take to fade in or out.
+pub const VANISH_FADE_TIME: f32 = 0.3;
+// Solid for two seconds, then gone for two, for groups no block gave a pattern for.
+pub const DEFAULT_VANISH_PATTERN: &[bool] = &[true, true, true, true, false, false, false, false];
+
+/// Parses a pattern property, one beat per character: '#' is solid and '.' is gone.
+pub fn parse_pattern(pattern: &str) -> Result<Vec<bool>, String> {
+  let beats = pattern
+    .chars()
+    .map(|c| match c {
+      '#' => Ok(true),
+      '.' => Ok(false),
+      _ => Err(format!(
+        "Bad character {:?} in vanish pattern {:?}",
+        c, pattern
+      )),
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+  match beats.is_empty() {
+    true => Err("Empty vanish pattern".to_string()),
+    false => Ok(beats),
+  }
+}
+
+/// Whether blocks following `pattern` should be solid at `clock` seconds into the game.
+pub fn is_solid_at(pattern: &[bool], clock: f32) -> bool {
+  let beat = (clock / VANISH_BEAT).floor() as i64;
+  pattern[beat.rem_euclid(pattern.len() as i64) as usize]
+}
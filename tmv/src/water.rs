@@ -0,0 +1,118 @@
+use std::collections::{HashMap, HashSet};
+
+use rapier2d::prelude::ColliderHandle;
+use web_sys::CanvasRenderingContext2d;
+
+use crate::{camera::Camera, math::Vec2};
+
+// The surface bobs by up to this much, in tiles, in waves about this many tiles long that
+// roll along at this many tiles per second.
+const WAVE_HEIGHT: f32 = 0.06;
+const WAVE_LENGTH: f32 = 3.0;
+const WAVE_SPEED: f32 = 1.5;
+// How many points make up each tile's width of the drawn surface line.
+const POINTS_PER_TILE: usize = 4;
+// Crossing the surface slower than this, in tiles per second, doesn't splash, so bobbing
+// at the surface stays quiet.
+const MIN_SPLASH_SPEED: f32 = 3.0;
+
+/// The top edge of a body of water: a run of water tiles with no water above them, from
+/// `x0` to `x1` along the line `y`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WaterSurface {
+  pub y:  f32,
+  pub x0: f32,
+  pub x1: f32,
+}
+
+impl WaterSurface {
+  pub fn spans(&self, x: f32) -> bool {
+    self.x0 <= x && x <= self.x1
+  }
+}
+
+/// Merges the tops of the water cells into surfaces, one per horizontal run.
+pub fn find_surfaces(water_cells: &HashSet<(i32, i32)>) -> Vec<WaterSurface> {
+  let mut tops: Vec<(i32, i32)> =
+    water_cells.iter().copied().filter(|&(x, y)| !water_cells.contains(&(x, y - 1))).collect();
+  tops.sort_by_key(|&(x, y)| (y, x));
+  let mut surfaces: Vec<WaterSurface> = Vec::new();
+  for (x, y) in tops {
+    match surfaces.last_mut() {
+      Some(surface) if surface.y == y as f32 && surface.x1 == x as f32 => surface.x1 += 1.0,
+      _ => surfaces.push(WaterSurface {
+        y:  y as f32,
+        x0: x as f32,
+        x1: x as f32 + 1.0,
+      }),
+    }
+  }
+  surfaces
+}
+
+/// How far the waves have moved the surface from its resting line at `x`, in tiles.
+pub fn wave_offset(x: f32, time: f32) -> f32 {
+  let phase = std::f32::consts::TAU * (x - WAVE_SPEED * time) / WAVE_LENGTH;
+  // A smaller second wave, out of step with the first, keeps it from looking too regular.
+  WAVE_HEIGHT * (phase.sin() + 0.4 * (2.3 * phase + 1.1 * time).sin()) / 1.4
+}
+
+pub fn draw_surface(
+  ctx: &CanvasRenderingContext2d,
+  camera: &Camera,
+  surface: &WaterSurface,
+  time: f32,
+) {
+  let steps = ((surface.x1 - surface.x0) * POINTS_PER_TILE as f32).round() as usize;
+  ctx.begin_path();
+  for i in 0..=steps {
+    let x = surface.x0 + i as f32 / POINTS_PER_TILE as f32;
+    let pos = camera.world_to_screen(Vec2(x, surface.y + wave_offset(x, time)));
+    match i {
+      0 => ctx.move_to(pos.0 as f64, pos.1 as f64),
+      _ => ctx.line_to(pos.0 as f64, pos.1 as f64),
+    }
+  }
+  ctx.stroke();
+}
+
+/// Remembers where things were last step, to tell when they go through a water surface.
+#[derive(Debug, Default)]
+pub struct SplashTracker {
+  last_positions: HashMap<ColliderHandle, Vec2>,
+}
+
+impl SplashTracker {
+  /// Records where everything tracked is now, returning where each thing that went through
+  /// a surface since the last update did so, and how fast it was moving up or down.
+  /// Anything not passed in is forgotten.
+  pub fn update(
+    &mut self,
+    surfaces: &[WaterSurface],
+    positions: impl Iterator<Item = (ColliderHandle, Vec2)>,
+    dt: f32,
+  ) -> Vec<(Vec2, f32)> {
+    let mut splashes = Vec::new();
+    let mut last_positions = HashMap::new();
+    for (handle, pos) in positions {
+      if let Some(last) = self.last_positions.get(&handle) {
+        let speed = (pos.1 - last.1).abs() / dt;
+        let crossed = surfaces
+          .iter()
+          .find(|surface| surface.spans(pos.0) && (last.1 < surface.y) != (pos.1 < surface.y));
+        if let (Some(surface), true) = (crossed, speed >= MIN_SPLASH_SPEED) {
+          splashes.push((Vec2(pos.0, surface.y), speed));
+        }
+      }
+      last_positions.insert(handle, pos);
+    }
+    self.last_positions = last_positions;
+    splashes
+  }
+
+  /// Forgets where everything was, for when things move without really moving, like on
+  /// respawning.
+  pub fn clear(&mut self) {
+    self.last_positions.clear();
+  }
+}
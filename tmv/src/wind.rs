@@ -0,0 +1,76 @@
+use crate::math::{Rect, Vec2};
+
+// How hard wind rects without a strength property push, in tiles per second per second.
+pub const DEFAULT_STRENGTH: f32 = 40.0;
+// How many streaks each zone gives off per second, for every 100 square tiles of it on screen.
+const STREAKS_PER_AREA: f32 = 6.0;
+// Streaks move this many times faster than the wind's acceleration, per second.
+const STREAK_SPEED_FACTOR: f32 = 0.4;
+
+/// A rect of wind, or water current, that keeps pushing on the player and on loose objects
+/// like bees and bullets while they're inside it. Made from wind rects in the collision layer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindZone {
+  pub rect:      Rect,
+  // Which way it blows, as a unit vector.
+  pub direction: Vec2,
+  pub strength:  f32,
+}
+
+impl WindZone {
+  pub fn acceleration(&self) -> Vec2 {
+    self.strength * self.direction
+  }
+
+  /// How fast the streaks showing the wind drift along.
+  pub fn streak_velocity(&self) -> Vec2 {
+    STREAK_SPEED_FACTOR * self.acceleration()
+  }
+
+  /// The part of the zone inside `view`, if any.
+  pub fn visible_part(&self, view: Rect) -> Option<Rect> {
+    let top_left = Vec2(
+      self.rect.pos.0.max(view.pos.0),
+      self.rect.pos.1.max(view.pos.1),
+    );
+    let bottom_right = Vec2(
+      self.rect.max_corner().0.min(view.max_corner().0),
+      self.rect.max_corner().1.min(view.max_corner().1),
+    );
+    let size = bottom_right - top_left;
+    match size.0 > 0.0 && size.1 > 0.0 {
+      true => Some(Rect::new(top_left, size)),
+      false => None,
+    }
+  }
+}
+
+/// How many streaks to give off over `dt` in a visible part of a zone. `roll` is uniform in
+/// [0, 1), and turns the fractional part into a chance of one more, so small zones still streak.
+pub fn streak_count(visible: Rect, dt: f32, density: f32, roll: f32) -> usize {
+  let expected = STREAKS_PER_AREA * density * dt * visible.size.0 * visible.size.1 / 100.0;
+  expected.floor() as usize + (roll < expected.fract()) as usize
+}
+
+/// Parses a wind rect's direction property: left, right, up, or down, or an angle in
+/// degrees clockwise from pointing right.
+pub fn parse_direction(direction: &str) -> Option<Vec2> {
+  match direction {
+    "left" => Some(Vec2(-1.0, 0.0)),
+    "right" => Some(Vec2(1.0, 0.0)),
+    "up" => Some(Vec2(0.0, -1.0)),
+    "down" => Some(Vec2(0.0, 1.0)),
+    _ => {
+      let angle = direction.parse::<f32>().ok()?.to_radians();
+      Some(Vec2(angle.cos(), angle.sin()))
+    }
+  }
+}
+
+/// The push on something at `pos`, from every zone it's in.
+pub fn acceleration_at(zones: &[WindZone], pos: Vec2) -> Vec2 {
+  zones
+    .iter()
+    .filter(|zone| zone.rect.contains_point(pos))
+    .fold(Vec2::default(), |total, zone| total + zone.acceleration())
+}
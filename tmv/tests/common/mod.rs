@@ -0,0 +1,17 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use strum::IntoEnumIterator;
+use tmv::BinaryResource;
+
+/// Reads the game's resources straight out of the web frontend's public directory.
+pub fn load_resources() -> HashMap<String, Vec<u8>> {
+  let public_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../web/public");
+  BinaryResource::iter()
+    .map(|resource| {
+      let path = resource.get_path();
+      let data = fs::read(public_dir.join(&path[1..]))
+        .unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e));
+      (path.to_string(), data)
+    })
+    .collect()
+}
@@ -0,0 +1,85 @@
+//! Tests for what count gates count and how they're drawn.
+
+use std::{fs, path::Path};
+
+use tmv::{
+  char_events::{CharEvent, CharEventLog},
+  count_gate::Currency,
+  theme::Theme,
+  triggers::{parse_actions, TriggerAction},
+  CharState,
+};
+
+const CURRENCIES: [Currency; 4] = [
+  Currency::Coins,
+  Currency::RareCoins,
+  Currency::Secrets,
+  Currency::HpUps,
+];
+
+#[test]
+fn currencies_from_properties() {
+  assert_eq!(Currency::from_property("coins"), Some(Currency::Coins));
+  assert_eq!(
+    Currency::from_property("rare_coins"),
+    Some(Currency::RareCoins)
+  );
+  assert_eq!(Currency::from_property("hp_ups"), Some(Currency::HpUps));
+  assert_eq!(Currency::from_property("secrets"), Some(Currency::Secrets));
+  assert_eq!(Currency::from_property("Coins"), None);
+}
+
+#[test]
+fn held_counts_each_currency_separately() {
+  let mut char_state = CharState::default();
+  char_state.coins.extend([1, 2, 3]);
+  char_state.rare_coins.insert(4);
+  assert_eq!(Currency::Coins.held(&char_state), 3);
+  assert_eq!(Currency::RareCoins.held(&char_state), 1);
+  assert_eq!(Currency::HpUps.held(&char_state), 0);
+  assert_eq!(Currency::Secrets.held(&char_state), 0);
+}
+
+#[test]
+fn secrets_count_once_each() {
+  let actions = parse_actions("find_secret behind_waterfall; set_flag int1_completed").unwrap();
+  assert_eq!(
+    actions[0],
+    TriggerAction::FindSecret {
+      name: "behind_waterfall".to_string(),
+    }
+  );
+  let mut char_state = CharState::default();
+  let mut log = CharEventLog::default();
+  for name in ["behind_waterfall", "behind_waterfall", "under_bridge"] {
+    log.apply(
+      &mut char_state,
+      CharEvent::Secret {
+        name: name.to_string(),
+      },
+    );
+  }
+  // Flags don't count as secrets.
+  log.apply(
+    &mut char_state,
+    CharEvent::Flag {
+      name: "int1_completed".to_string(),
+    },
+  );
+  assert_eq!(Currency::Secrets.held(&char_state), 2);
+}
+
+#[test]
+fn every_currency_has_a_theme_style() {
+  let theme_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../web/public/assets/theme.json");
+  let themes = [
+    Theme::default(),
+    Theme::from_json(&fs::read_to_string(theme_path).unwrap()).unwrap(),
+  ];
+  for theme in &themes {
+    for currency in CURRENCIES {
+      // Panics if the style is missing.
+      theme.style(currency.style());
+    }
+  }
+}
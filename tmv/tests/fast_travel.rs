@@ -0,0 +1,60 @@
+//! Tests for picking a save point on the map screen and confirming the trip.
+
+use tmv::{
+  camera::Camera,
+  fast_travel::{pick, FastTravel, PICK_RADIUS},
+  math::Vec2,
+};
+
+// 32 screen pixels to a tile, with the world's origin at the top left of the screen.
+fn camera() -> Camera {
+  Camera::game_view(Vec2(0.0, 0.0), 1.0)
+}
+
+#[test]
+fn pick_takes_the_nearest_save_point_in_range() {
+  let cursor = Vec2(320.0, 320.0);
+  // Just past PICK_RADIUS, to the right of the cursor.
+  let out_of_range = Vec2(10.0 + (PICK_RADIUS + 1.0) / 32.0, 10.0);
+  // 19.2 and 16 pixels from the cursor.
+  let save_points = [Vec2(10.0, 10.6), Vec2(10.5, 10.0), out_of_range];
+  assert_eq!(pick(save_points, &camera(), cursor), Some(Vec2(10.5, 10.0)));
+  assert_eq!(pick([out_of_range], &camera(), cursor), None);
+  assert_eq!(pick([], &camera(), cursor), None);
+}
+
+#[test]
+fn travel_takes_two_presses() {
+  let save_point = Vec2(10.5, 10.0);
+  let mut fast_travel = FastTravel::default();
+  // Nothing happens with nothing under the cursor.
+  assert_eq!(fast_travel.press(), None);
+  assert!(!fast_travel.confirming);
+
+  fast_travel.aim(Some(save_point));
+  assert_eq!(fast_travel.press(), None);
+  assert!(fast_travel.confirming);
+  // Staying on the same save point keeps it confirming.
+  fast_travel.aim(Some(save_point));
+  assert_eq!(fast_travel.press(), Some(save_point));
+  // Going resets it, so the next trip needs two presses too.
+  assert_eq!(fast_travel.target, None);
+  assert!(!fast_travel.confirming);
+}
+
+#[test]
+fn moving_off_the_save_point_cancels_confirming() {
+  let mut fast_travel = FastTravel::default();
+  fast_travel.aim(Some(Vec2(10.5, 10.0)));
+  assert_eq!(fast_travel.press(), None);
+  fast_travel.aim(Some(Vec2(40.5, 10.0)));
+  assert!(!fast_travel.confirming);
+  assert_eq!(fast_travel.press(), None);
+  assert_eq!(fast_travel.press(), Some(Vec2(40.5, 10.0)));
+
+  fast_travel.aim(Some(Vec2(10.5, 10.0)));
+  fast_travel.press();
+  fast_travel.aim(None);
+  assert!(!fast_travel.confirming);
+  assert_eq!(fast_travel.press(), None);
+}
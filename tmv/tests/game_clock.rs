@@ -0,0 +1,50 @@
+//! Tests for which timers keep running through which pauses.
+
+use tmv::game_clock::{Channel, GameClock, Pause, MAX_FRAME_DT};
+
+const CHANNELS: [Channel; 3] = [Channel::Gameplay, Channel::Ui, Channel::Always];
+
+#[test]
+fn pauses_stop_their_channels() {
+  for pause in [Pause::MapScreen, Pause::Dialogue, Pause::HitStop] {
+    assert!(pause.stops(Channel::Gameplay), "{:?}", pause);
+    assert!(!pause.stops(Channel::Always), "{:?}", pause);
+  }
+  // The map screen and dialogue are menus themselves, so they can't stop the UI.
+  assert!(!Pause::MapScreen.stops(Channel::Ui));
+  assert!(!Pause::Dialogue.stops(Channel::Ui));
+  assert!(Pause::HitStop.stops(Channel::Ui));
+}
+
+#[test]
+fn tick_runs_each_channel_separately() {
+  let mut clock = GameClock::default();
+  clock.tick(0.05, &[]);
+  for channel in CHANNELS {
+    assert_eq!(clock.dt(channel), 0.05);
+  }
+
+  clock.tick(0.05, &[Pause::MapScreen]);
+  assert_eq!(clock.dt(Channel::Gameplay), 0.0);
+  assert_eq!(clock.dt(Channel::Ui), 0.05);
+  assert_eq!(clock.time(Channel::Gameplay), 0.05);
+  assert_eq!(clock.time(Channel::Ui), 0.1);
+
+  // Overlapping pauses stop everything either of them would.
+  clock.tick(0.05, &[Pause::Dialogue, Pause::HitStop]);
+  assert_eq!(clock.dt(Channel::Gameplay), 0.0);
+  assert_eq!(clock.dt(Channel::Ui), 0.0);
+  assert_eq!(clock.dt(Channel::Always), 0.05);
+  assert_eq!(clock.time(Channel::Ui), 0.1);
+  assert!((clock.time(Channel::Always) - 0.15).abs() < 1e-6);
+}
+
+#[test]
+fn long_frames_are_clamped() {
+  let mut clock = GameClock::default();
+  clock.tick(5.0, &[]);
+  for channel in CHANNELS {
+    assert_eq!(clock.dt(channel), MAX_FRAME_DT);
+    assert_eq!(clock.time(channel), MAX_FRAME_DT);
+  }
+}
@@ -0,0 +1,71 @@
+//! Drives the simulation directly, without a browser, to check basic player movement.
+
+mod common;
+
+use tmv::{GameState, InputEvent};
+
+const DT: f32 = 1.0 / 60.0;
+
+fn run(game_state: &mut GameState, seconds: f32) {
+  for _ in 0..(seconds / DT).round() as usize {
+    game_state.step(DT).unwrap();
+  }
+}
+
+fn key_down(game_state: &mut GameState, key: &str) {
+  game_state.send_input(InputEvent::KeyDown {
+    key:       key.to_string(),
+    timestamp: None,
+  });
+}
+
+fn key_up(game_state: &mut GameState, key: &str) {
+  game_state.send_input(InputEvent::KeyUp {
+    key: key.to_string(),
+  });
+}
+
+/// A fresh game, with the player given time to land at the spawn point.
+fn landed_game_state() -> GameState {
+  let mut game_state = GameState::new_headless(common::load_resources());
+  run(&mut game_state, 2.0);
+  game_state
+}
+
+#[test]
+fn player_comes_to_rest_at_spawn() {
+  let mut game_state = landed_game_state();
+  let position = game_state.player_position();
+  run(&mut game_state, 0.5);
+  assert!((game_state.player_position() - position).length() < 1e-3);
+}
+
+#[test]
+fn player_walks_both_ways() {
+  let mut game_state = landed_game_state();
+  let start = game_state.player_position();
+  key_down(&mut game_state, "ArrowRight");
+  run(&mut game_state, 0.25);
+  key_up(&mut game_state, "ArrowRight");
+  let after_right = game_state.player_position();
+  assert!(after_right.0 > start.0, "{:?} -> {:?}", start, after_right);
+
+  key_down(&mut game_state, "ArrowLeft");
+  run(&mut game_state, 0.25);
+  key_up(&mut game_state, "ArrowLeft");
+  let after_left = game_state.player_position();
+  assert!(after_left.0 < after_right.0, "{:?} -> {:?}", after_right, after_left);
+}
+
+#[test]
+fn player_jumps_and_lands() {
+  let mut game_state = landed_game_state();
+  let ground = game_state.player_position();
+  key_down(&mut game_state, "ArrowUp");
+  run(&mut game_state, 0.2);
+  key_up(&mut game_state, "ArrowUp");
+  // Up is negative y.
+  assert!(game_state.player_position().1 < ground.1);
+  run(&mut game_state, 2.0);
+  assert!((game_state.player_position() - ground).length() < 1e-3);
+}
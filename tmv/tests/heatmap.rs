@@ -0,0 +1,65 @@
+//! Tests for how the session heatmap counts where things happen, and what it exports.
+
+use serde_json::{json, Value};
+use tmv::{
+  heatmap::{HeatKind, Heatmap, CELL_SIZE, SAMPLE_INTERVAL},
+  math::Vec2,
+};
+
+const MAP: &str = "/assets/map1.tmx";
+
+fn export(heatmap: &Heatmap) -> Value {
+  serde_json::from_str(&heatmap.to_json()).unwrap()
+}
+
+#[test]
+fn presence_is_sampled_every_interval() {
+  let mut heatmap = Heatmap::default();
+  // Three and a third intervals, in frames a third of one long.
+  for _ in 0..10 {
+    heatmap.update(SAMPLE_INTERVAL / 3.0, MAP, Vec2(1.0, 1.0));
+  }
+  assert_eq!(export(&heatmap)["maps"][MAP][0]["presence"], 3);
+}
+
+#[test]
+fn negative_coordinates_round_down_to_their_cell() {
+  let mut heatmap = Heatmap::default();
+  for pos in [Vec2(-0.5, -0.5), Vec2(-4.0, 3.9), Vec2(0.0, 0.0)] {
+    heatmap.update(0.0, MAP, pos);
+    heatmap.record(HeatKind::Death);
+  }
+  let corners: Vec<_> = export(&heatmap)["maps"][MAP]
+    .as_array()
+    .unwrap()
+    .iter()
+    .map(|cell| (cell["x"].as_i64().unwrap(), cell["y"].as_i64().unwrap()))
+    .collect();
+  let size = CELL_SIZE as i64;
+  // Just left of and above the origin is a different cell from the origin, rather than
+  // rounding toward zero into it.
+  assert_eq!(corners, vec![(-size, -size), (-size, 0), (0, 0)]);
+}
+
+#[test]
+fn export_groups_cells_by_map() {
+  let mut heatmap = Heatmap::default();
+  // Nothing is counted before the player has been placed anywhere.
+  heatmap.record(HeatKind::Damage);
+  heatmap.update(0.0, MAP, Vec2(9.0, 1.0));
+  heatmap.record(HeatKind::Damage);
+  heatmap.record(HeatKind::Damage);
+  heatmap.update(0.0, "/assets/other.tmx", Vec2(1.0, 1.0));
+  heatmap.record(HeatKind::Death);
+  assert_eq!(
+    export(&heatmap),
+    json!({
+      "cell_size": CELL_SIZE,
+      "sample_interval": SAMPLE_INTERVAL,
+      "maps": {
+        "/assets/map1.tmx": [{ "x": 8, "y": 0, "presence": 0, "deaths": 0, "damage": 2 }],
+        "/assets/other.tmx": [{ "x": 0, "y": 0, "presence": 0, "deaths": 1, "damage": 0 }],
+      },
+    })
+  );
+}
@@ -0,0 +1,294 @@
+//! Loads the small fixture maps in tests/maps through the same path as the real map, to
+//! catch regressions in how Tiled data turns into walls, objects, and spawn points.
+//!
+//! The fixtures use the game's real tilesets, so they can be opened and edited in Tiled
+//! from inside web/public/assets.
+
+mod common;
+
+use std::{collections::HashMap, fs, path::Path};
+
+use rapier2d::prelude::ColliderHandle;
+use tmv::{
+  collision::CollisionWorld,
+  game_maps::{GameMap, Placement, FRAGMENT_ID_STRIDE, START_MAP},
+  map_diff::{MapChange, MapDiff},
+  math::{Rect, Vec2},
+  minimap::{Minimap, MARGIN},
+  power_ups::PowerUp,
+  splits::GAME_FINISHED_FLAG,
+  triggers::TriggerAction,
+  CharState, GameObject, GameObjectData,
+};
+
+struct LoadedMap {
+  collision: CollisionWorld,
+  objects:   HashMap<ColliderHandle, GameObject>,
+}
+
+fn fixture_game_map(file_name: &str) -> GameMap {
+  let mut resources = common::load_resources();
+  let fixture_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/maps").join(file_name);
+  // Next to the tilesets, so the map's relative tileset paths resolve.
+  let map_name = format!("/assets/{}", file_name);
+  resources.insert(map_name.clone(), fs::read(fixture_path).unwrap());
+  GameMap::from_resources(&resources, &map_name).unwrap()
+}
+
+fn load_fixture(file_name: &str, char_state: &CharState) -> LoadedMap {
+  let game_map = fixture_game_map(file_name);
+  let mut collision = CollisionWorld::new();
+  let mut objects = HashMap::new();
+  collision.load_game_map(char_state, &game_map, &mut objects);
+  LoadedMap { collision, objects }
+}
+
+fn count_objects(loaded: &LoadedMap, predicate: fn(&GameObjectData) -> bool) -> usize {
+  loaded.objects.values().filter(|object| predicate(&object.data)).count()
+}
+
+/// The segment count of every polyline collider, smallest first.
+fn polyline_segment_counts(collision: &CollisionWorld) -> Vec<usize> {
+  let mut counts: Vec<_> = collision
+    .collider_set
+    .iter()
+    .filter_map(|(_, collider)| collider.shape().as_polyline())
+    .map(|polyline| polyline.num_segments())
+    .collect();
+  counts.sort();
+  counts
+}
+
+// In tiny.tmx, the tile at (x, y).
+fn tile_entity_id(x: i32, y: i32) -> i32 {
+  1_000_000 * y + x
+}
+
+#[test]
+fn tiny_map_objects() {
+  let loaded = load_fixture("tiny.tmx", &CharState::default());
+  assert_eq!(loaded.objects.len(), 6);
+  assert_eq!(
+    count_objects(&loaded, |data| matches!(data, GameObjectData::Coin { .. })),
+    2
+  );
+  assert_eq!(
+    count_objects(&loaded, |data| matches!(data, GameObjectData::HpUp { .. })),
+    1
+  );
+  assert_eq!(
+    count_objects(&loaded, |data| matches!(data, GameObjectData::Spike { .. })),
+    1
+  );
+}
+
+#[test]
+fn tiny_map_skips_collected_pickups() {
+  let mut char_state = CharState::default();
+  char_state.coins.insert(tile_entity_id(5, 14));
+  let loaded = load_fixture("tiny.tmx", &char_state);
+  let coin_ids: Vec<_> = loaded
+    .objects
+    .values()
+    .filter_map(|object| match object.data {
+      GameObjectData::Coin { entity_id } => Some(entity_id),
+      _ => None,
+    })
+    .collect();
+  assert_eq!(coin_ids, vec![tile_entity_id(6, 14)]);
+}
+
+#[test]
+fn tiny_map_walls() {
+  let loaded = load_fixture("tiny.tmx", &CharState::default());
+  // The polygon in the collision layer is a closed triangle, and the solid tiles are the
+  // floor plus a separate block, which the wall scan outlines with four segments each.
+  assert_eq!(polyline_segment_counts(&loaded.collision), vec![3, 8]);
+}
+
+#[test]
+fn tiny_map_spawn_point() {
+  let loaded = load_fixture("tiny.tmx", &CharState::default());
+  assert_eq!(loaded.collision.spawn_point, Vec2(2.0, 14.0));
+}
+
+#[test]
+fn tiny_map_as_fragment() {
+  let unshifted = load_fixture("tiny.tmx", &CharState::default());
+  let mut resources = common::load_resources();
+  let fixture_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/maps/tiny.tmx");
+  resources.insert(
+    "/assets/tiny.tmx".to_string(),
+    fs::read(fixture_path).unwrap(),
+  );
+  let game_map = GameMap::from_resources(&resources, "/assets/tiny.tmx").unwrap();
+  let mut collision = CollisionWorld::new();
+  let mut objects = HashMap::new();
+  let placement = Placement::fragment((0, 32), 1);
+  collision.load_game_map_at(&CharState::default(), &game_map, placement, &mut objects);
+  // Tiles and objects both move, and pickups get the ids of where they end up.
+  assert_eq!(collision.spawn_point, Vec2(2.0, 46.0));
+  assert_eq!(
+    collision.named_spawns["from_map1"],
+    unshifted.collision.named_spawns["from_map1"] + Vec2(0.0, 32.0)
+  );
+  assert!(objects.values().any(|object| matches!(
+    object.data,
+    GameObjectData::Coin { entity_id } if entity_id == tile_entity_id(5, 46)
+  )));
+  // The interaction gets a number of its own, and its script moves along with it.
+  assert_eq!(
+    collision.interaction_scripts[&(1 + FRAGMENT_ID_STRIDE)][0],
+    TriggerAction::DeleteObjects {
+      kind: "Stone".to_string(),
+      rect: Rect::new(Vec2(0.0, 32.0), Vec2(4.0, 4.0)),
+    }
+  );
+}
+
+#[test]
+fn tiny_map_interaction_rect() {
+  let loaded = load_fixture("tiny.tmx", &CharState::default());
+  let interactions: Vec<_> = loaded
+    .objects
+    .values()
+    .filter_map(|object| match &object.data {
+      GameObjectData::Interaction {
+        interaction_number,
+        requires_powerup,
+        ..
+      } => Some((object, *interaction_number, *requires_powerup)),
+      _ => None,
+    })
+    .collect();
+  assert_eq!(interactions.len(), 1);
+  let (object, interaction_number, requires_powerup) = &interactions[0];
+  assert_eq!(*interaction_number, 1);
+  assert_eq!(*requires_powerup, Some(PowerUp::Dash));
+  assert_eq!(
+    loaded.collision.interaction_scripts[&1],
+    vec![
+      TriggerAction::DeleteObjects {
+        kind: "Stone".to_string(),
+        rect: Rect::new(Vec2(0.0, 0.0), Vec2(4.0, 4.0)),
+      },
+      TriggerAction::SetFlag {
+        flag: "tiny_done".to_string(),
+      },
+    ]
+  );
+  // The rect is 64x32 pixels with its corner at (224, 416), so two tiles by one.
+  let bounds = loaded.collision.collider_bounds(object.physics_handle.collider).unwrap();
+  assert!(
+    (bounds.pos - Vec2(7.0, 13.0)).length() < 1e-3,
+    "{:?}",
+    bounds
+  );
+  assert!(
+    (bounds.size - Vec2(2.0, 1.0)).length() < 1e-3,
+    "{:?}",
+    bounds
+  );
+}
+
+#[test]
+fn tiny_map_doors_and_named_spawns() {
+  let loaded = load_fixture("tiny.tmx", &CharState::default());
+  let doors: Vec<_> = loaded
+    .objects
+    .values()
+    .filter_map(|object| match &object.data {
+      GameObjectData::Door {
+        target_map,
+        target_spawn,
+      } => Some((target_map.as_str(), target_spawn.as_str())),
+      _ => None,
+    })
+    .collect();
+  assert_eq!(doors, vec![("/assets/map1.tmx", "tiny_exit")]);
+  // Named spawns are at the middle of their rect.
+  assert_eq!(
+    loaded.collision.named_spawns.get("from_map1"),
+    Some(&Vec2(12.5, 14.5))
+  );
+}
+
+#[test]
+fn tiny_map_minimap() {
+  let game_map = fixture_game_map("tiny.tmx");
+  let minimap = Minimap::from_game_map(&game_map);
+  // The floor and the block above it are solid, while the markers and the nonsolid tile
+  // on the row above the floor aren't.
+  assert_eq!(
+    minimap.solid_runs,
+    vec![(10, 11, 2), (10, 12, 2), (0, 15, 16)]
+  );
+  assert_eq!(
+    minimap.tile_bounds,
+    ((-MARGIN, 11 - MARGIN), (16 + MARGIN, 16 + MARGIN))
+  );
+
+  let shifted = Minimap::from_game_map_at(&game_map, Placement::fragment((0, 32), 1));
+  assert_eq!(
+    shifted.solid_runs,
+    vec![(10, 43, 2), (10, 44, 2), (0, 47, 16)]
+  );
+}
+
+#[test]
+fn tiny_map_diff() {
+  let resources = common::load_resources();
+  let fixture_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/maps/tiny.tmx");
+  let old = fs::read_to_string(fixture_path).unwrap();
+  assert!(MapDiff::from_tmx(&resources, old.as_bytes(), old.as_bytes()).unwrap().is_empty());
+  // Move the spawn over a tile, retarget the door, and knock a hole in the floor.
+  let new = old
+    .replace(r#"<object id="4" x="384""#, r#"<object id="4" x="416""#)
+    .replace(r#"value="tiny_exit""#, r#"value="tiny_exit_2""#)
+    .replace(
+      "17,17,17,17,17,17,17,17,17,17,17,17,17,17,17,17",
+      "17,17,17,17,0,0,17,17,17,17,17,17,17,17,17,17",
+    );
+  let diff = MapDiff::from_tmx(&resources, old.as_bytes(), new.as_bytes()).unwrap();
+  assert_eq!(
+    diff.changes,
+    vec![
+      MapChange::ObjectEdited {
+        layer:   "Collision".to_string(),
+        id:      3,
+        name:    "door".to_string(),
+        details: vec!["target_spawn: tiny_exit -> tiny_exit_2".to_string()],
+      },
+      MapChange::ObjectMoved {
+        layer: "Collision".to_string(),
+        id:    4,
+        name:  "spawn".to_string(),
+        from:  Vec2(12.0, 14.0),
+        to:    Vec2(13.0, 14.0),
+      },
+      MapChange::WallsRemoved {
+        top_left: (4, 15),
+        size:     (2, 1),
+        cells:    2,
+      },
+    ]
+  );
+}
+
+#[test]
+fn map1_ending_finishes_the_game() {
+  let resources = common::load_resources();
+  let game_map = GameMap::from_resources(&resources, START_MAP).unwrap();
+  let mut collision = CollisionWorld::new();
+  collision.load_game_map(&CharState::default(), &game_map, &mut HashMap::new());
+  let finish = TriggerAction::SetFlag {
+    flag: GAME_FINISHED_FLAG.to_string(),
+  };
+  let finishing: Vec<_> = collision
+    .interaction_scripts
+    .iter()
+    .filter(|(_, actions)| actions.contains(&finish))
+    .map(|(number, _)| *number)
+    .collect();
+  assert_eq!(finishing, vec![3]);
+}
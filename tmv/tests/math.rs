@@ -0,0 +1,176 @@
+//! Tests for the batch ops and intersection tests in the math module.
+
+use proptest::prelude::*;
+use tmv::math::{
+  batch::{bounding_rect, points_in_rect, transform_points, untransform_points},
+  geometry::{
+    point_in_polygon, segment_intersection, segment_intersection_point, sweep_rect, SweepHit,
+  },
+  Rect, Vec2,
+};
+
+fn rect(x: f32, y: f32, w: f32, h: f32) -> Rect {
+  Rect::new(Vec2(x, y), Vec2(w, h))
+}
+
+#[test]
+fn transform_points_matches_single_transform() {
+  let points = [Vec2(0.0, 0.0), Vec2(1.5, -2.0), Vec2(10.0, 4.0)];
+  let mut out = [Vec2::default(); 3];
+  transform_points(&points, Vec2(1.0, 2.0), 32.0, &mut out);
+  for (p, o) in points.iter().zip(&out) {
+    assert_eq!(*o, 32.0 * (*p - Vec2(1.0, 2.0)));
+  }
+  let mut back = [Vec2::default(); 3];
+  untransform_points(&out, Vec2(1.0, 2.0), 32.0, &mut back);
+  assert_eq!(back, points);
+}
+
+#[test]
+fn bounding_rect_of_points() {
+  assert!(bounding_rect(&[]).is_none());
+  let r = bounding_rect(&[Vec2(1.0, 5.0), Vec2(-2.0, 3.0), Vec2(4.0, -1.0)]).unwrap();
+  assert_eq!((r.pos, r.size), (Vec2(-2.0, -1.0), Vec2(6.0, 6.0)));
+}
+
+#[test]
+fn points_in_rect_matches_contains_point() {
+  let r = rect(0.0, 0.0, 2.0, 2.0);
+  let points = [
+    Vec2(0.0, 0.0),
+    Vec2(1.0, 1.9),
+    Vec2(2.0, 1.0),
+    Vec2(-0.1, 1.0),
+  ];
+  let mut mask = [false; 4];
+  points_in_rect(&points, r, &mut mask);
+  assert_eq!(mask, [true, true, false, false]);
+}
+
+#[test]
+fn sweep_hits_box_in_the_way() {
+  let hit = sweep_rect(
+    rect(0.0, 0.0, 1.0, 1.0),
+    Vec2(4.0, 0.0),
+    rect(3.0, 0.5, 1.0, 1.0),
+  );
+  assert_eq!(
+    hit,
+    Some(SweepHit {
+      time:   0.5,
+      normal: Vec2(-1.0, 0.0),
+    })
+  );
+}
+
+#[test]
+fn sweep_misses_box_out_of_reach_or_off_the_path() {
+  let moving = rect(0.0, 0.0, 1.0, 1.0);
+  assert!(sweep_rect(moving, Vec2(1.0, 0.0), rect(3.0, 0.0, 1.0, 1.0)).is_none());
+  assert!(sweep_rect(moving, Vec2(4.0, 0.0), rect(3.0, 1.0, 1.0, 1.0)).is_none());
+  assert!(sweep_rect(moving, Vec2(-4.0, 0.0), rect(3.0, 0.0, 1.0, 1.0)).is_none());
+}
+
+#[test]
+fn sweep_of_overlapping_boxes_hits_immediately() {
+  let hit = sweep_rect(
+    rect(0.0, 0.0, 2.0, 2.0),
+    Vec2(5.0, 5.0),
+    rect(1.0, 1.0, 2.0, 2.0),
+  );
+  assert_eq!(hit.map(|hit| hit.time), Some(0.0));
+}
+
+#[test]
+fn segments_cross() {
+  let (t, u) = segment_intersection(
+    Vec2(0.0, 0.0),
+    Vec2(2.0, 2.0),
+    Vec2(0.0, 2.0),
+    Vec2(2.0, 0.0),
+  )
+  .unwrap();
+  assert_eq!((t, u), (0.5, 0.5));
+  assert_eq!(
+    segment_intersection_point(
+      Vec2(0.0, 0.0),
+      Vec2(4.0, 0.0),
+      Vec2(1.0, -1.0),
+      Vec2(1.0, 1.0)
+    ),
+    Some(Vec2(1.0, 0.0))
+  );
+}
+
+#[test]
+fn segments_that_fall_short_or_are_parallel_dont_cross() {
+  let a = (Vec2(0.0, 0.0), Vec2(1.0, 0.0));
+  assert!(segment_intersection(a.0, a.1, Vec2(2.0, -1.0), Vec2(2.0, 1.0)).is_none());
+  assert!(segment_intersection(a.0, a.1, Vec2(0.0, 1.0), Vec2(1.0, 1.0)).is_none());
+  assert!(segment_intersection(a.0, a.1, Vec2(0.5, 0.0), Vec2(2.0, 0.0)).is_none());
+}
+
+#[test]
+fn points_in_concave_polygon() {
+  // An L shape, with the notch at the top right.
+  let polygon = [
+    Vec2(0.0, 0.0),
+    Vec2(2.0, 0.0),
+    Vec2(2.0, 2.0),
+    Vec2(4.0, 2.0),
+    Vec2(4.0, 4.0),
+    Vec2(0.0, 4.0),
+  ];
+  assert!(point_in_polygon(Vec2(1.0, 1.0), &polygon));
+  assert!(point_in_polygon(Vec2(3.0, 3.0), &polygon));
+  assert!(!point_in_polygon(Vec2(3.0, 1.0), &polygon));
+  assert!(!point_in_polygon(Vec2(-1.0, 3.0), &polygon));
+  assert!(!point_in_polygon(Vec2(1.0, 1.0), &[]));
+}
+
+fn small_vec2() -> impl Strategy<Value = Vec2> {
+  (-100i8..100, -100i8..100).prop_map(|(x, y)| Vec2(x as f32, y as f32))
+}
+
+fn small_rect() -> impl Strategy<Value = Rect> {
+  (small_vec2(), 1i8..20, 1i8..20).prop_map(|(pos, w, h)| Rect::new(pos, Vec2(w as f32, h as f32)))
+}
+
+proptest! {
+  #[test]
+  fn segment_intersection_is_symmetric(
+    a0 in small_vec2(), a1 in small_vec2(), b0 in small_vec2(), b1 in small_vec2(),
+  ) {
+    let forward = segment_intersection(a0, a1, b0, b1);
+    let backward = segment_intersection(b0, b1, a0, a1).map(|(u, t)| (t, u));
+    prop_assert_eq!(forward.is_some(), backward.is_some());
+    if let (Some(f), Some(b)) = (forward, backward) {
+      prop_assert!((f.0 - b.0).abs() < 1e-4 && (f.1 - b.1).abs() < 1e-4);
+    }
+  }
+
+  #[test]
+  fn swept_box_touches_target_at_hit_time(
+    moving in small_rect(), delta in small_vec2(), target in small_rect(),
+  ) {
+    if let Some(hit) = sweep_rect(moving, delta, target) {
+      prop_assert!((0.0..=1.0).contains(&hit.time));
+      // Just past the time of impact, the boxes overlap.
+      let nudged = (hit.time + 1e-3).min(1.0);
+      let moved = Rect::new(moving.pos + nudged * delta, moving.size);
+      prop_assert!(moved.overlaps(target) || hit.time == 1.0);
+    }
+  }
+
+  #[test]
+  fn swept_box_never_passes_through_target(
+    moving in small_rect(), delta in small_vec2(), target in small_rect(),
+  ) {
+    if sweep_rect(moving, delta, target).is_none() {
+      for i in 0..=32 {
+        let moved = Rect::new(moving.pos + (i as f32 / 32.0) * delta, moving.size);
+        prop_assert!(!moved.overlaps(target));
+      }
+    }
+  }
+}
@@ -0,0 +1,99 @@
+//! Tests for the room graph behind the route hints.
+
+use std::collections::HashSet;
+
+use tmv::{
+  map_rooms::{Door, Room, RoomMap},
+  math::{Rect, Vec2},
+  power_ups::PowerUp,
+  progression::{gates_from_cells, Gate, ProgressionGraph},
+};
+
+fn square(x: f32) -> Room {
+  Room {
+    outline: vec![
+      Vec2(x, 0.0),
+      Vec2(x + 10.0, 0.0),
+      Vec2(x + 10.0, 10.0),
+      Vec2(x, 10.0),
+    ],
+  }
+}
+
+/// Three rooms in a row, each ten tiles across, with doors between them.
+fn row_of_rooms() -> RoomMap {
+  RoomMap {
+    rooms: vec![square(0.0), square(10.0), square(20.0)],
+    doors: vec![
+      Door {
+        rooms: (0, 1),
+        a:     Vec2(10.0, 2.0),
+        b:     Vec2(10.0, 8.0),
+      },
+      Door {
+        rooms: (1, 2),
+        a:     Vec2(20.0, 2.0),
+        b:     Vec2(20.0, 8.0),
+      },
+    ],
+  }
+}
+
+// Water over the door between the second and third rooms.
+fn water_gate() -> Gate {
+  Gate {
+    bounds:   Rect::new(Vec2(19.0, 4.0), Vec2(2.0, 2.0)),
+    requires: PowerUp::Water,
+  }
+}
+
+#[test]
+fn gates_block_doors_until_the_power_up() {
+  let graph = ProgressionGraph::new(&row_of_rooms(), &[water_gate()]);
+  assert_eq!(graph.reachable_rooms(0, &HashSet::new()), vec![0, 1]);
+  assert_eq!(
+    graph.reachable_rooms(0, &HashSet::from([PowerUp::Water])),
+    vec![0, 1, 2]
+  );
+  // Doors go both ways.
+  assert_eq!(graph.reachable_rooms(2, &HashSet::new()), vec![2]);
+}
+
+#[test]
+fn next_power_up_is_the_nearest_reachable() {
+  let rooms = row_of_rooms();
+  let graph = ProgressionGraph::new(&rooms, &[water_gate()]);
+  let available = [
+    (PowerUp::Dash, Vec2(25.0, 5.0)),
+    (PowerUp::Shoot, Vec2(15.0, 5.0)),
+  ];
+  let next = |power_ups: &[PowerUp]| {
+    let power_ups = power_ups.iter().copied().collect();
+    graph.next_power_up(&rooms, 0, &power_ups, &available).map(|(power_up, _)| power_up)
+  };
+  assert_eq!(next(&[]), Some(PowerUp::Shoot));
+  // The dash is behind the water.
+  assert_eq!(next(&[PowerUp::Shoot]), None);
+  assert_eq!(next(&[PowerUp::Shoot, PowerUp::Water]), Some(PowerUp::Dash));
+}
+
+#[test]
+fn gates_from_cells_merges_rows() {
+  let cells = HashSet::from([(0, 0), (1, 0), (2, 0), (4, 0), (1, 1)]);
+  let mut gates: Vec<Rect> = gates_from_cells(&cells, PowerUp::Lava)
+    .into_iter()
+    .map(|gate| {
+      assert_eq!(gate.requires, PowerUp::Lava);
+      gate.bounds
+    })
+    .collect();
+  gates.sort_by(|a, b| (a.pos.1, a.pos.0).partial_cmp(&(b.pos.1, b.pos.0)).unwrap());
+  assert_eq!(
+    gates,
+    vec![
+      Rect::new(Vec2(0.0, 0.0), Vec2(3.0, 1.0)),
+      Rect::new(Vec2(4.0, 0.0), Vec2(1.0, 1.0)),
+      Rect::new(Vec2(1.0, 1.0), Vec2(1.0, 1.0)),
+    ]
+  );
+}
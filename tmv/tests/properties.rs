@@ -0,0 +1,177 @@
+//! Property-based tests for the wall scan and the geometry helpers it and the renderer lean on.
+
+use std::collections::{HashMap, HashSet};
+
+use proptest::prelude::*;
+use tmv::{
+  collision::generate_walls,
+  math::{Rect, Vec2},
+};
+
+type Cell = (i32, i32);
+type UnitEdge = (Cell, Cell);
+
+fn solid_cells() -> impl Strategy<Value = HashSet<Cell>> {
+  prop::collection::hash_set((-8..8, -8..8), 0..80)
+}
+
+/// Splits walls into unit-length edges, failing if any segment isn't axis-aligned and forward.
+fn unit_edges(walls: &[(Cell, Cell)]) -> Result<Vec<UnitEdge>, TestCaseError> {
+  let mut edges = Vec::new();
+  for &((x1, y1), (x2, y2)) in walls {
+    if y1 == y2 && x1 < x2 {
+      edges.extend((x1..x2).map(|x| ((x, y1), (x + 1, y1))));
+    } else if x1 == x2 && y1 < y2 {
+      edges.extend((y1..y2).map(|y| ((x1, y), (x1, y + 1))));
+    } else {
+      return Err(TestCaseError::fail(format!(
+        "Bad segment: {:?}",
+        ((x1, y1), (x2, y2))
+      )));
+    }
+  }
+  Ok(edges)
+}
+
+// Integer-valued floats, so arithmetic on them is exact.
+fn exact_vec2() -> impl Strategy<Value = Vec2> {
+  (-1000i16..1000, -1000i16..1000).prop_map(|(x, y)| Vec2(x as f32, y as f32))
+}
+
+fn exact_rect() -> impl Strategy<Value = Rect> {
+  (exact_vec2(), 0i16..500, 0i16..500)
+    .prop_map(|(pos, w, h)| Rect::new(pos, Vec2(w as f32, h as f32)))
+}
+
+proptest! {
+  #[test]
+  fn walls_have_no_duplicate_edges(cells in solid_cells()) {
+    let edges = unit_edges(&generate_walls(&cells))?;
+    let unique: HashSet<_> = edges.iter().collect();
+    prop_assert_eq!(unique.len(), edges.len());
+  }
+
+  #[test]
+  fn walls_are_exactly_the_solid_boundaries(cells in solid_cells()) {
+    let edges: HashSet<_> = unit_edges(&generate_walls(&cells))?.into_iter().collect();
+    let mut expected = HashSet::new();
+    for &(x, y) in &cells {
+      if !cells.contains(&(x, y - 1)) {
+        expected.insert(((x, y), (x + 1, y)));
+      }
+      if !cells.contains(&(x, y + 1)) {
+        expected.insert(((x, y + 1), (x + 1, y + 1)));
+      }
+      if !cells.contains(&(x - 1, y)) {
+        expected.insert(((x, y), (x, y + 1)));
+      }
+      if !cells.contains(&(x + 1, y)) {
+        expected.insert(((x + 1, y), (x + 1, y + 1)));
+      }
+    }
+    prop_assert_eq!(edges, expected);
+  }
+
+  #[test]
+  fn walls_form_closed_boundaries(cells in solid_cells()) {
+    let mut degree: HashMap<Cell, usize> = HashMap::new();
+    for (a, b) in unit_edges(&generate_walls(&cells))? {
+      *degree.entry(a).or_default() += 1;
+      *degree.entry(b).or_default() += 1;
+    }
+    for (point, degree) in degree {
+      prop_assert!(degree % 2 == 0, "Open boundary at {:?}", point);
+    }
+  }
+
+  #[test]
+  fn cells_are_solid_exactly_when_inside_the_walls(
+    cells in solid_cells(),
+    probe in (-10..10, -10..10),
+  ) {
+    // Cast a ray from the probe cell's center off to the right, counting vertical edges crossed.
+    let crossings = unit_edges(&generate_walls(&cells))?
+      .into_iter()
+      .filter(|&((x1, y1), (x2, _))| x1 == x2 && y1 == probe.1 && x1 > probe.0)
+      .count();
+    prop_assert_eq!(crossings % 2 == 1, cells.contains(&probe));
+  }
+
+  #[test]
+  fn rect_contains_itself(r in exact_rect()) {
+    prop_assert!(r.contains_rect(r));
+  }
+
+  #[test]
+  fn rect_containment_is_transitive(a in exact_rect(), b in exact_rect(), c in exact_rect()) {
+    if a.contains_rect(b) && b.contains_rect(c) {
+      prop_assert!(a.contains_rect(c));
+    }
+  }
+
+  #[test]
+  fn rect_containment_matches_bounds(a in exact_rect(), b in exact_rect()) {
+    let within = b.pos.0 >= a.pos.0
+      && b.pos.1 >= a.pos.1
+      && b.pos.0 + b.size.0 <= a.pos.0 + a.size.0
+      && b.pos.1 + b.size.1 <= a.pos.1 + a.size.1;
+    prop_assert_eq!(a.contains_rect(b), within);
+  }
+
+  #[test]
+  fn rect_containment_survives_translation(a in exact_rect(), b in exact_rect(), shift in exact_vec2()) {
+    let shifted = |r: Rect| Rect::new(r.pos + shift, r.size);
+    prop_assert_eq!(a.contains_rect(b), shifted(a).contains_rect(shifted(b)));
+  }
+
+  #[test]
+  fn contained_rects_contain_their_corner(a in exact_rect(), b in exact_rect()) {
+    if a.contains_rect(b) && b.size.0 > 0.0 && b.size.1 > 0.0 {
+      prop_assert!(a.contains_point(b.pos));
+    }
+  }
+
+  #[test]
+  fn vec2_add_and_sub_are_inverses(a in exact_vec2(), b in exact_vec2()) {
+    prop_assert_eq!(a + b - b, a);
+    let mut c = a;
+    c += b;
+    c -= b;
+    prop_assert_eq!(c, a);
+  }
+
+  #[test]
+  fn vec2_neg_is_additive_inverse(a in exact_vec2()) {
+    prop_assert_eq!(a + -a, Vec2::default());
+    prop_assert_eq!(-(-a), a);
+    prop_assert_eq!(-a, -1.0 * a);
+  }
+
+  #[test]
+  fn vec2_scaling_matches_each_form(a in exact_vec2(), k in -16i8..16) {
+    let k = k as f32;
+    prop_assert_eq!(a * k, k * a);
+    let mut b = a;
+    b *= k;
+    prop_assert_eq!(b, a * k);
+    if k != 0.0 {
+      prop_assert_eq!((a * k) / k, a);
+      let mut c = a * k;
+      c /= k;
+      prop_assert_eq!(c, a);
+    }
+  }
+
+  #[test]
+  fn vec2_length_scales(a in exact_vec2(), k in -16i8..16) {
+    let k = k as f32;
+    let expected = k.abs() * a.length();
+    prop_assert!(((a * k).length() - expected).abs() <= 1e-3 * expected.max(1.0));
+  }
+
+  #[test]
+  fn vec2_to_unit_has_unit_length(a in exact_vec2()) {
+    prop_assume!(a != Vec2::default());
+    prop_assert!((a.to_unit().length() - 1.0).abs() < 1e-5);
+  }
+}
@@ -0,0 +1,87 @@
+//! Plays the replays in tests/replays through the simulation and compares checkpoints
+//! against the ones recorded next to each replay, to catch physics or tuning changes
+//! that alter how existing runs play out.
+//!
+//! To record a new replay, call `startRecording()` and `stopRecording()` from the browser
+//! console, drop the file in tests/replays, and run the tests with TMV_BLESS=1 to record its
+//! checkpoints, committing both. After an intentional change, rerun with TMV_BLESS=1 to
+//! rerecord them all.
+
+mod common;
+
+use std::{fs, path::Path};
+
+use tmv::{
+  replay::{Replay, ReplayCheckpoint},
+  GameState,
+};
+
+#[test]
+fn replays_match_recorded_checkpoints() {
+  let bless = std::env::var_os("TMV_BLESS").is_some();
+  let replay_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/replays");
+  let mut game_state = GameState::new_headless(common::load_resources());
+  let mut failures = Vec::new();
+  let mut replay_paths: Vec<_> = fs::read_dir(&replay_dir)
+    .unwrap()
+    .map(|entry| entry.unwrap().path())
+    .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("tmvreplay"))
+    .collect();
+  replay_paths.sort();
+  assert!(
+    !replay_paths.is_empty(),
+    "No replays in {}",
+    replay_dir.display()
+  );
+
+  for replay_path in replay_paths {
+    let replay = Replay::from_json(&fs::read_to_string(&replay_path).unwrap())
+      .unwrap_or_else(|e| panic!("Bad replay {}: {}", replay_path.display(), e));
+    let checkpoints = game_state.play_replay(&replay);
+    let checkpoints_path = replay_path.with_extension("checkpoints.json");
+    if bless {
+      fs::write(
+        &checkpoints_path,
+        serde_json::to_string_pretty(&checkpoints).unwrap() + "\n",
+      )
+      .unwrap();
+      eprintln!("Recorded checkpoints to {}", checkpoints_path.display());
+      continue;
+    }
+    if !checkpoints_path.exists() {
+      failures.push(format!(
+        "{}: no checkpoints recorded at {}",
+        replay_path.display(),
+        checkpoints_path.display()
+      ));
+      continue;
+    }
+    let expected: Vec<ReplayCheckpoint> =
+      serde_json::from_str(&fs::read_to_string(&checkpoints_path).unwrap()).unwrap();
+    if expected.len() != checkpoints.len() {
+      failures.push(format!(
+        "{}: expected {} checkpoints, got {}",
+        replay_path.display(),
+        expected.len(),
+        checkpoints.len()
+      ));
+      continue;
+    }
+    // Only report the first divergence, since everything after it will differ too.
+    if let Some((expected, actual)) = expected.iter().zip(&checkpoints).find(|(e, a)| e != a) {
+      failures.push(format!(
+        "{}: diverged by frame {}\n  expected: {:?}\n  actual:   {:?}",
+        replay_path.display(),
+        expected.frame,
+        expected,
+        actual
+      ));
+    }
+  }
+
+  assert!(
+    failures.is_empty(),
+    "Replays diverged (rerun with TMV_BLESS=1 if this is intended):\n{}",
+    failures.join("\n")
+  );
+}
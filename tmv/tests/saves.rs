@@ -0,0 +1,176 @@
+//! Round trips saves through the lenient parser and the repairs made against the maps.
+
+use std::{
+  collections::{HashMap, HashSet},
+  fs,
+  path::Path,
+};
+
+use tmv::{
+  breakable::BlockDrop,
+  char_events::CharEvent,
+  collision::{CollisionWorld, PhysicsKind, WALLS_INT_GROUPS},
+  math::Vec2,
+  power_ups::PowerUp,
+  save_repair::{parse_leniently, repair, RepairReport, SaveRepairContext, SAVE_VERSION},
+  CharState, GameObject, GameObjectData, LocalStorageSaveData,
+};
+
+const MAP: &str = "/assets/map1.tmx";
+const BLOCK_ID: i32 = 1234;
+
+/// The repair context for a map with nothing in it but one breakable block.
+fn block_map_context(drops: Option<BlockDrop>) -> SaveRepairContext {
+  let mut collision = CollisionWorld::new();
+  let handle = collision.new_cuboid(
+    PhysicsKind::Static,
+    Vec2(2.5, 3.5),
+    Vec2(1.0, 1.0),
+    0.05,
+    false,
+    WALLS_INT_GROUPS,
+  );
+  let mut objects = HashMap::new();
+  objects.insert(
+    handle.collider,
+    GameObject {
+      physics_handle: handle,
+      data:           GameObjectData::BreakableBlock {
+        entity_id: BLOCK_ID,
+        hp: 1,
+        max_hp: 1,
+        drops,
+        crumbling: None,
+      },
+    },
+  );
+  SaveRepairContext::from_objects(MAP, &objects, &collision)
+}
+
+fn save_json(char_state: CharState) -> String {
+  serde_json::to_string(&LocalStorageSaveData {
+    version: SAVE_VERSION,
+    char_state,
+    revealed_map: Default::default(),
+    revealed_map_granularity: tmv::MAP_REVELATION_DISCRETIZATION,
+    trial_best_times: Default::default(),
+    map_pins: Default::default(),
+    visited_rooms: Default::default(),
+    char_history: Default::default(),
+    run_stats: Default::default(),
+    recap: None,
+  })
+  .unwrap()
+}
+
+// A save in tests/saves, written by an old version of the game.
+fn fixture(file_name: &str) -> String {
+  let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/saves").join(file_name);
+  fs::read_to_string(path).unwrap()
+}
+
+fn load(json: &str, context: &SaveRepairContext) -> (LocalStorageSaveData, RepairReport) {
+  let mut report = RepairReport::default();
+  let mut save = parse_leniently(json, &mut report).unwrap();
+  repair(&mut save, context, &mut report);
+  (save, report)
+}
+
+#[test]
+fn collected_block_coin_survives_repair() {
+  let context = block_map_context(Some(BlockDrop::Coin));
+  assert_eq!(context.known_item_count, 1);
+  let mut char_state = CharState {
+    save_map: MAP.to_string(),
+    ..CharState::default()
+  };
+  char_state.broken_blocks.insert(BLOCK_ID);
+  char_state.coins.insert(BLOCK_ID);
+
+  let (save, report) = load(&save_json(char_state), &context);
+  assert!(report.fixes.is_empty(), "{:?}", report.fixes);
+  assert!(save.char_state.coins.contains(&BLOCK_ID));
+  assert!(save.char_state.broken_blocks.contains(&BLOCK_ID));
+}
+
+#[test]
+fn block_without_a_drop_isnt_an_item() {
+  let context = block_map_context(None);
+  assert_eq!(context.known_item_count, 0);
+  assert!(context.known_entity_ids.contains(&BLOCK_ID));
+}
+
+#[test]
+fn unknown_coin_is_dropped() {
+  let context = block_map_context(Some(BlockDrop::Coin));
+  let mut char_state = CharState {
+    save_map: MAP.to_string(),
+    ..CharState::default()
+  };
+  char_state.coins.insert(BLOCK_ID + 1);
+
+  let (save, report) = load(&save_json(char_state), &context);
+  assert!(save.char_state.coins.is_empty());
+  assert_eq!(report.fixes, vec!["Dropped 1 unknown coins".to_string()]);
+}
+
+#[test]
+fn unversioned_save_migrates() {
+  let mut report = RepairReport::default();
+  let save = parse_leniently(&fixture("v0.json"), &mut report).unwrap();
+  assert!(report.fixes.is_empty(), "{:?}", report.fixes);
+  assert_eq!(save.version, SAVE_VERSION);
+  // From before the granularity was configurable, when it was always 8.
+  assert_eq!(save.revealed_map_granularity, 8);
+  assert_eq!(save.revealed_map, HashSet::from([(0, 0), (1, 0), (1, 1)]));
+  assert_eq!(save.char_state.save_point, Vec2(12.5, 40.0));
+  assert_eq!(save.char_state.power_ups, HashSet::from([PowerUp::Dash]));
+  assert_eq!(save.char_state.coins, HashSet::from([3000012, 4000020]));
+  assert_eq!(save.char_state.hp_ups, HashSet::from([5000007]));
+}
+
+#[test]
+fn save_from_a_newer_version_is_refused() {
+  let json = save_json(CharState::default()).replacen(
+    &format!("\"version\":{}", SAVE_VERSION),
+    &format!("\"version\":{}", SAVE_VERSION + 1),
+    1,
+  );
+  match parse_leniently(&json, &mut RepairReport::default()) {
+    Err(error) => assert!(error.contains("newer version"), "{}", error),
+    Ok(_) => panic!("Loaded a save from a newer version"),
+  }
+}
+
+#[test]
+fn laser_puzzles_become_flags() {
+  let mut report = RepairReport::default();
+  let save = parse_leniently(&fixture("v1.json"), &mut report).unwrap();
+  assert!(report.fixes.is_empty(), "{:?}", report.fixes);
+  assert_eq!(save.version, SAVE_VERSION);
+  // Only the first migration fills this in.
+  assert_eq!(save.revealed_map_granularity, 16);
+  assert_eq!(
+    save.char_state.flags,
+    HashSet::from(["int1_completed".to_string()])
+  );
+  assert_eq!(
+    save.char_history,
+    vec![
+      CharEvent::Coin { entity_id: 3000012 },
+      CharEvent::Flag {
+        name: "int1_completed".to_string(),
+      },
+    ]
+  );
+}
+
+#[test]
+fn destroyed_hives_stay_destroyed() {
+  let mut char_state = CharState::default();
+  char_state.destroyed_hives.insert(1234);
+  let mut report = RepairReport::default();
+  let save = parse_leniently(&save_json(char_state), &mut report).unwrap();
+  assert!(report.fixes.is_empty(), "{:?}", report.fixes);
+  assert_eq!(save.char_state.destroyed_hives, HashSet::from([1234]));
+}
@@ -23,6 +23,7 @@
  </tile>
  <tile id="3" class="nonsolid">
   <properties>
+   <property name="entity" value="shooter"/>
    <property name="name" value="shooter1"/>
   </properties>
  </tile>
@@ -148,7 +149,9 @@
  </tile>
  <tile id="36" class="nonsolid">
   <properties>
+   <property name="entity" value="shooter"/>
    <property name="name" value="shooter2"/>
+   <property name="period" type="float" value="2"/>
   </properties>
  </tile>
  <tile id="38" class="nonsolid">
@@ -8,17 +8,68 @@ let frameTimes: number[] = [];
 let lastTimestamp: number | null = null;
 let debugOpen = false;
 
-function rafLoop(timestamp: number) {
-  const powerupState = gameState!.get_char_state();
-  const infoLine = gameState!.get_info_line();
-  document.getElementById('infoLine')!.innerText = infoLine;
-  document.getElementById('hpIndicator')!.innerText = '❤️'.repeat(Math.max(0, powerupState.hp));
-
-  ["wall_jump", "dash", "water", "small", "lava", "double_jump"].map((powerUpName, i) => {
-    const havePowerUp = powerupState.power_ups.includes(powerUpName);
-    document.getElementById('powerup' + (i + 1))!.style.display = havePowerUp ? 'flex' : 'none';
-  });
+// A synthesized heartbeat thump, played while the player is about to run out of air.
+let audioContext: AudioContext | null = null;
+let lastHeartbeat = 0;
+const HEARTBEAT_INTERVAL = 700;
+
+function getAudioContext(): AudioContext {
+  if (audioContext === null)
+    audioContext = new AudioContext();
+  return audioContext;
+}
+
+function playHeartbeat() {
+  const audioContext = getAudioContext();
+  const oscillator = audioContext.createOscillator();
+  const gain = audioContext.createGain();
+  oscillator.frequency.value = 55;
+  gain.gain.setValueAtTime(0.6, audioContext.currentTime);
+  gain.gain.exponentialRampToValueAtTime(0.001, audioContext.currentTime + 0.25);
+  oscillator.connect(gain).connect(audioContext.destination);
+  oscillator.start();
+  oscillator.stop(audioContext.currentTime + 0.25);
+}
+
+// A synthesized blip for each sound event the game reports: the waveform, the start and end
+// pitch, how long it lasts, and how loud it starts.
+const SOUNDS: { [event: string]: [OscillatorType, number, number, number, number] } = {
+  jump: ['square', 220, 440, 0.12, 0.15],
+  dash: ['sawtooth', 600, 150, 0.15, 0.15],
+  coin: ['square', 990, 1320, 0.1, 0.12],
+  damage: ['sawtooth', 200, 60, 0.25, 0.3],
+  save: ['sine', 523, 784, 0.4, 0.3],
+  laser: ['sawtooth', 1200, 80, 0.8, 0.25],
+  splash: ['triangle', 700, 180, 0.2, 0.15],
+  crumble: ['triangle', 160, 50, 0.3, 0.2],
+  door_open: ['square', 260, 520, 0.3, 0.15],
+  switch: ['square', 700, 350, 0.2, 0.08],
+  head_bump: ['triangle', 150, 90, 0.08, 0.2],
+};
+
+function playSound(event: string) {
+  const sound = SOUNDS[event];
+  if (sound === undefined)
+    return;
+  const [type, startPitch, endPitch, duration, volume] = sound;
+  const audioContext = getAudioContext();
+  const oscillator = audioContext.createOscillator();
+  const gain = audioContext.createGain();
+  oscillator.type = type;
+  oscillator.frequency.setValueAtTime(startPitch, audioContext.currentTime);
+  oscillator.frequency.exponentialRampToValueAtTime(endPitch, audioContext.currentTime + duration);
+  gain.gain.setValueAtTime(volume, audioContext.currentTime);
+  gain.gain.exponentialRampToValueAtTime(0.001, audioContext.currentTime + duration);
+  oscillator.connect(gain).connect(audioContext.destination);
+  oscillator.start();
+  oscillator.stop(audioContext.currentTime + duration);
+}
 
+function rafLoop(timestamp: number) {
+  if (gameState!.is_low_on_air() && timestamp - lastHeartbeat > HEARTBEAT_INTERVAL) {
+    playHeartbeat();
+    lastHeartbeat = timestamp;
+  }
   // const frameTime = timestamp - this.lastFrameTimestamp;
   // this.fps = 1000 / frameTime;
   // if (this.fpsCounterRef.current !== null) {
@@ -30,7 +81,15 @@ function rafLoop(timestamp: number) {
   if (lastTimestamp !== null) {
     // Don't step by more than a tenth of a second at a time.
     const dt = Math.min(0.1, 1e-3 * (timestamp - lastTimestamp));
-    gameState!.step(dt);
+    gameState!.report_frame_time(1e-3 * (timestamp - lastTimestamp));
+    gameState!.step_at(dt, timestamp);
+    // Release builds leave out audio, along with the other dev-only exports below.
+    for (const event of gameState!.drain_sound_events?.() ?? [])
+      playSound(event);
+    // Anything on the page can listen for these as 'tmvgameevent's on the window, with the
+    // event (like { kind: 'coin_collected', entity_id: 1234, rare: false }) as the detail.
+    for (const event of gameState!.drain_game_events())
+      window.dispatchEvent(new CustomEvent('tmvgameevent', { detail: event }));
     frameTimes.push(dt);
     if (frameTimes.length > 10) {
       frameTimes.shift();
@@ -44,14 +103,28 @@ function rafLoop(timestamp: number) {
 }
 
 function onKeyDown(e: KeyboardEvent) {
+  // While a replay is loaded, the keyboard scrubs through it instead of playing.
+  const replayPosition = gameState?.replay_position();
+  if (gameState !== null && replayPosition !== undefined) {
+    if (e.key === '.')
+      gameState.step_once();
+    if (e.key === ',')
+      gameState.seek_replay(Math.max(0, replayPosition - 1));
+    if (e.key === 'Escape')
+      gameState.close_replay();
+    return;
+  }
   if (e.repeat)
     return;
   if (e.key === 'f') {
     debugOpen = !debugOpen;
     document.getElementById('fpsCounter')!.style.display = debugOpen ? 'block' : 'none';
+    if (gameState !== null) {
+      gameState.set_debug_mode?.(debugOpen);
+    }
   }
   if (gameState !== null) {
-    gameState.apply_input_event(JSON.stringify({ type: 'KeyDown', key: e.key }));
+    gameState.apply_input_event(JSON.stringify({ type: 'KeyDown', key: e.key, timestamp: e.timeStamp }));
   }
 }
 
@@ -63,7 +136,43 @@ function onKeyUp(e: KeyboardEvent) {
   }
 }
 
+// Mouse positions are sent in game screen pixels, in case the canvas is scaled by CSS.
+function sendMouseEvent(type: string, e: MouseEvent, extra: object = {}) {
+  if (gameState === null)
+    return;
+  const canvas = e.target as HTMLCanvasElement;
+  const x = e.offsetX * canvas.width / window.devicePixelRatio / canvas.clientWidth;
+  const y = e.offsetY * canvas.height / window.devicePixelRatio / canvas.clientHeight;
+  gameState.apply_mouse_event(JSON.stringify({ type, x, y, ...extra }));
+}
+
+// Tells the game which parts of the screen are hidden by notches and rounded corners. The
+// browser only reports these through CSS, so read them back off a probe element.
+function sendSafeAreaInsets() {
+  if (gameState === null)
+    return;
+  const probe = document.createElement('div');
+  probe.style.cssText = 'position: fixed; visibility: hidden; padding: env(safe-area-inset-top) env(safe-area-inset-right) env(safe-area-inset-bottom) env(safe-area-inset-left)';
+  document.body.appendChild(probe);
+  const style = getComputedStyle(probe);
+  document.body.removeChild(probe);
+  // Insets are in CSS pixels of the page, so only the part overlapping the canvas matters.
+  const canvas = document.getElementById('uiCanvas') as HTMLCanvasElement;
+  const rect = canvas.getBoundingClientRect();
+  const scale = canvas.width / window.devicePixelRatio / rect.width;
+  const overlap = (inset: string, gap: number) => Math.max(0, parseFloat(inset) - gap) * scale;
+  gameState.set_safe_area_insets(
+    overlap(style.paddingTop, rect.top),
+    overlap(style.paddingRight, window.innerWidth - rect.right),
+    overlap(style.paddingBottom, window.innerHeight - rect.bottom),
+    overlap(style.paddingLeft, rect.left),
+  );
+}
+
 let savingInterval: any = null;
+// Set when the stored save couldn't be loaded (like one from a newer version of the game), so
+// it's left alone rather than overwritten by the fresh game.
+let keepSaveData = false;
 
 (window as any).clearProgress = function() {
   if (window.confirm('Are you sure you want to completely restart the game?')) {
@@ -73,16 +182,252 @@ let savingInterval: any = null;
   }
 }
 
+(window as any).setSetting = function(name: string, value: any) {
+  if (gameState === null)
+    return;
+  const settings = JSON.parse(gameState.get_settings());
+  settings[name] = value;
+  gameState.apply_settings(JSON.stringify(settings));
+  localStorage.setItem('pmvSettings', gameState.get_settings());
+}
+
+// Themes live next to the other assets, e.g. loadTheme('theme_colorblind').
+async function loadTheme(name: string) {
+  const theme = await fetch(ROOT + '/assets/' + name + '.json').then((res) => res.text());
+  gameState!.apply_theme(theme);
+}
+
+(window as any).setTheme = function(name: string) {
+  if (gameState === null)
+    return;
+  loadTheme(name).then(() => localStorage.setItem('pmvTheme', name));
+}
+
+(window as any).exportSave = function() {
+  if (gameState === null)
+    return;
+  const blob = new Blob([gameState.export_save_file()], { type: 'application/octet-stream' });
+  const link = document.createElement('a');
+  link.href = URL.createObjectURL(blob);
+  link.download = 'tmv.tmvsave';
+  link.click();
+  URL.revokeObjectURL(link.href);
+}
+
+(window as any).importSave = function() {
+  const input = document.createElement('input');
+  input.type = 'file';
+  input.accept = '.tmvsave';
+  input.onchange = async () => {
+    const file = input.files?.[0];
+    if (file === undefined || gameState === null)
+      return;
+    try {
+      const report = gameState.import_save_file(new Uint8Array(await file.arrayBuffer()));
+      if (report.fixes.length > 0)
+        window.alert('Imported save, with repairs:\n' + report.fixes.join('\n'));
+      keepSaveData = false;
+      localStorage.setItem('pmvSaveData', gameState.get_save_data());
+    } catch (e) {
+      window.alert('Could not import save: ' + e);
+    }
+  };
+  input.click();
+}
+
+// Today's daily challenge (by the UTC date, so everyone gets the same one at once), played
+// aside from the player's own game until they quit it.
+(window as any).startDailyChallenge = function() {
+  gameState?.start_daily_challenge(new Date().toISOString().slice(0, 10));
+}
+(window as any).quitDailyChallenge = () => gameState?.quit_daily_challenge();
+window.addEventListener('tmvgameevent', (e) => {
+  const event = (e as CustomEvent).detail;
+  if (event.kind === 'daily_challenge_finished')
+    window.prompt('Daily challenge complete! Share your result code:', event.code);
+});
+
+// An endless descent through procedurally stacked rooms. The same seed always gives the same
+// rooms, so seeded runs can go on a shared leaderboard.
+(window as any).startDescent = function(seed?: number) {
+  gameState?.start_descent(seed ?? Math.floor(Math.random() * 0xffffffff));
+}
+(window as any).quitDescent = () => gameState?.quit_descent();
+// Puts a copy of a room template into the world at a chunk edge, like
+// placeFragment('/assets/descent_room_spikes.tmx', 0, 32), to try it out in place.
+(window as any).placeFragment = (mapName: string, x: number, y: number) =>
+  gameState?.place_fragment(mapName, x, y);
+(window as any).clearFragments = () => gameState?.clear_fragments();
+window.addEventListener('tmvgameevent', (e) => {
+  const event = (e as CustomEvent).detail;
+  if (event.kind === 'descent_ended')
+    console.log('Descent over:', gameState?.descent_result());
+});
+
+// Sends the game's split signals to a LiveSplit One server connection, like
+// connectLiveSplit('ws://localhost:16834'), setting the game time before each split so the
+// timer matches the in-game clock. Each signal only splits once per connection, however many
+// times it goes off. Connecting again replaces the last connection.
+let disconnectLiveSplit: (() => void) | null = null;
+(window as any).connectLiveSplit = function(url: string) {
+  disconnectLiveSplit?.();
+  const socket = new WebSocket(url);
+  const splitIds = new Set<string>();
+  const onGameEvent = (e: Event) => {
+    const event = (e as CustomEvent).detail;
+    if (event.kind !== 'split' || splitIds.has(event.id) || socket.readyState !== WebSocket.OPEN)
+      return;
+    splitIds.add(event.id);
+    socket.send(JSON.stringify({ command: 'setGameTime', time: event.game_time.toFixed(3) }));
+    socket.send(JSON.stringify({ command: 'split' }));
+  };
+  window.addEventListener('tmvgameevent', onGameEvent);
+  disconnectLiveSplit = () => {
+    window.removeEventListener('tmvgameevent', onGameEvent);
+    socket.close();
+  };
+}
+
+// Downloads anonymized statistics comparing this browser's save (slot 0) with any picked
+// save files, for community spreadsheets.
+(window as any).exportRunStatistics = function() {
+  const input = document.createElement('input');
+  input.type = 'file';
+  input.accept = '.tmvsave';
+  input.multiple = true;
+  input.onchange = async () => {
+    if (gameState === null)
+      return;
+    const files = Array.from(input.files ?? []);
+    const saves = [gameState.get_save_data(), ...await Promise.all(files.map((file) => file.text()))];
+    downloadText(gameState.export_run_statistics(saves), 'tmv_run_statistics.json');
+  };
+  input.click();
+}
+
+function downloadText(text: string, fileName: string) {
+  const link = document.createElement('a');
+  link.href = URL.createObjectURL(new Blob([text], { type: 'application/json' }));
+  link.download = fileName;
+  link.click();
+  URL.revokeObjectURL(link.href);
+}
+
+// Replays, for analyzing runs frame by frame. Once loaded, '.' steps forward, ',' steps back,
+// and Escape returns to the game.
+(window as any).startRecording = () => gameState?.start_recording();
+(window as any).stopRecording = function() {
+  const replay = gameState?.stop_recording();
+  if (replay !== undefined)
+    downloadText(replay, 'tmv.tmvreplay');
+}
+(window as any).loadReplay = function() {
+  const input = document.createElement('input');
+  input.type = 'file';
+  input.accept = '.tmvreplay';
+  input.onchange = async () => {
+    const file = input.files?.[0];
+    if (file === undefined || gameState === null)
+      return;
+    try {
+      gameState.load_replay(await file.text());
+    } catch (e) {
+      window.alert('Could not load replay: ' + e);
+    }
+  };
+  input.click();
+}
+(window as any).seekReplay = (frame: number) => gameState?.seek_replay(frame);
+
+// Freezing the live game, to step through physics interactions one frame at a time. Keys
+// pressed while paused take effect on the next step.
+(window as any).pauseSimulation = () => gameState?.pause_simulation();
+(window as any).resumeSimulation = () => gameState?.resume_simulation();
+(window as any).stepOnce = () => gameState?.step_once();
+(window as any).runFrames = (n: number) => gameState?.run_n_frames(n);
+
+// In debug mode, camera bounds vertices can be dragged around; this downloads the result.
+(window as any).exportCameraBounds = function() {
+  if (gameState?.export_camera_bounds !== undefined)
+    downloadText(gameState.export_camera_bounds(), 'camera_bounds.json');
+}
+
+// Where players have lingered, died, and been hurt this session, in grid cells over each map.
+(window as any).exportHeatmap = function() {
+  if (gameState !== null)
+    downloadText(gameState.export_heatmap(), 'heatmap.json');
+}
+
+// Reviewing map edits, e.g. diffMaps('/assets/map1.tmx', '/map1_edited.tmx') logs what changed
+// in how the map plays.
+(window as any).diffMaps = async function(oldUrl: string, newUrl: string) {
+  const [oldTmx, newTmx] = await Promise.all([oldUrl, newUrl].map(async url => (await fetch(url)).text()));
+  if (gameState !== null)
+    console.log(gameState.diff_maps(oldTmx, newTmx));
+}
+
+// In debug mode, objects are labeled by kind. showDebugLabels('terrain', false) hides a category:
+// pickups, hazards, enemies, terrain, or triggers.
+(window as any).showDebugLabels = (category: string, shown: boolean) =>
+  gameState?.set_debug_labels?.(category, shown);
+
+// Live tuning, e.g. setTunable('camera.vertical_deadzone', 2). Once things feel right, the
+// export goes in as /assets/tuning.json.
+(window as any).setTunable = (name: string, value: number) => gameState?.set_tunable(name, value);
+(window as any).getTunables = () => gameState && JSON.parse(gameState.get_tunables());
+(window as any).exportTunables = function() {
+  if (gameState !== null)
+    downloadText(gameState.export_tunables(), 'tuning.json');
+}
+
+// Downloads the manifest to deploy as /assets/manifest.json alongside the assets it describes.
+(window as any).downloadAssetManifest = function() {
+  if (gameState !== null)
+    downloadText(gameState.get_asset_manifest(), 'manifest.json');
+}
+
+// Debugging helpers for keeping an eye on memory over long sessions.
+(window as any).getMemoryStats = () => gameState?.get_memory_stats();
+(window as any).freeMemory = function() {
+  gameState?.unload_resources();
+  gameState?.clear_render_caches();
+}
+
+// Entity inspection for devtools, e.g. queryObjects({ pos: [0, 0], size: [20, 20] }), then
+// getObjectDetails(handle) and editObject(handle, { position: [x, y] }) with a returned handle.
+(window as any).queryObjects = (rect: object) => gameState?.query_objects(JSON.stringify(rect));
+(window as any).getObjectDetails = (handle: number) => gameState?.get_object_details(handle);
+(window as any).editObject = (handle: number, edit: object) =>
+  gameState?.edit_object(handle, JSON.stringify(edit));
+
+// The deploy's asset manifest (path -> content hash, as from get_asset_manifest), if it has one.
+// It's always fetched fresh, and its hashes go on the asset URLs, so a new deploy can't be
+// served stale cached assets.
+async function fetchAssetManifest(): Promise<{ [path: string]: string } | null> {
+  try {
+    const res = await fetch(ROOT + '/assets/manifest.json', { cache: 'no-store' });
+    return res.ok ? await res.json() : null;
+  } catch (e) {
+    return null;
+  }
+}
+
+function assetUrl(path: string, manifest: { [path: string]: string } | null): string {
+  const hash = manifest?.[path];
+  return hash === undefined ? ROOT + path : ROOT + path + '?v=' + hash;
+}
+
 async function main() {
   await init();
   console.log('Hello, world: ' + get_wasm_version());
+  const assetManifest = await fetchAssetManifest();
 
   // Load all the images
   const allImagePaths = get_all_image_paths();
   console.log('Loading images:', allImagePaths);
   for (const path of allImagePaths) {
     const img = new Image();
-    img.src = ROOT + path;
+    img.src = assetUrl(path, assetManifest);
     img.style.display = 'none';
     img.style.imageRendering = 'pixelated';
     img.id = path;
@@ -93,7 +438,7 @@ async function main() {
   const allResourceNames = get_all_resource_names();
   console.log('Loading resources:', allResourceNames);
   const resourcePromises = allResourceNames.map((name: string) => {
-    return fetch(ROOT + name).then((res) => res.arrayBuffer()).then((buf) => {
+    return fetch(assetUrl(name, assetManifest)).then((res) => res.arrayBuffer()).then((buf) => {
       console.log(`Loaded resource ${name}: ${buf.byteLength} bytes`);
       return { name, buf };
     });
@@ -106,19 +451,57 @@ async function main() {
     
     console.log('All resources loaded');
     gameState = new GameState(resources);
+    if (assetManifest !== null) {
+      const staleAssets = gameState.verify_assets(assetManifest);
+      if (staleAssets.length > 0)
+        console.warn('Stale assets:', staleAssets);
+    }
+    gameState.set_device_pixel_ratio(window.devicePixelRatio);
+    // Settings go first, so the save data gets remapped to the right map reveal granularity.
+    const pmvSettings = localStorage.getItem('pmvSettings');
+    if (pmvSettings !== null) {
+      gameState.apply_settings(pmvSettings);
+    }
+    const pmvTheme = localStorage.getItem('pmvTheme');
+    if (pmvTheme !== null) {
+      loadTheme(pmvTheme);
+    }
     const pmvSaveData = localStorage.getItem('pmvSaveData');
     if (pmvSaveData !== null) {
-      gameState.apply_save_data(pmvSaveData);
+      try {
+        const report = gameState.apply_save_data(pmvSaveData);
+        if (report.fixes.length > 0)
+          console.warn('Save data needed repairs:', report.fixes);
+      } catch (e) {
+        keepSaveData = true;
+        window.alert('Could not load your save, so this game won\'t be saved: ' + e);
+      }
     }
     // FIXME: There's no need to save so frequently, but also it doesn't matter?
     savingInterval = setInterval(() => {
+      if (keepSaveData)
+        return;
       const saveData = gameState!.get_save_data();
       localStorage.setItem('pmvSaveData', saveData);
     }, 500);
 
+    sendSafeAreaInsets();
+    window.addEventListener('resize', sendSafeAreaInsets);
+
     window.requestAnimationFrame(rafLoop);
     window.addEventListener('keydown', onKeyDown);
     window.addEventListener('keyup', onKeyUp);
+    // The UI canvas is on top, so it gets the mouse events. The game draws its own cursor.
+    const uiCanvas = document.getElementById('uiCanvas')!;
+    uiCanvas.style.cursor = 'none';
+    uiCanvas.addEventListener('mousedown', (e) => sendMouseEvent('MouseDown', e, { button: e.button }));
+    uiCanvas.addEventListener('mouseup', (e) => sendMouseEvent('MouseUp', e, { button: e.button }));
+    uiCanvas.addEventListener('mousemove', (e) => sendMouseEvent('MouseMove', e));
+    uiCanvas.addEventListener('mouseleave', () => gameState!.apply_mouse_event(JSON.stringify({ type: 'MouseLeave' })));
+    uiCanvas.addEventListener('wheel', (e) => {
+      e.preventDefault();
+      sendMouseEvent('Wheel', e, { delta_y: e.deltaY });
+    }, { passive: false });
   });
 }
 